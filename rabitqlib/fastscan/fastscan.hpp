@@ -3,7 +3,11 @@
 
 #pragma once
 
+#if defined(__SSE2__)
 #include <immintrin.h>
+#elif defined(__ARM_NEON)
+#include <arm_neon.h>
+#endif
 
 #include <array>
 #include <cassert>
@@ -184,7 +188,7 @@ inline void accumulate(
 
     for (size_t i = 0; i < code_length; i += 64) {
         c = _mm256_loadu_si256((__m256i*)&codes[i]);
-        lut = _mm256_loadu_si256((__m256i*)&lut[i]);
+        lut = _mm256_loadu_si256((__m256i*)&lp_table[i]);
         lo = _mm256_and_si256(c, low_mask);
         hi = _mm256_and_si256(_mm256_srli_epi16(c, 4), low_mask);
 
@@ -197,7 +201,7 @@ inline void accumulate(
         accu3 = _mm256_add_epi16(accu3, _mm256_srli_epi16(res_hi, 8));
 
         c = _mm256_loadu_si256((__m256i*)&codes[i + 32]);
-        lut = _mm256_loadu_si256((__m256i*)&lut[i + 32]);
+        lut = _mm256_loadu_si256((__m256i*)&lp_table[i + 32]);
         lo = _mm256_and_si256(c, low_mask);
         hi = _mm256_and_si256(_mm256_srli_epi16(c, 4), low_mask);
 
@@ -223,12 +227,68 @@ inline void accumulate(
         _mm256_blend_epi32(accu2, accu3, 0xF0)
     );
     _mm256_storeu_si256((__m256i*)&result[16], dis1);
+#elif defined(__ARM_NEON)
+    // vqtbl1q_u8 works on a single 128-bit register, thus each iteration handles one
+    // codebook (4 dims) and no cross-lane reduction is needed at the end
+    const uint8x16_t lo_mask = vdupq_n_u8(0x0f);
+    uint16x8_t accu0 = vdupq_n_u16(0);
+    uint16x8_t accu1 = vdupq_n_u16(0);
+    uint16x8_t accu2 = vdupq_n_u16(0);
+    uint16x8_t accu3 = vdupq_n_u16(0);
+
+    for (size_t i = 0; i < code_length; i += 16) {
+        uint8x16_t c = vld1q_u8(&codes[i]);
+        uint8x16_t lut = vld1q_u8(&lp_table[i]);
+        uint8x16_t lo = vandq_u8(c, lo_mask);  // code of vector 0 to 15
+        uint8x16_t hi = vshrq_n_u8(c, 4);      // code of vector 16 to 31
+
+        uint16x8_t res_lo = vreinterpretq_u16_u8(vqtbl1q_u8(lut, lo));
+        uint16x8_t res_hi = vreinterpretq_u16_u8(vqtbl1q_u8(lut, hi));
+
+        accu0 = vaddq_u16(accu0, res_lo);
+        accu1 = vaddq_u16(accu1, vshrq_n_u16(res_lo, 8));
+        accu2 = vaddq_u16(accu2, res_hi);
+        accu3 = vaddq_u16(accu3, vshrq_n_u16(res_hi, 8));
+    }
+    // accu0: 0-7, accu1: 8-15, accu2: 16-23, accu3: 24-31
+    accu0 = vsubq_u16(accu0, vshlq_n_u16(accu1, 8));
+    accu2 = vsubq_u16(accu2, vshlq_n_u16(accu3, 8));
+
+    vst1q_u16(result, accu0);
+    vst1q_u16(result + 8, accu1);
+    vst1q_u16(result + 16, accu2);
+    vst1q_u16(result + 24, accu3);
 #else
-    std::cerr << "no avx simd supported!\n";
-    exit(1);
+    std::memset(result, 0, sizeof(uint16_t) * kBatchSize);
+    for (size_t i = 0; i < code_length; i += 16) {
+        for (size_t j = 0; j < 16; ++j) {
+            uint8_t c = codes[i + j];
+            result[kPerm0[j]] += lp_table[i + (c & 15)];
+            result[kPerm0[j] + 16] += lp_table[i + (c >> 4)];
+        }
+    }
 #endif
 }
 
+// accumulate one block with an unquantized float lookup table, dim % 16 == 0. Used when
+// the query is not quantized; the layout of lp_table matches pack_lut
+inline void accumulate_float(
+    const uint8_t* __restrict__ codes,
+    const float* __restrict__ lp_table,
+    float* __restrict__ result,
+    size_t dim
+) {
+    size_t code_length = dim << 2;
+    std::memset(result, 0, sizeof(float) * kBatchSize);
+    for (size_t i = 0; i < code_length; i += 16) {
+        for (size_t j = 0; j < 16; ++j) {
+            uint8_t c = codes[i + j];
+            result[kPerm0[j]] += lp_table[i + (c & 15)];
+            result[kPerm0[j] + 16] += lp_table[i + (c >> 4)];
+        }
+    }
+}
+
 // pack lookup table for fastscan, for each 4 dim, we have 16 (2^4) different results
 // ! dim % 4 == 0
 template <typename T>
@@ -1,6 +1,10 @@
 #pragma once
 
+#if defined(__SSE2__)
 #include <immintrin.h>
+#elif defined(__ARM_NEON)
+#include <arm_neon.h>
+#endif
 
 #include <cassert>
 #include <cstdint>
@@ -8,6 +12,8 @@
 #include <cstdlib>
 #include <cstring>
 
+#include "fastscan/fastscan.hpp"
+
 namespace rabitqlib::fastscan {
 /**
  * @brief Change u16 lookup table to u8. Since we use more bits (higher accuracy)
@@ -19,15 +25,9 @@ inline void transfer_lut_hacc(const uint16_t* lut, size_t dim, uint8_t* hc_lut)
     size_t num_codebook = dim >> 2;
 
     for (size_t i = 0; i < num_codebook; i++) {
-        // avx2 - 256, avx512 - 512
-#if defined(__AVX512F__)
+        // the layout always follows 512-bit registers, kernels with narrower registers
+        // (AVX2, NEON, scalar) consume each 512-bit group lane by lane
         constexpr size_t kRegBits = 512;
-#elif defined(__AVX2__)
-        constexpr size_t kRegBits = 256;
-#else
-        static_assert(false, "At least requried AVX2 for using fastscan\n");
-        exit(1);
-#endif
         constexpr size_t kLaneBits = 128;
         constexpr size_t kByteBits = 8;
 
@@ -64,6 +64,7 @@ inline void accumulate_hacc(
     int32_t* accu_res,
     size_t dim
 ) {
+#if defined(__AVX512F__)
     __m512i low_mask = _mm512_set1_epi8(0xf);
     __m512i accu[2][4];
 
@@ -142,5 +143,129 @@ inline void accumulate_hacc(
 
     _mm512_storeu_epi32(accu_res, res[0]);
     _mm512_storeu_epi32(accu_res + 16, res[1]);
+#elif defined(__AVX2__)
+    const __m256i lo_mask = _mm256_set1_epi8(0x0f);
+    __m256i accu[2][4];
+
+    for (auto& a : accu) {
+        for (auto& reg : a) {
+            reg = _mm256_setzero_si256();
+        }
+    }
+
+    size_t num_codebook = dim >> 2;
+
+    // two codebooks per register, their tables are adjacent in the 512-bit layout of
+    // transfer_lut_hacc
+    for (size_t m = 0; m < num_codebook; m += 2) {
+        __m256i c = _mm256_loadu_si256(reinterpret_cast<const __m256i*>(codes + (m * 16)));
+        __m256i lo = _mm256_and_si256(c, lo_mask);
+        __m256i hi = _mm256_and_si256(_mm256_srli_epi16(c, 4), lo_mask);
+
+        const uint8_t* lut_ptr = hc_lut + (m / 4 * 128) + ((m % 4) * 16);
+        for (auto& i : accu) {
+            __m256i lut = _mm256_loadu_si256(reinterpret_cast<const __m256i*>(lut_ptr));
+
+            __m256i res_lo = _mm256_shuffle_epi8(lut, lo);
+            __m256i res_hi = _mm256_shuffle_epi8(lut, hi);
+
+            i[0] = _mm256_add_epi16(i[0], res_lo);
+            i[1] = _mm256_add_epi16(i[1], _mm256_srli_epi16(res_lo, 8));
+
+            i[2] = _mm256_add_epi16(i[2], res_hi);
+            i[3] = _mm256_add_epi16(i[3], _mm256_srli_epi16(res_hi, 8));
+
+            lut_ptr += 64;
+        }
+    }
+
+    // fold the two codebooks of every register, then as in the NEON path
+    __m128i res[2][4];
+    for (size_t i = 0; i < 2; ++i) {
+        for (size_t j = 0; j < 4; ++j) {
+            res[i][j] = _mm_add_epi16(
+                _mm256_castsi256_si128(accu[i][j]), _mm256_extracti128_si256(accu[i][j], 1)
+            );
+        }
+        res[i][0] = _mm_sub_epi16(res[i][0], _mm_slli_epi16(res[i][1], 8));
+        res[i][2] = _mm_sub_epi16(res[i][2], _mm_slli_epi16(res[i][3], 8));
+    }
+    // shift res of high, add res of low
+    for (size_t j = 0; j < 4; ++j) {
+        __m256i lo = _mm256_cvtepu16_epi32(res[0][j]);
+        __m256i hi = _mm256_cvtepu16_epi32(res[1][j]);
+        _mm256_storeu_si256(
+            reinterpret_cast<__m256i*>(accu_res + (j * 8)),
+            _mm256_add_epi32(lo, _mm256_slli_epi32(hi, 8))
+        );
+    }
+#elif defined(__ARM_NEON)
+    const uint8x16_t lo_mask = vdupq_n_u8(0x0f);
+    uint16x8_t accu[2][4];
+
+    for (auto& a : accu) {
+        for (auto& reg : a) {
+            reg = vdupq_n_u16(0);
+        }
+    }
+
+    size_t num_codebook = dim >> 2;
+
+    for (size_t m = 0; m < num_codebook; ++m) {
+        uint8x16_t c = vld1q_u8(codes + (m * 16));
+        uint8x16_t lo = vandq_u8(c, lo_mask);
+        uint8x16_t hi = vshrq_n_u8(c, 4);
+
+        // position of current codebook in the 512-bit layout of transfer_lut_hacc
+        const uint8_t* lut_ptr = hc_lut + (m / 4 * 128) + ((m % 4) * 16);
+        for (auto& i : accu) {
+            uint8x16_t lut = vld1q_u8(lut_ptr);
+
+            uint16x8_t res_lo = vreinterpretq_u16_u8(vqtbl1q_u8(lut, lo));
+            uint16x8_t res_hi = vreinterpretq_u16_u8(vqtbl1q_u8(lut, hi));
+
+            i[0] = vaddq_u16(i[0], res_lo);
+            i[1] = vaddq_u16(i[1], vshrq_n_u16(res_lo, 8));
+
+            i[2] = vaddq_u16(i[2], res_hi);
+            i[3] = vaddq_u16(i[3], vshrq_n_u16(res_hi, 8));
+
+            lut_ptr += 64;
+        }
+    }
+
+    uint16_t res[2][kBatchSize];
+    for (size_t i = 0; i < 2; ++i) {
+        accu[i][0] = vsubq_u16(accu[i][0], vshlq_n_u16(accu[i][1], 8));
+        accu[i][2] = vsubq_u16(accu[i][2], vshlq_n_u16(accu[i][3], 8));
+        for (size_t j = 0; j < 4; ++j) {
+            vst1q_u16(res[i] + (j * 8), accu[i][j]);
+        }
+    }
+    // shift res of high, add res of low
+    for (size_t j = 0; j < kBatchSize; j += 4) {
+        int32x4_t lo = vreinterpretq_s32_u32(vmovl_u16(vld1_u16(res[0] + j)));
+        int32x4_t hi = vreinterpretq_s32_u32(vmovl_u16(vld1_u16(res[1] + j)));
+        vst1q_s32(accu_res + j, vaddq_s32(lo, vshlq_n_s32(hi, 8)));
+    }
+#else
+    uint16_t res[2][kBatchSize] = {};
+    size_t num_codebook = dim >> 2;
+
+    for (size_t m = 0; m < num_codebook; ++m) {
+        const uint8_t* lut_lo = hc_lut + (m / 4 * 128) + ((m % 4) * 16);
+        const uint8_t* lut_hi = lut_lo + 64;
+        for (size_t j = 0; j < 16; ++j) {
+            uint8_t c = codes[(m * 16) + j];
+            res[0][kPerm0[j]] += lut_lo[c & 15];
+            res[0][kPerm0[j] + 16] += lut_lo[c >> 4];
+            res[1][kPerm0[j]] += lut_hi[c & 15];
+            res[1][kPerm0[j] + 16] += lut_hi[c >> 4];
+        }
+    }
+    for (size_t j = 0; j < kBatchSize; ++j) {
+        accu_res[j] = static_cast<int32_t>(res[0][j]) + (static_cast<int32_t>(res[1][j]) << 8);
+    }
+#endif
 }
 }  // namespace rabitqlib::fastscan
\ No newline at end of file
@@ -33,8 +33,13 @@ inline void split_batch_estdist(
 ) {
     ConstBatchDataMap<float> cur_batch(batch_data, padded_dim);
     RowMajorArray<int32_t> accu_arr(1, fastscan::kBatchSize);
+    RowMajorArrayMap<float> ip_x0_qr_arr(ip_x0_qr, 1, fastscan::kBatchSize);
 
-    if (use_hacc) {
+    if (q_obj.lut_bits() == Lut<float>::kExact) {
+        fastscan::accumulate_float(
+            cur_batch.bin_code(), q_obj.lut_float(), ip_x0_qr, padded_dim
+        );
+    } else if (use_hacc) {
         std::array<int32_t, fastscan::kBatchSize> accu_res;
         fastscan::accumulate_hacc(
             cur_batch.bin_code(), q_obj.lut(), accu_res.data(), padded_dim
@@ -59,10 +64,11 @@ inline void split_batch_estdist(
     ConstRowMajorArrayMap<float> f_error_arr(cur_batch.f_error(), 1, fastscan::kBatchSize);
 
     RowMajorArrayMap<float> est_dist_arr(est_distance, 1, fastscan::kBatchSize);
-    RowMajorArrayMap<float> ip_x0_qr_arr(ip_x0_qr, 1, fastscan::kBatchSize);
     RowMajorArrayMap<float> low_dist_arr(low_distance, 1, fastscan::kBatchSize);
 
-    ip_x0_qr_arr = q_obj.delta() * (accu_arr.template cast<float>()) + q_obj.sum_vl_lut();
+    if (q_obj.lut_bits() != Lut<float>::kExact) {
+        ip_x0_qr_arr = q_obj.delta() * (accu_arr.template cast<float>()) + q_obj.sum_vl_lut();
+    }
 
     est_dist_arr =
         f_add_arr + q_obj.g_add() + f_rescale_arr * (ip_x0_qr_arr + q_obj.k1xsumq());
@@ -102,6 +108,32 @@ inline float split_distance_boosting(
     return ex_dist;
 }
 
+/**
+ * @brief <x0, q_r> of a batch of 1-bit codes, at the precision of the query's lookup table
+ */
+template <typename T>
+inline void batch_ip_x0_qr(
+    const uint8_t* bin_code, const BatchQuery<T>& q_obj, size_t padded_dim, float* ip_x0_qr
+) {
+    if (q_obj.lut_bits() == Lut<T>::kExact) {
+        fastscan::accumulate_float(bin_code, q_obj.lut_float(), ip_x0_qr, padded_dim);
+        return;
+    }
+    if (q_obj.lut_bits() == Lut<T>::kNumBitsHacc) {
+        std::array<int32_t, fastscan::kBatchSize> accu_res;
+        fastscan::accumulate_hacc(bin_code, q_obj.lut(), accu_res.data(), padded_dim);
+        for (size_t i = 0; i < fastscan::kBatchSize; ++i) {
+            ip_x0_qr[i] = q_obj.delta() * static_cast<float>(accu_res[i]) + q_obj.sum_vl_lut();
+        }
+        return;
+    }
+    std::array<uint16_t, fastscan::kBatchSize> accu_res;
+    fastscan::accumulate(bin_code, q_obj.lut(), accu_res.data(), padded_dim);
+    for (size_t i = 0; i < fastscan::kBatchSize; ++i) {
+        ip_x0_qr[i] = q_obj.delta() * static_cast<float>(accu_res[i]) + q_obj.sum_vl_lut();
+    }
+}
+
 /**
  * @brief Batch distance estimation for qg. Here, we do not need intermediate results and
  * lower bound
@@ -110,21 +142,19 @@ template <typename T, typename TA = uint16_t>
 inline void qg_batch_estdist(
     const char* batch_data, const BatchQuery<T>& q_obj, size_t padded_dim, T* est_distance
 ) {
-    std::vector<TA> accu_res(fastscan::kBatchSize);
-
     ConstQGBatchDataMap<T> cur_batch(batch_data, padded_dim);
 
-    fastscan::accumulate(cur_batch.bin_code(), q_obj.lut(), accu_res.data(), padded_dim);
+    std::array<float, fastscan::kBatchSize> ip_x0_qr;
+    batch_ip_x0_qr(cur_batch.bin_code(), q_obj, padded_dim, ip_x0_qr.data());
 
-    ConstRowMajorArrayMap<TA> ip_arr(accu_res.data(), 1, fastscan::kBatchSize);
+    ConstRowMajorArrayMap<float> ip_arr(ip_x0_qr.data(), 1, fastscan::kBatchSize);
     ConstRowMajorArrayMap<T> f_add_arr(cur_batch.f_add(), 1, fastscan::kBatchSize);
     ConstRowMajorArrayMap<T> f_rescale_arr(cur_batch.f_rescale(), 1, fastscan::kBatchSize);
 
     RowMajorArrayMap<T> est_dist_arr(est_distance, 1, fastscan::kBatchSize);
 
     est_dist_arr = f_add_arr + q_obj.g_add() +
-                   f_rescale_arr * (q_obj.delta() * (ip_arr.template cast<T>()) +
-                                    q_obj.sum_vl_lut() + q_obj.k1xsumq());
+                   f_rescale_arr * (ip_arr.template cast<T>() + q_obj.k1xsumq());
 }
 
 /**
@@ -135,21 +165,18 @@ template <typename TA = uint16_t>
 inline void batch_bin_estdist(
     const char* batch_data, const BatchQuery<float>& q_obj, size_t padded_dim, float* est_distance
 ) {
-    std::vector<TA> accu_res(fastscan::kBatchSize);
-
     ConstQGBatchDataMap<float> cur_batch(batch_data, padded_dim);
 
-    fastscan::accumulate(cur_batch.bin_code(), q_obj.lut(), accu_res.data(), padded_dim);
+    std::array<float, fastscan::kBatchSize> ip_x0_qr;
+    batch_ip_x0_qr(cur_batch.bin_code(), q_obj, padded_dim, ip_x0_qr.data());
 
-    ConstRowMajorArrayMap<TA> ip_arr(accu_res.data(), 1, fastscan::kBatchSize);
+    ConstRowMajorArrayMap<float> ip_arr(ip_x0_qr.data(), 1, fastscan::kBatchSize);
     ConstRowMajorArrayMap<float> f_add_arr(cur_batch.f_add(), 1, fastscan::kBatchSize);
     ConstRowMajorArrayMap<float> f_rescale_arr(cur_batch.f_rescale(), 1, fastscan::kBatchSize);
 
     RowMajorArrayMap<float> est_dist_arr(est_distance, 1, fastscan::kBatchSize);
 
-    est_dist_arr = f_add_arr + q_obj.g_add() +
-                   f_rescale_arr * (q_obj.delta() * (ip_arr.template cast<float>()) +
-                                    q_obj.sum_vl_lut() + q_obj.k1xsumq());
+    est_dist_arr = f_add_arr + q_obj.g_add() + f_rescale_arr * (ip_arr + q_obj.k1xsumq());
 }
 
 /**
@@ -198,14 +225,27 @@ inline void split_single_estdist(
 ) {
     ConstBinDataMap<float> cur_bin(bin_data, padded_dim);
 
-    ip_x0_qr = warmup_ip_x0_q<SplitSingleQuery<float>::kNumBits>(
-        cur_bin.bin_code(),
-        q_obj.query_bin(),
-        q_obj.delta(),
-        q_obj.vl(),
-        padded_dim,
-        SplitSingleQuery<float>::kNumBits
-    );
+    if (q_obj.query_bits() == SplitSingleQuery<float>::kExact) {
+        ip_x0_qr = mask_ip_x0_q(q_obj.rotated_query(), cur_bin.bin_code(), padded_dim);
+    } else if (q_obj.query_bits() == SplitSingleQuery<float>::kNumBits) {
+        ip_x0_qr = warmup_ip_x0_q<SplitSingleQuery<float>::kNumBits>(
+            cur_bin.bin_code(),
+            q_obj.query_bin(),
+            q_obj.delta(),
+            q_obj.vl(),
+            padded_dim,
+            SplitSingleQuery<float>::kNumBits
+        );
+    } else {
+        ip_x0_qr = warmup_ip_x0_q_dyn(
+            cur_bin.bin_code(),
+            q_obj.query_bin(),
+            q_obj.delta(),
+            q_obj.vl(),
+            padded_dim,
+            q_obj.query_bits()
+        );
+    }
 
     est_dist = cur_bin.f_add() + g_add + cur_bin.f_rescale() * (ip_x0_qr + q_obj.k1xsumq());
 
@@ -227,17 +267,31 @@ inline void single_centroid_estdist(
 ) {
     ConstBinDataMap<float> cur_bin(bin_data, padded_dim);
 
-    ip_x0_qr = warmup_ip_x0_q<SingleCentroidQuery<float>::kNumBits>(
-        cur_bin.bin_code(),
-        q_obj.query_bin(),
-        q_obj.delta(),
-        q_obj.vl(),
-        padded_dim,
-        SingleCentroidQuery<float>::kNumBits
-    );
-
+    if (q_obj.query_bits() == SingleCentroidQuery<float>::kExact) {
+        ip_x0_qr = mask_ip_x0_q(q_obj.rotated_query(), cur_bin.bin_code(), padded_dim);
+    } else if (q_obj.query_bits() == SingleCentroidQuery<float>::kNumBits) {
+        ip_x0_qr = warmup_ip_x0_q<SingleCentroidQuery<float>::kNumBits>(
+            cur_bin.bin_code(),
+            q_obj.query_bin(),
+            q_obj.delta(),
+            q_obj.vl(),
+            padded_dim,
+            SingleCentroidQuery<float>::kNumBits
+        );
+    } else {
+        ip_x0_qr = warmup_ip_x0_q_dyn(
+            cur_bin.bin_code(),
+            q_obj.query_bin(),
+            q_obj.delta(),
+            q_obj.vl(),
+            padded_dim,
+            q_obj.query_bits()
+        );
+    }
 
     est_dist = cur_bin.f_add() + g_add + cur_bin.f_rescale() * (ip_x0_qr + q_obj.k1xsumq());
+
+    low_dist = est_dist - cur_bin.f_error() * g_error;
 };
 
 /**
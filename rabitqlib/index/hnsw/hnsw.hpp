@@ -1,7 +1,9 @@
 // HNSW is developed from the [HNSW library](https://github.com/nmslib/hnswlib)
 #pragma once
 
+#if defined(__SSE2__)
 #include <immintrin.h>
+#endif
 #include <omp.h>
 
 #include <atomic>
@@ -9,9 +11,12 @@
 #include <cstddef>
 #include <cstdlib>
 #include <fstream>
+#include <functional>
 #include <iostream>
+#include <limits>
 #include <memory>
 #include <mutex>
+#include <numeric>
 #include <unordered_map>
 #include <vector>
 
@@ -21,8 +26,12 @@
 #include "index/query.hpp"
 #include "quantization/data_layout.hpp"
 #include "quantization/rabitq.hpp"
+#include "utils/budget.hpp"
 #include "utils/buffer.hpp"
+#include "utils/build_monitor.hpp"
+#include "utils/log.hpp"
 #include "utils/rotator.hpp"
+#include "utils/search_stats.hpp"
 #include "utils/space.hpp"
 #include "utils/tools.hpp"
 #include "utils/visited_pool.hpp"
@@ -46,11 +55,125 @@ class HierarchicalNSW {
     void save(const char*) const;
     void load(const char*, MetricType metric_type_input);
 
-    void construct(size_t, const float*, size_t, const float*, PID*, size_t, bool);
+    // Progress of the "insert vectors" phase is reported and cancellation checked every
+    // kBuildBatch insertions, or every chunk in construct_quantized()
+    void construct(
+        size_t, const float*, size_t, const float*, PID*, size_t, bool, const BuildMonitor&
+    );
+
+    // Writes the raw vectors of the given labels, returns false on failure. Called from
+    // several threads at once when exact re-scoring is on.
+    using FetchRows = std::function<bool(const PID*, size_t, float*)>;
+    // Construction without the dataset resident: raw vectors are fetched chunk_size rows at
+    // a time, only to quantize them, and edges are chosen on distances estimated from the
+    // stored codes. A fraction exact_sample of the insertions re-scores its level-0
+    // candidates with fetched raw vectors. Cluster ids are the nearest centroids under the
+    // metric of the index.
+    void construct_quantized(
+        size_t,
+        const float*,
+        size_t,
+        const FetchRows&,
+        size_t,
+        float,
+        size_t,
+        bool,
+        const BuildMonitor&
+    );
+    // Online insertion after construction: the vector is quantized with the existing
+    // rotator and centroids and linked on distances estimated from the codes. Safe to
+    // call concurrently with search() and with other insertions, once reserve() opened
+    // the index for insertion.
+    void insert(const float*, PID);
+
+    // Room for max_elements in total, not safe concurrently with anything else. Opens the
+    // index for insertion, from then on searches lock the neighbour lists they read.
+    void reserve(size_t);
+
+    // Use a copy of rotator, e.g. one shared with other indexes or a PcaRotator that changes
+    // the padded dim, must precede construction
+    void set_rotator(const Rotator<float>& rotator) {
+        Rotator<float>* copy = copy_matching_rotator(*rotator_, rotator);
+        delete rotator_;
+        rotator_ = copy;
+        padded_dim_ = rotator_->size();
+        type_ = rotator_->type();
+        init_layout();
+    }
+
+    // query_bits is the precision of the quantized query, SplitSingleQuery<float>::kExact
+    // keeps it in float. budgets and stats, if not null, hold one entry for each query.
     std::vector<std::vector<std::pair<float, PID>>> search(
-        const float*, size_t, size_t, size_t, size_t
+        const float*,
+        size_t,
+        size_t,
+        size_t,
+        size_t,
+        size_t = SplitSingleQuery<float>::kNumBits,
+        SearchBudget* budgets = nullptr,
+        SearchStats* stats = nullptr
     );
 
+    [[nodiscard]] size_t num_points() const { return cur_element_count_; }
+
+    [[nodiscard]] size_t capacity() const { return max_elements_; }
+
+    [[nodiscard]] size_t dim() const { return dim_; }
+
+    [[nodiscard]] size_t padded_dim() const { return padded_dim_; }
+
+    [[nodiscard]] size_t num_clusters() const { return num_cluster_; }
+
+    [[nodiscard]] size_t ex_bits() const { return ex_bits_; }
+
+    [[nodiscard]] size_t max_degree() const { return M_; }
+
+    [[nodiscard]] size_t ef_construction() const { return ef_construction_; }
+
+    [[nodiscard]] MetricType metric_type() const { return metric_type_; }
+
+    [[nodiscard]] PID entry_point() const { return enterpoint_node_; }
+
+    // num of levels of the graph, 0 if it is empty
+    [[nodiscard]] size_t num_levels() const { return static_cast<size_t>(maxlevel_ + 1); }
+
+    // out-degree of every element present on the given level
+    [[nodiscard]] std::vector<size_t> level_degrees(int level) const {
+        std::vector<size_t> degrees;
+        for (PID i = 0; i < cur_element_count_; ++i) {
+            if (element_levels_[i] < level) {
+                continue;
+            }
+            const PID* data = level == 0 ? get_linklist0(i) : get_linklist(i, level);
+            degrees.push_back(get_list_count(data));
+        }
+        return degrees;
+    }
+
+    // bytes used by the different parts of the graph
+    [[nodiscard]] size_t level0_links_bytes() const {
+        return size_links_level0_ * cur_element_count_;
+    }
+
+    // cluster id and external label of every element
+    [[nodiscard]] size_t labels_bytes() const { return 2 * sizeof(PID) * cur_element_count_; }
+
+    [[nodiscard]] size_t bin_data_bytes() const { return size_bin_data_ * cur_element_count_; }
+
+    [[nodiscard]] size_t ex_data_bytes() const { return size_ex_data_ * cur_element_count_; }
+
+    [[nodiscard]] size_t upper_links_bytes() const {
+        size_t total = 0;
+        for (PID i = 0; i < cur_element_count_; ++i) {
+            total += size_links_per_element_ * element_levels_[i];
+        }
+        return total;
+    }
+
+    [[nodiscard]] size_t centroids_bytes() const {
+        return sizeof(float) * num_cluster_ * padded_dim_;
+    }
+
     const float* rawDataPtr_{nullptr};
 
     struct ResultRecord {
@@ -106,6 +229,7 @@ class HierarchicalNSW {
 
    private:
     static constexpr PID kMaxLabelOperationLock = 65536;
+    static constexpr size_t kBuildBatch = 1 << 14;  // insertions between progress reports
     size_t max_elements_{0};
     mutable std::atomic<size_t> cur_element_count_{0};  // current number of elements
     size_t size_data_per_element_{0};
@@ -125,6 +249,7 @@ class HierarchicalNSW {
 
     std::mutex global_;
     std::vector<std::mutex> link_list_locks_;
+    bool insertable_ = false;  // set by reserve(), searches lock only then
 
     PID enterpoint_node_{0};
 
@@ -142,9 +267,22 @@ class HierarchicalNSW {
     size_t num_cluster_{0};
     size_t dim_{0};
     size_t padded_dim_{0};
+    RotatorType type_{RotatorType::FhtKacRotator};
+
+    // files start with kFileMagic and kFileVersion, files saved before have neither and
+    // no rotator type, their first field is max_elements_ and their rotator a FhtKacRotator
+    static constexpr uint64_t kFileMagic = 0x0048515449424152;  // "RABITQH\0"
+    static constexpr uint32_t kFileVersion = 1;
 
     char* centroids_memory_{nullptr};
 
+    // tells the per-thread build queries of this index from those of other indices
+    size_t instance_id_{next_instance_id()};
+
+    // State of construct_quantized(), empty otherwise
+    float exact_sample_{0};
+    const FetchRows* fetch_rows_{nullptr};
+
     mutable std::mutex label_lookup_lock_;  // lock for label_lookup_
     std::unordered_map<PID, PID> label_lookup_;
 
@@ -174,6 +312,9 @@ class HierarchicalNSW {
 
     float (*raw_dist_func_)(const float* __restrict__, const float* __restrict__, size_t);
 
+    // element layout and level 0 memory from padded_dim_, before any insertion
+    void init_layout();
+
     void free_memory() {
         free(data_level0_memory_);
         data_level0_memory_ = nullptr;
@@ -293,7 +434,14 @@ class HierarchicalNSW {
         std::vector<float>&, SplitSingleQuery<float>&, PID, HierarchicalNSW::EstimateRecord&
     ) const;
 
-    maxheap<std::pair<float, PID>> search_knn(const float*, size_t);
+    maxheap<std::pair<float, PID>> search_knn(
+        const float*,
+        size_t,
+        size_t,
+        size_t,
+        SearchBudget* budget = nullptr,
+        SearchStats* stats = nullptr
+    );
 
     void searchBaseLayerST_AdaptiveRerankOpt(
         PID ep_id,
@@ -302,12 +450,87 @@ class HierarchicalNSW {
         SplitSingleQuery<float>& query_wrapper,
         std::vector<float>& q_to_centroids,  // preprocess
         const float* query,
-        BoundedKNN& boundedKNN
+        BoundedKNN& boundedKNN,
+        SearchBudget* budget,
+        SearchStats* stats
     );
 
+    // Preprocess of a query - its distance to all centroids, the first half as g_add and
+    // the second half as g_error for METRIC_IP
+    void query_to_centroids(const float* rotated_query, std::vector<float>& q_to_centroids)
+        const;
+
+    // Query side of the estimated distances in construct_quantized(), the rotated vector of
+    // the vertex being inserted or the vector reconstructed from the codes of another one
+    struct BuildQuery {
+        size_t build = 0;
+        PID internal_id = 0;
+        std::vector<float> rotated;
+        std::vector<float> q_to_centroids;
+        std::unique_ptr<SplitSingleQuery<float>> query;
+    };
+
+    static size_t next_instance_id() {
+        static std::atomic<size_t> num_instances{0};
+        return ++num_instances;
+    }
+
+    static BuildQuery& inserted_build_query() {
+        thread_local BuildQuery query;
+        return query;
+    }
+
+    void init_build_query(BuildQuery& query, PID internal_id) const {
+        query.build = instance_id_;
+        query.internal_id = internal_id;
+        query.query = std::make_unique<SplitSingleQuery<float>>(
+            query.rotated.data(),
+            padded_dim_,
+            ex_bits_,
+            quant::RabitqConfig(),
+            metric_type_,
+            SplitSingleQuery<float>::kExact
+        );
+        query_to_centroids(query.rotated.data(), query.q_to_centroids);
+    }
+
+    BuildQuery& get_build_query(PID internal_id) {
+        BuildQuery& inserted = inserted_build_query();
+        if (inserted.build == instance_id_ && inserted.internal_id == internal_id) {
+            return inserted;
+        }
+        thread_local BuildQuery other;
+        if (other.build != instance_id_ || other.internal_id != internal_id) {
+            other.rotated.resize(padded_dim_);
+            quant::reconstruct_split_single(
+                get_bindata_by_internalid(internal_id),
+                get_exdata_by_internalid(internal_id),
+                reinterpret_cast<float*>(centroids_memory_) +
+                    (get_clusterid_by_internalid(internal_id) * padded_dim_),
+                padded_dim_,
+                ex_bits_,
+                other.rotated.data(),
+                metric_type_
+            );
+            init_build_query(other, internal_id);
+        }
+        return other;
+    }
+
     // Construction
-    // Currently only support index construction with non-quantized vectors
+    // Distances of raw vectors, or estimated from the codes of obj1 in construct_quantized()
+    // and insert()
     float get_data_dist(PID obj1, PID obj2) {
+        if (rawDataPtr_ == nullptr) {
+            BuildQuery& query = get_build_query(obj2);
+            EstimateRecord res;
+            if (ex_bits_ > 0) {
+                get_full_est(query.q_to_centroids, *query.query, obj1, res);
+            } else {
+                get_bin_est(query.q_to_centroids, *query.query, obj1, res);
+            }
+            return res.est_dist;
+        }
         PID label1 = get_external_label(obj1);
         PID label2 = get_external_label(obj2);
         return raw_dist_func_(
@@ -315,7 +538,47 @@ class HierarchicalNSW {
         );
     }
 
-    void add_point(PID, PID, const quant::RabitqConfig&);
+    // neighbours of an element on a level, the count first. In an index open for
+    // insertion, insert() may be rewriting the list, so it is copied under its lock into
+    // buf of maxM0_ + 2 entries.
+    const PID* search_linklist(PID internal_id, int level, std::vector<PID>& buf) {
+        const PID* data =
+            level == 0 ? get_linklist0(internal_id) : get_linklist(internal_id, level);
+        if (!insertable_) {
+            return data;
+        }
+        std::unique_lock<std::mutex> lock(link_list_locks_[internal_id]);
+        std::copy(data, data + 1 + get_list_count(data), buf.begin());
+        return buf.data();
+    }
+
+    void prefetch_build_data(PID internal_id) const {
+        if (rawDataPtr_ == nullptr) {
+            rabitqlib::memory::mem_prefetch_l1(get_bindata_by_internalid(internal_id), 2);
+        } else {
+            rabitqlib::memory::mem_prefetch_l1(
+                reinterpret_cast<const char*>(
+                    rawDataPtr_ + (get_external_label(internal_id) * dim_)
+                ),
+                padded_dim_ / 16
+            );
+        }
+    }
+
+    // deterministic sample of the insertions that re-score their candidates exactly
+    [[nodiscard]] bool sampled_for_exact(PID label) const {
+        uint32_t hash = label * 2654435761U;
+        hash ^= hash >> 16;
+        return static_cast<double>(hash) < exact_sample_ * 4294967296.0;
+    }
+
+    void rescore_exact(const float* vec, maxheap<std::pair<float, PID>>& top_candidates);
+
+    void init_centroids(size_t, const float*);
+
+    void end_quantized_construction();
+
+    void add_point(PID, const float*, PID, const quant::RabitqConfig&);
 
     maxheap<std::pair<float, PID>> search_base_layer(PID, PID, int);
 
@@ -343,7 +606,7 @@ inline HierarchicalNSW::HierarchicalNSW(
     max_elements_ = max_elements;
     dim_ = dim;
     rotator_ = choose_rotator<float>(
-        dim, RotatorType::FhtKacRotator, round_up_to_multiple(dim_, 64)
+        dim, RotatorType::FhtKacRotator, round_up_to_multiple(dim_, 64), random_seed
     );
     padded_dim_ = rotator_->size();
     /* check size */
@@ -352,10 +615,12 @@ inline HierarchicalNSW::HierarchicalNSW(
     ex_bits_ = total_bits - 1;
 
     if (total_bits < 1 || total_bits > 9) {
-        std::cerr << "Invalid number of bits for quantization in "
-                     "HierarchicalNSW::HierarchicalNSW\n";
-        std::cerr << "Expected: 1 to 9  Input:" << total_bits << '\n';
-        std::cerr.flush();
+        log_message(
+            LogLevel::Error,
+            "Invalid number of bits for quantization in HierarchicalNSW::HierarchicalNSW, "
+            "expected: 1 to 9  input: ",
+            total_bits
+        );
         exit(1);
     };
 
@@ -366,9 +631,11 @@ inline HierarchicalNSW::HierarchicalNSW(
     if (M <= 10000) {
         M_ = M;
     } else {
-        std::cout << "warning: M parameter exceeds 10000 which may lead to adverse effects."
-                  << '\n';
-        std::cout << "Cap to 10000 will be applied for the rest of the processing." << '\n';
+        log_message(
+            LogLevel::Warn,
+            "M parameter exceeds 10000 which may lead to adverse effects, cap to 10000 will "
+            "be applied for the rest of the processing"
+        );
         M_ = 10000;
     }
 
@@ -377,23 +644,7 @@ inline HierarchicalNSW::HierarchicalNSW(
     ef_construction_ = std::max(ef_construction, M_);
     ef_ = 10;
 
-    size_bin_data_ = BinDataMap<float>::data_bytes(padded_dim_);
-    size_ex_data_ = ExDataMap<float>::data_bytes(padded_dim_, ex_bits_);
-    size_links_level0_ = maxM0_ * sizeof(PID) + sizeof(PID);
-    label_offset_ =
-        size_links_level0_ + sizeof(PID);  // (# of edges + edges) + (cluster_id)
-    offsetBinData_ = label_offset_ +
-                     sizeof(PID);  // (# of edges + edges) + (cluster_id) + (external label)
-    offsetExData_ = offsetBinData_ + size_bin_data_;  // (# of edges + edges) + (cluster_id)
-                                                      // + (external label) + (BinData)
-    size_data_per_element_ =
-        offsetExData_ + size_ex_data_;  // (# of edges + edges) + (cluster_id) + (external
-                                        // label) + (BinData) + (ExData)
-    data_level0_memory_ =
-        reinterpret_cast<char*>(malloc(max_elements_ * size_data_per_element_));
-    if (data_level0_memory_ == nullptr) {
-        throw std::runtime_error("Not enough memory");
-    }
+    init_layout();
 
     level_generator_.seed(random_seed);
     update_probability_generator_.seed(random_seed + 1);
@@ -413,6 +664,27 @@ inline HierarchicalNSW::HierarchicalNSW(
     size_links_per_element_ = maxM_ * sizeof(PID) + sizeof(PID);
     mult_ = 1 / log(1.0 * static_cast<double>(M_));
     revSize_ = 1.0 / mult_;
+}
+
+inline void HierarchicalNSW::init_layout() {
+    size_bin_data_ = BinDataMap<float>::data_bytes(padded_dim_);
+    size_ex_data_ = ExDataMap<float>::data_bytes(padded_dim_, ex_bits_);
+    size_links_level0_ = maxM0_ * sizeof(PID) + sizeof(PID);
+    label_offset_ =
+        size_links_level0_ + sizeof(PID);  // (# of edges + edges) + (cluster_id)
+    offsetBinData_ = label_offset_ +
+                     sizeof(PID);  // (# of edges + edges) + (cluster_id) + (external label)
+    offsetExData_ = offsetBinData_ + size_bin_data_;  // (# of edges + edges) + (cluster_id)
+                                                      // + (external label) + (BinData)
+    size_data_per_element_ =
+        offsetExData_ + size_ex_data_;  // (# of edges + edges) + (cluster_id) + (external
+                                        // label) + (BinData) + (ExData)
+    free(data_level0_memory_);
+    data_level0_memory_ =
+        reinterpret_cast<char*>(malloc(max_elements_ * size_data_per_element_));
+    if (data_level0_memory_ == nullptr) {
+        throw std::runtime_error("Not enough memory");
+    }
 
     this->query_config_ =
         quant::faster_config(padded_dim_, SplitSingleQuery<float>::kNumBits);
@@ -423,6 +695,9 @@ inline HierarchicalNSW::~HierarchicalNSW() { free_memory(); }
 inline void HierarchicalNSW::save(const char* filename) const {
     std::ofstream output(filename, std::ios::binary);
 
+    output.write(reinterpret_cast<const char*>(&kFileMagic), sizeof(uint64_t));
+    output.write(reinterpret_cast<const char*>(&kFileVersion), sizeof(uint32_t));
+
     output.write(reinterpret_cast<const char*>(&max_elements_), sizeof(size_t));
     output.write(reinterpret_cast<const char*>(&cur_element_count_), sizeof(size_t));
 
@@ -449,7 +724,7 @@ inline void HierarchicalNSW::save(const char* filename) const {
     output.write(reinterpret_cast<const char*>(&mult_), sizeof(double));
     output.write(reinterpret_cast<const char*>(&ef_construction_), sizeof(size_t));
 
-    std::cout << "cur_element_count = " << cur_element_count_ << '\n';
+    log_message(LogLevel::Debug, "cur_element_count = ", cur_element_count_);
 
     output.write(
         reinterpret_cast<const char*>(centroids_memory_),
@@ -470,6 +745,7 @@ inline void HierarchicalNSW::save(const char* filename) const {
         }
     }
 
+    output.write(reinterpret_cast<const char*>(&type_), sizeof(type_));
     rotator_->save(output);
     output.close();
 }
@@ -487,6 +763,18 @@ inline void HierarchicalNSW::load(const char* filename, MetricType metric_type_i
         (metric_type_input == METRIC_IP) ? dot_product_dis<float> : euclidean_sqr<float>;
     metric_type_ = (metric_type_input == METRIC_IP) ? METRIC_IP : METRIC_L2;
 
+    uint64_t magic = 0;
+    uint32_t version = 0;
+    input.read(reinterpret_cast<char*>(&magic), sizeof(uint64_t));
+    if (magic == kFileMagic) {
+        input.read(reinterpret_cast<char*>(&version), sizeof(uint32_t));
+        if (version > kFileVersion) {
+            throw std::runtime_error("HierarchicalNSW file saved by a newer version");
+        }
+    } else {
+        input.seekg(0);
+    }
+
     input.read(reinterpret_cast<char*>(&max_elements_), sizeof(size_t));
     input.read(reinterpret_cast<char*>(&cur_element_count_), sizeof(size_t));
 
@@ -525,7 +813,7 @@ inline void HierarchicalNSW::load(const char* filename, MetricType metric_type_i
 
     input.read(data_level0_memory_, cur_element_count_ * size_data_per_element_);
 
-    std::cout << "cur_element_count = " << cur_element_count_ << '\n';
+    log_message(LogLevel::Debug, "cur_element_count = ", cur_element_count_);
 
     std::vector<std::mutex>(max_elements_).swap(link_list_locks_);
     std::vector<std::mutex>(kMaxLabelOperationLock).swap(label_op_locks_);
@@ -562,14 +850,17 @@ inline void HierarchicalNSW::load(const char* filename, MetricType metric_type_i
 
     visited_list_pool_ = std::make_unique<VisitedListPool>(1, max_elements_);
 
-    rotator_ = choose_rotator<float>(
-        dim_, RotatorType::FhtKacRotator, round_up_to_multiple(dim_, 64)
-    );
+    type_ = RotatorType::FhtKacRotator;
+    if (version >= 1) {
+        input.read(reinterpret_cast<char*>(&type_), sizeof(type_));
+    }
+
+    rotator_ = choose_rotator<float>(dim_, type_, round_up_to_multiple(dim_, 64));
+    rotator_->load(input);
     if (rotator_->size() != padded_dim_) {
-        std::cerr << "Bad padded_dim_ for rotator in hnsw.load()\n";
+        log_message(LogLevel::Error, "Bad padded_dim_ for rotator in hnsw.load()");
         exit(1);
     }
-    rotator_->load(input);
     input.close();
 
     this->query_config_ =
@@ -583,8 +874,88 @@ inline void HierarchicalNSW::construct(
     const float* data,
     PID* cluster_ids,
     size_t num_threads = 0,
-    bool faster = false
+    bool faster = false,
+    const BuildMonitor& monitor = BuildMonitor()
 ) {
+    init_centroids(cluster_num, centroids);
+
+    quant::RabitqConfig config;
+    if (faster) {
+        config = quant::faster_config(padded_dim_, ex_bits_ + 1);
+    }
+
+    log_message(LogLevel::Info, "Start HierarchicalNSW construction...");
+    rawDataPtr_ = data;
+    log_message(LogLevel::Debug, "Build edges with non-quantized vectors...");
+    try {
+        monitor.for_batches("insert vectors", data_num, kBuildBatch, [&](size_t begin, size_t end) {
+            rabitqlib::ivf::parallel_for(
+                begin,
+                end,
+                num_threads,
+                [&](size_t idx, size_t /*threadId*/) {
+                    add_point(idx, data + (idx * dim_), cluster_ids[idx], config);
+                }
+            );
+        });
+    } catch (...) {
+        rawDataPtr_ = nullptr;
+        throw;
+    }
+    // the caller owns the data, later insertions work on the codes
+    rawDataPtr_ = nullptr;
+}
+
+inline void HierarchicalNSW::insert(const float* vec, PID label) {
+    if (num_cluster_ == 0 || rawDataPtr_ != nullptr) {
+        throw std::runtime_error("HNSW insertion needs a constructed index");
+    }
+    if (!insertable_) {
+        throw std::runtime_error("HNSW insertion needs reserve() first");
+    }
+    std::vector<float> rotated_data(padded_dim_);
+    rotator_->rotate(vec, rotated_data.data());
+    PID cluster_id = 0;
+    float best = std::numeric_limits<float>::max();
+    for (size_t c = 0; c < num_cluster_; ++c) {
+        const float* centroid =
+            reinterpret_cast<float*>(centroids_memory_) + (c * padded_dim_);
+        float dist = metric_type_ == METRIC_IP
+                         ? -dot_product(rotated_data.data(), centroid, padded_dim_)
+                         : euclidean_sqr(rotated_data.data(), centroid, padded_dim_);
+        if (dist < best) {
+            best = dist;
+            cluster_id = static_cast<PID>(c);
+        }
+    }
+    add_point(label, vec, cluster_id, quant::RabitqConfig());
+}
+
+inline void HierarchicalNSW::reserve(size_t max_elements) {
+    insertable_ = true;
+    if (max_elements <= max_elements_) {
+        return;
+    }
+    char* data_level0 = reinterpret_cast<char*>(
+        realloc(data_level0_memory_, max_elements * size_data_per_element_)
+    );
+    if (data_level0 == nullptr) {
+        throw std::runtime_error("Not enough memory: HNSW failed to reserve elements");
+    }
+    data_level0_memory_ = data_level0;
+    char** link_lists =
+        reinterpret_cast<char**>(realloc(linkLists_, sizeof(void*) * max_elements));
+    if (link_lists == nullptr) {
+        throw std::runtime_error("Not enough memory: HNSW failed to reserve linklists");
+    }
+    linkLists_ = link_lists;
+    element_levels_.resize(max_elements);
+    std::vector<std::mutex>(max_elements).swap(link_list_locks_);
+    max_elements_ = max_elements;
+    visited_list_pool_ = std::make_unique<VisitedListPool>(1, max_elements_);
+}
+
+inline void HierarchicalNSW::init_centroids(size_t cluster_num, const float* centroids) {
     num_cluster_ = cluster_num;
     centroids_memory_ =
         reinterpret_cast<char*>(malloc(num_cluster_ * padded_dim_ * sizeof(float)));
@@ -598,25 +969,106 @@ inline void HierarchicalNSW::construct(
             reinterpret_cast<float*>(centroids_memory_) + (i * padded_dim_)
         );
     }
+}
+
+inline void HierarchicalNSW::construct_quantized(
+    size_t cluster_num,
+    const float* centroids,
+    size_t data_num,
+    const FetchRows& fetch_rows,
+    size_t chunk_size,
+    float exact_sample = 0,
+    size_t num_threads = 0,
+    bool faster = false,
+    const BuildMonitor& monitor = BuildMonitor()
+) {
+    if (chunk_size == 0) {
+        throw std::invalid_argument("chunk_size must be positive");
+    }
+    if (cur_element_count_ + data_num > max_elements_) {
+        throw std::runtime_error("The number of elements exceeds the specified limit");
+    }
+    init_centroids(cluster_num, centroids);
 
     quant::RabitqConfig config;
     if (faster) {
         config = quant::faster_config(padded_dim_, ex_bits_ + 1);
     }
+    exact_sample_ = exact_sample;
+    fetch_rows_ = &fetch_rows;
+
+    log_message(LogLevel::Info, "Start HierarchicalNSW construction...");
+    log_message(LogLevel::Debug, "Build edges with quantized vectors...");
+    std::vector<float> chunk;
+    std::vector<PID> labels;
+    try {
+        for (size_t begin = 0; begin < data_num; begin += chunk_size) {
+            monitor.check();
+            size_t count = std::min(chunk_size, data_num - begin);
+            labels.resize(count);
+            std::iota(labels.begin(), labels.end(), static_cast<PID>(begin));
+            chunk.resize(count * dim_);
+            if (!fetch_rows(labels.data(), count, chunk.data())) {
+                throw std::runtime_error("HNSW failed to fetch raw vectors");
+            }
+            rabitqlib::ivf::parallel_for(
+                0,
+                count,
+                num_threads,
+                [&](size_t idx, size_t /*threadId*/) {
+                    const float* vec = chunk.data() + (idx * dim_);
+                    PID cluster_id = 0;
+                    float best = std::numeric_limits<float>::max();
+                    for (size_t c = 0; c < cluster_num; ++c) {
+                        const float* centroid = centroids + (c * dim_);
+                        float dist = metric_type_ == METRIC_IP
+                                         ? -dot_product(vec, centroid, dim_)
+                                         : euclidean_sqr(vec, centroid, dim_);
+                        if (dist < best) {
+                            best = dist;
+                            cluster_id = static_cast<PID>(c);
+                        }
+                    }
+                    add_point(static_cast<PID>(begin + idx), vec, cluster_id, config);
+                }
+            );
+            monitor.report("insert vectors", begin + count, data_num);
+        }
+    } catch (...) {
+        end_quantized_construction();
+        throw;
+    }
+    end_quantized_construction();
+}
 
-    std::cout << "Start HierarchicalNSW construction..." << '\n';
-    rawDataPtr_ = data;
-    std::cout << "Build edges with non-quantized vectors..." << '\n';
-    rabitqlib::ivf::parallel_for(
-        0,
-        data_num,
-        num_threads,
-        [&](size_t idx, size_t /*threadId*/) { add_point(idx, cluster_ids[idx], config); }
-    );
+inline void HierarchicalNSW::end_quantized_construction() {
+    exact_sample_ = 0;
+    fetch_rows_ = nullptr;
+}
+
+inline void HierarchicalNSW::rescore_exact(
+    const float* vec, maxheap<std::pair<float, PID>>& top_candidates
+) {
+    std::vector<std::pair<float, PID>> candidates;
+    std::vector<PID> labels;
+    while (!top_candidates.empty()) {
+        candidates.push_back(top_candidates.top());
+        labels.push_back(get_external_label(top_candidates.top().second));
+        top_candidates.pop();
+    }
+    std::vector<float> raw(labels.size() * dim_);
+    if (!(*fetch_rows_)(labels.data(), labels.size(), raw.data())) {
+        throw std::runtime_error("HNSW failed to fetch raw vectors");
+    }
+    for (size_t i = 0; i < candidates.size(); ++i) {
+        top_candidates.emplace(
+            raw_dist_func_(vec, raw.data() + (i * dim_), dim_), candidates[i].second
+        );
+    }
 }
 
 inline void HierarchicalNSW::add_point(
-    PID label, PID cluster_id, const quant::RabitqConfig& config
+    PID label, const float* vec, PID cluster_id, const quant::RabitqConfig& config
 ) {
     std::unique_lock<std::mutex> lock_label(get_lable_op_mutex(label));
 
@@ -665,7 +1117,7 @@ inline void HierarchicalNSW::add_point(
 
     // Quantize raw data and initialize quantized data
     std::vector<float> rotated_data(padded_dim_);
-    rotator_->rotate(rawDataPtr_ + (label * dim_), rotated_data.data());
+    rotator_->rotate(vec, rotated_data.data());
     quant::quantize_split_single(
         rotated_data.data(),
         reinterpret_cast<float*>(centroids_memory_) + (cluster_id * padded_dim_),
@@ -676,6 +1128,11 @@ inline void HierarchicalNSW::add_point(
         metric_type_,
         config
     );
+    if (rawDataPtr_ == nullptr) {
+        BuildQuery& inserted = inserted_build_query();
+        inserted.rotated = rotated_data;
+        init_build_query(inserted, cur_c);
+    }
 
     // If the current vertex is at level >0, it needs some space to store the extra edges.
     if (curlevel > 0) {
@@ -721,6 +1178,9 @@ inline void HierarchicalNSW::add_point(
         for (int level = std::min(curlevel, maxlevelcopy); level >= 0; level--) {
             maxheap<std::pair<float, PID>> top_candidates =
                 search_base_layer(curr_obj, cur_c, level);
+            if (level == 0 && fetch_rows_ != nullptr && sampled_for_exact(label)) {
+                rescore_exact(vec, top_candidates);
+            }
             curr_obj = mutually_connect_new_element(cur_c, top_candidates, level);
         }
     } else {
@@ -769,19 +1229,8 @@ inline maxheap<std::pair<float, PID>> HierarchicalNSW::search_base_layer(
         size_t size = get_list_count(reinterpret_cast<PID*>(data));
         auto* datal = reinterpret_cast<PID*>(data + 1);
 
-        rabitqlib::memory::mem_prefetch_l1(
-            reinterpret_cast<const char*>(
-                rawDataPtr_ + (get_external_label(*datal) * dim_)
-            ),
-            padded_dim_ / 16
-        );
-
-        rabitqlib::memory::mem_prefetch_l1(
-            reinterpret_cast<const char*>(
-                rawDataPtr_ + (get_external_label(*(datal + 1)) * dim_)
-            ),
-            padded_dim_ / 16
-        );
+        prefetch_build_data(*datal);
+        prefetch_build_data(*(datal + 1));
 
         for (size_t j = 0; j < size; j++) {
             PID candidate_id = *(datal + j);
@@ -791,12 +1240,7 @@ inline maxheap<std::pair<float, PID>> HierarchicalNSW::search_base_layer(
             vl->set(candidate_id);
 
             if (j < size - 1) {
-                rabitqlib::memory::mem_prefetch_l1(
-                    reinterpret_cast<const char*>(
-                        rawDataPtr_ + (get_external_label(*(datal + j + 1)) * dim_)
-                    ),
-                    padded_dim_ / 16
-                );
+                prefetch_build_data(*(datal + j + 1));
             }
 
             float dist1 = get_data_dist(candidate_id, cur_c);
@@ -1062,10 +1506,47 @@ inline void HierarchicalNSW::get_full_est(
     }
 }
 
+inline void HierarchicalNSW::query_to_centroids(
+    const float* rotated_query, std::vector<float>& q_to_centroids
+) const {
+    q_to_centroids.resize(num_cluster_);
+
+    if (metric_type_ == METRIC_L2) {
+        for (size_t i = 0; i < num_cluster_; i++) {
+            q_to_centroids[i] = std::sqrt(raw_dist_func_(
+                rotated_query,
+                reinterpret_cast<float*>(centroids_memory_) + (i * padded_dim_),
+                padded_dim_
+            ));
+        }
+    } else if (metric_type_ == METRIC_IP) {
+        q_to_centroids.resize(2 * num_cluster_);
+        // first half as g_add, second half as g_error
+        for (size_t i = 0; i < num_cluster_; i++) {
+            q_to_centroids[i] = dot_product(
+                rotated_query,
+                reinterpret_cast<float*>(centroids_memory_) + (i * padded_dim_),
+                padded_dim_
+            );
+            q_to_centroids[i + num_cluster_] = std::sqrt(euclidean_sqr(
+                rotated_query,
+                reinterpret_cast<float*>(centroids_memory_) + (i * padded_dim_),
+                padded_dim_
+            ));
+        }
+    }
+}
+
 inline std::vector<std::vector<std::pair<float, PID>>> HierarchicalNSW::search(
-    const float* queries, size_t query_num, size_t TOPK, size_t efSearch, size_t thread_num
+    const float* queries,
+    size_t query_num,
+    size_t TOPK,
+    size_t efSearch,
+    size_t thread_num,
+    size_t query_bits,
+    SearchBudget* budgets,
+    SearchStats* stats
 ) {
-    set_ef(efSearch);
     std::vector<std::vector<std::pair<float, PID>>> results(query_num);
     rabitqlib::ivf::parallel_for(
         0,
@@ -1074,7 +1555,14 @@ inline std::vector<std::vector<std::pair<float, PID>>> HierarchicalNSW::search(
         [&](size_t idx, size_t /*threadId*/) {
             std::vector<float> rotated_query(padded_dim_);
             this->rotator_->rotate(queries + (idx * dim_), rotated_query.data());
-            maxheap<std::pair<float, PID>> knn = search_knn(rotated_query.data(), TOPK);
+            maxheap<std::pair<float, PID>> knn = search_knn(
+                rotated_query.data(),
+                TOPK,
+                efSearch,
+                query_bits,
+                budgets == nullptr ? nullptr : budgets + idx,
+                stats == nullptr ? nullptr : stats + idx
+            );
             while (knn.size()) {
                 results[idx].emplace_back(knn.top());
                 knn.pop();
@@ -1086,62 +1574,79 @@ inline std::vector<std::vector<std::pair<float, PID>>> HierarchicalNSW::search(
 }
 
 inline maxheap<std::pair<float, PID>> HierarchicalNSW::search_knn(
-    const float* rotated_query, size_t TOPK
+    const float* rotated_query,
+    size_t TOPK,
+    size_t ef,
+    size_t query_bits,
+    SearchBudget* budget,
+    SearchStats* stats
 ) {
+    if (budget != nullptr) {
+        budget->start();
+    }
+    if (stats != nullptr) {
+        *stats = SearchStats();
+    }
     maxheap<std::pair<float, PID>> result;
     if (cur_element_count_ == 0) {
         return result;
     }
 
+    // query_config_ is tuned for the default precision
     SplitSingleQuery<float> query_wrapper(
-        rotated_query, padded_dim_, ex_bits_, query_config_, metric_type_
+        rotated_query,
+        padded_dim_,
+        ex_bits_,
+        query_bits == SplitSingleQuery<float>::kNumBits ? query_config_ : quant::RabitqConfig(),
+        metric_type_,
+        query_bits
     );
 
     // Preprocess - get the distance from query to all centroids
-    std::vector<float> q_to_centroids(num_cluster_);
+    std::vector<float> q_to_centroids;
+    query_to_centroids(rotated_query, q_to_centroids);
 
-    if (metric_type_ == METRIC_L2) {
-        for (size_t i = 0; i < num_cluster_; i++) {
-            q_to_centroids[i] = std::sqrt(raw_dist_func_(
-                rotated_query,
-                reinterpret_cast<float*>(centroids_memory_) + (i * padded_dim_),
-                padded_dim_
-            ));
-        }
-    } else if (metric_type_ == METRIC_IP) {
-        q_to_centroids.resize(2 * num_cluster_);
-        // first half as g_add, second half as g_error
-        for (size_t i = 0; i < num_cluster_; i++) {
-            q_to_centroids[i] = dot_product(
-                rotated_query,
-                reinterpret_cast<float*>(centroids_memory_) + (i * padded_dim_),
-                padded_dim_
-            );
-            q_to_centroids[i + num_cluster_] = std::sqrt(euclidean_sqr(
-                rotated_query,
-                reinterpret_cast<float*>(centroids_memory_) + (i * padded_dim_),
-                padded_dim_
-            ));
+    // insert() may be raising the top level
+    PID curr_obj;
+    int maxlevel;
+    {
+        std::unique_lock<std::mutex> lock(global_, std::defer_lock);
+        if (insertable_) {
+            lock.lock();
         }
+        curr_obj = enterpoint_node_;
+        maxlevel = maxlevel_;
+    }
+    if (static_cast<signed>(curr_obj) == -1) {
+        return result;
     }
-
-    PID curr_obj = enterpoint_node_;
     EstimateRecord curest;
 
     get_bin_est(q_to_centroids, query_wrapper, curr_obj, curest);
+    if (stats != nullptr) {
+        ++stats->distances;
+    }
 
-    for (int level = maxlevel_; level > 0; level--) {
+    // a spent budget stops the descent, the base layer then keeps the entry it reached
+    bool in_budget = true;
+    std::vector<PID> buf(insertable_ ? maxM0_ + 2 : 0);
+    for (int level = maxlevel; level > 0 && in_budget; level--) {
         bool changed = true;
         while (changed) {
             changed = false;
-            unsigned int* data;
-
-            data = static_cast<unsigned int*>(get_linklist(curr_obj, level));
-            int size = get_list_count(data);
+            const PID* neighbors = search_linklist(curr_obj, level, buf) + 1;
+            size_t size = get_list_count(neighbors - 1);
+            if (budget != nullptr && (!budget->in_time() || !budget->take_distances(size))) {
+                in_budget = false;
+                break;
+            }
+            if (stats != nullptr) {
+                ++stats->hops;
+                stats->distances += size;
+            }
 
-            PID* datal = static_cast<PID*>(data + 1);
-            for (int i = 0; i < size; i++) {
-                PID cand = datal[i];
+            for (size_t i = 0; i < size; i++) {
+                PID cand = neighbors[i];
                 if (cand > max_elements_) {
                     throw std::runtime_error("cand error");
                 }
@@ -1161,12 +1666,14 @@ inline maxheap<std::pair<float, PID>> HierarchicalNSW::search_knn(
     BoundedKNN boundedKnn(TOPK);
     searchBaseLayerST_AdaptiveRerankOpt(
         curr_obj,
-        std::max(ef_, TOPK),
+        std::max(ef, TOPK),
         TOPK,
         query_wrapper,
         q_to_centroids,
         rotated_query,
-        boundedKnn
+        boundedKnn,
+        budget,
+        stats
     );
     for (auto& candidate : boundedKnn.candidates()) {
         result.emplace(candidate.record.est_dist, get_external_label(candidate.id));
@@ -1187,7 +1694,9 @@ void HierarchicalNSW::searchBaseLayerST_AdaptiveRerankOpt(
     SplitSingleQuery<float>& query_wrapper,
     std::vector<float>& q_to_centroids,  // preprocess
     [[maybe_unused]] const float* query,
-    BoundedKNN& boundedKNN
+    BoundedKNN& boundedKNN,
+    SearchBudget* budget,
+    SearchStats* stats
 ) {
     HashBasedBooleanSet* vl = visited_list_pool_->get_free_vislist();
 
@@ -1196,8 +1705,19 @@ void HierarchicalNSW::searchBaseLayerST_AdaptiveRerankOpt(
 
     float distk = 1e10;
 
+    // the entry point is estimated even on a spent budget, so a search finds something
     EstimateRecord start_estimate_record;
-    get_full_est(q_to_centroids, query_wrapper, ep_id, start_estimate_record);
+    bool boost = budget == nullptr || budget->take_boost();
+    if (boost) {
+        get_full_est(q_to_centroids, query_wrapper, ep_id, start_estimate_record);
+    } else {
+        get_bin_est(q_to_centroids, query_wrapper, ep_id, start_estimate_record);
+    }
+    if (stats != nullptr) {
+        ++stats->distances;
+        ++stats->visited;
+        stats->boosts += boost && ex_bits_ > 0 ? 1 : 0;
+    }
     float est_dist = start_estimate_record.est_dist;
     float low_dist = start_estimate_record.low_dist;
 
@@ -1209,25 +1729,41 @@ void HierarchicalNSW::searchBaseLayerST_AdaptiveRerankOpt(
 
     vl->set(ep_id);
 
-    while (candidate_set.has_next()) {
+    bool in_budget = true;
+    std::vector<PID> buf(insertable_ ? maxM0_ + 2 : 0);
+    while (candidate_set.has_next() && in_budget) {
+        if (budget != nullptr && !budget->in_time()) {
+            break;
+        }
         // Step 1 - get the next node to explore.
         PID current_node_id = candidate_set.pop();
-        int* data = (int*)get_linklist0(current_node_id);
-        size_t size = get_list_count((PID*)data);
+        const PID* neighbors = search_linklist(current_node_id, 0, buf) + 1;
+        size_t size = get_list_count(neighbors - 1);
+        if (stats != nullptr) {
+            ++stats->hops;
+        }
 
-        rabitqlib::memory::mem_prefetch_l1(get_bindata_by_internalid(*(data + 1)), 2);
-        // Iterate over neighbors. (List starts at index 1.)
-        for (size_t j = 1; j <= size; j++) {
-            int candidate_id = *(data + j);
+        rabitqlib::memory::mem_prefetch_l1(get_bindata_by_internalid(neighbors[0]), 2);
+        // Iterate over neighbors.
+        for (size_t j = 0; j < size; j++) {
+            PID candidate_id = neighbors[j];
 
             rabitqlib::memory::mem_prefetch_l1(
-                get_bindata_by_internalid(*(data + j + 1)), 2
+                get_bindata_by_internalid(neighbors[j + 1]), 2
             );
 
             if (!vl->get(candidate_id)) {
+                if (budget != nullptr && !budget->take_distances(1)) {
+                    in_budget = false;
+                    break;
+                }
                 vl->set(candidate_id);
                 EstimateRecord candest;
                 get_bin_est(q_to_centroids, query_wrapper, candidate_id, candest);
+                if (stats != nullptr) {
+                    ++stats->visited;
+                    ++stats->distances;
+                }
 
                 if (ex_bits_ > 0) {
                     // Check preliminary score against current worst full estimate.
@@ -1235,14 +1771,24 @@ void HierarchicalNSW::searchBaseLayerST_AdaptiveRerankOpt(
                         boundedKNN.size() < TOPK || candest.low_dist < distk;
 
                     if (flag_update_KNNs) {
-                        // Compute the full estimate if promising.
-                        get_full_est(q_to_centroids, query_wrapper, candidate_id, candest);
+                        // Compute the full estimate if promising, out of boostings the
+                        // 1-bit estimate stands in.
+                        if (budget == nullptr || budget->take_boost()) {
+                            get_full_est(
+                                q_to_centroids, query_wrapper, candidate_id, candest
+                            );
+                            if (stats != nullptr) {
+                                ++stats->boosts;
+                            }
+                        }
                         Candidate cand{
                             ResultRecord(candest.est_dist, candest.low_dist),
                             static_cast<PID>(candidate_id)
                         };
                         boundedKNN.insert(cand);
                         distk = boundedKNN.worst().record.est_dist;
+                    } else if (stats != nullptr) {
+                        ++stats->pruned;
                     }
                 } else {
                     Candidate cand{
@@ -10,6 +10,7 @@
 
 #include "defines.hpp"
 #include "third/hnswlib/hnswlib.h"
+#include "utils/log.hpp"
 #include "utils/space.hpp"
 
 namespace rabitqlib::ivf {
@@ -156,14 +157,14 @@ class HNSWInitializer : public Initializer {
     }
 
     void add_vectors(const float* cent) override {
-        std::cout << "Inserting vectors into hnsw...\n";
+        log_message(LogLevel::Debug, "Inserting vectors into hnsw...");
         size_t start = 0;
         size_t rows = num_cluster_;
         size_t num_threads = 0;
         parallel_for(start, rows, num_threads, [&](size_t row, size_t /*thread_id*/) {
             alg_hnsw_->addPoint(cent + (row * dim_), row);
         });
-        std::cout << "Inserted vectors into hnsw...\n" << std::flush;
+        log_message(LogLevel::Debug, "Inserted vectors into hnsw");
     }
 
     [[nodiscard]] const float* centroid(PID id) const override {
@@ -177,7 +178,8 @@ class HNSWInitializer : public Initializer {
         std::priority_queue<std::pair<float, hnswlib::labeltype>> result =
             alg_hnsw_->searchKnn(query, nprobe);
 
-        for (size_t i = 0; i < nprobe; ++i) {
+        // nearest first like FlatInitializer, a search budget probes clusters in this order
+        for (size_t i = nprobe; i-- > 0;) {
             candidates[i].distance = std::sqrt(result.top().first);
             candidates[i].id = result.top().second;
             result.pop();
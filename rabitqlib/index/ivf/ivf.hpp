@@ -1,12 +1,15 @@
 #pragma once
 
+#if defined(__SSE2__)
 #include <immintrin.h>
+#endif
 #include <omp.h>
 
 #include <algorithm>
 #include <cassert>
 #include <cmath>
 #include <cstddef>
+#include <cstring>
 #include <fstream>
 #include <iostream>
 #include <vector>
@@ -19,13 +22,19 @@
 #include "index/query.hpp"
 #include "quantization/data_layout.hpp"
 #include "quantization/rabitq.hpp"
+#include "utils/budget.hpp"
 #include "utils/buffer.hpp"
+#include "utils/build_monitor.hpp"
+#include "utils/log.hpp"
 #include "utils/memory.hpp"
 #include "utils/rotator.hpp"
+#include "utils/search_stats.hpp"
 #include "utils/space.hpp"
 
 namespace rabitqlib::ivf {
 class IVF {
+    friend class IVFBuilder;
+
    private:
     Initializer* initer_ = nullptr;      // initializer for find candidate cluster
     char* batch_data_ = nullptr;         // 1-bit code and factors
@@ -71,8 +80,14 @@ class IVF {
         std::free(ids_);
     }
 
-    void search_cluster(
-        const Cluster&, const SplitBatchQuery<float>&, buffer::SearchBuffer<float>&, bool
+    // false if the budget ran out in the cluster
+    bool search_cluster(
+        const Cluster&,
+        const SplitBatchQuery<float>&,
+        buffer::SearchBuffer<float>&,
+        bool,
+        SearchBudget*,
+        SearchStats*
     ) const;
 
     void scan_one_batch(
@@ -82,31 +97,98 @@ class IVF {
         const SplitBatchQuery<float>& q_obj,
         buffer::SearchBuffer<float>& knns,
         size_t num_points,
-        bool
+        bool,
+        SearchBudget*,
+        SearchStats*
     ) const;
 
    public:
     explicit IVF() {}
     explicit IVF(
-        size_t, size_t, size_t, size_t, RotatorType type = RotatorType::FhtKacRotator
+        size_t,
+        size_t,
+        size_t,
+        size_t,
+        RotatorType type = RotatorType::FhtKacRotator,
+        uint64_t seed = random_seed()
     );
 
     ~IVF();
 
-    void construct(const float*, const float*, const PID*, bool);
+    void construct(const float*, const float*, const PID*, bool, const BuildMonitor&);
 
     void save(const char*) const;
 
     void load(const char*);
 
+    // use a copy of rotator, e.g. one shared with other indexes or a PcaRotator that
+    // changes the padded dim, must precede construct()
+    void set_rotator(const Rotator<float>& rotator) {
+        Rotator<float>* copy = copy_matching_rotator(*rotator_, rotator);
+        delete rotator_;
+        rotator_ = copy;
+        padded_dim_ = rotator_->size();
+        type_ = rotator_->type();
+    }
+
     void search(const float*, size_t, size_t, PID*, bool) const;
 
+    // lut_bits is Lut<float>::kNumBits, Lut<float>::kNumBitsHacc or Lut<float>::kExact.
+    // distances, if not null, gets the estimated squared distance of every result found.
+    // budget, if not null, limits the work of the search and tells if it was cut short.
+    // stats, if not null, gets what the search did.
+    void search(
+        const float*,
+        size_t,
+        size_t,
+        PID*,
+        size_t,
+        float* distances = nullptr,
+        SearchBudget* budget = nullptr,
+        SearchStats* stats = nullptr
+    ) const;
+
+    [[nodiscard]] size_t num() const { return this->num_; }
+
+    [[nodiscard]] size_t dim() const { return this->dim_; }
+
     [[nodiscard]] size_t padded_dim() const { return this->padded_dim_; }
 
     [[nodiscard]] size_t num_clusters() const { return this->num_cluster_; }
+
+    [[nodiscard]] size_t ex_bits() const { return this->ex_bits_; }
+
+    // num of bytes of each part of the index, the centroids are those kept by the
+    // initializer, excluding its own search structure
+    struct MemorySections {
+        size_t batch_data;
+        size_t ex_data;
+        size_t ids;
+        size_t centroids;
+    };
+
+    [[nodiscard]] MemorySections memory_sections() const {
+        return {
+            batch_data_bytes(cluster_sizes()),
+            ex_data_bytes(),
+            ids_bytes(),
+            sizeof(float) * num_cluster_ * padded_dim_
+        };
+    }
+
+    // num of vectors in each cluster
+    [[nodiscard]] std::vector<size_t> cluster_sizes() const {
+        std::vector<size_t> sizes(num_cluster_, 0);
+        for (size_t i = 0; i < cluster_lst_.size(); ++i) {
+            sizes[i] = cluster_lst_[i].num();
+        }
+        return sizes;
+    }
 };
 
-inline IVF::IVF(size_t n, size_t dim, size_t cluster_num, size_t bits, RotatorType type)
+inline IVF::IVF(
+    size_t n, size_t dim, size_t cluster_num, size_t bits, RotatorType type, uint64_t seed
+)
     : num_(n)
     , dim_(dim)
     , padded_dim_(dim)
@@ -114,12 +196,14 @@ inline IVF::IVF(size_t n, size_t dim, size_t cluster_num, size_t bits, RotatorTy
     , ex_bits_(bits - 1)
     , type_(type) {
     if (bits < 1 || bits > 9) {
-        std::cerr << "Invalid number of bits for quantization in IVF::IVF\n";
-        std::cerr << "Expected: 1 to 9  Input:" << bits << '\n';
-        std::cerr.flush();
+        log_message(
+            LogLevel::Error,
+            "Invalid number of bits for quantization in IVF::IVF, expected: 1 to 9  input: ",
+            bits
+        );
         exit(1);
     };
-    rotator_ = choose_rotator<float>(dim, type, round_up_to_multiple(dim_, 64));
+    rotator_ = choose_rotator<float>(dim, type, round_up_to_multiple(dim_, 64), seed);
     padded_dim_ = rotator_->size();
     /* check size */
     assert(padded_dim_ % 64 == 0);
@@ -137,20 +221,25 @@ inline IVF::~IVF() {
  * @param data Data objects (N*DIM)
  * @param centroids Centroid vectors (K*DIM)
  * @param clustter_ids Cluster ID for each data objects
+ * @param monitor Progress of the "quantize clusters" phase, cancellation between batches
  */
 inline void IVF::construct(
-    const float* data, const float* centroids, const PID* cluster_ids, bool faster = false
+    const float* data,
+    const float* centroids,
+    const PID* cluster_ids,
+    bool faster = false,
+    const BuildMonitor& monitor = BuildMonitor()
 ) {
-    std::cout << "Start IVF construction...\n";
+    log_message(LogLevel::Info, "Start IVF construction...");
 
     // get id list for each cluster
-    std::cout << "\tLoading clustering information...\n";
+    log_message(LogLevel::Debug, "Loading clustering information...");
     std::vector<size_t> counts(num_cluster_, 0);
     std::vector<std::vector<PID>> id_lists(num_cluster_);
     for (size_t i = 0; i < num_; ++i) {
         PID cid = cluster_ids[i];
-        if (cid > num_cluster_) {
-            std::cerr << "Bad cluster id\n";
+        if (cid >= num_cluster_) {
+            log_message(LogLevel::Error, "Bad cluster id ", cid);
             exit(1);
         }
         id_lists[cid].push_back(static_cast<PID>(i));
@@ -170,20 +259,23 @@ inline void IVF::construct(
         config = quant::faster_config(padded_dim_, ex_bits_ + 1);
     }
 
-    /* Quantize each cluster */
+    /* Quantize each cluster, a few per thread between progress reports */
+    size_t batch = 4 * static_cast<size_t>(omp_get_max_threads());
+    monitor.for_batches("quantize clusters", num_cluster_, batch, [&](size_t begin, size_t end) {
 #pragma omp parallel for schedule(dynamic)
-    for (size_t i = 0; i < num_cluster_; ++i) {
-        const float* cur_centroid = centroids + (i * dim_);
-        float* cur_rotated_c = &rotated_centroids[i * padded_dim_];
-        Cluster& cp = cluster_lst_[i];
-        quantize_cluster(cp, id_lists[i], data, cur_centroid, cur_rotated_c, config);
-    }
+        for (size_t i = begin; i < end; ++i) {
+            const float* cur_centroid = centroids + (i * dim_);
+            float* cur_rotated_c = &rotated_centroids[i * padded_dim_];
+            Cluster& cp = cluster_lst_[i];
+            quantize_cluster(cp, id_lists[i], data, cur_centroid, cur_rotated_c, config);
+        }
+    });
 
     this->initer_->add_vectors(rotated_centroids.data());
 }
 
 inline void IVF::allocate_memory(const std::vector<size_t>& cluster_sizes) {
-    std::cout << "Allocating memory for IVF...\n";
+    log_message(LogLevel::Debug, "Allocating memory for IVF...");
     if (num_cluster_ < 20000UL) {
         this->initer_ = new FlatInitializer(padded_dim_, num_cluster_);
     } else {
@@ -191,6 +283,8 @@ inline void IVF::allocate_memory(const std::vector<size_t>& cluster_sizes) {
     }
     this->batch_data_ =
         memory::align_allocate<64, char, true>(batch_data_bytes(cluster_sizes));
+    // the tail of a cluster's last batch is never written, zero it so saves repeat
+    std::memset(batch_data_, 0, batch_data_bytes(cluster_sizes));
     if (ex_bits_ > 0) {
         this->ex_data_ = memory::align_allocate<64, char, true>(ex_data_bytes());
     }
@@ -236,8 +330,13 @@ inline void IVF::quantize_cluster(
 ) {
     size_t num_points = IDs.size();
     if (cp.num() != num_points) {
-        std::cerr << "Size of cluster and IDs are inequivalent\n";
-        std::cerr << "Cluster: " << cp.num() << " IDs: " << num_points << '\n';
+        log_message(
+            LogLevel::Error,
+            "Size of cluster and IDs are inequivalent, cluster: ",
+            cp.num(),
+            " IDs: ",
+            num_points
+        );
         exit(1);
     }
 
@@ -277,7 +376,7 @@ inline void IVF::quantize_cluster(
 
 inline void IVF::save(const char* filename) const {
     if (cluster_lst_.size() == 0) {
-        std::cerr << "IVF not constructed\n";
+        log_message(LogLevel::Error, "IVF not constructed");
         return;
     }
 
@@ -319,12 +418,12 @@ inline void IVF::save(const char* filename) const {
 }
 
 inline void IVF::load(const char* filename) {
-    std::cout << "Loading IVF...\n";
+    log_message(LogLevel::Info, "Loading IVF from ", filename);
     std::ifstream input(filename, std::ios::binary);
     assert(input.is_open());
 
     /* Load meta data */
-    std::cout << "\tLoading meta data...\n";
+    log_message(LogLevel::Debug, "Loading meta data...");
     input.read(reinterpret_cast<char*>(&this->num_), sizeof(size_t));
     input.read(reinterpret_cast<char*>(&this->dim_), sizeof(size_t));
     input.read(reinterpret_cast<char*>(&this->num_cluster_), sizeof(size_t));
@@ -332,7 +431,6 @@ inline void IVF::load(const char* filename) {
     input.read(reinterpret_cast<char*>(&type_), sizeof(type_));
 
     rotator_ = choose_rotator<float>(dim_, type_, round_up_to_multiple(dim_, 64));
-    padded_dim_ = rotator_->size();
 
     /* Load number of vectors of each cluster */
     std::vector<size_t> cluster_sizes(num_cluster_, 0);
@@ -344,12 +442,13 @@ inline void IVF::load(const char* filename) {
     size_t tmp =
         std::accumulate(cluster_sizes.begin(), cluster_sizes.end(), static_cast<size_t>(0));
     if (tmp != num_) {
-        std::cerr << "The sum of cluster num != total number of points\n";
+        log_message(LogLevel::Error, "The sum of cluster num != total number of points");
         exit(1);
     }
 
-    /* Load rotator */
+    /* Load rotator, a PcaRotator sets its own padded dim */
     this->rotator_->load(input);
+    padded_dim_ = rotator_->size();
 
     /* Load data */
     free_memory();
@@ -363,7 +462,7 @@ inline void IVF::load(const char* filename) {
     init_clusters(cluster_sizes);
 
     input.close();
-    std::cout << "Index loaded\n";
+    log_message(LogLevel::Info, "Index loaded");
 }
 
 inline void IVF::search(
@@ -373,12 +472,30 @@ inline void IVF::search(
     PID* __restrict__ results,
     bool use_hacc = true
 ) const {
+    search(query, k, nprobe, results, use_hacc ? Lut<float>::kNumBitsHacc : Lut<float>::kNumBits);
+}
+
+inline void IVF::search(
+    const float* __restrict__ query,
+    size_t k,
+    size_t nprobe,
+    PID* __restrict__ results,
+    size_t lut_bits,
+    float* distances,
+    SearchBudget* budget,
+    SearchStats* stats
+) const {
+    if (budget != nullptr) {
+        budget->start();
+    }
+    if (stats != nullptr) {
+        *stats = SearchStats();
+    }
+    bool use_hacc = lut_bits == Lut<float>::kNumBitsHacc;
     nprobe = std::min(nprobe, num_cluster_);  // corner case
     std::vector<float> rotated_query(padded_dim_);
     this->rotator_->rotate(query, rotated_query.data());
 
-    std::cout << l2norm_sqr(query, dim_) << '\t' << l2norm_sqr(rotated_query.data(), padded_dim_) << '\n';
-
     // use initer to get closest nprobe centroids
     std::vector<AnnCandidate<float>> centroid_dist(nprobe);
     this->initer_->centroids_distances(rotated_query.data(), nprobe, centroid_dist);
@@ -386,26 +503,40 @@ inline void IVF::search(
     buffer::SearchBuffer knns(k);
 
     SplitBatchQuery<float> q_obj(
-        rotated_query.data(), padded_dim_, ex_bits_, METRIC_L2, use_hacc
+        rotated_query.data(), padded_dim_, ex_bits_, METRIC_L2, lut_bits
     );
 
     for (size_t i = 0; i < nprobe; ++i) {
+        if (budget != nullptr && !budget->take_cluster()) {
+            break;
+        }
         PID cid = centroid_dist[i].id;
         float dist = centroid_dist[i].distance;
         const Cluster& cur_cluster = cluster_lst_[cid];
 
         q_obj.set_g_add(dist);
-        search_cluster(cur_cluster, q_obj, knns, use_hacc);
+        if (stats != nullptr) {
+            ++stats->clusters;
+        }
+        if (!search_cluster(cur_cluster, q_obj, knns, use_hacc, budget, stats)) {
+            break;
+        }
     }
 
-    knns.copy_results(results);
+    if (distances != nullptr) {
+        knns.copy_results(results, distances);
+    } else {
+        knns.copy_results(results);
+    }
 }
 
-inline void IVF::search_cluster(
+inline bool IVF::search_cluster(
     const Cluster& cur_cluster,
     const SplitBatchQuery<float>& q_obj,
     buffer::SearchBuffer<float>& knns,
-    bool use_hacc
+    bool use_hacc,
+    SearchBudget* budget,
+    SearchStats* stats
 ) const {
     size_t iter = cur_cluster.num() / fastscan::kBatchSize;
     size_t remain = cur_cluster.num() - (iter * fastscan::kBatchSize);
@@ -416,8 +547,20 @@ inline void IVF::search_cluster(
 
     /* Compute distances block by block */
     for (size_t i = 0; i < iter; ++i) {
+        if (budget != nullptr &&
+            (!budget->in_time() || !budget->take_distances(fastscan::kBatchSize))) {
+            return false;
+        }
         scan_one_batch(
-            batch_data, ex_data, ids, q_obj, knns, fastscan::kBatchSize, use_hacc
+            batch_data,
+            ex_data,
+            ids,
+            q_obj,
+            knns,
+            fastscan::kBatchSize,
+            use_hacc,
+            budget,
+            stats
         );
 
         batch_data += BatchDataMap<float>::data_bytes(padded_dim_);
@@ -427,9 +570,15 @@ inline void IVF::search_cluster(
     }
 
     if (remain > 0) {
+        if (budget != nullptr && (!budget->in_time() || !budget->take_distances(remain))) {
+            return false;
+        }
         // scan the last block
-        scan_one_batch(batch_data, ex_data, ids, q_obj, knns, remain, use_hacc);
+        scan_one_batch(
+            batch_data, ex_data, ids, q_obj, knns, remain, use_hacc, budget, stats
+        );
     }
+    return true;
 }
 
 inline void IVF::scan_one_batch(
@@ -439,7 +588,9 @@ inline void IVF::scan_one_batch(
     const SplitBatchQuery<float>& q_obj,
     buffer::SearchBuffer<float>& knns,
     size_t num_points,
-    bool use_hacc
+    bool use_hacc,
+    SearchBudget* budget,
+    SearchStats* stats
 ) const {
     std::array<float, fastscan::kBatchSize> est_distance;  // estimated distance
     std::array<float, fastscan::kBatchSize> low_distance;  // lower distance
@@ -454,6 +605,10 @@ inline void IVF::scan_one_batch(
         ip_x0_qr.data(),
         use_hacc
     );
+    if (stats != nullptr) {
+        ++stats->batches;
+        stats->distances += num_points;
+    }
 
     // if only use 1-bit code, directly return
     if (ex_bits_ == 0) {
@@ -472,11 +627,20 @@ inline void IVF::scan_one_batch(
         if (lower_dist < distk) {
             PID id = ids[i];
             ConstExDataMap<float> cur_ex(ex_data, padded_dim_, ex_bits_);
-            float ex_dist = split_distance_boosting(
-                ex_data, ip_func_, q_obj, padded_dim_, ex_bits_, ip_x0_qr[i]
-            );
+            // out of boostings, the 1-bit estimate stands in
+            float ex_dist = est_distance[i];
+            if (budget == nullptr || budget->take_boost()) {
+                ex_dist = split_distance_boosting(
+                    ex_data, ip_func_, q_obj, padded_dim_, ex_bits_, ip_x0_qr[i]
+                );
+                if (stats != nullptr) {
+                    ++stats->boosts;
+                }
+            }
             knns.insert(id, ex_dist);
             distk = knns.top_dist();
+        } else if (stats != nullptr) {
+            ++stats->pruned;
         }
         ex_data += ExDataMap<float>::data_bytes(padded_dim_, ex_bits_);
     }
@@ -0,0 +1,223 @@
+#pragma once
+
+#include <omp.h>
+
+#include <cstddef>
+#include <stdexcept>
+#include <utility>
+#include <vector>
+
+#include "defines.hpp"
+#include "fastscan/fastscan.hpp"
+#include "index/ivf/ivf.hpp"
+#include "quantization/data_layout.hpp"
+#include "quantization/rabitq.hpp"
+#include "quantization/rabitq_impl.hpp"
+#include "utils/build_monitor.hpp"
+#include "utils/log.hpp"
+
+namespace rabitqlib::ivf {
+
+/**
+ * @brief Streaming builder of an IVF, for data that does not fit in memory. The cluster
+ * sizes are counted beforehand, e.g. in a first pass over the data, so the storage of every
+ * cluster is allocated up front; vectors are then added in chunks of any size and in any
+ * cluster order, and quantized into the next free slot of their cluster.
+ *
+ * The 1-bit codes of a block are written unpacked into the block and packed once its 32
+ * slots are filled, so the builder needs no memory beyond the index and one chunk. Vectors
+ * get the ids 0, 1, ... in the order they are added; with the same cluster ids the index
+ * is the same as the one of IVF::construct().
+ */
+class IVFBuilder {
+   private:
+    IVF& ivf_;
+    quant::RabitqConfig config_;
+    BuildMonitor monitor_;
+    std::vector<float> rotated_centroids_;  // num_cluster * padded_dim
+    std::vector<size_t> cluster_sizes_;     // num of vectors of each cluster
+    std::vector<size_t> filled_;            // num of vectors added to each cluster
+    size_t added_ = 0;                      // num of vectors added
+
+    [[nodiscard]] char* block(PID cid, size_t slot) const {
+        return ivf_.cluster_lst_[cid].batch_data() +
+               (BatchDataMap<float>::data_bytes(ivf_.padded_dim_) *
+                (slot / fastscan::kBatchSize));
+    }
+
+    void quantize_one(const float* rotated, PID cid, size_t slot, PID id);
+
+    void pack_block(PID cid, size_t slot, size_t num) const;
+
+   public:
+    /**
+     * @param ivf Index created with the total num of vectors, not constructed yet
+     * @param centroids Centroid vectors (K*DIM)
+     * @param cluster_sizes Num of vectors of each cluster, summing to ivf.num()
+     * @param faster Use the fast quantizer
+     * @param monitor Progress of the "quantize vectors" phase, cancellation in add()
+     */
+    explicit IVFBuilder(
+        IVF& ivf,
+        const float* centroids,
+        std::vector<size_t> cluster_sizes,
+        bool faster = false,
+        BuildMonitor monitor = BuildMonitor()
+    );
+
+    /**
+     * @brief Quantizes num vectors (num*DIM) into their clusters. Throws
+     * std::invalid_argument, before adding any vector, if a cluster id is out of range or a
+     * cluster would get more vectors than its size.
+     */
+    void add(const float* data, const PID* cluster_ids, size_t num);
+
+    // Packs the last block of every cluster, throws std::invalid_argument if a cluster
+    // is not full. The index can be searched and saved afterwards.
+    void finish();
+
+    [[nodiscard]] size_t added() const { return added_; }
+};
+
+inline IVFBuilder::IVFBuilder(
+    IVF& ivf,
+    const float* centroids,
+    std::vector<size_t> cluster_sizes,
+    bool faster,
+    BuildMonitor monitor
+)
+    : ivf_(ivf)
+    , monitor_(std::move(monitor))
+    , rotated_centroids_(ivf.num_cluster_ * ivf.padded_dim_)
+    , cluster_sizes_(std::move(cluster_sizes))
+    , filled_(ivf.num_cluster_, 0) {
+    size_t total = 0;
+    for (auto size : cluster_sizes_) {
+        total += size;
+    }
+    if (cluster_sizes_.size() != ivf_.num_cluster_ || total != ivf_.num_) {
+        throw std::invalid_argument("cluster sizes do not match the IVF");
+    }
+    log_message(LogLevel::Info, "Start streaming IVF construction...");
+
+    ivf_.allocate_memory(cluster_sizes_);
+    ivf_.init_clusters(cluster_sizes_);
+
+    if (faster) {
+        config_ = quant::faster_config(ivf_.padded_dim_, ivf_.ex_bits_ + 1);
+    }
+    for (size_t i = 0; i < ivf_.num_cluster_; ++i) {
+        ivf_.rotator_->rotate(
+            centroids + (i * ivf_.dim_), &rotated_centroids_[i * ivf_.padded_dim_]
+        );
+    }
+}
+
+inline void IVFBuilder::add(const float* data, const PID* cluster_ids, size_t num) {
+    monitor_.check();
+
+    // assign the slots first, so a bad chunk leaves the builder as it was
+    std::vector<size_t> counts(ivf_.num_cluster_, 0);
+    for (size_t i = 0; i < num; ++i) {
+        PID cid = cluster_ids[i];
+        if (cid >= ivf_.num_cluster_ ||
+            filled_[cid] + counts[cid] >= cluster_sizes_[cid]) {
+            throw std::invalid_argument("bad cluster id or cluster already full");
+        }
+        counts[cid] += 1;
+    }
+    std::vector<size_t> slots(num);
+    std::vector<std::pair<PID, size_t>> full_blocks;  // (cluster, slot in the block)
+    for (size_t i = 0; i < num; ++i) {
+        PID cid = cluster_ids[i];
+        slots[i] = filled_[cid]++;
+        if (filled_[cid] % fastscan::kBatchSize == 0) {
+            full_blocks.emplace_back(cid, slots[i]);
+        }
+    }
+
+    size_t padded_dim = ivf_.padded_dim_;
+#pragma omp parallel
+    {
+        std::vector<float> rotated(padded_dim);
+#pragma omp for schedule(dynamic, 64)
+        for (size_t i = 0; i < num; ++i) {
+            ivf_.rotator_->rotate(data + (i * ivf_.dim_), rotated.data());
+            quantize_one(
+                rotated.data(), cluster_ids[i], slots[i], static_cast<PID>(added_ + i)
+            );
+        }
+    }
+
+#pragma omp parallel for schedule(dynamic)
+    for (size_t i = 0; i < full_blocks.size(); ++i) {
+        pack_block(full_blocks[i].first, full_blocks[i].second, fastscan::kBatchSize);
+    }
+
+    added_ += num;
+    monitor_.report("quantize vectors", added_, ivf_.num_);
+}
+
+inline void IVFBuilder::finish() {
+    for (size_t i = 0; i < ivf_.num_cluster_; ++i) {
+        if (filled_[i] != cluster_sizes_[i]) {
+            throw std::invalid_argument("not every vector was added to the IVF");
+        }
+    }
+
+#pragma omp parallel for schedule(dynamic)
+    for (size_t i = 0; i < ivf_.num_cluster_; ++i) {
+        size_t rest = cluster_sizes_[i] % fastscan::kBatchSize;
+        if (rest != 0) {
+            pack_block(static_cast<PID>(i), cluster_sizes_[i] - 1, rest);
+        }
+    }
+
+    ivf_.initer_->add_vectors(rotated_centroids_.data());
+}
+
+/**
+ * @brief Writes the unpacked 1-bit code and the factors of a vector into its block, and its
+ * ex code and id into the cluster
+ */
+inline void IVFBuilder::quantize_one(const float* rotated, PID cid, size_t slot, PID id) {
+    size_t padded_dim = ivf_.padded_dim_;
+    const float* centroid = &rotated_centroids_[cid * padded_dim];
+    Cluster& cp = ivf_.cluster_lst_[cid];
+    size_t j = slot % fastscan::kBatchSize;
+
+    BatchDataMap<float> this_batch(block(cid, slot), padded_dim);
+    quant::rabitq_impl::one_bit::one_bit_compact_code(
+        rotated,
+        centroid,
+        padded_dim,
+        this_batch.bin_code() + (j * padded_dim / 8),
+        this_batch.f_add()[j],
+        this_batch.f_rescale()[j],
+        this_batch.f_error()[j],
+        METRIC_L2
+    );
+
+    if (ivf_.ex_bits_ > 0) {
+        quant::quantize_compact_ex_bits(
+            rotated,
+            centroid,
+            padded_dim,
+            ivf_.ex_bits_,
+            cp.ex_data() + (slot * ExDataMap<float>::data_bytes(padded_dim, ivf_.ex_bits_)),
+            METRIC_L2,
+            config_
+        );
+    }
+
+    cp.ids()[slot] = id;
+}
+
+// packs in place the num unpacked codes of the block holding slot
+inline void IVFBuilder::pack_block(PID cid, size_t slot, size_t num) const {
+    size_t padded_dim = ivf_.padded_dim_;
+    auto* codes = reinterpret_cast<uint8_t*>(block(cid, slot));
+    std::vector<uint8_t> unpacked(codes, codes + (num * padded_dim / 8));
+    fastscan::pack_codes(padded_dim, unpacked.data(), num, codes);
+}
+}  // namespace rabitqlib::ivf
@@ -12,29 +12,41 @@ namespace rabitqlib {
 
 template <typename T>
 class Lut {
+   public:
     static constexpr size_t kNumBits = 8;
     static constexpr size_t kNumBitsHacc = 16;
-    static_assert(std::is_floating_point_v<T>, "T must be an floating type in Lut");
+    static constexpr size_t kExact = 0;  // keep the float table, no quantization
 
    private:
+    static_assert(std::is_floating_point_v<T>, "T must be an floating type in Lut");
     size_t table_length_ = 0;
+    size_t num_bits_ = kNumBits;
     std::vector<uint8_t> lut_;
-    T delta_;
-    T sum_vl_lut_;
+    std::vector<float> lut_float_;
+    T delta_ = 1;
+    T sum_vl_lut_ = 0;
 
    public:
     explicit Lut() = default;
     explicit Lut(const T* rotated_query, size_t padded_dim, bool use_hacc = false)
-        : table_length_(padded_dim << 2)
-        , lut_(table_length_ * (static_cast<int>(use_hacc) + 1)) {
+        : Lut(rotated_query, padded_dim, use_hacc ? kNumBitsHacc : kNumBits) {}
+
+    // num_bits is kNumBits, kNumBitsHacc or kExact
+    explicit Lut(const T* rotated_query, size_t padded_dim, size_t num_bits)
+        : table_length_(padded_dim << 2), num_bits_(num_bits) {
         // quantize float lut
         std::vector<float> lut_float(table_length_);
         fastscan::pack_lut(padded_dim, rotated_query, lut_float.data());
+        if (num_bits == kExact) {
+            lut_float_ = std::move(lut_float);
+            return;
+        }
         T vl_lut;
         T vr_lut;
         data_range(lut_float.data(), table_length_, vl_lut, vr_lut);
 
-        if (use_hacc) {
+        if (num_bits == kNumBitsHacc) {
+            lut_.resize(table_length_ * 2);
             delta_ = (vr_lut - vl_lut) / ((1 << kNumBitsHacc) - 1);
 
             // quantize float lut into uint16 then change to split table
@@ -44,6 +56,7 @@ class Lut {
             );
             fastscan::transfer_lut_hacc(lut_u16.data(), padded_dim, lut_.data());
         } else {
+            lut_.resize(table_length_);
             delta_ = (vr_lut - vl_lut) / ((1 << kNumBits) - 1);
             scalar_quantize(lut_.data(), lut_float.data(), table_length_, vl_lut, delta_);
         }
@@ -51,14 +64,21 @@ class Lut {
         size_t num_table = table_length_ / 16;
         sum_vl_lut_ = vl_lut * static_cast<float>(num_table);
     }
+    Lut(const Lut&) = default;
+    Lut& operator=(const Lut&) = default;
     Lut& operator=(Lut&& other) noexcept {
+        table_length_ = other.table_length_;
+        num_bits_ = other.num_bits_;
         lut_ = std::move(other.lut_);
+        lut_float_ = std::move(other.lut_float_);
         delta_ = other.delta_;
         sum_vl_lut_ = other.sum_vl_lut_;
         return *this;
     }
 
     [[nodiscard]] const uint8_t* lut() const { return lut_.data(); };
+    [[nodiscard]] const float* lut_float() const { return lut_float_.data(); };
+    [[nodiscard]] size_t num_bits() const { return num_bits_; };
     [[nodiscard]] T delta() const { return delta_; };
     [[nodiscard]] T sum_vl() const { return sum_vl_lut_; };
 };
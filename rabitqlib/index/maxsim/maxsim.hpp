@@ -0,0 +1,392 @@
+#pragma once
+
+#include <algorithm>
+#include <array>
+#include <cstddef>
+#include <cstdint>
+#include <fstream>
+#include <iostream>
+#include <limits>
+#include <stdexcept>
+#include <vector>
+
+#include "defines.hpp"
+#include "fastscan/fastscan.hpp"
+#include "index/estimator.hpp"
+#include "index/query.hpp"
+#include "quantization/data_layout.hpp"
+#include "quantization/rabitq.hpp"
+#include "utils/buffer.hpp"
+#include "utils/memory.hpp"
+#include "utils/rotator.hpp"
+#include "utils/space.hpp"
+#include "utils/tools.hpp"
+
+namespace rabitqlib::maxsim {
+/**
+ * @brief Multi-vector index for late-interaction (ColBERT-style) retrieval. A document is
+ * a bag of token vectors and its score for a query is MaxSim: the sum over query tokens of
+ * the largest inner product with a document token.
+ *
+ * The tokens of every document are quantized with METRIC_IP against the document's mean
+ * and packed into FastScan batches of their own, so one SplitBatchQuery per query token
+ * scores a whole document batch by batch. Documents are ranked on the 1-bit estimates and
+ * the best ones re-scored with the ex-bit codes.
+ */
+class MultiVectorIndex {
+   private:
+    // files start with kFileMagic and kFileVersion, files saved before have neither, their
+    // first field is the num of documents
+    static constexpr uint64_t kFileMagic = 0x004d515449424152;  // "RABITQM\0"
+    static constexpr uint32_t kFileVersion = 1;
+
+    size_t num_docs_ = 0;                // num of documents
+    size_t num_tokens_ = 0;              // num of token vectors over all documents
+    size_t dim_ = 0;                     // dimension of token vectors
+    size_t padded_dim_ = 0;              // dimension after padding
+    size_t ex_bits_ = 0;                 // total bits = ex_bits_ + 1
+    RotatorType type_ = RotatorType::FhtKacRotator;
+    Rotator<float>* rotator_ = nullptr;  // token rotator
+    std::vector<size_t> doc_offsets_;    // first token of every document, num_docs_ + 1
+    std::vector<size_t> batch_offsets_;  // first batch of every document, num_docs_ + 1
+    std::vector<float> centroids_;       // rotated mean token of every document
+    std::vector<char, memory::AlignedAllocator<char>> batch_data_;  // 1-bit codes
+    std::vector<char, memory::AlignedAllocator<char>> ex_data_;     // ex-bit codes
+    float (*ip_func_)(const float*, const uint8_t*, size_t) = nullptr;
+
+    void init_layout();
+
+    void quantize_doc(size_t doc, const float* tokens, const quant::RabitqConfig& config);
+
+    // MaxSim of doc for the queries, from 1-bit estimates or with ex-bit boosting
+    float score_doc(size_t doc, std::vector<SplitBatchQuery<float>>&, bool, bool) const;
+
+   public:
+    explicit MultiVectorIndex() = default;
+
+    explicit MultiVectorIndex(
+        size_t dim,
+        size_t total_bits,
+        RotatorType type = RotatorType::FhtKacRotator,
+        uint64_t seed = random_seed()
+    );
+
+    ~MultiVectorIndex() { delete rotator_; }
+
+    MultiVectorIndex(const MultiVectorIndex&) = delete;
+    MultiVectorIndex& operator=(const MultiVectorIndex&) = delete;
+
+    /* Quantize num_docs documents. tokens holds the doc_offsets[num_docs] unrotated token
+     * vectors in document order, document i being rows doc_offsets[i] to
+     * doc_offsets[i + 1]. Every document needs at least one token. */
+    void construct(const float* tokens, const size_t* doc_offsets, size_t num_docs, bool);
+
+    // use a copy of rotator, e.g. one shared with other indexes or a PcaRotator that
+    // changes the padded dim, must precede construct()
+    void set_rotator(const Rotator<float>& rotator) {
+        Rotator<float>* copy = copy_matching_rotator(*rotator_, rotator);
+        delete rotator_;
+        rotator_ = copy;
+        padded_dim_ = rotator_->size();
+        type_ = rotator_->type();
+    }
+
+    /* Top k documents of a query of num_query_tokens unrotated tokens and their MaxSim.
+     * All documents are scored on 1-bit estimates, the best rerank of them again with the
+     * ex-bit codes. lut_bits is Lut<float>::kNumBits, Lut<float>::kNumBitsHacc or
+     * Lut<float>::kExact. Unfilled result slots are left untouched. */
+    void search(
+        const float* query,
+        size_t num_query_tokens,
+        size_t k,
+        size_t rerank,
+        PID* results,
+        float* scores,
+        size_t lut_bits = Lut<float>::kNumBitsHacc
+    ) const;
+
+    void save(const char*) const;
+
+    void load(const char*);
+
+    [[nodiscard]] size_t num_docs() const { return num_docs_; }
+
+    [[nodiscard]] size_t num_tokens() const { return num_tokens_; }
+
+    [[nodiscard]] size_t dim() const { return dim_; }
+
+    [[nodiscard]] size_t padded_dim() const { return padded_dim_; }
+
+    [[nodiscard]] size_t ex_bits() const { return ex_bits_; }
+
+    [[nodiscard]] size_t doc_len(size_t doc) const {
+        return doc_offsets_[doc + 1] - doc_offsets_[doc];
+    }
+
+    [[nodiscard]] size_t batch_data_bytes() const { return batch_data_.size(); }
+
+    [[nodiscard]] size_t ex_data_bytes() const { return ex_data_.size(); }
+
+    [[nodiscard]] size_t centroids_bytes() const { return centroids_.size() * sizeof(float); }
+};
+
+inline MultiVectorIndex::MultiVectorIndex(
+    size_t dim, size_t total_bits, RotatorType type, uint64_t seed
+)
+    : dim_(dim), ex_bits_(total_bits - 1), type_(type) {
+    if (total_bits < 1 || total_bits > 9) {
+        throw std::invalid_argument("total_bits of MultiVectorIndex must be in 1..=9");
+    }
+    rotator_ = choose_rotator<float>(dim, type, round_up_to_multiple(dim_, 64), seed);
+    padded_dim_ = rotator_->size();
+}
+
+// batch offsets and buffer sizes from doc_offsets_
+inline void MultiVectorIndex::init_layout() {
+    batch_offsets_.assign(num_docs_ + 1, 0);
+    for (size_t i = 0; i < num_docs_; ++i) {
+        batch_offsets_[i + 1] =
+            batch_offsets_[i] + div_round_up(doc_len(i), fastscan::kBatchSize);
+    }
+    num_tokens_ = doc_offsets_[num_docs_];
+    centroids_.assign(num_docs_ * padded_dim_, 0);
+    batch_data_.assign(batch_offsets_[num_docs_] * BatchDataMap<float>::data_bytes(padded_dim_), 0);
+    ex_data_.assign(num_tokens_ * ExDataMap<float>::data_bytes(padded_dim_, ex_bits_), 0);
+    ip_func_ = select_excode_ipfunc(ex_bits_);
+}
+
+inline void MultiVectorIndex::construct(
+    const float* tokens, const size_t* doc_offsets, size_t num_docs, bool faster = false
+) {
+    if (num_docs == 0 || doc_offsets[0] != 0) {
+        throw std::invalid_argument("need at least one document, starting at token 0");
+    }
+    for (size_t i = 0; i < num_docs; ++i) {
+        if (doc_offsets[i + 1] <= doc_offsets[i]) {
+            throw std::invalid_argument("every document needs at least one token");
+        }
+    }
+    num_docs_ = num_docs;
+    doc_offsets_.assign(doc_offsets, doc_offsets + num_docs + 1);
+    init_layout();
+
+    quant::RabitqConfig config;
+    if (faster) {
+        config = quant::faster_config(padded_dim_, ex_bits_ + 1);
+    }
+
+#pragma omp parallel for schedule(dynamic)
+    for (size_t i = 0; i < num_docs_; ++i) {
+        quantize_doc(i, tokens + (doc_offsets_[i] * dim_), config);
+    }
+}
+
+inline void MultiVectorIndex::quantize_doc(
+    size_t doc, const float* tokens, const quant::RabitqConfig& config
+) {
+    size_t num = doc_len(doc);
+    std::vector<float> rotated(num * padded_dim_);
+    for (size_t i = 0; i < num; ++i) {
+        rotator_->rotate(tokens + (i * dim_), rotated.data() + (i * padded_dim_));
+    }
+
+    // the mean token is the centroid, keeping the residuals small
+    float* centroid = &centroids_[doc * padded_dim_];
+    for (size_t i = 0; i < num; ++i) {
+        for (size_t j = 0; j < padded_dim_; ++j) {
+            centroid[j] += rotated[(i * padded_dim_) + j];
+        }
+    }
+    for (size_t j = 0; j < padded_dim_; ++j) {
+        centroid[j] /= static_cast<float>(num);
+    }
+
+    char* batch_data =
+        &batch_data_[batch_offsets_[doc] * BatchDataMap<float>::data_bytes(padded_dim_)];
+    char* ex_data = ex_data_.data() +
+                    (doc_offsets_[doc] * ExDataMap<float>::data_bytes(padded_dim_, ex_bits_));
+    for (size_t i = 0; i < num; i += fastscan::kBatchSize) {
+        size_t n = std::min(fastscan::kBatchSize, num - i);
+        quant::quantize_split_batch(
+            rotated.data() + (i * padded_dim_),
+            centroid,
+            n,
+            padded_dim_,
+            ex_bits_,
+            batch_data,
+            ex_data,
+            METRIC_IP,
+            config
+        );
+        batch_data += BatchDataMap<float>::data_bytes(padded_dim_);
+        ex_data += ExDataMap<float>::data_bytes(padded_dim_, ex_bits_) * n;
+    }
+}
+
+inline float MultiVectorIndex::score_doc(
+    size_t doc, std::vector<SplitBatchQuery<float>>& queries, bool use_hacc, bool boost
+) const {
+    std::array<float, fastscan::kBatchSize> est_distance;
+    std::array<float, fastscan::kBatchSize> low_distance;
+    std::array<float, fastscan::kBatchSize> ip_x0_qr;
+    const float* centroid = &centroids_[doc * padded_dim_];
+    size_t num = doc_len(doc);
+    size_t ex_bytes = ExDataMap<float>::data_bytes(padded_dim_, ex_bits_);
+
+    float total = 0;
+    for (auto& q_obj : queries) {
+        // estimated distances are 1 - <q, x>
+        q_obj.set_g_add(0, dot_product(q_obj.rotated_query(), centroid, padded_dim_));
+        const char* batch_data =
+            &batch_data_[batch_offsets_[doc] * BatchDataMap<float>::data_bytes(padded_dim_)];
+        const char* ex_data = ex_data_.data() + (doc_offsets_[doc] * ex_bytes);
+        float best = std::numeric_limits<float>::lowest();
+        for (size_t i = 0; i < num; i += fastscan::kBatchSize) {
+            size_t n = std::min(fastscan::kBatchSize, num - i);
+            split_batch_estdist(
+                batch_data,
+                q_obj,
+                padded_dim_,
+                est_distance.data(),
+                low_distance.data(),
+                ip_x0_qr.data(),
+                use_hacc
+            );
+            for (size_t j = 0; j < n; ++j) {
+                float dist = est_distance[j];
+                if (boost) {
+                    dist = split_distance_boosting(
+                        ex_data + (j * ex_bytes),
+                        ip_func_,
+                        q_obj,
+                        padded_dim_,
+                        ex_bits_,
+                        ip_x0_qr[j]
+                    );
+                }
+                best = std::max(best, 1 - dist);
+            }
+            batch_data += BatchDataMap<float>::data_bytes(padded_dim_);
+            ex_data += ex_bytes * n;
+        }
+        total += best;
+    }
+    return total;
+}
+
+inline void MultiVectorIndex::search(
+    const float* query,
+    size_t num_query_tokens,
+    size_t k,
+    size_t rerank,
+    PID* results,
+    float* scores,
+    size_t lut_bits
+) const {
+    if (num_docs_ == 0 || num_query_tokens == 0 || k == 0) {
+        return;
+    }
+    bool use_hacc = lut_bits == Lut<float>::kNumBitsHacc;
+    rerank = std::max(rerank, k);
+
+    std::vector<float> rotated(num_query_tokens * padded_dim_);
+    std::vector<SplitBatchQuery<float>> queries;
+    queries.reserve(num_query_tokens);
+    for (size_t i = 0; i < num_query_tokens; ++i) {
+        float* rotated_token = rotated.data() + (i * padded_dim_);
+        rotator_->rotate(query + (i * dim_), rotated_token);
+        queries.emplace_back(rotated_token, padded_dim_, ex_bits_, METRIC_IP, lut_bits);
+    }
+
+    // buffers keep the smallest distances, so they hold negated scores
+    buffer::SearchBuffer candidates(rerank);
+    for (size_t doc = 0; doc < num_docs_; ++doc) {
+        candidates.insert(static_cast<PID>(doc), -score_doc(doc, queries, use_hacc, false));
+    }
+    size_t num = std::min(rerank, num_docs_);
+    std::vector<PID> ids(num);
+    std::vector<float> distances(num);
+    candidates.copy_results(ids.data(), distances.data());
+    if (ex_bits_ > 0) {
+        buffer::SearchBuffer top(k);
+        for (PID doc : ids) {
+            top.insert(doc, -score_doc(doc, queries, use_hacc, true));
+        }
+        top.copy_results(ids.data(), distances.data());
+    }
+    for (size_t i = 0; i < std::min(k, num_docs_); ++i) {
+        results[i] = ids[i];
+        scores[i] = -distances[i];
+    }
+}
+
+inline void MultiVectorIndex::save(const char* filename) const {
+    if (num_docs_ == 0) {
+        throw std::runtime_error("MultiVectorIndex not constructed");
+    }
+    std::ofstream output(filename, std::ios::binary);
+    if (!output) {
+        throw std::runtime_error("cannot open the index file for writing");
+    }
+    output.write(reinterpret_cast<const char*>(&kFileMagic), sizeof(uint64_t));
+    output.write(reinterpret_cast<const char*>(&kFileVersion), sizeof(uint32_t));
+    output.write(reinterpret_cast<const char*>(&num_docs_), sizeof(size_t));
+    output.write(reinterpret_cast<const char*>(&dim_), sizeof(size_t));
+    output.write(reinterpret_cast<const char*>(&ex_bits_), sizeof(size_t));
+    output.write(reinterpret_cast<const char*>(&type_), sizeof(type_));
+    output.write(
+        reinterpret_cast<const char*>(doc_offsets_.data()),
+        static_cast<long>(sizeof(size_t) * (num_docs_ + 1))
+    );
+    rotator_->save(output);
+    output.write(
+        reinterpret_cast<const char*>(centroids_.data()), static_cast<long>(centroids_bytes())
+    );
+    output.write(batch_data_.data(), static_cast<long>(batch_data_.size()));
+    output.write(ex_data_.data(), static_cast<long>(ex_data_.size()));
+}
+
+inline void MultiVectorIndex::load(const char* filename) {
+    std::ifstream input(filename, std::ios::binary);
+    if (!input) {
+        throw std::runtime_error("cannot open the index file");
+    }
+    uint64_t magic = 0;
+    input.read(reinterpret_cast<char*>(&magic), sizeof(uint64_t));
+    if (magic == kFileMagic) {
+        uint32_t version = 0;
+        input.read(reinterpret_cast<char*>(&version), sizeof(uint32_t));
+        if (version > kFileVersion) {
+            throw std::runtime_error("MultiVectorIndex file saved by a newer version");
+        }
+    } else {
+        input.clear();
+        input.seekg(0);
+    }
+    input.read(reinterpret_cast<char*>(&num_docs_), sizeof(size_t));
+    input.read(reinterpret_cast<char*>(&dim_), sizeof(size_t));
+    input.read(reinterpret_cast<char*>(&ex_bits_), sizeof(size_t));
+    input.read(reinterpret_cast<char*>(&type_), sizeof(type_));
+    if (!input || num_docs_ == 0 || ex_bits_ > 8) {
+        throw std::runtime_error("bad MultiVectorIndex header");
+    }
+
+    delete rotator_;
+    rotator_ = choose_rotator<float>(dim_, type_, round_up_to_multiple(dim_, 64));
+
+    doc_offsets_.resize(num_docs_ + 1);
+    input.read(
+        reinterpret_cast<char*>(doc_offsets_.data()),
+        static_cast<long>(sizeof(size_t) * (num_docs_ + 1))
+    );
+    rotator_->load(input);
+    padded_dim_ = rotator_->size();
+    init_layout();
+    input.read(reinterpret_cast<char*>(centroids_.data()), static_cast<long>(centroids_bytes()));
+    input.read(batch_data_.data(), static_cast<long>(batch_data_.size()));
+    input.read(ex_data_.data(), static_cast<long>(ex_data_.size()));
+    if (!input) {
+        throw std::runtime_error("truncated MultiVectorIndex file");
+    }
+}
+}  // namespace rabitqlib::maxsim
@@ -19,10 +19,19 @@ class BatchQuery {
     Lut<T> lookup_table_;
     T G_add_ = 0;
     T G_k1xSumq_ = 0;  // G_k1xSumq
+    MetricType metric_type_ = METRIC_L2;
 
    public:
-    explicit BatchQuery(const T* rotated_query, size_t padded_dim) {
-        lookup_table_ = std::move(Lut<T>(rotated_query, padded_dim));
+    // lut_bits is Lut<T>::kNumBits, Lut<T>::kNumBitsHacc or Lut<T>::kExact
+    explicit BatchQuery(
+        const T* rotated_query,
+        size_t padded_dim,
+        size_t lut_bits = Lut<T>::kNumBits,
+        MetricType metric_type = METRIC_L2
+    ) {
+        lookup_table_ = std::move(Lut<T>(rotated_query, padded_dim, lut_bits));
+
+        metric_type_ = (metric_type == METRIC_IP) ? METRIC_IP : METRIC_L2;
 
         float c_1 = -((1 << 1) - 1) / 2.F;
 
@@ -40,11 +49,16 @@ class BatchQuery {
 
     [[nodiscard]] T g_add() const { return G_add_; }
 
-    void set_g_add(T sqr_norm) {
-        G_add_ = sqr_norm;
-    }  // may need to be edited if we want to support ip
+    // sqr_norm is the squared distance between query and centroid, ip their inner product
+    void set_g_add(T sqr_norm, T ip = 0) {
+        G_add_ = (metric_type_ == METRIC_IP) ? -ip : sqr_norm;
+    }
 
     [[nodiscard]] const uint8_t* lut() const { return lookup_table_.lut(); }
+
+    [[nodiscard]] const float* lut_float() const { return lookup_table_.lut_float(); }
+
+    [[nodiscard]] size_t lut_bits() const { return lookup_table_.num_bits(); }
 };
 
 template <typename T>
@@ -65,9 +79,25 @@ class SplitBatchQuery {
         size_t ex_bits,
         MetricType metric_type = METRIC_L2,
         bool use_hacc = true
+    )
+        : SplitBatchQuery(
+              rotated_query,
+              padded_dim,
+              ex_bits,
+              metric_type,
+              use_hacc ? Lut<T>::kNumBitsHacc : Lut<T>::kNumBits
+          ) {}
+
+    // lut_bits is Lut<T>::kNumBits, Lut<T>::kNumBitsHacc or Lut<T>::kExact
+    explicit SplitBatchQuery(
+        const T* rotated_query,
+        size_t padded_dim,
+        size_t ex_bits,
+        MetricType metric_type,
+        size_t lut_bits
     )
         : rotated_query_(rotated_query) {
-        lookup_table_ = std::move(Lut<T>(rotated_query, padded_dim, use_hacc));
+        lookup_table_ = std::move(Lut<T>(rotated_query, padded_dim, lut_bits));
 
         metric_type_ = (metric_type == METRIC_IP) ? METRIC_IP : METRIC_L2;
 
@@ -79,6 +109,13 @@ class SplitBatchQuery {
         G_k1xSumq_ = sumq * c_1;
         G_kbxSumq_ = sumq * c_b;
     }
+
+    // copy of `other` that reads the rotated query from another buffer
+    SplitBatchQuery(const SplitBatchQuery& other, const T* rotated_query)
+        : SplitBatchQuery(other) {
+        rotated_query_ = rotated_query;
+    }
+
     [[nodiscard]] const T* rotated_query() const { return rotated_query_; }
 
     [[nodiscard]] T delta() const { return lookup_table_.delta(); }
@@ -104,6 +141,10 @@ class SplitBatchQuery {
     }
 
     [[nodiscard]] const uint8_t* lut() const { return lookup_table_.lut(); }
+
+    [[nodiscard]] const float* lut_float() const { return lookup_table_.lut_float(); }
+
+    [[nodiscard]] size_t lut_bits() const { return lookup_table_.num_bits(); }
 };
 
 template <typename T>
@@ -121,18 +162,28 @@ class SingleCentroidQuery {
     T centroid_ip_;
     T sumq_centroid_;
     MetricType metric_type_ = METRIC_L2;
+    size_t query_bits_;
 
    public:
     static constexpr size_t kNumBits = 4;
+    static constexpr size_t kExact = 0;  // use the float query, no quantization
     explicit SingleCentroidQuery(
         const T* rotated_query,
         const T* centroid,
         size_t padded_dim,
         size_t ex_bits,
         quant::RabitqConfig config,
-        size_t metric_type = METRIC_L2
+        size_t metric_type = METRIC_L2,
+        size_t query_bits = kNumBits
     )
-        : rotated_query_(rotated_query), residual_query_(padded_dim), QueryBin_(padded_dim * kNumBits / 64, 0) {
+        : rotated_query_(rotated_query)
+        , residual_query_(padded_dim)
+        , QueryBin_(padded_dim * query_bits / 64, 0)
+        , delta_(0)
+        , vl_(0)
+        , centroid_ip_(0)
+        , sumq_centroid_(0)
+        , query_bits_(query_bits) {
         float c_1 = -static_cast<float>((1 << 1) - 1) / 2.F;
         float c_b = -static_cast<float>((1 << (ex_bits + 1)) - 1) / 2.F;
         
@@ -149,22 +200,34 @@ class SingleCentroidQuery {
 
         metric_type_ = (metric_type == METRIC_IP) ? METRIC_IP : METRIC_L2;
 
+        if (query_bits == kExact) {
+            return;
+        }
+
         std::vector<uint16_t> quant_query = std::vector<uint16_t>(padded_dim);
 
         // quantize query by rabitq
         quant::quantize_centroid<float, uint16_t>(
-            rotated_query, centroid, padded_dim, kNumBits, quant_query.data(), delta_, vl_, centroid_ip_, sumq_centroid_, config
+            rotated_query, centroid, padded_dim, query_bits, quant_query.data(), delta_, vl_, centroid_ip_, sumq_centroid_, config
         );
 
         // represent quantized query as u64
         rabitqlib::new_transpose_bin(
-            quant_query.data(), QueryBin_.data(), padded_dim, kNumBits
+            quant_query.data(), QueryBin_.data(), padded_dim, query_bits
         );
     }
 
+    [[nodiscard]] size_t query_bits() const { return query_bits_; }
+
     [[nodiscard]] const uint64_t* query_bin() const { return QueryBin_.data(); }
 
 
+    // copy of `other` that reads the rotated query from another buffer
+    SingleCentroidQuery(const SingleCentroidQuery& other, const T* rotated_query)
+        : SingleCentroidQuery(other) {
+        rotated_query_ = rotated_query;
+    }
+
     [[nodiscard]] const T* rotated_query() const { return rotated_query_; }
 
     [[nodiscard]] const T* residual_query() const { return residual_query_.data(); }
@@ -210,17 +273,24 @@ class SplitSingleQuery {
     T delta_;
     T vl_;
     MetricType metric_type_ = METRIC_L2;
+    size_t query_bits_;
 
    public:
     static constexpr size_t kNumBits = 4;
+    static constexpr size_t kExact = 0;  // use the float query, no quantization
     explicit SplitSingleQuery(
         const T* rotated_query,
         size_t padded_dim,
         size_t ex_bits,
         quant::RabitqConfig config,
-        size_t metric_type = METRIC_L2
+        size_t metric_type = METRIC_L2,
+        size_t query_bits = kNumBits
     )
-        : rotated_query_(rotated_query), QueryBin_(padded_dim * kNumBits / 64, 0) {
+        : rotated_query_(rotated_query)
+        , QueryBin_(padded_dim * query_bits / 64, 0)
+        , delta_(0)
+        , vl_(0)
+        , query_bits_(query_bits) {
         float c_1 = -static_cast<float>((1 << 1) - 1) / 2.F;
         float c_b = -static_cast<float>((1 << (ex_bits + 1)) - 1) / 2.F;
         T sumq =
@@ -231,21 +301,33 @@ class SplitSingleQuery {
 
         metric_type_ = (metric_type == METRIC_IP) ? METRIC_IP : METRIC_L2;
 
+        if (query_bits == kExact) {
+            return;
+        }
+
         std::vector<uint16_t> quant_query = std::vector<uint16_t>(padded_dim);
 
         // quantize query by rabitq
         quant::quantize_scalar<float, uint16_t>(
-            rotated_query, padded_dim, kNumBits, quant_query.data(), delta_, vl_, config
+            rotated_query, padded_dim, query_bits, quant_query.data(), delta_, vl_, config
         );
 
         // represent quantized query as u64
         rabitqlib::new_transpose_bin(
-            quant_query.data(), QueryBin_.data(), padded_dim, kNumBits
+            quant_query.data(), QueryBin_.data(), padded_dim, query_bits
         );
     }
 
     [[nodiscard]] const uint64_t* query_bin() const { return QueryBin_.data(); }
 
+    [[nodiscard]] size_t query_bits() const { return query_bits_; }
+
+    // copy of `other` that reads the rotated query from another buffer
+    SplitSingleQuery(const SplitSingleQuery& other, const T* rotated_query)
+        : SplitSingleQuery(other) {
+        rotated_query_ = rotated_query;
+    }
+
     [[nodiscard]] const T* rotated_query() const { return rotated_query_; }
 
     [[nodiscard]] T delta() const { return delta_; }
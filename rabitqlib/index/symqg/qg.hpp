@@ -2,6 +2,8 @@
 
 #include <omp.h>
 
+#include <algorithm>
+#include <atomic>
 #include <cassert>
 #include <cstddef>
 #include <cstdint>
@@ -9,7 +11,10 @@
 #include <cstring>
 #include <fstream>
 #include <iostream>
+#include <limits>
+#include <mutex>
 #include <ostream>
+#include <stdexcept>
 #include <vector>
 
 #include "defines.hpp"
@@ -19,10 +24,13 @@
 #include "quantization/data_layout.hpp"
 #include "quantization/rabitq.hpp"
 #include "utils/array.hpp"
+#include "utils/budget.hpp"
 #include "utils/buffer.hpp"
 #include "utils/hashset.hpp"
 #include "utils/io.hpp"
+#include "utils/log.hpp"
 #include "utils/memory.hpp"
+#include "utils/search_stats.hpp"
 #include "utils/rotator.hpp"
 #include "utils/space.hpp"
 #include "utils/visited_pool.hpp"
@@ -34,21 +42,25 @@ class QuantizedGraph {
     friend class QGBuilder;
 
    private:
-    size_t num_points_ = 0;    // num points
-    size_t degree_bound_ = 0;  // degree bound
+    using CandidateList = std::vector<AnnCandidate<T>>;
+    using RowArray =
+        Array<char, std::vector<size_t>, memory::AlignedAllocator<char, 1 << 22, true>>;
+    static constexpr size_t kNumRowLocks = 4096;
+    // files start with kFileMagic and kFileVersion, files saved before have neither and
+    // no metric, their first field is the num of points
+    static constexpr uint64_t kFileMagic = 0x0047515449424152;  // "RABITQG\0"
+    static constexpr uint32_t kFileVersion = 1;
+
+    std::atomic<size_t> num_points_ = 0;  // num points
+    size_t capacity_ = 0;                 // num of rows allocated
+    size_t degree_bound_ = 0;             // degree bound
     size_t dim_ = 0;           // dimension
     size_t padded_dim_ = 0;    // padded dimension
     PID entry_point_ = 0;      // Entry point of graph
     RotatorType type_ = RotatorType::FhtKacRotator;
+    MetricType metric_type_ = METRIC_L2;
 
-    Array<
-        char,
-        std::vector<size_t>,
-        memory::AlignedAllocator<
-            char,
-            1 << 22,
-            true>>
-        data_;                       // vectors + graph + quantization codes + factors
+    RowArray data_;                  // vectors + graph + quantization codes + factors
     Rotator<T>* rotator_ = nullptr;  // data rotator
     std::unique_ptr<VisitedListPool> visited_list_pool_ = nullptr;
 
@@ -60,10 +72,26 @@ class QuantizedGraph {
     size_t row_offset_ = 0;         // length of entire row
     size_t ef_ = 0;
 
-    void initialize();
+    // insert() rewrites the neighbour ids and codes of existing rows under these, searches
+    // of a graph opened for insertion by reserve() read them under the same locks
+    mutable std::vector<std::mutex> row_locks_ = std::vector<std::mutex>(kNumRowLocks);
+    std::mutex insert_lock_;  // one insertion at a time
+    bool insertable_ = false;  // set by reserve(), searches lock rows only then
+
+    std::mutex& row_lock(PID data_id) const { return row_locks_[data_id % kNumRowLocks]; }
+
+    void initialize(uint64_t seed);
+
+    void init_layout();
 
     void copy_vectors(const T*);
 
+    // exact distance, squared L2 or 1 - inner product
+    [[nodiscard]] T distance(const T* vec0, const T* vec1) const {
+        return metric_type_ == METRIC_IP ? dot_product_dis(vec0, vec1, dim_)
+                                         : euclidean_sqr(vec0, vec1, dim_);
+    }
+
     [[nodiscard]] T* get_vector(PID data_id) {
         return reinterpret_cast<T*>(&data_.at(row_offset_ * data_id));
     }
@@ -91,15 +119,26 @@ class QuantizedGraph {
         );
     }
 
-    void
-    find_candidates(PID, size_t, std::vector<AnnCandidate<T>>&, HashBasedBooleanSet&, const std::vector<uint32_t>&)
+    // degrees of the vertices, nullptr if all rows are full
+    void find_candidates(PID, size_t, CandidateList&, HashBasedBooleanSet&, const uint32_t*)
         const;
 
-    void update_qg(PID, const std::vector<AnnCandidate<T>>&);
+    void heuristic_prune(const CandidateList&, CandidateList&, CandidateList*, size_t) const;
+
+    void fill_neighbors(const CandidateList&, CandidateList&) const;
+
+    void update_qg(PID, const CandidateList&);
+
+    void quantize_neighbors(PID, const CandidateList&, char*) const;
 
-    void update_results(buffer::SearchBuffer<T>&, HashBasedBooleanSet&, const T*);
+    void add_reverse_edge(PID, PID, T);
 
-    void scan_neighbors(
+    void update_results(
+        buffer::SearchBuffer<T>&, HashBasedBooleanSet&, const T*, SearchBudget*, SearchStats*
+    );
+
+    // returns the num of neighbours kept out of the full search pool
+    size_t scan_neighbors(
         const BatchQuery<T>&,
         PID,
         T*,
@@ -113,14 +152,18 @@ class QuantizedGraph {
         size_t num,
         size_t dim,
         size_t max_deg,
-        RotatorType type = RotatorType::FhtKacRotator
+        RotatorType type = RotatorType::FhtKacRotator,
+        uint64_t seed = random_seed(),
+        MetricType metric_type = METRIC_L2
     );
 
     explicit QuantizedGraph() = default;
 
     ~QuantizedGraph();
 
-    [[nodiscard]] auto num_vertices() const { return this->num_points_; }
+    [[nodiscard]] size_t num_vertices() const { return this->num_points_; }
+
+    [[nodiscard]] size_t capacity() const { return this->capacity_; }
 
     [[nodiscard]] auto dimension() const { return this->dim_; }
 
@@ -128,6 +171,34 @@ class QuantizedGraph {
 
     [[nodiscard]] auto entry_point() const { return this->entry_point_; }
 
+    [[nodiscard]] MetricType metric_type() const { return this->metric_type_; }
+
+    // num of distinct valid neighbors of every vertex, at most degree_bound
+    [[nodiscard]] std::vector<size_t> degrees() const {
+        std::vector<size_t> degrees(num_points_);
+        for (PID i = 0; i < num_points_; ++i) {
+            const PID* neighbors = get_neighbors(i);
+            std::vector<PID> ids(neighbors, neighbors + degree_bound_);
+            std::sort(ids.begin(), ids.end());
+            ids.erase(std::unique(ids.begin(), ids.end()), ids.end());
+            degrees[i] = std::count_if(ids.begin(), ids.end(), [&](PID id) {
+                return id != i && id < num_points_;
+            });
+        }
+        return degrees;
+    }
+
+    // bytes of raw vectors, packed codes with factors, and neighbor ids
+    [[nodiscard]] size_t vectors_bytes() const { return batch_data_offset_ * num_points_; }
+
+    [[nodiscard]] size_t batch_data_bytes() const {
+        return (neighbor_offset_ - batch_data_offset_) * num_points_;
+    }
+
+    [[nodiscard]] size_t neighbors_bytes() const {
+        return (row_offset_ - neighbor_offset_) * num_points_;
+    }
+
     void set_ep(PID entry) { this->entry_point_ = entry; };
 
     void save(const char*) const;
@@ -136,18 +207,62 @@ class QuantizedGraph {
 
     void set_ef(size_t);
 
+    /* Room for capacity vertices in total, not safe concurrently with anything else. Opens
+     * the graph for insertion, from then on searches lock the rows they read. */
+    void reserve(size_t capacity);
+
+    /* Add an unrotated vector linked to its neighbours found with a search pool of size
+     * ef, returns its id. Needs reserve() first, safe to call concurrently with searches,
+     * insertions run one at a time. */
+    PID insert(const T* vec, size_t ef);
+
+    /* use a copy of rotator, e.g. one shared with other indexes or a PcaRotator that
+     * changes the padded dim, must precede the build */
+    void set_rotator(const Rotator<float>& rotator) {
+        Rotator<float>* copy = copy_matching_rotator(*rotator_, rotator);
+        ::delete rotator_;
+        rotator_ = copy;
+        padded_dim_ = rotator_->size();
+        type_ = rotator_->type();
+        init_layout();
+    }
+
     /* search and copy results to KNN */
     void search(const T* __restrict__ query, uint32_t knn, uint32_t* __restrict__ results);
+
+    /* same as above with an explicit search pool size, does not touch ef_. lut_bits is
+     * Lut<T>::kNumBits, Lut<T>::kNumBitsHacc or Lut<T>::kExact. distances, if not null,
+     * gets the exact distance of every result found. budget, if not null, limits the work
+     * of the search and tells if it was cut short, a vertex takes 1 + degree distances.
+     * stats, if not null, gets what the search did */
+    void search(
+        const T* __restrict__ query,
+        uint32_t knn,
+        size_t ef,
+        uint32_t* __restrict__ results,
+        size_t lut_bits = Lut<T>::kNumBits,
+        T* __restrict__ distances = nullptr,
+        SearchBudget* budget = nullptr,
+        SearchStats* stats = nullptr
+    );
 };
 
 template <typename T>
 inline QuantizedGraph<T>::QuantizedGraph(
-    size_t num, size_t dim, size_t max_deg, RotatorType type
+    size_t num,
+    size_t dim,
+    size_t max_deg,
+    RotatorType type,
+    uint64_t seed,
+    MetricType metric_type
 )
-    : num_points_(num), degree_bound_(max_deg), dim_(dim), padded_dim_(dim), type_(type) {
-    // choose rotator
-
-    initialize();
+    : num_points_(num)
+    , degree_bound_(max_deg)
+    , dim_(dim)
+    , padded_dim_(dim)
+    , type_(type)
+    , metric_type_((metric_type == METRIC_IP) ? METRIC_IP : METRIC_L2) {
+    initialize(seed);
 }
 
 template <typename T>
@@ -163,55 +278,79 @@ inline void QuantizedGraph<T>::copy_vectors(const T* data) {
         T* dst = get_vector(i);
         std::copy(src, src + dim_, dst);
     }
-    std::cout << "\tVectors Copied\n";
+    log_message(LogLevel::Debug, "Vectors copied");
 }
 
 template <typename T>
 inline void QuantizedGraph<T>::save(const char* filename) const {
-    std::cout << "Saving quantized graph to " << filename << '\n';
+    log_message(LogLevel::Info, "Saving quantized graph to ", filename);
     std::ofstream output(filename, std::ios::binary);
     assert(output.is_open());
 
+    output.write(reinterpret_cast<const char*>(&kFileMagic), sizeof(uint64_t));
+    output.write(reinterpret_cast<const char*>(&kFileVersion), sizeof(uint32_t));
+
     /* Basic variants */
-    output.write(reinterpret_cast<const char*>(&num_points_), sizeof(size_t));
+    size_t num_points = num_points_;
+    output.write(reinterpret_cast<const char*>(&num_points), sizeof(size_t));
     output.write(reinterpret_cast<const char*>(&degree_bound_), sizeof(size_t));
     output.write(reinterpret_cast<const char*>(&dim_), sizeof(size_t));
     output.write(reinterpret_cast<const char*>(&padded_dim_), sizeof(size_t));
     output.write(reinterpret_cast<const char*>(&entry_point_), sizeof(PID));
     output.write(reinterpret_cast<const char*>(&type_), sizeof(RotatorType));
+    output.write(reinterpret_cast<const char*>(&metric_type_), sizeof(MetricType));
 
-    /* Data */
-    data_.save(output);
+    /* Data, rows reserved for insertions are not written */
+    output.write(data_.data(), static_cast<std::streamsize>(num_points * row_offset_));
 
     /* Rotator */
     this->rotator_->save(output);
 
     output.close();
-    std::cout << "\tQuantized graph saved!\n";
+    log_message(LogLevel::Info, "Quantized graph saved");
 }
 
 template <typename T>
 inline void QuantizedGraph<T>::load(const char* filename) {
-    std::cout << "loading quantized graph " << filename << '\n';
+    log_message(LogLevel::Info, "Loading quantized graph ", filename);
 
     /* Check existence */
     if (!file_exists(filename)) {
-        std::cerr << "Index does not exist!\n";
+        log_message(LogLevel::Error, "Index ", filename, " does not exist");
         exit(1);
     }
 
     std::ifstream input(filename, std::ios::binary);
     assert(input.is_open());
 
+    uint64_t magic = 0;
+    uint32_t version = 0;
+    input.read(reinterpret_cast<char*>(&magic), sizeof(uint64_t));
+    if (magic == kFileMagic) {
+        input.read(reinterpret_cast<char*>(&version), sizeof(uint32_t));
+        if (version > kFileVersion) {
+            throw std::runtime_error("QuantizedGraph file saved by a newer version");
+        }
+    } else {
+        input.seekg(0);
+    }
+
     /* Basic variants */
-    input.read(reinterpret_cast<char*>(&num_points_), sizeof(size_t));
+    size_t num_points = 0;
+    input.read(reinterpret_cast<char*>(&num_points), sizeof(size_t));
+    num_points_ = num_points;
     input.read(reinterpret_cast<char*>(&degree_bound_), sizeof(size_t));
     input.read(reinterpret_cast<char*>(&dim_), sizeof(size_t));
     input.read(reinterpret_cast<char*>(&padded_dim_), sizeof(size_t));
     input.read(reinterpret_cast<char*>(&entry_point_), sizeof(PID));
     input.read(reinterpret_cast<char*>(&type_), sizeof(RotatorType));
+    metric_type_ = METRIC_L2;
+    if (version >= 1) {
+        input.read(reinterpret_cast<char*>(&metric_type_), sizeof(MetricType));
+    }
 
-    initialize();
+    // the rotator is read below
+    initialize(0);
 
     /* Data */
     data_.load(input);
@@ -219,12 +358,12 @@ inline void QuantizedGraph<T>::load(const char* filename) {
     /* Rotator */
     this->rotator_->load(input);
     if (rotator_->size() != padded_dim_) {
-        std::cerr << "Bad padded_dim_ for rotator in QuantizedGraph<T>.load()\n";
+        log_message(LogLevel::Error, "Bad padded_dim_ for rotator in QuantizedGraph<T>.load()");
         exit(1);
     }
 
     input.close();
-    std::cout << "Quantized graph loaded!\n";
+    log_message(LogLevel::Info, "Quantized graph loaded");
 }
 
 template <typename T>
@@ -243,13 +382,33 @@ template <typename T>
 inline void QuantizedGraph<T>::search(
     const T* __restrict__ query, uint32_t k, uint32_t* __restrict__ results
 ) {
+    search(query, k, ef_, results);
+}
+
+template <typename T>
+inline void QuantizedGraph<T>::search(
+    const T* __restrict__ query,
+    uint32_t k,
+    size_t ef,
+    uint32_t* __restrict__ results,
+    size_t lut_bits,
+    T* __restrict__ distances,
+    SearchBudget* budget,
+    SearchStats* stats
+) {
+    if (budget != nullptr) {
+        budget->start();
+    }
+    if (stats != nullptr) {
+        *stats = SearchStats();
+    }
     std::vector<T> rotated_query(padded_dim_);
     rotator_->rotate(query, rotated_query.data());
 
     // init query
-    BatchQuery<T> q_obj(rotated_query.data(), padded_dim_);
+    BatchQuery<T> q_obj(rotated_query.data(), padded_dim_, lut_bits, metric_type_);
 
-    buffer::SearchBuffer<T> search_pool(ef_);
+    buffer::SearchBuffer<T> search_pool(ef);
     // init search buffer
     search_pool.insert(this->entry_point_, std::numeric_limits<T>::max());
 
@@ -263,25 +422,41 @@ inline void QuantizedGraph<T>::search(
         if (vis->get(cur_node)) {
             continue;
         }
+        if (budget != nullptr &&
+            (!budget->in_time() || !budget->take_distances(1 + degree_bound_))) {
+            break;
+        }
         vis->set(cur_node);
 
-        q_obj.set_g_add(euclidean_sqr(query, get_vector(cur_node), dim_));
+        T dist = distance(query, get_vector(cur_node));
+        q_obj.set_g_add(dist, 1 - dist);
 
-        scan_neighbors(
+        size_t pruned = scan_neighbors(
             q_obj, cur_node, est_dist.data(), search_pool, *vis, this->degree_bound_
         );
-        res_pool.insert(cur_node, q_obj.g_add());
+        res_pool.insert(cur_node, dist);
+        if (stats != nullptr) {
+            ++stats->hops;
+            ++stats->visited;
+            stats->batches += degree_bound_ / fastscan::kBatchSize;
+            stats->distances += 1 + degree_bound_;
+            stats->pruned += pruned;
+        }
     }
 
-    update_results(res_pool, *vis, query);
+    update_results(res_pool, *vis, query, budget, stats);
     visited_list_pool_->release_vis_list(vis);
-    res_pool.copy_results(results);
+    if (distances != nullptr) {
+        res_pool.copy_results(results, distances);
+    } else {
+        res_pool.copy_results(results);
+    }
 }
 
 // scan a data row (including data vec and quantization codes for its neighbors)
 // store estimated distance & return exact distnace for current vertex
 template <typename T>
-void QuantizedGraph<T>::scan_neighbors(
+size_t QuantizedGraph<T>::scan_neighbors(
     const BatchQuery<T>& q_obj,
     PID data_id,
     T* est_dist,
@@ -289,18 +464,27 @@ void QuantizedGraph<T>::scan_neighbors(
     HashBasedBooleanSet& vis,
     size_t cur_degree
 ) const {
+    std::unique_lock<std::mutex> lock(row_lock(data_id), std::defer_lock);
+    if (insertable_) {
+        lock.lock();
+    }
     const auto* batch_data = get_batch_data(data_id);
     for (size_t i = 0; i < cur_degree; i += fastscan::kBatchSize) {
         qg_batch_estdist(batch_data, q_obj, padded_dim_, est_dist + i);
         batch_data += QGBatchDataMap<T>::data_bytes(padded_dim_);
     }
 
+    size_t pruned = 0;
     const PID* ptr_nb = get_neighbors(data_id);
     for (size_t i = 0; i < cur_degree; ++i) {
         PID cur_neighbor = ptr_nb[i];
         T dist = est_dist[i];
 
-        if (search_pool.is_full(dist) || vis.get(cur_neighbor)) {
+        if (search_pool.is_full(dist)) {
+            ++pruned;
+            continue;
+        }
+        if (vis.get(cur_neighbor)) {
             continue;
         }
         search_pool.insert(cur_neighbor, dist);  // update search buffer
@@ -308,26 +492,42 @@ void QuantizedGraph<T>::scan_neighbors(
             reinterpret_cast<const char*>(get_vector(search_pool.next_id())), 10
         );
     }
+    return pruned;
 }
 
 template <typename T>
 inline void QuantizedGraph<T>::update_results(
-    buffer::SearchBuffer<T>& result_pool, HashBasedBooleanSet& vis, const T* query
+    buffer::SearchBuffer<T>& result_pool,
+    HashBasedBooleanSet& vis,
+    const T* query,
+    SearchBudget* budget,
+    SearchStats* stats
 ) {
     if (result_pool.is_full()) {
         return;
     }
 
     auto data = result_pool.data();
+    std::vector<PID> copied(insertable_ ? degree_bound_ : 0);
     for (auto record : data) {
-        PID* ptr_nb = get_neighbors(record.id);
+        const PID* ptr_nb = get_neighbors(record.id);
+        if (insertable_) {
+            std::lock_guard<std::mutex> lock(row_lock(record.id));
+            std::copy(ptr_nb, ptr_nb + degree_bound_, copied.begin());
+            ptr_nb = copied.data();
+        }
         for (uint32_t i = 0; i < this->degree_bound_; ++i) {
             PID cur_neighbor = ptr_nb[i];
             if (!vis.get(cur_neighbor)) {
+                if (budget != nullptr && !budget->take_distances(1)) {
+                    return;
+                }
                 vis.set(cur_neighbor);
-                result_pool.insert(
-                    cur_neighbor, euclidean_sqr(query, get_vector(cur_neighbor), dim_)
-                );
+                result_pool.insert(cur_neighbor, distance(query, get_vector(cur_neighbor)));
+                if (stats != nullptr) {
+                    ++stats->visited;
+                    ++stats->distances;
+                }
             }
         }
         if (result_pool.is_full()) {
@@ -336,17 +536,24 @@ inline void QuantizedGraph<T>::update_results(
     }
 }
 
-// initialize const offsets & data array
+// initialize const offsets & data array, with a rotator drawn from seed
 template <typename T>
-inline void QuantizedGraph<T>::initialize() {
+inline void QuantizedGraph<T>::initialize(uint64_t seed) {
     ::delete rotator_;
 
-    rotator_ = choose_rotator<float>(dim_, type_, round_up_to_multiple(dim_, 64));
+    // a PcaRotator is only loaded, its padded dim comes from the index file
+    size_t padded_dim = type_ == RotatorType::PcaRotator ? padded_dim_
+                                                         : round_up_to_multiple(dim_, 64);
+    rotator_ = choose_rotator<float>(dim_, type_, padded_dim, seed);
     padded_dim_ = rotator_->size();
+    init_layout();
+}
 
+// offsets in a row and the data array, from padded_dim_
+template <typename T>
+inline void QuantizedGraph<T>::init_layout() {
     /* check size */
     assert(padded_dim_ % 64 == 0);
-    assert(padded_dim_ >= dim_);
 
     this->batch_data_offset_ = dim_ * sizeof(T);  // pos of packed code (aligned)
     this->neighbor_offset_ =
@@ -354,9 +561,8 @@ inline void QuantizedGraph<T>::initialize() {
         QGBatchDataMap<T>::data_bytes(padded_dim_) * (degree_bound_ / fastscan::kBatchSize);
     this->row_offset_ = neighbor_offset_ + degree_bound_ * sizeof(PID);
 
-    data_ = Array<char, std::vector<size_t>, memory::AlignedAllocator<char, 1 << 22, true>>(
-        std::vector<size_t>{num_points_, row_offset_}
-    );
+    capacity_ = num_points_;
+    data_ = RowArray(std::vector<size_t>{capacity_, row_offset_});
 
     visited_list_pool_ = std::make_unique<VisitedListPool>(1, num_points_);
 }
@@ -366,16 +572,16 @@ template <typename T>
 inline void QuantizedGraph<T>::find_candidates(
     PID cur_id,
     size_t search_ef,
-    std::vector<AnnCandidate<T>>& results,
+    CandidateList& results,
     HashBasedBooleanSet& vis,
-    const std::vector<uint32_t>& degrees
+    const uint32_t* degrees
 ) const {
     const T* query = get_vector(cur_id);
     std::vector<T> rotated_query(padded_dim_);
     rotator_->rotate(query, rotated_query.data());
 
     // init query
-    BatchQuery<T> q_obj(rotated_query.data(), padded_dim_);
+    BatchQuery<T> q_obj(rotated_query.data(), padded_dim_, Lut<T>::kNumBits, metric_type_);
 
     // insert entry point to initialize search buffer
     buffer::SearchBuffer tmp_pool(search_ef);
@@ -392,20 +598,19 @@ inline void QuantizedGraph<T>::find_candidates(
             continue;
         }
         vis.set(cur_candi);
-        auto cur_degree = degrees[cur_candi];
-        q_obj.set_g_add(euclidean_sqr(query, get_vector(cur_candi), dim_));
+        size_t cur_degree = degrees == nullptr ? degree_bound_ : degrees[cur_candi];
+        T dist = distance(query, get_vector(cur_candi));
+        q_obj.set_g_add(dist, 1 - dist);
         scan_neighbors(q_obj, cur_candi, est_dist.data(), tmp_pool, vis, cur_degree);
         if (cur_candi != cur_id) {
-            results.emplace_back(cur_candi, q_obj.g_add());
+            results.emplace_back(cur_candi, dist);
         }
     }
 }
 
 // based on new neighbor lists to update quantization code and factors
 template <typename T>
-inline void QuantizedGraph<T>::update_qg(
-    PID cur_id, const std::vector<AnnCandidate<T>>& new_neighbors
-) {
+inline void QuantizedGraph<T>::update_qg(PID cur_id, const CandidateList& new_neighbors) {
     size_t cur_degree = new_neighbors.size();
 
     if (cur_degree == 0) {
@@ -417,6 +622,16 @@ inline void QuantizedGraph<T>::update_qg(
         neighbor_ptr[i] = new_neighbors[i].id;
     }
 
+    quantize_neighbors(cur_id, new_neighbors, get_batch_data(cur_id));
+}
+
+// quantization codes and factors of the neighbors relative to cur_id
+template <typename T>
+inline void QuantizedGraph<T>::quantize_neighbors(
+    PID cur_id, const CandidateList& new_neighbors, char* batch_data
+) const {
+    size_t cur_degree = new_neighbors.size();
+
     // rotated data
     std::vector<T> rotated_data(cur_degree * padded_dim_);
     std::vector<T> rotated_centroid(padded_dim_);
@@ -427,7 +642,6 @@ inline void QuantizedGraph<T>::update_qg(
     this->rotator_->rotate(get_vector(cur_id), rotated_centroid.data());
 
     // quantize batches for current vertex
-    auto* batch_data = get_batch_data(cur_id);
     const auto* data = rotated_data.data();
     for (size_t i = 0; i < cur_degree; i += fastscan::kBatchSize) {
         quant::quantize_qg_batch(
@@ -435,11 +649,184 @@ inline void QuantizedGraph<T>::update_qg(
             rotated_centroid.data(),
             std::min(cur_degree - i, fastscan::kBatchSize),
             padded_dim_,
-            batch_data
+            batch_data,
+            metric_type_
         );
 
         data += fastscan::kBatchSize * padded_dim_;
         batch_data += QGBatchDataMap<T>::data_bytes(padded_dim_);
     }
 }
+
+// keep candidates of the sorted pool that are closer to the vertex than to every kept one,
+// at most degree_bound_. Pruned candidates are recorded in pruned up to max_pruned.
+template <typename T>
+inline void QuantizedGraph<T>::heuristic_prune(
+    const CandidateList& pool,
+    CandidateList& pruned_results,
+    CandidateList* pruned_list,
+    size_t max_pruned
+) const {
+    pruned_results.clear();
+    size_t poolsize = pool.size();
+
+    // if we dont have enough candidates, just keep all neighbors
+    if (poolsize <= degree_bound_) {
+        pruned_results = pool;
+        return;
+    }
+
+    std::vector<bool> pruned(
+        poolsize, false
+    );                 // bool vector to record if this neighbor is pruned
+    size_t start = 0;  // start position
+
+    while (pruned_results.size() < degree_bound_ && start < poolsize) {
+        auto candidate_id = pool[start].id;
+
+        // if already pruned, move to next
+        if (pruned[start]) {
+            ++start;
+            continue;
+        }
+
+        pruned_results.emplace_back(pool[start]);  // add current candidate to result
+        const T* data_j = get_vector(candidate_id);
+
+        // i : current vertex
+        // j : neighbor added in this iter
+        // k : remained unpruned candidate neighbor
+        for (size_t k = start + 1; k < poolsize; ++k) {
+            if (pruned[k]) {
+                continue;
+            }
+            T dik = pool[k].distance;
+            T djk = distance(data_j, get_vector(pool[k].id));
+
+            if (djk < dik) {
+                if (pruned_list != nullptr && pruned_list->size() < max_pruned) {
+                    pruned_list->emplace_back(pool[k]);
+                }
+                pruned[k] = true;
+            }
+        }
+
+        ++start;
+    }
+}
+
+// top up pruned neighbors to degree_bound_ with the nearest candidates left out, since
+// every row is scanned in full
+template <typename T>
+inline void QuantizedGraph<T>::fill_neighbors(
+    const CandidateList& pool, CandidateList& neighbors
+) const {
+    if (neighbors.empty()) {
+        throw std::runtime_error("QG vertex without neighbors");
+    }
+    for (const auto& cand : pool) {
+        if (neighbors.size() >= degree_bound_) {
+            break;
+        }
+        bool dup = std::any_of(neighbors.begin(), neighbors.end(), [&](const auto& nei) {
+            return nei.id == cand.id;
+        });
+        if (!dup) {
+            neighbors.emplace_back(cand);
+        }
+    }
+    // only graphs with fewer vertices than degree_bound_ get repeated neighbors
+    for (size_t i = 0; neighbors.size() < degree_bound_; ++i) {
+        AnnCandidate<T> nei = neighbors[i];
+        neighbors.emplace_back(nei);
+    }
+}
+
+template <typename T>
+inline void QuantizedGraph<T>::reserve(size_t capacity) {
+    insertable_ = true;
+    if (capacity <= capacity_) {
+        return;
+    }
+    RowArray data(std::vector<size_t>{capacity, row_offset_});
+    std::copy(data_.data(), data_.data() + (num_points_ * row_offset_), data.data());
+    data_ = std::move(data);
+    capacity_ = capacity;
+}
+
+template <typename T>
+inline PID QuantizedGraph<T>::insert(const T* vec, size_t ef) {
+    std::lock_guard<std::mutex> insert_lock(insert_lock_);
+    PID cur_id = num_points_;
+    if (cur_id == 0) {
+        throw std::runtime_error("QG insertion needs a built graph");
+    }
+    if (!insertable_) {
+        throw std::runtime_error("QG insertion needs reserve() first");
+    }
+    if (cur_id >= capacity_) {
+        throw std::runtime_error("QG is full, reserve more vertices before inserting");
+    }
+    std::copy(vec, vec + dim_, get_vector(cur_id));
+
+    // only this thread writes rows, the new one is not reachable until its reverse edges
+    CandidateList candidates;
+    auto* vis = visited_list_pool_->get_free_vislist();
+    find_candidates(cur_id, std::max(ef, degree_bound_), candidates, *vis, nullptr);
+    visited_list_pool_->release_vis_list(vis);
+    std::sort(candidates.begin(), candidates.end());
+
+    CandidateList neighbors;
+    heuristic_prune(candidates, neighbors, nullptr, 0);
+    fill_neighbors(candidates, neighbors);
+    update_qg(cur_id, neighbors);
+    num_points_ = cur_id + 1;
+
+    for (const auto& nei : neighbors) {
+        add_reverse_edge(nei.id, cur_id, nei.distance);
+    }
+    return cur_id;
+}
+
+// re-prune the neighbors of dst with src among the candidates, re-quantize the row if src
+// makes it
+template <typename T>
+inline void QuantizedGraph<T>::add_reverse_edge(PID dst, PID src, T dist) {
+    const PID* ptr_nb = get_neighbors(dst);
+    CandidateList pool;
+    pool.reserve(degree_bound_ + 1);
+    for (size_t i = 0; i < degree_bound_; ++i) {
+        PID id = ptr_nb[i];
+        if (id == src) {
+            return;
+        }
+        bool dup = std::any_of(pool.begin(), pool.end(), [&](const auto& cand) {
+            return cand.id == id;
+        });
+        if (!dup) {
+            pool.emplace_back(id, distance(get_vector(dst), get_vector(id)));
+        }
+    }
+    pool.emplace_back(src, dist);
+    std::sort(pool.begin(), pool.end());
+
+    CandidateList neighbors;
+    heuristic_prune(pool, neighbors, nullptr, 0);
+    fill_neighbors(pool, neighbors);
+    if (std::none_of(neighbors.begin(), neighbors.end(), [&](const auto& nei) {
+            return nei.id == src;
+        })) {
+        return;
+    }
+
+    std::vector<char> batch_data(neighbor_offset_ - batch_data_offset_);
+    quantize_neighbors(dst, neighbors, batch_data.data());
+
+    std::lock_guard<std::mutex> lock(row_lock(dst));
+    std::copy(batch_data.begin(), batch_data.end(), get_batch_data(dst));
+    PID* neighbor_ptr = get_neighbors(dst);
+    for (size_t i = 0; i < degree_bound_; ++i) {
+        neighbor_ptr[i] = neighbors[i].id;
+    }
+}
 }  // namespace rabitqlib::symqg
@@ -6,12 +6,15 @@
 #include <cstdint>
 #include <mutex>
 #include <numeric>
+#include <random>
 #include <unordered_set>
 #include <vector>
 
 #include "defines.hpp"
 #include "index/symqg/qg.hpp"
+#include "utils/build_monitor.hpp"
 #include "utils/hashset.hpp"
+#include "utils/log.hpp"
 #include "utils/tools.hpp"
 
 namespace rabitqlib::symqg {
@@ -35,27 +38,30 @@ class QGBuilder {
         750;  // max num of candidates for indexing
     static constexpr size_t kMaxPrunedSize =
         300;  // max number of recorded pruned candidates
+    static constexpr size_t kBuildBatch = 1 << 14;  // vertices between progress reports
     float (*dist_func_)(const float*, const float*, size_t);
     std::vector<CandidateList> new_neighbors_;       // new neighbors for current iteration
     std::vector<CandidateList> pruned_neighbors_;    // recorded pruned neighbors
     std::vector<HashBasedBooleanSet> visited_list_;  // list of visited hash set
     std::vector<uint32_t> degrees_;                  // record degree of qg
+    uint64_t seed_;  // random edges of vertex i use streams i and num_nodes_ + i of it
     void random_init();
-    void search_new_neighbors(bool refine);
+    void search_new_neighbors(bool, const BuildMonitor&, size_t, size_t);
     void heuristic_prune(PID, CandidateList&, CandidateList&, bool);
     void add_reverse_edges(bool);
     void add_pruned_edges(
-        const CandidateList&, const CandidateList&, CandidateList&, float
+        PID, const CandidateList&, const CandidateList&, CandidateList&, float
     );
     void graph_refine();
-    void iter(bool);
+    void iter(bool, const BuildMonitor&, size_t, size_t);
 
    public:
     explicit QGBuilder(
         QuantizedGraph<float>& index,
         uint32_t ef_build,
         const float* data,
-        size_t num_threads = std::numeric_limits<size_t>::max()
+        size_t num_threads = std::numeric_limits<size_t>::max(),
+        uint64_t seed = random_seed()
     )
         : qg_{index}
         , ef_build_{ef_build}
@@ -63,14 +69,17 @@ class QGBuilder {
         , num_nodes_{qg_.num_vertices()}
         , dim_{qg_.dimension()}
         , degree_bound_(qg_.degree_bound())
-        , dist_func_{euclidean_sqr<float>}
+        , dist_func_{
+              qg_.metric_type() == METRIC_IP ? dot_product_dis<float> : euclidean_sqr<float>
+          }
         , new_neighbors_(qg_.num_vertices())
         , pruned_neighbors_(qg_.num_vertices())
         , visited_list_(
               num_threads_,
               HashBasedBooleanSet(std::min(ef_build_ * ef_build_, num_nodes_ / 10))
           )
-        , degrees_(qg_.num_vertices(), degree_bound_) {
+        , degrees_(qg_.num_vertices(), degree_bound_)
+        , seed_(seed) {
         omp_set_num_threads(static_cast<int>(num_threads_));
 
         std::vector<float> centroid =
@@ -79,7 +88,7 @@ class QGBuilder {
         PID entry_point =
             exact_nn(data, centroid.data(), num_nodes_, dim_, num_threads_, dist_func_);
 
-        std::cout << "Setting entry_point to " << entry_point << '\n' << std::flush;
+        log_message(LogLevel::Debug, "Setting entry_point to ", entry_point);
 
         qg_.set_ep(entry_point);
         qg_.copy_vectors(data);
@@ -87,16 +96,18 @@ class QGBuilder {
         random_init();
     }
 
-    void build(size_t num_iter = 3) {
+    // Progress of the "search neighbors" phase spans all iterations, num_iter * vertices
+    // in total, cancellation leaves a graph that has to be built again
+    void build(size_t num_iter = 3, const BuildMonitor& monitor = BuildMonitor()) {
         if (num_iter < 2) {
-            std::cerr << "The number of iter for building qg should >= 3\n";
+            log_message(LogLevel::Error, "The number of iter for building qg should >= 3");
             exit(1);
         }
         // for first iterations, we do not need to refine the graph structure
         for (size_t i = 0; i < num_iter - 1; ++i) {
-            iter(false);
+            iter(false, monitor, i, num_iter);
         }
-        iter(true);
+        iter(true, monitor, num_iter - 1, num_iter);
     }
 
     [[nodiscard]] bool check_dup() const {
@@ -121,6 +132,7 @@ class QGBuilder {
 };
 
 inline void QGBuilder::add_pruned_edges(
+    PID cur_id,
     const CandidateList& result,
     const CandidateList& pruned_list,
     CandidateList& new_result,
@@ -141,6 +153,10 @@ inline void QGBuilder::add_pruned_edges(
         bool occlude = false;
         const float* cur_data = qg_.get_vector(cur.id);
         float dik_sqr = cur.distance;
+        // the angle between the edges needs Euclidean distances
+        if (qg_.metric_type() == METRIC_IP) {
+            dik_sqr = euclidean_sqr(qg_.get_vector(cur_id), cur_data, dim_);
+        }
 
         if (nei_set.find(cur.id) != nei_set.end()) {
             occlude = true;
@@ -148,11 +164,14 @@ inline void QGBuilder::add_pruned_edges(
         }
 
         for (auto& nei : new_result) {
-            float dij_sqr = nei.distance;
-            if (dij_sqr > dik_sqr) {
+            if (nei.distance > cur.distance) {
                 break;
             }
-            float djk_sqr = dist_func_(qg_.get_vector(nei.id), cur_data, dim_);
+            float dij_sqr = nei.distance;
+            if (qg_.metric_type() == METRIC_IP) {
+                dij_sqr = euclidean_sqr(qg_.get_vector(cur_id), qg_.get_vector(nei.id), dim_);
+            }
+            float djk_sqr = euclidean_sqr(qg_.get_vector(nei.id), cur_data, dim_);
             float cosine =
                 (dik_sqr + dij_sqr - djk_sqr) / (2 * std::sqrt(dij_sqr * dik_sqr));
             if (cosine > threshold) {
@@ -177,52 +196,9 @@ inline void QGBuilder::heuristic_prune(
     if (pool.empty()) {
         return;
     }
-    pruned_results.clear();
-    size_t poolsize = pool.size();
-
-    // if we dont have enough candidates, just keep all neighbors
-    if (poolsize <= degree_bound_) {
-        pruned_results = pool;
-        return;
-    }
-
-    std::vector<bool> pruned(
-        poolsize, false
-    );                 // bool vector to record if this neighbor is pruned
-    size_t start = 0;  // start position
-
-    while (pruned_results.size() < degree_bound_ && start < poolsize) {
-        auto candidate_id = pool[start].id;
-
-        // if already pruned, move to next
-        if (pruned[start]) {
-            ++start;
-            continue;
-        }
-
-        pruned_results.emplace_back(pool[start]);  // add current candidate to result
-        const float* data_j = qg_.get_vector(candidate_id);
-
-        // i : current vertex
-        // j : neighbor added in this iter
-        // k : remained unpruned candidate neighbor
-        for (size_t k = start + 1; k < poolsize; ++k) {
-            if (pruned[k]) {
-                continue;
-            }
-            float dik = pool[k].distance;
-            auto djk = dist_func_(data_j, qg_.get_vector(pool[k].id), dim_);
-
-            if (djk < dik) {
-                if (refine && pruned_neighbors_[cur_id].size() < kMaxPrunedSize) {
-                    pruned_neighbors_[cur_id].emplace_back(pool[k]);
-                }
-                pruned[k] = true;
-            }
-        }
-
-        ++start;
-    }
+    qg_.heuristic_prune(
+        pool, pruned_results, refine ? &pruned_neighbors_[cur_id] : nullptr, kMaxPrunedSize
+    );
 }
 
 /**
@@ -230,78 +206,86 @@ inline void QGBuilder::heuristic_prune(
  *
  * @param refine refine = true means recording pruned candidates
  */
-inline void QGBuilder::search_new_neighbors(bool refine) {
+inline void QGBuilder::search_new_neighbors(
+    bool refine, const BuildMonitor& monitor, size_t round, size_t rounds
+) {
+    auto search = [&](size_t begin, size_t end) {
 #pragma omp parallel for schedule(dynamic)
-    for (size_t i = 0; i < num_nodes_; ++i) {
-        PID cur_id = i;
-        auto tid = omp_get_thread_num();
-        CandidateList candidates;
-        HashBasedBooleanSet& vis = visited_list_[tid];
-        candidates.reserve(2 * kMaxCandidatePoolSize);
-        vis.clear();
-        qg_.find_candidates(cur_id, ef_build_, candidates, vis, degrees_);
-
-        // add current neighbors
-        for (auto& nei : new_neighbors_[cur_id]) {
-            auto neighbor_id = nei.id;
-            if (neighbor_id != cur_id && !vis.get(neighbor_id)) {
-                candidates.emplace_back(nei);
+        for (size_t i = begin; i < end; ++i) {
+            PID cur_id = i;
+            auto tid = omp_get_thread_num();
+            CandidateList candidates;
+            HashBasedBooleanSet& vis = visited_list_[tid];
+            candidates.reserve(2 * kMaxCandidatePoolSize);
+            vis.clear();
+            qg_.find_candidates(cur_id, ef_build_, candidates, vis, degrees_.data());
+
+            // add current neighbors
+            for (auto& nei : new_neighbors_[cur_id]) {
+                auto neighbor_id = nei.id;
+                if (neighbor_id != cur_id && !vis.get(neighbor_id)) {
+                    candidates.emplace_back(nei);
+                }
             }
-        }
 
-        size_t min_size = std::min(candidates.size(), kMaxCandidatePoolSize);
-        std::partial_sort(
-            candidates.begin(),
-            candidates.begin() + static_cast<long>(min_size),
-            candidates.end()
-        );
-        candidates.resize(min_size);
+            size_t min_size = std::min(candidates.size(), kMaxCandidatePoolSize);
+            std::partial_sort(
+                candidates.begin(),
+                candidates.begin() + static_cast<long>(min_size),
+                candidates.end()
+            );
+            candidates.resize(min_size);
 
-        // prune and update qg
-        heuristic_prune(cur_id, candidates, new_neighbors_[cur_id], refine);
-    }
+            // prune and update qg
+            heuristic_prune(cur_id, candidates, new_neighbors_[cur_id], refine);
+        }
+    };
+    monitor.for_batches(
+        "search neighbors", num_nodes_, kBuildBatch, search, round * num_nodes_, rounds * num_nodes_
+    );
 }
 
 inline void QGBuilder::add_reverse_edges(bool refine) {
     std::vector<std::mutex> locks(num_nodes_);
-    std::vector<CandidateList> reverse_buffer(num_nodes_);
+    std::vector<CandidateList> reverse_edges(num_nodes_);
 
+    // collect the reverse edges first, the neighbor lists are only read here
 #pragma omp parallel for schedule(dynamic)
     for (PID data_id = 0; data_id < num_nodes_; ++data_id) {
         for (const auto& nei : new_neighbors_[data_id]) {
-            PID dst = nei.id;
-            bool dup = false;
-            CandidateList& dst_neighbors = new_neighbors_[dst];
-            std::lock_guard lock(locks[dst]);
-            for (auto& dst_nei : dst_neighbors) {
-                if (dst_nei.id == data_id) {
-                    dup = true;
-                    break;
-                }
-            }
+            std::lock_guard lock(locks[nei.id]);
+            reverse_edges[nei.id].emplace_back(data_id, nei.distance);
+        }
+    }
+
+    // then add them by source id, so that the graph does not depend on thread scheduling
+#pragma omp parallel for schedule(dynamic)
+    for (PID data_id = 0; data_id < num_nodes_; ++data_id) {
+        CandidateList& sources = reverse_edges[data_id];
+        std::sort(sources.begin(), sources.end(), [](const auto& lhs, const auto& rhs) {
+            return lhs.id < rhs.id;
+        });
+        CandidateList& neighbors = new_neighbors_[data_id];
+        CandidateList tmp_pool;
+        for (const auto& src : sources) {
+            bool dup = std::any_of(neighbors.begin(), neighbors.end(), [&](const auto& nei) {
+                return nei.id == src.id;
+            });
             if (dup) {
                 continue;
             }
-
-            if (dst_neighbors.size() < degree_bound_) {
-                dst_neighbors.emplace_back(data_id, nei.distance);
-            } else {
-                if (reverse_buffer[dst].size() < kMaxCandidatePoolSize) {
-                    reverse_buffer[dst].emplace_back(data_id, nei.distance);
-                }
+            if (neighbors.size() < degree_bound_) {
+                neighbors.emplace_back(src);
+            } else if (tmp_pool.size() < kMaxCandidatePoolSize) {
+                tmp_pool.emplace_back(src);
             }
         }
-    }
+        CandidateList().swap(sources);
 
-#pragma omp parallel for schedule(dynamic)
-    for (PID data_id = 0; data_id < num_nodes_; ++data_id) {
-        CandidateList& tmp_pool = reverse_buffer[data_id];
         tmp_pool.reserve(tmp_pool.size() + degree_bound_);
-        tmp_pool.insert(
-            tmp_pool.end(), new_neighbors_[data_id].begin(), new_neighbors_[data_id].end()
-        );
+        tmp_pool.insert(tmp_pool.end(), neighbors.begin(), neighbors.end());
         std::sort(tmp_pool.begin(), tmp_pool.end());
-        heuristic_prune(data_id, tmp_pool, new_neighbors_[data_id], refine);
+        heuristic_prune(data_id, tmp_pool, neighbors, refine);
     }
 }
 
@@ -310,10 +294,12 @@ inline void QGBuilder::random_init() {
     const PID max_id = num_nodes_ - 1;
 #pragma omp parallel for schedule(dynamic)
     for (size_t i = 0; i < num_nodes_; ++i) {
+        std::mt19937_64 gen(mix_seed(seed_, i));
+        std::uniform_int_distribution<PID> dist(min_id, max_id);
         std::unordered_set<PID> neighbor_set;
         neighbor_set.reserve(degree_bound_);
         while (neighbor_set.size() < degree_bound_) {
-            PID rand_id = rand_integer<PID>(min_id, max_id);
+            PID rand_id = dist(gen);
             if (rand_id != i) {
                 neighbor_set.emplace(rand_id);
             }
@@ -338,7 +324,7 @@ inline void QGBuilder::random_init() {
  *
  */
 inline void QGBuilder::graph_refine() {
-    std::cout << "Supplementing edges...\n";
+    log_message(LogLevel::Debug, "Supplementing edges...");
 
 #pragma omp parallel for schedule(dynamic)
     for (size_t i = 0; i < num_nodes_; ++i) {
@@ -362,7 +348,7 @@ inline void QGBuilder::graph_refine() {
         size_t iter = 0;
         while (iter++ < kMaxBsIter) {
             float mid = (left + right) / 2;
-            add_pruned_edges(cur_neighbors, pruned_list, new_result, mid);
+            add_pruned_edges(i, cur_neighbors, pruned_list, new_result, mid);
             if (new_result.size() < degree_bound_) {
                 left = mid;
             } else {
@@ -371,7 +357,7 @@ inline void QGBuilder::graph_refine() {
         }
 
         // update neighbors with larger cosine value since we want to retain more edges
-        add_pruned_edges(cur_neighbors, pruned_list, new_result, right);
+        add_pruned_edges(i, cur_neighbors, pruned_list, new_result, right);
 
         // if the vertex still doesn't have enough neighbors, use random vertices
         if (new_result.size() < degree_bound_) {
@@ -380,8 +366,10 @@ inline void QGBuilder::graph_refine() {
             for (auto& neighbor : new_result) {
                 ids.emplace(neighbor.id);
             }
+            std::mt19937_64 gen(mix_seed(seed_, num_nodes_ + i));
+            std::uniform_int_distribution<PID> dist(0, static_cast<PID>(num_nodes_) - 1);
             while (new_result.size() < degree_bound_) {
-                PID rand_id = rand_integer<PID>(0, static_cast<PID>(num_nodes_) - 1);
+                PID rand_id = dist(gen);
                 if (rand_id != static_cast<PID>(i) && ids.find(rand_id) == ids.end()) {
                     new_result.emplace_back(
                         rand_id,
@@ -394,10 +382,12 @@ inline void QGBuilder::graph_refine() {
 
         cur_neighbors = new_result;
     }
-    std::cout << "Supplementing finished...\n";
+    log_message(LogLevel::Debug, "Supplementing finished");
 }
 
-inline void QGBuilder::iter(bool refine) {
+inline void QGBuilder::iter(
+    bool refine, const BuildMonitor& monitor, size_t round, size_t rounds
+) {
     if (refine) {
         for (size_t i = 0; i < num_nodes_; ++i) {
             pruned_neighbors_[i].clear();
@@ -405,7 +395,7 @@ inline void QGBuilder::iter(bool refine) {
         }
     }
 
-    search_new_neighbors(refine);
+    search_new_neighbors(refine, monitor, round, rounds);
 
     add_reverse_edges(refine);
 
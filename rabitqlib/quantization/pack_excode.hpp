@@ -1,17 +1,23 @@
 #pragma once
 
+#if defined(__SSE2__)
 #include <immintrin.h>
+#endif
 #include <omp.h>
 
+#include <algorithm>
+#include <array>
 #include <cmath>
 #include <cstddef>
 #include <cstdint>
 #include <cstring>
 #include <iostream>
+#include <vector>
+
+#include "utils/log.hpp"
 
 namespace rabitqlib::quant::rabitq_impl::ex_bits {
 inline void packing_1bit_excode(const uint8_t* o_raw, uint8_t* o_compact, size_t dim) {
-#if defined(__AVX512F__)
     // ! require dim % 16 == 0
     for (size_t j = 0; j < dim; j += 16) {
         uint16_t code = 0;
@@ -23,14 +29,9 @@ inline void packing_1bit_excode(const uint8_t* o_raw, uint8_t* o_compact, size_t
         o_raw += 16;
         o_compact += 2;
     }
-#else
-    std::cerr << "Current only support AVX512F only for packing excode\n" << std::flush;
-    exit(1);
-#endif
 }
 
 inline void packing_2bit_excode(const uint8_t* o_raw, uint8_t* o_compact, size_t dim) {
-#if defined(__AVX512F__)
     // ! require dim % 16 == 0
     for (size_t j = 0; j < dim; j += 16) {
         // pack 16 2-bit codes into int32
@@ -47,20 +48,18 @@ inline void packing_2bit_excode(const uint8_t* o_raw, uint8_t* o_compact, size_t
         o_raw += 16;
         o_compact += 4;
     }
-#else
-    std::cerr << "Current only support AVX512F only for packing excode\n" << std::flush;
-    exit(1);
-#endif
 }
 
 inline void packing_3bit_excode(const uint8_t* o_raw, uint8_t* o_compact, size_t dim) {
-#if defined(__AVX512F__)
     // ! require dim % 64 == 0
+#if defined(__SSE2__)
     const __m128i mask = _mm_set1_epi8(0b11);
+#endif
     for (size_t d = 0; d < dim; d += 64) {
         // split 3-bit codes into 2 bits and 1 bit
         // for 2-bit part, compact it like 2-bit code
         // for 1-bit part, compact 64 1-bit code into a int64
+#if defined(__SSE2__)
         __m128i vec_00_to_15 = _mm_loadu_si128(reinterpret_cast<const __m128i*>(o_raw));
         __m128i vec_16_to_31 =
             _mm_loadu_si128(reinterpret_cast<const __m128i*>(o_raw + 16));
@@ -80,6 +79,12 @@ inline void packing_3bit_excode(const uint8_t* o_raw, uint8_t* o_compact, size_t
         );
 
         _mm_storeu_si128(reinterpret_cast<__m128i*>(o_compact), compact2);
+#else
+        for (size_t k = 0; k < 16; ++k) {
+            o_compact[k] = (o_raw[k] & 0b11) | ((o_raw[16 + k] & 0b11) << 2) |
+                           ((o_raw[32 + k] & 0b11) << 4) | ((o_raw[48 + k] & 0b11) << 6);
+        }
+#endif
         o_compact += 16;
 
         // from lower to upper, each bit in each byte represents vec00 to vec07,
@@ -95,16 +100,9 @@ inline void packing_3bit_excode(const uint8_t* o_raw, uint8_t* o_compact, size_t
         o_raw += 64;
         o_compact += 8;
     }
-#else
-    std::cerr << "Current only support AVX512F only for packing excode\n" << std::flush;
-    exit(1);
-#endif
 }
 
 inline void packing_4bit_excode(const uint8_t* o_raw, uint8_t* o_compact, size_t dim) {
-// although this part only requries SSE, computing inner product for this orgnization
-// requires AVX512F, similar for remaining functions
-#if defined(__AVX512F__)
     // ! require dim % 16 == 0
     for (size_t j = 0; j < dim; j += 16) {
         // pack 16 4-bit codes into uint64
@@ -120,17 +118,15 @@ inline void packing_4bit_excode(const uint8_t* o_raw, uint8_t* o_compact, size_t
         o_raw += 16;
         o_compact += 8;
     }
-#else
-    std::cerr << "Current only support AVX512F only for packing excode\n" << std::flush;
-    exit(1);
-#endif
 }
 
 inline void packing_5bit_excode(const uint8_t* o_raw, uint8_t* o_compact, size_t dim) {
-#if defined(__AVX512F__)
     // ! require dim % 64 == 0
+#if defined(__SSE2__)
     const __m128i mask = _mm_set1_epi8(0b1111);
+#endif
     for (size_t j = 0; j < dim; j += 64) {
+#if defined(__SSE2__)
         __m128i vec_00_to_15 = _mm_loadu_si128(reinterpret_cast<const __m128i*>(o_raw));
         __m128i vec_16_to_31 =
             _mm_loadu_si128(reinterpret_cast<const __m128i*>(o_raw + 16));
@@ -149,6 +145,12 @@ inline void packing_5bit_excode(const uint8_t* o_raw, uint8_t* o_compact, size_t
 
         _mm_storeu_si128(reinterpret_cast<__m128i*>(o_compact), compact4_1);
         _mm_storeu_si128(reinterpret_cast<__m128i*>(o_compact + 16), compact4_2);
+#else
+        for (size_t k = 0; k < 16; ++k) {
+            o_compact[k] = (o_raw[k] & 0b1111) | ((o_raw[16 + k] & 0b1111) << 4);
+            o_compact[16 + k] = (o_raw[32 + k] & 0b1111) | ((o_raw[48 + k] & 0b1111) << 4);
+        }
+#endif
 
         o_compact += 32;
 
@@ -165,14 +167,9 @@ inline void packing_5bit_excode(const uint8_t* o_raw, uint8_t* o_compact, size_t
         o_raw += 64;
         o_compact += 8;
     }
-#else
-    std::cerr << "Current only support AVX512F only for packing excode\n" << std::flush;
-    exit(1);
-#endif
 }
 
 inline void packing_6bit_excode(const uint8_t* o_raw, uint8_t* o_compact, size_t dim) {
-#if defined(__AVX512F__)
     constexpr int64_t kMask4 = 0x0f0f0f0f0f0f0f0f;
     constexpr int32_t kMask2 = 0x30303030;
     for (size_t j = 0; j < dim; j += 16) {
@@ -198,19 +195,17 @@ inline void packing_6bit_excode(const uint8_t* o_raw, uint8_t* o_compact, size_t
         o_raw += 16;
         o_compact += 4;
     }
-#else
-    std::cerr << "Current only support AVX512F only for packing excode\n" << std::flush;
-    exit(1);
-#endif
 }
 
 inline void packing_7bit_excode(const uint8_t* o_raw, uint8_t* o_compact, size_t dim) {
-#if defined(__AVX512F__)
     // for vec00 to vec47, split code into 6 + 1
     // for vec48 to vec63, split code into 2 + 2 + 2 + 1
+#if defined(__SSE2__)
     const __m128i mask2 = _mm_set1_epi8(0b11000000);
     const __m128i mask6 = _mm_set1_epi8(0b00111111);
+#endif
     for (size_t d = 0; d < dim; d += 64) {
+#if defined(__SSE2__)
         __m128i vec_00_to_15 = _mm_loadu_si128(reinterpret_cast<const __m128i*>(o_raw));
         __m128i vec_16_to_31 =
             _mm_loadu_si128(reinterpret_cast<const __m128i*>(o_raw + 16));
@@ -236,6 +231,14 @@ inline void packing_7bit_excode(const uint8_t* o_raw, uint8_t* o_compact, size_t
             _mm_and_si128(_mm_slli_epi16(vec_48_to_63, 2), mask2)
         );
         _mm_storeu_si128(reinterpret_cast<__m128i*>(o_compact + 32), compact);
+#else
+        for (size_t j = 0; j < 3; ++j) {
+            for (size_t k = 0; k < 16; ++k) {
+                o_compact[(16 * j) + k] = (o_raw[(16 * j) + k] & 0b00111111) |
+                                          (((o_raw[48 + k] >> (2 * j)) & 0b11) << 6);
+            }
+        }
+#endif
         o_compact += 48;
 
         int64_t top_bit = 0;
@@ -249,10 +252,6 @@ inline void packing_7bit_excode(const uint8_t* o_raw, uint8_t* o_compact, size_t
         o_compact += 8;
         o_raw += 64;
     }
-#else
-    std::cerr << "Current only support AVX512F only for packing excode\n" << std::flush;
-    exit(1);
-#endif
 }
 
 inline void packing_8bit_excode(const uint8_t* o_raw, uint8_t* o_compact, size_t dim) {
@@ -291,8 +290,59 @@ inline void packing_rabitqplus_code(
     } else if (ex_bits == 8) {
         packing_8bit_excode(o_raw, o_compact, dim);
     } else {
-        std::cerr << "Bad value for ex_bits in packing_rabitqplus_code()\b";
+        log_message(LogLevel::Error, "Bad value for ex_bits in packing_rabitqplus_code()");
         exit(1);
     }
 }
+
+/**
+ * @brief Inverse of packing_rabitqplus_code(). Every packing above only moves bits within
+ * a block of 64 dims, so where each bit of each code lands is found once by packing
+ * one-hot codes.
+ *
+ * @param o_compact compact format of code
+ * @param o_raw unpacked code, code for each dim is represented by uint8
+ * @param dim   dimension of code, multiple of 64
+ * @param ex_bits number of bits used for code
+ */
+inline void unpacking_rabitqplus_code(
+    const uint8_t* o_compact, uint8_t* o_raw, size_t dim, size_t ex_bits
+) {
+    static const std::array<std::vector<uint16_t>, 9> kBitPos = [] {
+        std::array<std::vector<uint16_t>, 9> bit_pos;
+        std::array<uint8_t, 64> raw{};
+        std::array<uint8_t, 64> compact{};
+        for (size_t bits = 1; bits <= 8; ++bits) {
+            bit_pos[bits].resize(64 * bits);
+            for (size_t i = 0; i < 64; ++i) {
+                for (size_t b = 0; b < bits; ++b) {
+                    raw.fill(0);
+                    compact.fill(0);
+                    raw[i] = static_cast<uint8_t>(1 << b);
+                    packing_rabitqplus_code(raw.data(), compact.data(), 64, bits);
+                    for (size_t k = 0; k < 8 * bits * 8; ++k) {
+                        if (((compact[k / 8] >> (k % 8)) & 1) != 0) {
+                            bit_pos[bits][(i * bits) + b] = static_cast<uint16_t>(k);
+                        }
+                    }
+                }
+            }
+        }
+        return bit_pos;
+    }();
+
+    const std::vector<uint16_t>& bit_pos = kBitPos[ex_bits];
+    for (size_t d = 0; d < dim; d += 64) {
+        for (size_t i = 0; i < 64; ++i) {
+            uint8_t code = 0;
+            for (size_t b = 0; b < ex_bits; ++b) {
+                uint16_t k = bit_pos[(i * ex_bits) + b];
+                code |= static_cast<uint8_t>(((o_compact[k / 8] >> (k % 8)) & 1) << b);
+            }
+            o_raw[i] = code;
+        }
+        o_compact += 8 * ex_bits;
+        o_raw += 64;
+    }
+}
 }  // namespace rabitqlib::quant::rabitq_impl::ex_bits
\ No newline at end of file
@@ -265,6 +265,55 @@ inline void quantize_split_single(
     }
 }
 
+/**
+ * @brief Approximate vector of codes made by quantize_split_single(), centroid plus a
+ * residual along the quantized one. For L2 the residual keeps its exact norm, recovered
+ * from the factors; for IP it takes the scale used by the distance estimator.
+ */
+inline void reconstruct_split_single(
+    const char* bin_data,
+    const char* ex_data,
+    const float* centroid,
+    size_t padded_dim,
+    size_t ex_bits,
+    float* results,
+    MetricType metric_type = METRIC_L2
+) {
+    thread_local std::vector<uint8_t> ex_code;
+    ex_code.assign(padded_dim, 0);
+
+    ConstBinDataMap<float> cur_bin_data(bin_data, padded_dim);
+    float f_add = cur_bin_data.f_add();
+    float f_rescale = cur_bin_data.f_rescale();
+    if (ex_bits > 0) {
+        ConstExDataMap<float> cur_ex_data(ex_data, padded_dim, ex_bits);
+        rabitq_impl::ex_bits::unpacking_rabitqplus_code(
+            cur_ex_data.ex_code(), ex_code.data(), padded_dim, ex_bits
+        );
+        f_add = cur_ex_data.f_add_ex();
+        f_rescale = cur_ex_data.f_rescale_ex();
+    }
+
+    // xu_cb, the sign bit is the top bit of the total code
+    float cb = -(static_cast<float>(1 << ex_bits) - 0.5F);
+    const uint64_t* bin_code = cur_bin_data.bin_code();
+    for (size_t i = 0; i < padded_dim; ++i) {
+        uint32_t bit = (bin_code[i / 64] >> (63 - (i % 64))) & 1;
+        results[i] = static_cast<float>((bit << ex_bits) | ex_code[i]) + cb;
+    }
+
+    // the estimator scales xu_cb by l2_sqr / <residual, xu_cb>
+    float scale = metric_type == METRIC_IP ? -f_rescale : -f_rescale / 2;
+    if (metric_type == METRIC_L2) {
+        float l2_sqr = f_add - (2 * scale * dot_product(centroid, results, padded_dim));
+        float xucb_norm = std::sqrt(l2norm_sqr(results, padded_dim));
+        scale = (l2_sqr > 0 && xucb_norm > 0) ? std::sqrt(l2_sqr) / xucb_norm : 0;
+    }
+    for (size_t i = 0; i < padded_dim; ++i) {
+        results[i] = centroid[i] + (scale * results[i]);
+    }
+}
+
 template <typename T, typename TP>
 inline void quantize_full_single(
     const T* data,
@@ -1,6 +1,10 @@
 #pragma once
 
+#if defined(__SSE2__)
 #include <immintrin.h>
+#elif defined(__ARM_NEON)
+#include <arm_neon.h>
+#endif
 #include <omp.h>
 
 #include <cmath>
@@ -11,6 +15,7 @@
 #include "defines.hpp"
 #include "fastscan/fastscan.hpp"
 #include "quantization/pack_excode.hpp"
+#include "utils/log.hpp"
 #include "utils/space.hpp"
 
 namespace rabitqlib::quant::rabitq_impl {
@@ -131,7 +136,7 @@ inline void one_bit_code_with_factor(
         f_rescale = -l2_sqr / ip_resi_xucb;
         f_error = 1 * tmp_error;
     } else {
-        std::cerr << "Unsupported metric type in quantization\n" << std::flush;
+        log_message(LogLevel::Error, "Unsupported metric type in quantization");
         exit(1);
     }
 }
@@ -359,11 +364,13 @@ inline T quantize_ex(const T* o_abs, TP* code, size_t dim, size_t ex_bits) {
     return ipnorm_inv;
 }
 
-// For given dim and ex_bits, use random vectors to get the const rescale factor
+// For given dim and ex_bits, use random vectors to get the const rescale factor. The seed is
+// fixed so that the same data always gets the same codes.
 inline double get_const_scaling_factors(size_t dim, size_t ex_bits) {
     constexpr long kConstNum = 100;
+    constexpr uint64_t kConstSeed = 0;
 
-    RowMajorArray<double> rand = random_gaussian_matrix<double>(kConstNum, dim);
+    RowMajorArray<double> rand = random_gaussian_matrix<double>(kConstNum, dim, kConstSeed);
     rand = rand.rowwise().normalized().abs();
 
     double sum = 0;
@@ -493,7 +500,7 @@ inline void ex_bits_code_with_factor(
         f_rescale_ex = ipnorm_inv * -l2_norm;
         f_error_ex = 1 * tmp_error;
     } else {
-        std::cerr << "Unsupport metric for ex_bits_code()\n" << std::flush;
+        log_message(LogLevel::Error, "Unsupport metric for ex_bits_code()");
         exit(1);
     }
 }
@@ -588,6 +595,7 @@ void rangeSIMD(float* q, float* c, float& vl, float &vr, int B)
     vl = +1e20;
     vr = -1e20;
 
+#if defined(__AVX2__)
     __m256 min_m = _mm256_set1_ps(vl);
     __m256 max_m = _mm256_set1_ps(vr);
     for(int i = 0; i < B; i += 8) {
@@ -603,6 +611,23 @@ void rangeSIMD(float* q, float* c, float& vl, float &vr, int B)
 
     _mm256_storeu_ps(r.data(), min_m);
     vl = *std::min_element(r.begin(), r.end());
+#elif defined(__ARM_NEON)
+    float32x4_t min_m = vdupq_n_f32(vl);
+    float32x4_t max_m = vdupq_n_f32(vr);
+    for (int i = 0; i < B; i += 4) {
+        float32x4_t t = vsubq_f32(vld1q_f32(q + i), vld1q_f32(c + i));
+        max_m = vmaxq_f32(max_m, t);
+        min_m = vminq_f32(min_m, t);
+    }
+    vr = vmaxvq_f32(max_m);
+    vl = vminvq_f32(min_m);
+#else
+    for (int i = 0; i < B; ++i) {
+        float t = q[i] - c[i];
+        vr = std::max(vr, t);
+        vl = std::min(vl, t);
+    }
+#endif
 }
 
 // ==============================================================
@@ -619,11 +644,14 @@ void quantizeSIMD(uint8_t *result, float *q, float* c, const float * u, float vl
     //float one_over_width = 1.0 / width;
     uint8_t *ptr_res = result; //这里uint8 但是实际只有4bit 即没有sq的q_u[i]
 
+#if defined(__AVX2__)
     __m256 vl_256 = _mm256_set1_ps(vl);
     __m256 width_256 = _mm256_set1_ps(width);
     __m256 u_256 = _mm256_set1_ps(u[0]);
     __m256i sum_256 = _mm256_set1_epi8(0);
+#if defined(__AVX512F__) && defined(__AVX512VL__)
     const __mmask8 ff_mask = 0xff;
+#endif
     for(int i=0; i<B; i+=8) {
         //对应公式18， uint8的转换是因为转换后不会超过uint8
         __m256 v01 = _mm256_loadu_ps(q + i);
@@ -635,7 +663,13 @@ void quantizeSIMD(uint8_t *result, float *q, float* c, const float * u, float vl
 
         __m256i ti = _mm256_cvtps_epi32(t); //float to int
         sum_256 = _mm256_add_epi32(sum_256, ti);
+#if defined(__AVX512F__) && defined(__AVX512VL__)
         _mm256_mask_cvtepi32_storeu_epi8((void*)(ptr_res+i), ff_mask, ti);
+#else
+        // the codes fit in 8 bits, so the saturating packs keep them as they are
+        __m128i ti16 = _mm_packus_epi32(_mm256_castsi256_si128(ti), _mm256_extracti128_si256(ti, 1));
+        _mm_storel_epi64(reinterpret_cast<__m128i*>(ptr_res + i), _mm_packus_epi16(ti16, ti16));
+#endif
     }
     uint32_t u32_t[8];
     sum_q = 0;
@@ -643,6 +677,15 @@ void quantizeSIMD(uint8_t *result, float *q, float* c, const float * u, float vl
     for(int i = 0; i < 8; i++) {
         sum_q += u32_t[i];
     }
+#else
+    sum_q = 0;
+    for (int i = 0; i < B; ++i) {
+        // same rounding as _mm256_cvtps_epi32 (to nearest even)
+        auto ti = static_cast<int32_t>(std::nearbyint((q[i] - c[i] - vl) / width + u[0]));
+        sum_q += ti;
+        ptr_res[i] = static_cast<uint8_t>(ti);
+    }
+#endif
 }
 
 void simd_residual_query(std::vector<float>& processed_query, float* random_cent_vec, float* query_residual,
@@ -653,6 +696,7 @@ void simd_residual_query(std::vector<float>& processed_query, float* random_cent
 {//        query_residual[d] =  processed_query[d] - random_cent_vec[d];
  //        query_square_dist_to_centroid += query_residual[d] * query_residual[d];
  //        sum_longq += query_residual[d];
+#if defined(__AVX512F__)
     __m512 l2dist_to_cent = _mm512_setzero_ps();
     __m512 sum_residual = _mm512_setzero_ps();
     for (uint32_t d = 0; d < ndims; d+=16)
@@ -667,6 +711,15 @@ void simd_residual_query(std::vector<float>& processed_query, float* random_cent
 
     query_square_dist_to_centroid = _mm512_reduce_add_ps(l2dist_to_cent);
     sum_longq = _mm512_reduce_add_ps(sum_residual);
+#else
+    query_square_dist_to_centroid = 0;
+    sum_longq = 0;
+    for (uint32_t d = 0; d < ndims; ++d) {
+        query_residual[d] = processed_query[d] - random_cent_vec[d];
+        query_square_dist_to_centroid += query_residual[d] * query_residual[d];
+        sum_longq += query_residual[d];
+    }
+#endif
 }
 
 template <typename T, typename TP>
@@ -0,0 +1,98 @@
+#pragma once
+
+#include <chrono>
+#include <cstddef>
+#include <cstdint>
+#include <limits>
+
+namespace rabitqlib {
+
+/**
+ * @brief Limits on the work of one search. Once one is reached the search stops, keeps the
+ * best results found so far and sets partial. Distances count the vectors whose distance is
+ * estimated or computed, boostings the ex-bit refinements of an estimate. The deadline is
+ * checked between clusters, batches of vectors and graph vertices, it runs from start().
+ * A budget serves one query at a time, its counters hold the work of the last search.
+ */
+struct SearchBudget {
+    using Clock = std::chrono::steady_clock;
+    static constexpr size_t kUnlimited = std::numeric_limits<size_t>::max();
+
+    Clock::duration time_limit = Clock::duration::max();
+    size_t max_clusters = kUnlimited;
+    size_t max_distances = kUnlimited;
+    size_t max_boosts = kUnlimited;
+
+    Clock::time_point deadline = Clock::time_point::max();  // set by start()
+    size_t clusters = 0;
+    size_t distances = 0;
+    size_t boosts = 0;
+    bool partial = false;
+
+    SearchBudget() = default;
+
+    // limits of 0 are unlimited
+    explicit SearchBudget(
+        uint64_t time_us, size_t max_clusters, size_t max_distances, size_t max_boosts
+    )
+        : time_limit(
+              time_us == 0 ? Clock::duration::max()
+                           : std::chrono::duration_cast<Clock::duration>(
+                                 std::chrono::microseconds(time_us)
+                             )
+          )
+        , max_clusters(max_clusters == 0 ? kUnlimited : max_clusters)
+        , max_distances(max_distances == 0 ? kUnlimited : max_distances)
+        , max_boosts(max_boosts == 0 ? kUnlimited : max_boosts) {}
+
+    // reset the counters and start the clock, called by the search
+    void start() {
+        clusters = 0;
+        distances = 0;
+        boosts = 0;
+        partial = false;
+        deadline = time_limit == Clock::duration::max() ? Clock::time_point::max()
+                                                        : Clock::now() + time_limit;
+    }
+
+    // false once the deadline has passed
+    bool in_time() {
+        if (deadline != Clock::time_point::max() && Clock::now() >= deadline) {
+            partial = true;
+            return false;
+        }
+        return true;
+    }
+
+    // take one more cluster to scan, false if over the limit or the deadline
+    bool take_cluster() {
+        if (clusters >= max_clusters) {
+            partial = true;
+            return false;
+        }
+        ++clusters;
+        return in_time();
+    }
+
+    // take num more distances, false if they would exceed the limit
+    bool take_distances(size_t num) {
+        if (num > max_distances - distances) {
+            partial = true;
+            return false;
+        }
+        distances += num;
+        return true;
+    }
+
+    // take one more boosting, false if over the limit. The search then goes on with the
+    // 1-bit estimates.
+    bool take_boost() {
+        if (boosts >= max_boosts) {
+            partial = true;
+            return false;
+        }
+        ++boosts;
+        return true;
+    }
+};
+}  // namespace rabitqlib
@@ -90,6 +90,14 @@ class SearchBuffer {
         }
     }
 
+    // copy the ids and distances of the buffered candidates
+    void copy_results(PID* knn, T* distances) const {
+        for (size_t i = 0; i < size_; ++i) {
+            knn[i] = data_[i].id;
+            distances[i] = data_[i].distance;
+        }
+    }
+
     T top_dist() const {
         return is_full() ? data_[size_ - 1].distance : std::numeric_limits<T>::max();
     }
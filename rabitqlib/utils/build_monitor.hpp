@@ -0,0 +1,65 @@
+#pragma once
+
+#include <algorithm>
+#include <atomic>
+#include <cstddef>
+#include <functional>
+#include <stdexcept>
+
+namespace rabitqlib {
+
+// thrown by a build whose cancellation token was set, the index has to be built again
+class BuildCancelled : public std::runtime_error {
+   public:
+    BuildCancelled() : std::runtime_error("index build cancelled") {}
+};
+
+/**
+ * @brief Observer of a long index build. Builds run their items in batches; between two
+ * batches progress(phase, done, total) is called by the thread that started the build, and
+ * the build throws BuildCancelled once *cancel is set. The default monitor does nothing.
+ */
+struct BuildMonitor {
+    std::function<void(const char* phase, size_t done, size_t total)> progress;
+    const std::atomic<bool>* cancel = nullptr;
+
+    [[nodiscard]] bool cancelled() const {
+        return cancel != nullptr && cancel->load(std::memory_order_relaxed);
+    }
+
+    void check() const {
+        if (cancelled()) {
+            throw BuildCancelled();
+        }
+    }
+
+    void report(const char* phase, size_t done, size_t total) const {
+        if (progress) {
+            progress(phase, done, total);
+        }
+    }
+
+    /**
+     * @brief Calls fn(begin, end) on consecutive batches of [0, num), checking cancellation
+     * before and reporting progress after each. offset and total place the batches in a
+     * longer phase, e.g. one of several rounds over the same items.
+     */
+    template <typename Function>
+    void for_batches(
+        const char* phase,
+        size_t num,
+        size_t batch,
+        Function fn,
+        size_t offset = 0,
+        size_t total = 0
+    ) const {
+        total = std::max(total, offset + num);
+        for (size_t begin = 0; begin < num; begin += batch) {
+            check();
+            size_t end = std::min(num, begin + batch);
+            fn(begin, end);
+            report(phase, offset + end, total);
+        }
+    }
+};
+}  // namespace rabitqlib
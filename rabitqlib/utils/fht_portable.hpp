@@ -0,0 +1,59 @@
+// Portable fast Walsh-Hadamard transform, used when the AVX kernels in fht_avx.hpp are not
+// available (e.g., aarch64). The result is the same unnormalized transform in natural order.
+
+#pragma once
+
+#if defined(__ARM_NEON)
+#include <arm_neon.h>
+#endif
+
+#include <cstddef>
+
+namespace rabitqlib::fht_impl {
+template <size_t LogN>
+inline void fht_float(float* buf) {
+    constexpr size_t kLen = 1 << LogN;
+
+    // the first two levels work inside groups of 4 floats
+    for (size_t j = 0; j < kLen; j += 4) {
+        float a0 = buf[j] + buf[j + 1];
+        float a1 = buf[j] - buf[j + 1];
+        float a2 = buf[j + 2] + buf[j + 3];
+        float a3 = buf[j + 2] - buf[j + 3];
+        buf[j] = a0 + a2;
+        buf[j + 1] = a1 + a3;
+        buf[j + 2] = a0 - a2;
+        buf[j + 3] = a1 - a3;
+    }
+
+    for (size_t h = 4; h < kLen; h *= 2) {
+        for (size_t i = 0; i < kLen; i += 2 * h) {
+            float* x = buf + i;
+            float* y = buf + i + h;
+#if defined(__ARM_NEON)
+            for (size_t j = 0; j < h; j += 4) {
+                float32x4_t u = vld1q_f32(x + j);
+                float32x4_t v = vld1q_f32(y + j);
+                vst1q_f32(x + j, vaddq_f32(u, v));
+                vst1q_f32(y + j, vsubq_f32(u, v));
+            }
+#else
+            for (size_t j = 0; j < h; ++j) {
+                float u = x[j];
+                float v = y[j];
+                x[j] = u + v;
+                y[j] = u - v;
+            }
+#endif
+        }
+    }
+}
+}  // namespace rabitqlib::fht_impl
+
+// same names as the kernels in fht_avx.hpp, only the sizes used by FhtKacRotator
+inline void helper_float_6(float* buf) { rabitqlib::fht_impl::fht_float<6>(buf); }
+inline void helper_float_7(float* buf) { rabitqlib::fht_impl::fht_float<7>(buf); }
+inline void helper_float_8(float* buf) { rabitqlib::fht_impl::fht_float<8>(buf); }
+inline void helper_float_9(float* buf) { rabitqlib::fht_impl::fht_float<9>(buf); }
+inline void helper_float_10(float* buf) { rabitqlib::fht_impl::fht_float<10>(buf); }
+inline void helper_float_11(float* buf) { rabitqlib::fht_impl::fht_float<11>(buf); }
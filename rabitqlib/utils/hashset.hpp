@@ -24,6 +24,7 @@
 #include <unordered_set>
 
 #include "defines.hpp"
+#include "utils/log.hpp"
 #include "utils/memory.hpp"
 
 namespace rabitqlib {
@@ -79,7 +80,7 @@ class HashBasedBooleanSet {
         mask_ = static_cast<PID>(table_size_ - 1);
         const PID check_val = hash1(static_cast<PID>(table_size));
         if (check_val != 0) {
-            std::cerr << "[WARN] table size is not 2^N :  " << table_size << '\n';
+            log_message(LogLevel::Warn, "table size is not 2^N :  ", table_size);
         }
 
         table_ = std::vector<PID, memory::AlignedAllocator<PID>>(table_size);
@@ -8,6 +8,8 @@
 #include <iostream>
 #include <type_traits>
 
+#include "utils/log.hpp"
+
 namespace rabitqlib {
 // get num of bytes
 inline size_t get_filesize(const char* filename) {
@@ -22,7 +24,7 @@ inline bool file_exists(const char* filename) { return std::filesystem::exists(f
 template <typename T, class M>
 void load_vecs(const char* filename, M& row_mat) {
     if (!file_exists(filename)) {
-        std::cerr << "File " << filename << " not exists\n";
+        log_message(LogLevel::Error, "File ", filename, " not exists");
         exit(1);
     }
 
@@ -45,8 +47,7 @@ void load_vecs(const char* filename, M& row_mat) {
         input.read(reinterpret_cast<char*>(&row_mat(i, 0)), sizeof(T) * cols);
     }
 
-    std::cout << "File " << filename << " loaded\n";
-    std::cout << "Rows " << rows << " Cols " << cols << '\n' << std::flush;
+    log_message(LogLevel::Info, "File ", filename, " loaded, rows ", rows, " cols ", cols);
     input.close();
 }
 
@@ -54,7 +55,7 @@ void load_vecs(const char* filename, M& row_mat) {
 template <typename T, class M>
 void load_bin(const char* filename, M& row_mat) {
     if (!file_exists(filename)) {
-        std::cerr << "File " << filename << " not exists\n";
+        log_message(LogLevel::Error, "File ", filename, " not exists");
         exit(1);
     }
 
@@ -73,8 +74,7 @@ void load_bin(const char* filename, M& row_mat) {
         input.read(reinterpret_cast<char*>(&row_mat(i, 0)), sizeof(T) * cols);
     }
 
-    std::cout << "File " << filename << " loaded\n";
-    std::cout << "Rows " << rows << " Cols " << cols << '\n' << std::flush;
+    log_message(LogLevel::Info, "File ", filename, " loaded, rows ", rows, " cols ", cols);
     input.close();
 }
 }  // namespace rabitqlib
\ No newline at end of file
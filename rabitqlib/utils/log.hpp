@@ -0,0 +1,49 @@
+#pragma once
+
+#include <atomic>
+#include <iostream>
+#include <sstream>
+
+namespace rabitqlib {
+
+enum class LogLevel : int { Error, Warn, Info, Debug };
+
+/**
+ * @brief Receives each message of the library, without a trailing newline. Construction
+ * runs OpenMP loops, so it may be called from several threads at once.
+ */
+using LogCallback = void (*)(LogLevel level, const char* message);
+
+namespace log_impl {
+inline std::atomic<LogCallback> callback{nullptr};
+inline std::atomic<int> max_level{static_cast<int>(LogLevel::Info)};
+}  // namespace log_impl
+
+/**
+ * @brief Routes the messages of the library to callback. Nothing is printed by default,
+ * nullptr silences it again. Messages above max_level are not even formatted.
+ */
+inline void set_log_callback(LogCallback callback, LogLevel max_level = LogLevel::Info) {
+    log_impl::max_level.store(static_cast<int>(max_level), std::memory_order_relaxed);
+    log_impl::callback.store(callback, std::memory_order_release);
+}
+
+// formats args with operator<< and passes them to the registered callback, if any
+template <typename... Args>
+void log_message(LogLevel level, const Args&... args) {
+    LogCallback callback = log_impl::callback.load(std::memory_order_acquire);
+    if (callback == nullptr ||
+        static_cast<int>(level) > log_impl::max_level.load(std::memory_order_relaxed)) {
+        return;
+    }
+    std::ostringstream message;
+    (message << ... << args);
+    callback(level, message.str().c_str());
+}
+
+// callback printing to std::cerr, what the samples register
+inline void log_to_stderr(LogLevel level, const char* message) {
+    static constexpr const char* kNames[] = {"error", "warn", "info", "debug"};
+    std::cerr << '[' << kNames[static_cast<int>(level)] << "] " << message << '\n';
+}
+}  // namespace rabitqlib
@@ -7,16 +7,32 @@
 #include <fstream>
 #include <functional>
 #include <iostream>
+#include <numeric>
 #include <random>
+#include <stdexcept>
+
+#if defined(__ARM_NEON)
+#include <arm_neon.h>
+#endif
 
 #include "defines.hpp"
+#if defined(__AVX__)
 #include "utils/fht_avx.hpp"
+#else
+#include "utils/fht_portable.hpp"
+#endif
+#include "utils/log.hpp"
 #include "utils/space.hpp"
 #include "utils/tools.hpp"
 
 namespace rabitqlib {
 
-enum class RotatorType : uint8_t { MatrixRotator, FhtKacRotator };
+enum class RotatorType : uint8_t {
+    MatrixRotator,
+    FhtKacRotator,
+    PcaRotator,
+    LearnedRotator
+};
 
 // abstract rotator
 template <typename T>
@@ -32,6 +48,9 @@ class Rotator {
     virtual void rotate(const T* src, T* dst) const = 0;
     virtual void load(std::ifstream&) = 0;
     virtual void save(std::ofstream&) const = 0;
+    // deep copy, e.g. to share one rotation between several indexes
+    [[nodiscard]] virtual Rotator* clone() const = 0;
+    [[nodiscard]] virtual RotatorType type() const = 0;
     [[nodiscard]] size_t size() const { return this->padded_dim_; }
     size_t dim() const { return this->dim_; }
 };
@@ -43,10 +62,10 @@ inline size_t padding_requirement(size_t dim, RotatorType type) {
     if (type == RotatorType::MatrixRotator) {
         return dim;
     }
-    if (type == RotatorType::FhtKacRotator) {
+    if (type == RotatorType::FhtKacRotator || type == RotatorType::LearnedRotator) {
         return round_up_to_multiple(dim, 64);
     }
-    std::cerr << "Invalid rotator type in padding_requirement()\n" << std::flush;
+    log_message(LogLevel::Error, "Invalid rotator type in padding_requirement()");
     exit(1);
 }
 
@@ -55,9 +74,9 @@ class MatrixRotator : public Rotator<T> {
    private:
     RowMajorMatrix<T> rand_mat_;  // Rotation Maxtrix
    public:
-    explicit MatrixRotator(size_t dim, size_t padded_dim)
+    explicit MatrixRotator(size_t dim, size_t padded_dim, uint64_t seed = random_seed())
         : Rotator<T>(dim, padded_dim), rand_mat_(dim, padded_dim) {
-        RowMajorMatrix<T> rand = random_gaussian_matrix<T>(padded_dim, padded_dim);
+        RowMajorMatrix<T> rand = random_gaussian_matrix<T>(padded_dim, padded_dim, seed);
         Eigen::HouseholderQR<RowMajorMatrix<T>> qr(rand);
         RowMajorMatrix<T> q_inv =
             qr.householderQ().transpose();  // inverse of orthogonal mat is its inverse
@@ -76,6 +95,14 @@ class MatrixRotator : public Rotator<T> {
         return *this;
     }
 
+    [[nodiscard]] Rotator<T>* clone() const override {
+        auto* copy = new MatrixRotator();
+        *copy = *this;
+        return copy;
+    }
+
+    [[nodiscard]] RotatorType type() const override { return RotatorType::MatrixRotator; }
+
     void load(std::ifstream& input) override {
         input.read(
             reinterpret_cast<char*>(rand_mat_.data()),
@@ -97,6 +124,119 @@ class MatrixRotator : public Rotator<T> {
     }
 };
 
+/**
+ * @brief Orthogonal rotation learned from a sample in the way of ITQ: alternately take the
+ * sign codes of the rotated sample and solve the orthogonal Procrustes problem that maps the
+ * sample closest to them. Each step raises <o_bar, o> of the 1-bit codes, which the RaBitQ
+ * error shrinks with. The sample is centered on its mean and normalized, as residuals are
+ * before quantization. Stored and applied like a MatrixRotator, padded to a multiple of 64.
+ */
+class LearnedRotator : public Rotator<float> {
+   private:
+    RowMajorMatrix<float> rot_mat_;  // dim * padded_dim, first dim rows of the rotation
+
+    static constexpr size_t kChunkRows = 4096;
+
+    // centered and normalized rows [begin, begin + rows) of the sample
+    static RowMajorMatrix<float> unit_rows(
+        const float* data, size_t begin, size_t rows, size_t dim, const Vector<float>& mean
+    ) {
+        RowMajorMatrix<float> chunk = ConstRowMajorMatrixMap<float>(
+            data + (begin * dim), static_cast<long>(rows), static_cast<long>(dim)
+        );
+        chunk.rowwise() -= mean.transpose();
+        for (long i = 0; i < chunk.rows(); ++i) {
+            float norm = chunk.row(i).norm();
+            if (norm > 0) {
+                chunk.row(i) /= norm;
+            }
+        }
+        return chunk;
+    }
+
+   public:
+    // empty rotator of the given dims, only to be filled by load()
+    explicit LearnedRotator(size_t dim, size_t padded_dim)
+        : Rotator<float>(dim, padded_dim), rot_mat_(dim, padded_dim) {}
+
+    // learn the rotation of the num * dim sample in iters alternating steps, starting from a
+    // random one drawn from seed
+    explicit LearnedRotator(
+        const float* data, size_t num, size_t dim, size_t iters, uint64_t seed = random_seed()
+    )
+        : Rotator<float>(dim, round_up_to_multiple(dim, 64)) {
+        if (num < 2 || dim == 0) {
+            throw std::invalid_argument("learning a rotation needs 2 samples");
+        }
+        auto padded = static_cast<long>(this->padded_dim_);
+        RowMajorMatrix<float> rand = random_gaussian_matrix<float>(padded, padded, seed);
+        Eigen::HouseholderQR<RowMajorMatrix<float>> qr(rand);
+        rot_mat_ = RowMajorMatrix<float>(qr.householderQ().transpose())
+                       .topRows(static_cast<long>(dim));
+
+        Vector<float> mean = Vector<float>::Zero(static_cast<long>(dim));
+        for (size_t i = 0; i < num; ++i) {
+            mean += ConstVectorMap<float>(data + (i * dim), static_cast<long>(dim));
+        }
+        mean /= static_cast<float>(num);
+
+        // padded rows of X^T B are zero, the Procrustes solution still is a full rotation
+        RowMajorMatrix<double> cross(padded, padded);
+        for (size_t iter = 0; iter < iters; ++iter) {
+            cross.setZero();
+            for (size_t begin = 0; begin < num; begin += kChunkRows) {
+                size_t rows = std::min(kChunkRows, num - begin);
+                RowMajorMatrix<float> chunk = unit_rows(data, begin, rows, dim, mean);
+                RowMajorMatrix<float> codes = (chunk * rot_mat_).array().sign();
+                cross.topRows(static_cast<long>(dim)).noalias() +=
+                    (chunk.transpose() * codes).cast<double>();
+            }
+            Eigen::BDCSVD<RowMajorMatrix<double>> svd(
+                cross, Eigen::ComputeFullU | Eigen::ComputeFullV
+            );
+            rot_mat_ = (svd.matrixU() * svd.matrixV().transpose())
+                           .topRows(static_cast<long>(dim))
+                           .cast<float>();
+        }
+    }
+
+    LearnedRotator() = default;
+    ~LearnedRotator() override = default;
+
+    LearnedRotator& operator=(const LearnedRotator& other) = default;
+
+    [[nodiscard]] Rotator<float>* clone() const override {
+        auto* copy = new LearnedRotator();
+        *copy = *this;
+        return copy;
+    }
+
+    [[nodiscard]] RotatorType type() const override { return RotatorType::LearnedRotator; }
+
+    void load(std::ifstream& input) override {
+        input.read(
+            reinterpret_cast<char*>(rot_mat_.data()),
+            static_cast<long>(sizeof(float) * this->dim_ * this->padded_dim_)
+        );
+        if (!input) {
+            throw std::runtime_error("bad learned rotator");
+        }
+    }
+
+    void save(std::ofstream& output) const override {
+        output.write(
+            reinterpret_cast<const char*>(rot_mat_.data()),
+            static_cast<long>(sizeof(float) * this->dim_ * this->padded_dim_)
+        );
+    }
+
+    void rotate(const float* vec, float* rotated_vec) const override {
+        ConstRowMajorMatrixMap<float> v(vec, 1, static_cast<long>(this->dim_));
+        RowMajorMatrixMap<float> rv(rotated_vec, 1, static_cast<long>(this->padded_dim_));
+        rv = v * rot_mat_;
+    }
+};
+
 static inline void flip_sign(const uint8_t* flip, float* data, size_t dim) {
     constexpr size_t kFloatsPerChunk = 64;  // Process 64 floats per iteration
     // constexpr size_t bits_per_chunk = floats_per_chunk;  // 64 bits = 8 bytes
@@ -111,6 +251,27 @@ static inline void flip_sign(const uint8_t* flip, float* data, size_t dim) {
         uint64_t mask_bits;
         std::memcpy(&mask_bits, &flip[i / 8], sizeof(mask_bits));
 
+#if defined(__ARM_NEON)
+        static const uint32_t kBit[4] = {1, 2, 4, 8};
+        const uint32x4_t bit = vld1q_u32(kBit);
+        const uint32x4_t sign_flip = vdupq_n_u32(0x80000000);
+
+        // Process 4 floats at a time with each 4-bit mask segment
+        for (size_t j = 0; j < kFloatsPerChunk; j += 4) {
+            uint32x4_t mask =
+                vtstq_u32(vdupq_n_u32(static_cast<uint32_t>(mask_bits >> j)), bit);
+            uint32x4_t vec = vreinterpretq_u32_f32(vld1q_f32(&data[i + j]));
+            vec = veorq_u32(vec, vandq_u32(mask, sign_flip));
+            vst1q_f32(&data[i + j], vreinterpretq_f32_u32(vec));
+        }
+#elif !defined(__AVX512F__)
+        for (size_t j = 0; j < kFloatsPerChunk; ++j) {
+            if ((mask_bits >> j) & 1) {
+                data[i + j] = -data[i + j];
+            }
+        }
+#else
+
         // Split into four 16-bit mask segments
         const __mmask16 mask0 = _cvtu32_mask16(static_cast<uint32_t>(mask_bits & 0xFFFF));
         const __mmask16 mask1 =
@@ -139,6 +300,7 @@ static inline void flip_sign(const uint8_t* flip, float* data, size_t dim) {
         __m512 vec3 = _mm512_loadu_ps(&data[i + 48]);
         vec3 = _mm512_mask_xor_ps(vec3, mask3, vec3, sign_flip);
         _mm512_storeu_ps(&data[i + 48], vec3);
+#endif
     }
 }
 
@@ -152,10 +314,9 @@ class FhtKacRotator : public Rotator<float> {
     static constexpr size_t kByteLen = 8;
 
    public:
-    explicit FhtKacRotator(size_t dim, size_t padded_dim)
+    explicit FhtKacRotator(size_t dim, size_t padded_dim, uint64_t seed = random_seed())
         : Rotator<float>(dim, padded_dim), flip_(4 * padded_dim / kByteLen) {
-        std::random_device rd;   // Seed
-        std::mt19937 gen(rd());  // Mersenne Twister RNG
+        std::mt19937_64 gen(seed);
 
         // Uniform distribution in the range [0, 255]
         std::uniform_int_distribution<int> dist(0, 255);
@@ -191,7 +352,7 @@ class FhtKacRotator : public Rotator<float> {
                 break;
             default:
                 // TODO(lib): should we do more?
-                std::cerr << "dimension of vector is too big\n";
+                log_message(LogLevel::Error, "dimension of vector is too big");
                 exit(1);
         }
     }
@@ -222,8 +383,33 @@ class FhtKacRotator : public Rotator<float> {
         return *this;
     }
 
+    [[nodiscard]] Rotator<float>* clone() const override {
+        auto* copy = new FhtKacRotator();
+        *copy = *this;
+        return copy;
+    }
+
+    [[nodiscard]] RotatorType type() const override { return RotatorType::FhtKacRotator; }
+
     static void kacs_walk(float* data, size_t len) {
         // ! len % 32 == 0;
+#if defined(__ARM_NEON)
+        for (size_t i = 0; i < len / 2; i += 4) {
+            float32x4_t x = vld1q_f32(&data[i]);
+            float32x4_t y = vld1q_f32(&data[i + (len / 2)]);
+
+            vst1q_f32(&data[i], vaddq_f32(x, y));
+            vst1q_f32(&data[i + (len / 2)], vsubq_f32(x, y));
+        }
+#elif !defined(__AVX512F__)
+        for (size_t i = 0; i < len / 2; ++i) {
+            float x = data[i];
+            float y = data[i + (len / 2)];
+
+            data[i] = x + y;
+            data[i + (len / 2)] = x - y;
+        }
+#else
         for (size_t i = 0; i < len / 2; i += 16) {
             __m512 x = _mm512_loadu_ps(&data[i]);
             __m512 y = _mm512_loadu_ps(&data[i + (len / 2)]);
@@ -234,10 +420,14 @@ class FhtKacRotator : public Rotator<float> {
             _mm512_storeu_ps(&data[i], new_x);
             _mm512_storeu_ps(&data[i + (len / 2)], new_y);
         }
+#endif
     }
 
+    // rotated_vec may be data, rotating in place
     void rotate(const float* data, float* rotated_vec) const override {
-        std::memcpy(rotated_vec, data, sizeof(float) * dim_);
+        if (rotated_vec != data) {
+            std::memcpy(rotated_vec, data, sizeof(float) * dim_);
+        }
         std::fill(rotated_vec + dim_, rotated_vec + padded_dim_, 0);
 
         if (trunc_dim_ == padded_dim_) {
@@ -291,43 +481,218 @@ class FhtKacRotator : public Rotator<float> {
         vec_rescale(rotated_vec, padded_dim_, 0.25F);
     }
 };
+
+/**
+ * @brief PCA projection from dim to out_dim followed by a FhtKacRotator on out_dim padded
+ * to a multiple of 64, so that codes only spend bits on the leading principal components.
+ * The components are learned from a sample; the projection itself is linear (the sample
+ * mean is not subtracted), so differences and inner products keep their form.
+ */
+class PcaRotator : public Rotator<float> {
+   private:
+    size_t out_dim_ = 0;                // num of kept components
+    RowMajorMatrix<float> components_;  // dim * out_dim, leading components as columns
+    std::vector<float> variances_;      // variance along each kept component
+    float total_variance_ = 0;          // variance of the sample over all dimensions
+    FhtKacRotator rotator_;             // rotation of the projected vectors
+
+    static constexpr size_t kChunkRows = 4096;
+
+   public:
+    // empty rotator of the given input dim, only to be filled by load()
+    explicit PcaRotator(size_t dim, size_t padded_dim) : Rotator<float>(dim, padded_dim) {}
+
+    // learn the out_dim leading components of the num * dim sample, the rotation after them
+    // is drawn from seed
+    explicit PcaRotator(
+        const float* data, size_t num, size_t dim, size_t out_dim, uint64_t seed = random_seed()
+    )
+        : Rotator<float>(dim, round_up_to_multiple(out_dim, 64))
+        , out_dim_(out_dim)
+        , components_(dim, out_dim)
+        , variances_(out_dim) {
+        if (num < 2 || out_dim == 0 || out_dim > dim) {
+            throw std::invalid_argument("PCA needs 2 samples and out_dim in 1..=dim");
+        }
+        rotator_ = FhtKacRotator(this->padded_dim_, this->padded_dim_, seed);
+        // covariance accumulated chunk by chunk in double
+        Vector<double> mean = Vector<double>::Zero(static_cast<long>(dim));
+        for (size_t i = 0; i < num; ++i) {
+            mean += ConstVectorMap<float>(data + (i * dim), static_cast<long>(dim))
+                        .cast<double>();
+        }
+        mean /= static_cast<double>(num);
+        RowMajorMatrix<double> cov = RowMajorMatrix<double>::Zero(
+            static_cast<long>(dim), static_cast<long>(dim)
+        );
+        for (size_t begin = 0; begin < num; begin += kChunkRows) {
+            size_t rows = std::min(kChunkRows, num - begin);
+            RowMajorMatrix<double> chunk =
+                ConstRowMajorMatrixMap<float>(
+                    data + (begin * dim), static_cast<long>(rows), static_cast<long>(dim)
+                )
+                    .cast<double>();
+            chunk.rowwise() -= mean.transpose();
+            cov.noalias() += chunk.transpose() * chunk;
+        }
+        cov /= static_cast<double>(num - 1);
+
+        // eigenvalues come in increasing order
+        Eigen::SelfAdjointEigenSolver<RowMajorMatrix<double>> solver(cov);
+        if (solver.info() != Eigen::Success) {
+            throw std::runtime_error("PCA eigendecomposition failed");
+        }
+        total_variance_ = static_cast<float>(cov.trace());
+        for (size_t j = 0; j < out_dim; ++j) {
+            auto col = static_cast<long>(dim - 1 - j);
+            components_.col(static_cast<long>(j)) =
+                solver.eigenvectors().col(col).cast<float>();
+            variances_[j] = static_cast<float>(std::max(solver.eigenvalues()(col), 0.0));
+        }
+    }
+
+    PcaRotator() = default;
+    ~PcaRotator() override = default;
+
+    PcaRotator& operator=(const PcaRotator& other) = default;
+
+    [[nodiscard]] Rotator<float>* clone() const override {
+        auto* copy = new PcaRotator();
+        *copy = *this;
+        return copy;
+    }
+
+    [[nodiscard]] RotatorType type() const override { return RotatorType::PcaRotator; }
+
+    [[nodiscard]] size_t out_dim() const { return out_dim_; }
+
+    [[nodiscard]] const std::vector<float>& variances() const { return variances_; }
+
+    // fraction of the sample variance kept by the projection
+    [[nodiscard]] float explained_variance() const {
+        float kept = std::accumulate(variances_.begin(), variances_.end(), 0.0F);
+        return total_variance_ > 0 ? kept / total_variance_ : 1.0F;
+    }
+
+    void load(std::ifstream& input) override {
+        input.read(reinterpret_cast<char*>(&this->dim_), sizeof(size_t));
+        input.read(reinterpret_cast<char*>(&out_dim_), sizeof(size_t));
+        input.read(reinterpret_cast<char*>(&this->padded_dim_), sizeof(size_t));
+        if (!input || out_dim_ == 0 || out_dim_ > this->dim_ ||
+            this->padded_dim_ != round_up_to_multiple(out_dim_, 64)) {
+            throw std::runtime_error("bad PCA rotator");
+        }
+        components_.resize(static_cast<long>(this->dim_), static_cast<long>(out_dim_));
+        variances_.resize(out_dim_);
+        input.read(
+            reinterpret_cast<char*>(components_.data()),
+            static_cast<long>(sizeof(float) * this->dim_ * out_dim_)
+        );
+        input.read(
+            reinterpret_cast<char*>(variances_.data()),
+            static_cast<long>(sizeof(float) * out_dim_)
+        );
+        input.read(reinterpret_cast<char*>(&total_variance_), sizeof(float));
+        rotator_ = FhtKacRotator(this->padded_dim_, this->padded_dim_);
+        rotator_.load(input);
+    }
+
+    void save(std::ofstream& output) const override {
+        output.write(reinterpret_cast<const char*>(&this->dim_), sizeof(size_t));
+        output.write(reinterpret_cast<const char*>(&out_dim_), sizeof(size_t));
+        output.write(reinterpret_cast<const char*>(&this->padded_dim_), sizeof(size_t));
+        output.write(
+            reinterpret_cast<const char*>(components_.data()),
+            static_cast<long>(sizeof(float) * this->dim_ * out_dim_)
+        );
+        output.write(
+            reinterpret_cast<const char*>(variances_.data()),
+            static_cast<long>(sizeof(float) * out_dim_)
+        );
+        output.write(reinterpret_cast<const char*>(&total_variance_), sizeof(float));
+        rotator_.save(output);
+    }
+
+    // projects into rotated_vec and rotates it in place, data must not overlap it
+    void rotate(const float* data, float* rotated_vec) const override {
+        RowMajorMatrixMap<float>(rotated_vec, 1, static_cast<long>(out_dim_)).noalias() =
+            ConstRowMajorMatrixMap<float>(data, 1, static_cast<long>(this->dim_)) *
+            components_;
+        std::fill(rotated_vec + out_dim_, rotated_vec + this->padded_dim_, 0);
+        rotator_.rotate(rotated_vec, rotated_vec);
+    }
+};
 }  // namespace rotator_impl
 
-// for given dim & type, set rotator, return padded dimension
+// for given dim & type, set rotator drawn from seed, return padded dimension. PcaRotator and
+// LearnedRotator have to be learned from data, the ones returned here are empty and only
+// serve to load().
 template <typename T>
 Rotator<T>* choose_rotator(
-    size_t dim, RotatorType type = RotatorType::FhtKacRotator, size_t padded_dim = 0
+    size_t dim,
+    RotatorType type = RotatorType::FhtKacRotator,
+    size_t padded_dim = 0,
+    uint64_t seed = random_seed()
 ) {
+    if (type == RotatorType::PcaRotator) {
+        if (!std::is_same_v<T, float>) {
+            log_message(LogLevel::Error, "PcaRotator is only for float type currently");
+            exit(1);
+        }
+        return ::new rotator_impl::PcaRotator(dim, padded_dim);
+    }
+
     if (padded_dim == 0) {
         padded_dim = rotator_impl::padding_requirement(dim, type);
         if (padded_dim != dim) {
-            std::cerr << "vectors are padded to " << padded_dim
-                      << " dimensions for aligned computation\n";
-            std::cerr << "check rabitqlib/utils/rotator.hpp in case that users want to "
-                         "remove padding\n";
+            log_message(
+                LogLevel::Info,
+                "vectors are padded to ",
+                padded_dim,
+                " dimensions for aligned computation, check rabitqlib/utils/rotator.hpp in "
+                "case that users want to remove padding"
+            );
         }
     }
 
     if (padded_dim != rotator_impl::padding_requirement(padded_dim, type)) {
-        std::cerr << "Invalid padded dim for the given rotator type\n" << std::flush;
+        log_message(LogLevel::Error, "Invalid padded dim for the given rotator type");
         exit(1);
     }
 
     if (type == RotatorType::FhtKacRotator) {
         if (!std::is_same_v<T, float>) {
-            std::cerr << "FhtKacRotator is only for float type currently\n";
+            log_message(LogLevel::Error, "FhtKacRotator is only for float type currently");
             exit(1);
         }
-        std::cerr << "FhtKacRotator is selected\n";
-        return ::new rotator_impl::FhtKacRotator(dim, padded_dim);
+        log_message(LogLevel::Debug, "FhtKacRotator is selected");
+        return ::new rotator_impl::FhtKacRotator(dim, padded_dim, seed);
+    }
+
+    if (type == RotatorType::LearnedRotator) {
+        if (!std::is_same_v<T, float>) {
+            log_message(LogLevel::Error, "LearnedRotator is only for float type currently");
+            exit(1);
+        }
+        return ::new rotator_impl::LearnedRotator(dim, padded_dim);
     }
 
     if (type == RotatorType::MatrixRotator) {
-        std::cerr << "MatrixRotator is selected\n";
-        return ::new rotator_impl::MatrixRotator<T>(dim, padded_dim);
+        log_message(LogLevel::Debug, "MatrixRotator is selected");
+        return ::new rotator_impl::MatrixRotator<T>(dim, padded_dim, seed);
     }
 
-    std::cerr << "Invaid rotator type in choose_rotator()\n";
+    log_message(LogLevel::Error, "Invaid rotator type in choose_rotator()");
     exit(1);
 }
+
+// copy of rotator to use in place of current. It must take the same input dimension, its
+// output (padded) dimension and type may differ, e.g. for a PcaRotator.
+template <typename T>
+Rotator<T>* copy_matching_rotator(const Rotator<T>& current, const Rotator<T>& rotator) {
+    if (rotator.dim() != current.dim() || rotator.size() % 64 != 0) {
+        throw std::runtime_error("rotator does not match the dimension of the index");
+    }
+    return rotator.clone();
+}
 }  // namespace rabitqlib
\ No newline at end of file
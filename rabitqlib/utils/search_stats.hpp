@@ -0,0 +1,30 @@
+#pragma once
+
+#include <cstddef>
+
+namespace rabitqlib {
+
+/**
+ * @brief What one search did, filled by the searches that get a pointer to it. Fields that
+ * do not apply to an index stay 0.
+ *  - clusters:  clusters probed (IVF)
+ *  - batches:   FastScan batches of 32 codes scanned (IVF, QG)
+ *  - distances: vectors whose 1-bit or FastScan distance was estimated, plus the exact
+ *               distances computed by QG
+ *  - pruned:    estimated vectors dropped without boosting because their lower bound was
+ *               above the current k-th distance (IVF, HNSW), or neighbours kept out of the
+ *               full search pool (QG)
+ *  - boosts:    ex-bit refinements of an estimate (IVF, HNSW)
+ *  - hops:      graph vertices whose neighbours were scanned (HNSW, QG)
+ *  - visited:   graph vertices marked visited (HNSW, QG)
+ */
+struct SearchStats {
+    size_t clusters = 0;
+    size_t batches = 0;
+    size_t distances = 0;
+    size_t pruned = 0;
+    size_t boosts = 0;
+    size_t hops = 0;
+    size_t visited = 0;
+};
+}  // namespace rabitqlib
@@ -1,16 +1,23 @@
 #pragma once
 
+#if defined(__SSE2__)
 #include <emmintrin.h>
 #include <immintrin.h>
+#elif defined(__ARM_NEON)
+#include <arm_neon.h>
+#endif
 #include <omp.h>
 
 #include <cassert>
+#include <cmath>
 #include <cstdint>
+#include <cstring>
 #include <iostream>
 #include <limits>
 #include <type_traits>
 
 #include "defines.hpp"
+#include "utils/log.hpp"
 #include "utils/tools.hpp"
 
 namespace rabitqlib {
@@ -66,8 +73,28 @@ inline void scalar_quantize_optimized<uint8_t>(
     for (; i < dim; ++i) {
         result[i] = static_cast<uint8_t>(std::round((vec0[i] - lo) * one_over_delta));
     }
+#elif defined(__ARM_NEON)
+    size_t mul8 = dim - (dim & 0b111);
+    size_t i = 0;
+    float one_over_delta = 1 / delta;
+    auto lo128 = vdupq_n_f32(lo);
+    auto od128 = vdupq_n_f32(one_over_delta);
+    for (; i < mul8; i += 8) {
+        auto cur0 = vmulq_f32(vsubq_f32(vld1q_f32(&vec0[i]), lo128), od128);
+        auto cur1 = vmulq_f32(vsubq_f32(vld1q_f32(&vec0[i + 4]), lo128), od128);
+        // round to nearest even, same as _mm512_cvtps_epi32
+        auto u16 = vcombine_u16(vmovn_u32(vcvtnq_u32_f32(cur0)), vmovn_u32(vcvtnq_u32_f32(cur1)));
+        vst1_u8(&result[i], vmovn_u16(u16));
+    }
+    for (; i < dim; ++i) {
+        result[i] = static_cast<uint8_t>(std::nearbyint((vec0[i] - lo) * one_over_delta));
+    }
 #else
-    scalar_quantize_normal(result, vec0, dim, lo, delta);
+    // round to nearest even, thus the codes are the same as the simd versions
+    float one_over_delta = 1 / delta;
+    for (size_t i = 0; i < dim; ++i) {
+        result[i] = static_cast<uint8_t>(std::nearbyint((vec0[i] - lo) * one_over_delta));
+    }
 #endif
 }
 
@@ -103,14 +130,33 @@ inline void scalar_quantize_optimized<uint16_t>(
     for (; i < mul8; i += 8) {
         auto cur = _mm256_loadu_ps(&vec0[i]);
         cur = _mm256_mul_ps(_mm256_sub_ps(cur, lo256), ow256);
-        auto i16 = _mm256_cvtepi32_epi16(_mm256_cvtps_epi32(cur));
-        _mm_storeu_epi16(&result[i], i16);
+        auto i32 = _mm256_cvtps_epi32(cur);
+        auto i16 = _mm_packus_epi32(
+            _mm256_castsi256_si128(i32), _mm256_extracti128_si256(i32, 1)
+        );
+        _mm_storeu_si128(reinterpret_cast<__m128i*>(&result[i]), i16);
     }
     for (; i < dim; ++i) {
         result[i] = static_cast<uint16_t>(std::round((vec0[i] - lo) * one_over_delta));
     }
+#elif defined(__ARM_NEON)
+    size_t mul4 = dim - (dim & 0b11);
+    size_t i = 0;
+    float one_over_delta = 1 / delta;
+    auto lo128 = vdupq_n_f32(lo);
+    auto ow128 = vdupq_n_f32(one_over_delta);
+    for (; i < mul4; i += 4) {
+        auto cur = vmulq_f32(vsubq_f32(vld1q_f32(&vec0[i]), lo128), ow128);
+        vst1_u16(&result[i], vmovn_u32(vcvtnq_u32_f32(cur)));
+    }
+    for (; i < dim; ++i) {
+        result[i] = static_cast<uint16_t>(std::nearbyint((vec0[i] - lo) * one_over_delta));
+    }
 #else
-    scalar_quantize_normal(result, vec0, dim, lo, delta);
+    float one_over_delta = 1 / delta;
+    for (size_t i = 0; i < dim; ++i) {
+        result[i] = static_cast<uint16_t>(std::nearbyint((vec0[i] - lo) * one_over_delta));
+    }
 #endif
 }
 }  // namespace scalar_impl
@@ -205,7 +251,8 @@ inline std::vector<T> compute_centroid(
     omp_set_num_threads(static_cast<int>(num_threads));
     std::vector<std::vector<T>> all_results(num_threads, std::vector<T>(dim, 0));
 
-#pragma omp parallel for schedule(dynamic)
+// static schedule: the same threads sum the same rows, so results repeat for a thread count
+#pragma omp parallel for schedule(static)
     for (size_t i = 0; i < num_points; ++i) {
         auto tid = omp_get_thread_num();
         std::vector<T>& cur_results = all_results[tid];
@@ -241,7 +288,7 @@ inline PID exact_nn(
 ) {
     std::vector<AnnCandidate<T, PID>> best_entries(num_threads);
 
-#pragma omp parallel for schedule(dynamic)
+#pragma omp parallel for schedule(static)
     for (size_t i = 0; i < num_points; ++i) {
         auto tid = omp_get_thread_num();
         AnnCandidate<T, PID>& cur_entry = best_entries[tid];
@@ -271,6 +318,7 @@ namespace excode_ipimpl {
 // fxu1: the inner product is computed between float and 1-bit unsigned int (lay out can be
 // found rabitq_impl.hpp)
 // avx512: only applicable for avx512
+#if defined(__AVX512F__)
 inline float ip16_fxu1_avx512(
     const float* __restrict__ query, const uint8_t* __restrict__ compact_code, size_t dim
 ) {
@@ -550,6 +598,303 @@ inline float ip64_fxu7_avx512(
 
     return _mm512_reduce_add_ps(sum);
 }
+#endif
+
+#if defined(__ARM_NEON)
+// neon: the codes are unpacked to one byte per dim (the same layout as the avx512
+// kernels), then multiplied with the query 16 dims at a time
+static inline float32x4_t fmadd_u8x16_neon(
+    const float* __restrict__ query, uint8x16_t code, float32x4_t sum
+) {
+    uint16x8_t lo = vmovl_u8(vget_low_u8(code));
+    uint16x8_t hi = vmovl_high_u8(code);
+    sum = vfmaq_f32(sum, vld1q_f32(query), vcvtq_f32_u32(vmovl_u16(vget_low_u16(lo))));
+    sum = vfmaq_f32(sum, vld1q_f32(query + 4), vcvtq_f32_u32(vmovl_high_u16(lo)));
+    sum = vfmaq_f32(sum, vld1q_f32(query + 8), vcvtq_f32_u32(vmovl_u16(vget_low_u16(hi))));
+    sum = vfmaq_f32(sum, vld1q_f32(query + 12), vcvtq_f32_u32(vmovl_high_u16(hi)));
+    return sum;
+}
+
+// byte b of the result is (code[b % 4] >> (2 * (b / 4))) & 0b11
+static inline uint8x16_t unpack_u2x16_neon(const uint8_t* __restrict__ compact_code) {
+    static const int8_t kShift[16] = {0, 0, 0, 0, -2, -2, -2, -2, -4, -4, -4, -4, -6, -6, -6, -6};
+    uint32_t compact;
+    std::memcpy(&compact, compact_code, sizeof(compact));
+    uint8x16_t code = vreinterpretq_u8_u32(vdupq_n_u32(compact));
+    return vandq_u8(vshlq_u8(code, vld1q_s8(kShift)), vdupq_n_u8(0b11));
+}
+
+// top bits of 64 dims are stored in 8 bytes, bit t of byte b is for dim 8 * t + b, return
+// the top bits of dim 16 * group to 16 * group + 15
+static inline uint8x16_t unpack_top_bits_neon(uint8x8_t top_bit, int group) {
+    const uint8x8_t one = vdup_n_u8(1);
+    uint8x8_t lo = vand_u8(vshl_u8(top_bit, vdup_n_s8(static_cast<int8_t>(-2 * group))), one);
+    uint8x8_t hi =
+        vand_u8(vshl_u8(top_bit, vdup_n_s8(static_cast<int8_t>(-2 * group - 1))), one);
+    return vcombine_u8(lo, hi);
+}
+
+inline float ip16_fxu1_neon(
+    const float* __restrict__ query, const uint8_t* __restrict__ compact_code, size_t dim
+) {
+    static const uint8_t kBit[16] = {1, 2, 4, 8, 16, 32, 64, 128, 1, 2, 4, 8, 16, 32, 64, 128};
+    const uint8x16_t bit = vld1q_u8(kBit);
+    const uint8x16_t one = vdupq_n_u8(1);
+    float32x4_t sum = vdupq_n_f32(0);
+
+    for (size_t i = 0; i < dim; i += 16) {
+        uint8x16_t code =
+            vcombine_u8(vdup_n_u8(compact_code[0]), vdup_n_u8(compact_code[1]));
+        code = vandq_u8(vtstq_u8(code, bit), one);
+        sum = fmadd_u8x16_neon(&query[i], code, sum);
+        compact_code += 2;
+    }
+    return vaddvq_f32(sum);
+}
+
+inline float ip16_fxu2_neon(
+    const float* __restrict__ query, const uint8_t* __restrict__ compact_code, size_t dim
+) {
+    float32x4_t sum = vdupq_n_f32(0);
+    for (size_t i = 0; i < dim; i += 16) {
+        sum = fmadd_u8x16_neon(&query[i], unpack_u2x16_neon(compact_code), sum);
+        compact_code += 4;
+    }
+    return vaddvq_f32(sum);
+}
+
+inline float ip64_fxu3_neon(
+    const float* __restrict__ query, const uint8_t* __restrict__ compact_code, size_t dim
+) {
+    const uint8x16_t mask = vdupq_n_u8(0b11);
+    float32x4_t sum = vdupq_n_f32(0);
+
+    for (size_t i = 0; i < dim; i += 64) {
+        uint8x16_t compact2 = vld1q_u8(compact_code);
+        uint8x8_t top_bit = vld1_u8(compact_code + 16);
+        compact_code += 24;
+
+        uint8x16_t vec[4] = {
+            vandq_u8(compact2, mask),
+            vandq_u8(vshrq_n_u8(compact2, 2), mask),
+            vandq_u8(vshrq_n_u8(compact2, 4), mask),
+            vshrq_n_u8(compact2, 6),
+        };
+        for (int j = 0; j < 4; ++j) {
+            uint8x16_t code = vorrq_u8(vec[j], vshlq_n_u8(unpack_top_bits_neon(top_bit, j), 2));
+            sum = fmadd_u8x16_neon(&query[i + (16 * j)], code, sum);
+        }
+    }
+    return vaddvq_f32(sum);
+}
+
+inline float ip16_fxu4_neon(
+    const float* __restrict__ query, const uint8_t* __restrict__ compact_code, size_t dim
+) {
+    const uint8x8_t mask = vdup_n_u8(0x0f);
+    float32x4_t sum = vdupq_n_f32(0);
+    for (size_t i = 0; i < dim; i += 16) {
+        uint8x8_t compact = vld1_u8(compact_code);
+        uint8x16_t code = vcombine_u8(vand_u8(compact, mask), vshr_n_u8(compact, 4));
+        sum = fmadd_u8x16_neon(&query[i], code, sum);
+        compact_code += 8;
+    }
+    return vaddvq_f32(sum);
+}
+
+inline float ip64_fxu5_neon(
+    const float* __restrict__ query, const uint8_t* __restrict__ compact_code, size_t dim
+) {
+    const uint8x16_t mask = vdupq_n_u8(0b1111);
+    float32x4_t sum = vdupq_n_f32(0);
+
+    for (size_t i = 0; i < dim; i += 64) {
+        uint8x16_t compact4_1 = vld1q_u8(compact_code);
+        uint8x16_t compact4_2 = vld1q_u8(compact_code + 16);
+        uint8x8_t top_bit = vld1_u8(compact_code + 32);
+        compact_code += 40;
+
+        uint8x16_t vec[4] = {
+            vandq_u8(compact4_1, mask),
+            vshrq_n_u8(compact4_1, 4),
+            vandq_u8(compact4_2, mask),
+            vshrq_n_u8(compact4_2, 4),
+        };
+        for (int j = 0; j < 4; ++j) {
+            uint8x16_t code = vorrq_u8(vec[j], vshlq_n_u8(unpack_top_bits_neon(top_bit, j), 4));
+            sum = fmadd_u8x16_neon(&query[i + (16 * j)], code, sum);
+        }
+    }
+    return vaddvq_f32(sum);
+}
+
+inline float ip16_fxu6_neon(
+    const float* __restrict__ query, const uint8_t* __restrict__ compact_code, size_t dim
+) {
+    const uint8x8_t mask = vdup_n_u8(0x0f);
+    float32x4_t sum = vdupq_n_f32(0);
+    for (size_t i = 0; i < dim; i += 16) {
+        uint8x8_t compact4 = vld1_u8(compact_code);
+        uint8x16_t c4 = vcombine_u8(vand_u8(compact4, mask), vshr_n_u8(compact4, 4));
+        uint8x16_t c2 = unpack_u2x16_neon(compact_code + 8);
+        sum = fmadd_u8x16_neon(&query[i], vorrq_u8(c4, vshlq_n_u8(c2, 4)), sum);
+        compact_code += 12;
+    }
+    return vaddvq_f32(sum);
+}
+
+inline float ip64_fxu7_neon(
+    const float* __restrict__ query, const uint8_t* __restrict__ compact_code, size_t dim
+) {
+    const uint8x16_t mask6 = vdupq_n_u8(0b00111111);
+    float32x4_t sum = vdupq_n_f32(0);
+
+    for (size_t i = 0; i < dim; i += 64) {
+        uint8x16_t cpt1 = vld1q_u8(compact_code);
+        uint8x16_t cpt2 = vld1q_u8(compact_code + 16);
+        uint8x16_t cpt3 = vld1q_u8(compact_code + 32);
+        uint8x8_t top_bit = vld1_u8(compact_code + 48);
+        compact_code += 56;
+
+        uint8x16_t vec[4] = {
+            vandq_u8(cpt1, mask6),
+            vandq_u8(cpt2, mask6),
+            vandq_u8(cpt3, mask6),
+            vorrq_u8(
+                vorrq_u8(vshrq_n_u8(cpt1, 6), vshlq_n_u8(vshrq_n_u8(cpt2, 6), 2)),
+                vshlq_n_u8(vshrq_n_u8(cpt3, 6), 4)
+            ),
+        };
+        for (int j = 0; j < 4; ++j) {
+            uint8x16_t code = vorrq_u8(vec[j], vshlq_n_u8(unpack_top_bits_neon(top_bit, j), 6));
+            sum = fmadd_u8x16_neon(&query[i + (16 * j)], code, sum);
+        }
+    }
+    return vaddvq_f32(sum);
+}
+#endif
+
+// scalar: portable version of the kernels above, the data layout is exactly the same
+inline float ip16_fxu1_scalar(
+    const float* __restrict__ query, const uint8_t* __restrict__ compact_code, size_t dim
+) {
+    float result = 0;
+    for (size_t i = 0; i < dim; i += 16) {
+        uint32_t mask = compact_code[0] | (compact_code[1] << 8);
+        for (size_t j = 0; j < 16; ++j) {
+            if ((mask >> j) & 1) {
+                result += query[i + j];
+            }
+        }
+        compact_code += 2;
+    }
+    return result;
+}
+
+inline float ip16_fxu2_scalar(
+    const float* __restrict__ query, const uint8_t* __restrict__ compact_code, size_t dim
+) {
+    float result = 0;
+    for (size_t i = 0; i < dim; i += 16) {
+        for (size_t j = 0; j < 16; ++j) {
+            uint8_t code = (compact_code[j % 4] >> (2 * (j / 4))) & 0b11;
+            result += query[i + j] * static_cast<float>(code);
+        }
+        compact_code += 4;
+    }
+    return result;
+}
+
+// for ex_bits = 3, 5, 7: the top bit of dim j (in a block of 64 dims) is stored in bit
+// (8 * (j % 8) + j / 8) of an u64
+static inline uint8_t top_bit_u64(const uint8_t* __restrict__ top_bit, size_t j) {
+    return (top_bit[j % 8] >> (j / 8)) & 1;
+}
+
+inline float ip64_fxu3_scalar(
+    const float* __restrict__ query, const uint8_t* __restrict__ compact_code, size_t dim
+) {
+    float result = 0;
+    for (size_t i = 0; i < dim; i += 64) {
+        const uint8_t* top_bit = compact_code + 16;
+        for (size_t j = 0; j < 64; ++j) {
+            uint8_t code = ((compact_code[j % 16] >> (2 * (j / 16))) & 0b11) |
+                           (top_bit_u64(top_bit, j) << 2);
+            result += query[i + j] * static_cast<float>(code);
+        }
+        compact_code += 24;
+    }
+    return result;
+}
+
+inline float ip16_fxu4_scalar(
+    const float* __restrict__ query, const uint8_t* __restrict__ compact_code, size_t dim
+) {
+    float result = 0;
+    for (size_t i = 0; i < dim; i += 16) {
+        for (size_t j = 0; j < 16; ++j) {
+            uint8_t code = (compact_code[j % 8] >> (4 * (j / 8))) & 0b1111;
+            result += query[i + j] * static_cast<float>(code);
+        }
+        compact_code += 8;
+    }
+    return result;
+}
+
+inline float ip64_fxu5_scalar(
+    const float* __restrict__ query, const uint8_t* __restrict__ compact_code, size_t dim
+) {
+    float result = 0;
+    for (size_t i = 0; i < dim; i += 64) {
+        const uint8_t* top_bit = compact_code + 32;
+        for (size_t j = 0; j < 64; ++j) {
+            uint8_t low = compact_code[(j / 32 * 16) + (j % 16)] >> (4 * ((j / 16) % 2));
+            uint8_t code = (low & 0b1111) | (top_bit_u64(top_bit, j) << 4);
+            result += query[i + j] * static_cast<float>(code);
+        }
+        compact_code += 40;
+    }
+    return result;
+}
+
+inline float ip16_fxu6_scalar(
+    const float* __restrict__ query, const uint8_t* __restrict__ compact_code, size_t dim
+) {
+    float result = 0;
+    for (size_t i = 0; i < dim; i += 16) {
+        for (size_t j = 0; j < 16; ++j) {
+            uint8_t low = (compact_code[j % 8] >> (4 * (j / 8))) & 0b1111;
+            uint8_t high = (compact_code[8 + (j % 4)] >> (2 * (j / 4))) & 0b11;
+            result += query[i + j] * static_cast<float>(low | (high << 4));
+        }
+        compact_code += 12;
+    }
+    return result;
+}
+
+inline float ip64_fxu7_scalar(
+    const float* __restrict__ query, const uint8_t* __restrict__ compact_code, size_t dim
+) {
+    float result = 0;
+    for (size_t i = 0; i < dim; i += 64) {
+        const uint8_t* top_bit = compact_code + 48;
+        for (size_t j = 0; j < 64; ++j) {
+            uint8_t code;
+            if (j < 48) {
+                code = compact_code[j] & 0b00111111;
+            } else {
+                // the upper 2 bits of the three 16-byte blocks
+                size_t k = j - 48;
+                code = (compact_code[k] >> 6) | ((compact_code[16 + k] >> 6) << 2) |
+                       ((compact_code[32 + k] >> 6) << 4);
+            }
+            code |= top_bit_u64(top_bit, j) << 6;
+            result += query[i + j] * static_cast<float>(code);
+        }
+        compact_code += 56;
+    }
+    return result;
+}
 
 // inner product between float type and int type vectors
 template <typename TF, typename TI>
@@ -565,37 +910,47 @@ inline TF ip_fxi(const TF* __restrict__ vec0, const TI* __restrict__ vec1, size_
 
 using ex_ipfunc = float (*)(const float*, const uint8_t*, size_t);
 
+#if defined(__AVX512F__)
+#define RABITQ_EXCODE_IPFUNC(name) excode_ipimpl::name##_avx512
+#elif defined(__ARM_NEON)
+#define RABITQ_EXCODE_IPFUNC(name) excode_ipimpl::name##_neon
+#else
+#define RABITQ_EXCODE_IPFUNC(name) excode_ipimpl::name##_scalar
+#endif
+
 inline ex_ipfunc select_excode_ipfunc(size_t ex_bits) {
     if (ex_bits <= 1) {
         // when ex_bits = 0, we do not use it
-        return excode_ipimpl::ip16_fxu1_avx512;
+        return RABITQ_EXCODE_IPFUNC(ip16_fxu1);
     }
     if (ex_bits == 2) {
-        return excode_ipimpl::ip16_fxu2_avx512;
+        return RABITQ_EXCODE_IPFUNC(ip16_fxu2);
     }
     if (ex_bits == 3) {
-        return excode_ipimpl::ip64_fxu3_avx512;
+        return RABITQ_EXCODE_IPFUNC(ip64_fxu3);
     }
     if (ex_bits == 4) {
-        return excode_ipimpl::ip16_fxu4_avx512;
+        return RABITQ_EXCODE_IPFUNC(ip16_fxu4);
     }
     if (ex_bits == 5) {
-        return excode_ipimpl::ip64_fxu5_avx512;
+        return RABITQ_EXCODE_IPFUNC(ip64_fxu5);
     }
     if (ex_bits == 6) {
-        return excode_ipimpl::ip16_fxu6_avx512;
+        return RABITQ_EXCODE_IPFUNC(ip16_fxu6);
     }
     if (ex_bits == 7) {
-        return excode_ipimpl::ip64_fxu7_avx512;
+        return RABITQ_EXCODE_IPFUNC(ip64_fxu7);
     }
     if (ex_bits == 8) {
         return excode_ipimpl::ip_fxi;
     }
 
-    std::cerr << "Bad IP function for IVF\n";
+    log_message(LogLevel::Error, "Bad IP function for IVF");
     exit(1);
 }
 
+#undef RABITQ_EXCODE_IPFUNC
+
 static inline uint32_t reverse_bits(uint32_t n) {
     n = ((n >> 1) & 0x55555555) | ((n << 1) & 0xaaaaaaaa);
     n = ((n >> 2) & 0x33333333) | ((n << 2) & 0xcccccccc);
@@ -618,6 +973,7 @@ static inline uint64_t reverse_bits_u64(uint64_t n) {
 inline void transpose_bin(
     const uint16_t* q, uint64_t* tq, size_t padded_dim, size_t b_query
 ) {
+#if defined(__AVX512BW__)
     // 512 / 16 = 32
     for (size_t i = 0; i < padded_dim; i += 32) {
         __m512i v = _mm512_loadu_si512(q);
@@ -631,11 +987,21 @@ inline void transpose_bin(
         }
         q += 32;
     }
+#else
+    // bit j of dim i goes to bit (63 - i % 64) of the (i / 64)-th u64 of the j-th plane
+    for (size_t i = 0; i < padded_dim; ++i) {
+        for (size_t j = 0; j < b_query; ++j) {
+            tq[(j * (padded_dim / 64)) + (i / 64)] |=
+                (static_cast<uint64_t>((q[i] >> j) & 1) << (63 - (i % 64)));
+        }
+    }
+#endif
 }
 
 static inline void new_transpose_bin(
     const uint16_t* q, uint64_t* tq, size_t padded_dim, size_t b_query
 ) {
+#if defined(__AVX512BW__)
     // 512 / 16 = 32
     for (size_t i = 0; i < padded_dim; i += 64) {
         __m512i vec_00_to_31 = _mm512_loadu_si512(q);
@@ -661,6 +1027,21 @@ static inline void new_transpose_bin(
         tq += b_query;
         q += 64;
     }
+#else
+    // same bit order as transpose_bin, but the b_query planes of every 64 dims are stored
+    // together
+    for (size_t i = 0; i < padded_dim; i += 64) {
+        for (size_t j = 0; j < b_query; ++j) {
+            uint64_t v = 0;
+            for (size_t k = 0; k < 64; ++k) {
+                v |= static_cast<uint64_t>((q[k] >> j) & 1) << (63 - k);
+            }
+            tq[j] = v;
+        }
+        tq += b_query;
+        q += 64;
+    }
+#endif
 }
 
 inline float mask_ip_x0_q_old(const float* query, const uint64_t* data, size_t padded_dim) {
@@ -668,6 +1049,20 @@ inline float mask_ip_x0_q_old(const float* query, const uint64_t* data, size_t p
     const auto* it_data = data;
     const auto* it_query = query;
 
+#if !defined(__AVX512F__)
+    float sum = 0;
+    for (size_t i = 0; i < num_blk; ++i) {
+        uint64_t bits = reverse_bits_u64(*it_data);
+        for (size_t j = 0; j < 64; ++j) {
+            if ((bits >> j) & 1) {
+                sum += it_query[j];
+            }
+        }
+        it_data++;
+        it_query += 64;
+    }
+    return sum;
+#else
     __m512 sum = _mm512_setzero_ps();
     for (size_t i = 0; i < num_blk; ++i) {
         uint64_t bits = reverse_bits_u64(*it_data);
@@ -695,6 +1090,7 @@ inline float mask_ip_x0_q_old(const float* query, const uint64_t* data, size_t p
         it_query += 64;
     }
     return _mm512_reduce_add_ps(sum);
+#endif
 }
 
 inline float mask_ip_x0_q(const float* query, const uint64_t* data, size_t padded_dim) {
@@ -702,6 +1098,45 @@ inline float mask_ip_x0_q(const float* query, const uint64_t* data, size_t padde
     const uint64_t* it_data = data;
     const float* it_query = query;
 
+#if defined(__ARM_NEON)
+    // lane k of each group of 4 floats is kept if bit k of the corresponding nibble is set
+    static const uint32_t kBit[4] = {1, 2, 4, 8};
+    const uint32x4_t bit = vld1q_u32(kBit);
+    float32x4_t sum0 = vdupq_n_f32(0);
+    float32x4_t sum1 = vdupq_n_f32(0);
+    for (size_t i = 0; i < num_blk; ++i) {
+        uint64_t bits = reverse_bits_u64(*it_data);
+        for (size_t j = 0; j < 64; j += 8) {
+            uint32x4_t mask0 = vtstq_u32(vdupq_n_u32(static_cast<uint32_t>(bits >> j)), bit);
+            uint32x4_t mask1 =
+                vtstq_u32(vdupq_n_u32(static_cast<uint32_t>(bits >> (j + 4))), bit);
+            float32x4_t q0 = vld1q_f32(it_query + j);
+            float32x4_t q1 = vld1q_f32(it_query + j + 4);
+            sum0 = vaddq_f32(
+                sum0, vreinterpretq_f32_u32(vandq_u32(vreinterpretq_u32_f32(q0), mask0))
+            );
+            sum1 = vaddq_f32(
+                sum1, vreinterpretq_f32_u32(vandq_u32(vreinterpretq_u32_f32(q1), mask1))
+            );
+        }
+        ++it_data;
+        it_query += 64;
+    }
+    return vaddvq_f32(vaddq_f32(sum0, sum1));
+#elif !defined(__AVX512F__)
+    float sum = 0;
+    for (size_t i = 0; i < num_blk; ++i) {
+        uint64_t bits = reverse_bits_u64(*it_data);
+        for (size_t j = 0; j < 64; ++j) {
+            if ((bits >> j) & 1) {
+                sum += it_query[j];
+            }
+        }
+        ++it_data;
+        it_query += 64;
+    }
+    return sum;
+#else
     //    __m512 sum0 = _mm512_setzero_ps();
     //    __m512 sum1 = _mm512_setzero_ps();
     //    __m512 sum2 = _mm512_setzero_ps();
@@ -734,6 +1169,7 @@ inline float mask_ip_x0_q(const float* query, const uint64_t* data, size_t padde
 
     //    __m512 sum = _mm512_add_ps(_mm512_add_ps(sum0, sum1), _mm512_add_ps(sum2, sum3));
     return _mm512_reduce_add_ps(sum);
+#endif
 }
 
 inline float ip_x0_q(
@@ -799,10 +1235,11 @@ inline size_t popcount(const uint64_t* __restrict__ d, size_t length) {
 }
 
 template <typename T>
-RowMajorMatrix<T> random_gaussian_matrix(size_t rows, size_t cols) {
+RowMajorMatrix<T> random_gaussian_matrix(
+    size_t rows, size_t cols, uint64_t seed = random_seed()
+) {
     RowMajorMatrix<T> rand(rows, cols);
-    static std::random_device rd;
-    static std::mt19937 gen(rd());
+    std::mt19937_64 gen(seed);
     std::normal_distribution<T> dist(0, 1);
 
     for (size_t i = 0; i < rows; ++i) {
@@ -1,5 +1,6 @@
 #pragma once
 
+#include <cstdint>
 #include <ctime>
 #include <queue>
 #include <random>
@@ -23,6 +24,21 @@ inline void assert_floating() {
     );
 }
 
+// seed drawn from the system, for builds that are not asked to be reproducible
+inline uint64_t random_seed() {
+    std::random_device rd;
+    return (static_cast<uint64_t>(rd()) << 32) | rd();
+}
+
+// independent seed for the stream-th use of seed (splitmix64), e.g. one per vertex so that
+// results do not depend on how work is scheduled over threads
+inline uint64_t mix_seed(uint64_t seed, uint64_t stream) {
+    uint64_t z = seed + ((stream + 1) * 0x9E3779B97F4A7C15ULL);
+    z = (z ^ (z >> 30)) * 0xBF58476D1CE4E5B9ULL;
+    z = (z ^ (z >> 27)) * 0x94D049BB133111EBULL;
+    return z ^ (z >> 31);
+}
+
 // thread save rand int
 template <typename T>
 inline T rand_integer(T min, T max) {
@@ -1,9 +1,13 @@
 #pragma once
 
+#if defined(__SSE2__)
 #include <immintrin.h>
+#endif
 
+#include <array>
 #include <cstddef>
 #include <cstdint>
+#include <utility>
 
 template <uint32_t b_query>
 inline float warmup_ip_x0_q(
@@ -19,6 +23,7 @@ inline float warmup_ip_x0_q(
     size_t ip_scalar = 0;
     size_t ppc_scalar = 0;
 
+#if defined(__AVX512VPOPCNTDQ__) && defined(__AVX512DQ__)
     // Process blocks in chunks of 8
     const size_t vec_width = 8;
     size_t vec_end = (num_blk / vec_width) * vec_width;
@@ -83,6 +88,10 @@ inline float warmup_ip_x0_q(
         ip_scalar += ip_arr[k];
         ppc_scalar += ppc_arr[k];
     }
+#else
+    // without vpopcntdq, all blocks are processed by the scalar loop below
+    const size_t vec_end = 0;
+#endif
 
     // Process remaining blocks that did not fit in the vectorized loop.
     for (size_t i = vec_end; i < num_blk; i++) {
@@ -140,15 +149,16 @@ inline float warmup_ip_centroid_q(
     size_t ip_scalar = 0;
     size_t ppc_scalar = 0;
 
+    // 计算cB = -(2^b_query - 1)/2
+    const float cB = -(static_cast<float>((1ULL << b_query) - 1)) / 2.0f;
+
+#if defined(__AVX512VPOPCNTDQ__) && defined(__AVX512DQ__)
     const size_t vec_width = 8;
     size_t vec_end = (num_blk / vec_width) * vec_width;
 
     __m512i ip_vec = _mm512_setzero_si512();
     __m512i ppc_vec = _mm512_setzero_si512();
 
-    // 计算cB = -(2^b_query - 1)/2
-    const float cB = -(static_cast<float>((1ULL << b_query) - 1)) / 2.0f;
-
     for (size_t i = 0; i < vec_end; i += vec_width) {
         __m512i x_vec = _mm512_loadu_si512(reinterpret_cast<const __m512i*>(data + i));
         __m512i popcnt_x_vec = _mm512_popcnt_epi64(x_vec);
@@ -185,6 +195,9 @@ inline float warmup_ip_centroid_q(
         ip_scalar += ip_arr[k];
         ppc_scalar += ppc_arr[k];
     }
+#else
+    const size_t vec_end = 0;
+#endif
 
     for (size_t i = vec_end; i < num_blk; i++) {
         const uint64_t x = data[i];
@@ -199,4 +212,30 @@ inline float warmup_ip_centroid_q(
     float ip_est = (delta * static_cast<float>(ip_scalar)) + (vl * static_cast<float>(ppc_scalar));
     
     return ip_est;
-}
\ No newline at end of file
+}
+
+// max num of bits of a quantized query, codes are kept as uint16
+constexpr size_t kMaxQueryBits = 16;
+
+namespace warmup_impl {
+using WarmupFunc = float (*)(const uint64_t*, const uint64_t*, float, float, size_t, size_t);
+
+template <size_t... B>
+constexpr std::array<WarmupFunc, sizeof...(B)> make_warmup_table(std::index_sequence<B...>) {
+    return {&warmup_ip_x0_q<static_cast<uint32_t>(B + 1)>...};
+}
+}  // namespace warmup_impl
+
+// same as warmup_ip_x0_q with b_query chosen at runtime, 1 <= b_query <= kMaxQueryBits
+inline float warmup_ip_x0_q_dyn(
+    const uint64_t* data,
+    const uint64_t* query,
+    float delta,
+    float vl,
+    size_t padded_dim,
+    size_t b_query
+) {
+    static constexpr auto kTable =
+        warmup_impl::make_warmup_table(std::make_index_sequence<kMaxQueryBits>{});
+    return kTable[b_query - 1](data, query, delta, vl, padded_dim, b_query);
+}
@@ -0,0 +1,823 @@
+//! Python bindings of `rabitq-rs`, built as the `rabitq` extension module.
+//!
+//! Vectors are passed as C-contiguous `float32` NumPy arrays and read in place, results
+//! come back as NumPy arrays that take ownership of the Rust buffers. The GIL is released
+//! while indexes are built and searched.
+
+use std::io;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use numpy::{
+    IntoPyArray, PyArray1, PyArray2, PyArrayMethods, PyReadonlyArray1, PyReadonlyArray2,
+    PyUntypedArrayMethods,
+};
+use pyo3::exceptions::{PyFileNotFoundError, PyIOError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{IntoPyDict, PyDict};
+use rabitq_rs::estimator::{SingleCentroidEstimator, select_excode_ipfunc};
+use rabitq_rs::index::{
+    self, BuildMonitor, Distribution, Hnsw, HnswParams, Ivf, IvfParams, Qg, QgParams,
+};
+use rabitq_rs::quantizer::quantize_split_single;
+use rabitq_rs::{
+    AnnIndex, BatchBinEstimator, DistanceBound, MetricType, QueryPrecision, RabitqConfig, Rotator,
+    SearchParams, SingleEstimator, SplitBatchEstimator, quantize_full_single, reconstruct_vec,
+};
+
+type Bytes<'py> = Bound<'py, PyArray1<u8>>;
+type Floats<'py> = Bound<'py, PyArray1<f32>>;
+
+fn to_py_err(e: io::Error) -> PyErr {
+    match e.kind() {
+        io::ErrorKind::InvalidInput => PyValueError::new_err(e.to_string()),
+        io::ErrorKind::NotFound => PyFileNotFoundError::new_err(e.to_string()),
+        _ => PyIOError::new_err(e.to_string()),
+    }
+}
+
+fn parse_metric(metric: &str) -> PyResult<MetricType> {
+    metric.parse().map_err(PyValueError::new_err)
+}
+
+/// `query_bits` argument: a number of bits or `"exact"`
+fn parse_precision(value: &Bound<'_, PyAny>) -> PyResult<QueryPrecision> {
+    if let Ok(bits) = value.extract::<usize>() {
+        return Ok(QueryPrecision::Bits(bits));
+    }
+    let name: String = value.extract()?;
+    name.parse().map_err(PyValueError::new_err)
+}
+
+/// Rows of a `(n, dim)` array, without copying
+fn rows<'a>(array: &'a PyReadonlyArray2<f32>) -> PyResult<(&'a [f32], usize, usize)> {
+    let [n, dim] = array.shape() else {
+        unreachable!()
+    };
+    Ok((array.as_slice()?, *n, *dim))
+}
+
+fn config_or_default(config: Option<&PyRabitqConfig>) -> &RabitqConfig {
+    static DEFAULT: OnceLock<RabitqConfig> = OnceLock::new();
+    match config {
+        Some(config) => &config.0,
+        None => DEFAULT.get_or_init(RabitqConfig::new),
+    }
+}
+
+fn distribution_dict<'py>(py: Python<'py>, d: &Distribution) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("count", d.count)?;
+    dict.set_item("min", d.min)?;
+    dict.set_item("max", d.max)?;
+    dict.set_item("mean", d.mean)?;
+    dict.set_item("histogram", d.histogram.clone())?;
+    Ok(dict)
+}
+
+fn bound_tuple(bound: DistanceBound) -> (f32, f32, f32) {
+    (bound.estimate, bound.lower, bound.upper)
+}
+
+/// Randomized orthogonal transform (FHT-Kac) applied to data and queries before
+/// quantization.
+#[pyclass(name = "Rotator", module = "rabitq")]
+struct PyRotator(Rotator);
+
+#[pymethods]
+impl PyRotator {
+    /// `padded_dim` defaults to `dim` rounded up to a multiple of 64.
+    #[new]
+    #[pyo3(signature = (dim, padded_dim = None))]
+    fn new(dim: usize, padded_dim: Option<usize>) -> PyResult<Self> {
+        let padded_dim = padded_dim.unwrap_or(dim.next_multiple_of(64));
+        // the C++ side exits the process on these
+        if !(64..4096).contains(&dim) || padded_dim < dim || !padded_dim.is_multiple_of(64) {
+            return Err(PyValueError::new_err(format!(
+                "dim must be in 64..4096 and padded_dim a multiple of 64 no smaller than dim, \
+                 got {dim} and {padded_dim}"
+            )));
+        }
+        Rotator::new(dim, padded_dim)
+            .map(Self)
+            .ok_or_else(|| PyValueError::new_err("failed to create rotator"))
+    }
+
+    /// Load a rotator saved by [`save`](Self::save), its dimensions must match.
+    #[staticmethod]
+    #[pyo3(signature = (path, dim, padded_dim = None))]
+    fn load(path: PathBuf, dim: usize, padded_dim: Option<usize>) -> PyResult<Self> {
+        let mut rotator = Self::new(dim, padded_dim)?;
+        rotator.0.load(&path).map_err(to_py_err)?;
+        Ok(rotator)
+    }
+
+    fn save(&self, path: PathBuf) -> PyResult<()> {
+        self.0.save(&path).map_err(to_py_err)
+    }
+
+    #[getter]
+    fn dim(&self) -> usize {
+        self.0.dim()
+    }
+
+    #[getter]
+    fn padded_dim(&self) -> usize {
+        self.0.padded_dim()
+    }
+
+    /// Rotate one `(dim,)` vector or the rows of a `(n, dim)` array.
+    fn rotate<'py>(&self, py: Python<'py>, x: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+        let (dim, padded_dim) = (self.0.dim(), self.0.padded_dim());
+        if let Ok(x) = x.extract::<PyReadonlyArray1<f32>>() {
+            let x = x.as_slice()?;
+            if x.len() != dim {
+                return Err(PyValueError::new_err(format!("expected {dim} values")));
+            }
+            let mut y = vec![0.0; padded_dim];
+            self.0.rotate(x, &mut y);
+            return Ok(y.into_pyarray(py).into_any());
+        }
+        let x = x.extract::<PyReadonlyArray2<f32>>()?;
+        let (x, n, x_dim) = rows(&x)?;
+        if x_dim != dim {
+            return Err(PyValueError::new_err(format!("expected {dim} columns")));
+        }
+        let rotator = &self.0;
+        let y = py.detach(|| {
+            let mut y = vec![0.0; n * padded_dim];
+            for (x, y) in x.chunks_exact(dim).zip(y.chunks_exact_mut(padded_dim)) {
+                rotator.rotate(x, y);
+            }
+            y
+        });
+        Ok(y.into_pyarray(py).reshape([n, padded_dim])?.into_any())
+    }
+}
+
+/// Quantizer settings, [`faster`](Self::faster) trades a little accuracy for speed.
+#[pyclass(name = "RabitqConfig", module = "rabitq")]
+struct PyRabitqConfig(RabitqConfig);
+
+#[pymethods]
+impl PyRabitqConfig {
+    #[new]
+    fn new() -> Self {
+        Self(RabitqConfig::new())
+    }
+
+    #[staticmethod]
+    fn faster(dim: usize, total_bits: usize) -> Self {
+        Self(RabitqConfig::faster_config(dim, total_bits))
+    }
+}
+
+/// Quantize a (rotated) vector with `total_bits` per dimension.
+///
+/// Returns `(codes, f_add, f_rescale, f_error)`.
+#[pyfunction]
+#[pyo3(signature = (data, total_bits, metric = "l2", config = None))]
+fn quantize<'py>(
+    py: Python<'py>,
+    data: PyReadonlyArray1<'py, f32>,
+    total_bits: usize,
+    metric: &str,
+    config: Option<&PyRabitqConfig>,
+) -> PyResult<(Bytes<'py>, f32, f32, f32)> {
+    let metric = parse_metric(metric)?;
+    let config = config_or_default(config);
+    let (codes, f_add, f_rescale, f_error) =
+        quantize_full_single(data.as_slice()?, total_bits, metric, config);
+    Ok((codes.into_pyarray(py), f_add, f_rescale, f_error))
+}
+
+/// Quantize a rotated vector against a rotated centroid into a 1-bit code and an
+/// `ex_bits` extended code, in the layout the estimators read.
+///
+/// Returns `(bin_codes, ex_codes)`.
+#[pyfunction]
+#[pyo3(signature = (data, centroid, ex_bits, metric = "l2", config = None))]
+fn quantize_split<'py>(
+    py: Python<'py>,
+    data: PyReadonlyArray1<'py, f32>,
+    centroid: PyReadonlyArray1<'py, f32>,
+    ex_bits: usize,
+    metric: &str,
+    config: Option<&PyRabitqConfig>,
+) -> PyResult<(Bytes<'py>, Bytes<'py>)> {
+    let metric = parse_metric(metric)?;
+    let (data, centroid) = (data.as_slice()?, centroid.as_slice()?);
+    if data.len() != centroid.len() {
+        return Err(PyValueError::new_err("data and centroid differ in length"));
+    }
+    let config = config_or_default(config);
+    let (bin_codes, ex_codes) = quantize_split_single(data, centroid, ex_bits, metric, config);
+    Ok((bin_codes.into_pyarray(py), ex_codes.into_pyarray(py)))
+}
+
+/// Map codes from [`quantize`] back to `delta * code + vl`.
+#[pyfunction]
+fn reconstruct<'py>(
+    py: Python<'py>,
+    codes: PyReadonlyArray1<'py, u8>,
+    delta: f32,
+    vl: f32,
+) -> PyResult<Floats<'py>> {
+    Ok(reconstruct_vec(codes.as_slice()?, delta, vl).into_pyarray(py))
+}
+
+/// Distance estimator of one rotated query against split codes of single vectors.
+#[pyclass(name = "SingleEstimator", module = "rabitq")]
+struct PySingleEstimator(SingleEstimator);
+
+#[pymethods]
+impl PySingleEstimator {
+    #[new]
+    #[pyo3(signature = (rotated_query, ex_bits, metric = "l2", config = None))]
+    fn new(
+        rotated_query: PyReadonlyArray1<f32>,
+        ex_bits: usize,
+        metric: &str,
+        config: Option<&PyRabitqConfig>,
+    ) -> PyResult<Self> {
+        let metric = parse_metric(metric)?;
+        let query = rotated_query.as_slice()?;
+        let config = config_or_default(config);
+        Ok(Self(SingleEstimator::new(
+            query,
+            query.len(),
+            ex_bits,
+            config,
+            metric as u32,
+        )))
+    }
+
+    fn set_g_add(&mut self, norm: f32, ip: f32) {
+        self.0.set_g_add(norm, ip);
+    }
+
+    #[getter]
+    fn epsilon0(&self) -> f32 {
+        self.0.epsilon0()
+    }
+
+    #[setter]
+    fn set_epsilon0(&mut self, epsilon0: f32) -> PyResult<()> {
+        if epsilon0 < 0.0 {
+            return Err(PyValueError::new_err("epsilon0 must be non-negative"));
+        }
+        self.0.set_epsilon0(epsilon0);
+        Ok(())
+    }
+
+    /// 1-bit estimate, returns `(est_dist, low_dist, ip_x0_qr)`.
+    fn est_dist(
+        &self,
+        bin_data: PyReadonlyArray1<u8>,
+        g_add: f32,
+        g_error: f32,
+    ) -> PyResult<(f32, f32, f32)> {
+        Ok(self.0.est_dist(bin_data.as_slice()?, g_add, g_error))
+    }
+
+    /// Full-bit estimate, returns `(est_dist, low_dist, ip_x0_qr)`.
+    fn full_dist(
+        &self,
+        bin_data: PyReadonlyArray1<u8>,
+        ex_data: PyReadonlyArray1<u8>,
+        g_add: f32,
+        g_error: f32,
+    ) -> PyResult<(f32, f32, f32)> {
+        Ok(self
+            .0
+            .full_dist(bin_data.as_slice()?, ex_data.as_slice()?, g_add, g_error))
+    }
+
+    /// 1-bit estimate with its bound at `epsilon0`, returns `(estimate, lower, upper)`.
+    fn est_dist_bound(
+        &self,
+        bin_data: PyReadonlyArray1<u8>,
+        g_add: f32,
+        g_error: f32,
+    ) -> PyResult<(f32, f32, f32)> {
+        Ok(bound_tuple(self.0.est_dist_bound(
+            bin_data.as_slice()?,
+            g_add,
+            g_error,
+        )))
+    }
+
+    /// Full-bit estimate with its bound at `epsilon0`, returns `(estimate, lower, upper)`.
+    fn full_dist_bound(
+        &self,
+        bin_data: PyReadonlyArray1<u8>,
+        ex_data: PyReadonlyArray1<u8>,
+        g_add: f32,
+        g_error: f32,
+    ) -> PyResult<(f32, f32, f32)> {
+        Ok(bound_tuple(self.0.full_dist_bound(
+            bin_data.as_slice()?,
+            ex_data.as_slice()?,
+            g_add,
+            g_error,
+        )))
+    }
+
+    fn distance_boosting(&self, ex_data: PyReadonlyArray1<u8>, ip_x0_qr: f32) -> PyResult<f32> {
+        Ok(self.0.distance_boosting(ex_data.as_slice()?, ip_x0_qr))
+    }
+}
+
+/// Like [`PySingleEstimator`] but with the query expressed relative to a centroid.
+#[pyclass(name = "SingleCentroidEstimator", module = "rabitq")]
+struct PySingleCentroidEstimator(SingleCentroidEstimator);
+
+#[pymethods]
+impl PySingleCentroidEstimator {
+    #[new]
+    #[pyo3(signature = (rotated_query, centroid, ex_bits, metric = "l2", config = None))]
+    fn new(
+        rotated_query: PyReadonlyArray1<f32>,
+        centroid: PyReadonlyArray1<f32>,
+        ex_bits: usize,
+        metric: &str,
+        config: Option<&PyRabitqConfig>,
+    ) -> PyResult<Self> {
+        let metric = parse_metric(metric)?;
+        let (query, centroid) = (rotated_query.as_slice()?, centroid.as_slice()?);
+        if query.len() != centroid.len() {
+            return Err(PyValueError::new_err("query and centroid differ in length"));
+        }
+        let config = config_or_default(config);
+        Ok(Self(SingleCentroidEstimator::new(
+            query,
+            centroid,
+            query.len(),
+            ex_bits,
+            config,
+            metric as u32,
+        )))
+    }
+
+    fn set_g_add(&mut self, norm: f32, ip: f32) {
+        self.0.set_g_add(norm, ip);
+    }
+
+    fn set_g_error(&mut self, norm: f32) {
+        self.0.set_g_error(norm);
+    }
+
+    #[getter]
+    fn epsilon0(&self) -> f32 {
+        self.0.epsilon0()
+    }
+
+    #[setter]
+    fn set_epsilon0(&mut self, epsilon0: f32) -> PyResult<()> {
+        if epsilon0 < 0.0 {
+            return Err(PyValueError::new_err("epsilon0 must be non-negative"));
+        }
+        self.0.set_epsilon0(epsilon0);
+        Ok(())
+    }
+
+    fn est_dist(
+        &self,
+        bin_data: PyReadonlyArray1<u8>,
+        g_add: f32,
+        g_error: f32,
+    ) -> PyResult<(f32, f32, f32)> {
+        Ok(self.0.est_dist(bin_data.as_slice()?, g_add, g_error))
+    }
+
+    fn full_dist(
+        &self,
+        bin_data: PyReadonlyArray1<u8>,
+        ex_data: PyReadonlyArray1<u8>,
+        g_add: f32,
+        g_error: f32,
+    ) -> PyResult<(f32, f32, f32)> {
+        Ok(self
+            .0
+            .full_dist(bin_data.as_slice()?, ex_data.as_slice()?, g_add, g_error))
+    }
+
+    fn est_dist_bound(
+        &self,
+        bin_data: PyReadonlyArray1<u8>,
+        g_add: f32,
+        g_error: f32,
+    ) -> PyResult<(f32, f32, f32)> {
+        Ok(bound_tuple(self.0.est_dist_bound(
+            bin_data.as_slice()?,
+            g_add,
+            g_error,
+        )))
+    }
+
+    fn full_dist_bound(
+        &self,
+        bin_data: PyReadonlyArray1<u8>,
+        ex_data: PyReadonlyArray1<u8>,
+        g_add: f32,
+        g_error: f32,
+    ) -> PyResult<(f32, f32, f32)> {
+        Ok(bound_tuple(self.0.full_dist_bound(
+            bin_data.as_slice()?,
+            ex_data.as_slice()?,
+            g_add,
+            g_error,
+        )))
+    }
+
+    fn distance_boosting(&self, ex_data: PyReadonlyArray1<u8>, ip_x0_qr: f32) -> PyResult<f32> {
+        Ok(self.0.distance_boosting(ex_data.as_slice()?, ip_x0_qr))
+    }
+}
+
+/// FastScan estimator of one rotated query against packed batches of 1-bit codes.
+#[pyclass(name = "BatchBinEstimator", module = "rabitq")]
+struct PyBatchBinEstimator(BatchBinEstimator);
+
+#[pymethods]
+impl PyBatchBinEstimator {
+    #[classattr]
+    const BATCH_SIZE: usize = BatchBinEstimator::K_BATCH_SIZE;
+
+    #[new]
+    fn new(rotated_query: PyReadonlyArray1<f32>) -> PyResult<Self> {
+        let query = rotated_query.as_slice()?;
+        Ok(Self(BatchBinEstimator::new(query, query.len())))
+    }
+
+    fn set_g_add(&mut self, sqr_norm: f32) {
+        self.0.set_g_add(sqr_norm);
+    }
+
+    /// Estimated distances of the `BATCH_SIZE` vectors of one packed batch.
+    fn batch_est<'py>(
+        &self,
+        py: Python<'py>,
+        batch_data: PyReadonlyArray1<'py, u8>,
+    ) -> PyResult<Floats<'py>> {
+        let mut est = vec![0.0; BatchBinEstimator::K_BATCH_SIZE];
+        self.0.batch_est(batch_data.as_slice()?, &mut est);
+        Ok(est.into_pyarray(py))
+    }
+}
+
+/// FastScan estimator over split batches, as used by the IVF index.
+#[pyclass(name = "SplitBatchEstimator", module = "rabitq")]
+struct PySplitBatchEstimator {
+    inner: SplitBatchEstimator,
+    ex_bits: usize,
+    use_hacc: bool,
+}
+
+#[pymethods]
+impl PySplitBatchEstimator {
+    #[new]
+    #[pyo3(signature = (rotated_query, ex_bits, metric = "l2", use_hacc = true))]
+    fn new(
+        rotated_query: PyReadonlyArray1<f32>,
+        ex_bits: usize,
+        metric: &str,
+        use_hacc: bool,
+    ) -> PyResult<Self> {
+        let metric = parse_metric(metric)?;
+        let query = rotated_query.as_slice()?;
+        Ok(Self {
+            inner: SplitBatchEstimator::new(query, query.len(), ex_bits, metric as u32, use_hacc),
+            ex_bits,
+            use_hacc,
+        })
+    }
+
+    fn set_g_add(&mut self, norm: f32, ip: f32) {
+        self.inner.set_g_add(norm, ip);
+    }
+
+    /// Returns `(est_dist, low_dist, ip_x0_qr)`, one array entry per vector of the batch.
+    fn est_dist<'py>(
+        &self,
+        py: Python<'py>,
+        batch_data: PyReadonlyArray1<'py, u8>,
+    ) -> PyResult<(Floats<'py>, Floats<'py>, Floats<'py>)> {
+        let (est, low, ip) = self.inner.estdist(batch_data.as_slice()?, self.use_hacc);
+        Ok((
+            PyArray1::from_slice(py, &est),
+            PyArray1::from_slice(py, &low),
+            PyArray1::from_slice(py, &ip),
+        ))
+    }
+
+    fn distance_boosting(&self, ex_data: PyReadonlyArray1<u8>, ip_x0_qr: f32) -> PyResult<f32> {
+        Ok(self.inner.distance_boosting(
+            ex_data.as_slice()?,
+            select_excode_ipfunc(self.ex_bits),
+            self.ex_bits,
+            ip_x0_qr,
+        ))
+    }
+}
+
+/// Common base of [`PyIvf`], [`PyHnsw`] and [`PyQg`], also returned by [`open`].
+#[pyclass(name = "Index", module = "rabitq", subclass)]
+struct PyIndex(Box<dyn AnnIndex>);
+
+#[pymethods]
+impl PyIndex {
+    #[getter]
+    fn kind(&self) -> String {
+        self.0.kind().to_string()
+    }
+
+    #[getter]
+    fn metric(&self) -> String {
+        self.0.meta().metric.to_string()
+    }
+
+    #[getter]
+    fn dim(&self) -> usize {
+        self.0.dim()
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Build and tuning parameters stored with the index, as strings.
+    #[getter]
+    fn params(&self) -> Vec<(String, String)> {
+        self.0.meta().params.clone().into_iter().collect()
+    }
+
+    /// Ids of the `k` nearest neighbours of every row of `queries`, shape `(n, k)`.
+    /// Unset `nprobe` / `ef` / `query_bits` fall back to the index defaults, missing
+    /// neighbours are `2**32 - 1`. `query_bits` is a number of bits or `"exact"`.
+    #[pyo3(signature = (queries, k, nprobe = None, ef = None, query_bits = None, num_threads = 0))]
+    #[allow(clippy::too_many_arguments)]
+    fn search<'py>(
+        &self,
+        py: Python<'py>,
+        queries: PyReadonlyArray2<'py, f32>,
+        k: usize,
+        nprobe: Option<usize>,
+        ef: Option<usize>,
+        query_bits: Option<&Bound<'py, PyAny>>,
+        num_threads: usize,
+    ) -> PyResult<Bound<'py, PyArray2<u32>>> {
+        let (queries, nq, dim) = rows(&queries)?;
+        if dim != self.0.dim() {
+            return Err(PyValueError::new_err(format!(
+                "queries have {dim} columns, the index {}",
+                self.0.dim()
+            )));
+        }
+        let defaults = self.0.meta().default_search_params();
+        let params = SearchParams {
+            nprobe: nprobe.unwrap_or(defaults.nprobe),
+            ef: ef.unwrap_or(defaults.ef),
+            query_precision: match query_bits {
+                Some(value) => Some(parse_precision(value)?),
+                None => defaults.query_precision,
+            },
+            ..defaults
+        };
+        params.validate(self.0.kind()).map_err(to_py_err)?;
+        let index = &*self.0;
+        let ids = py.detach(|| index::search_batch(index, queries, k, &params, num_threads));
+        ids.into_pyarray(py).reshape([nq, k])
+    }
+
+    /// Memory use per section in bytes, plus cluster sizes (IVF) or out-degrees per level
+    /// (HNSW, QG), as a dict.
+    fn stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let index = &*self.0;
+        let stats = py.detach(|| index.stats());
+        let dict = PyDict::new(py);
+        dict.set_item("kind", stats.kind.to_string())?;
+        dict.set_item("num", stats.num)?;
+        dict.set_item("dim", stats.dim)?;
+        dict.set_item("memory", stats.memory.to_vec().into_py_dict(py)?)?;
+        dict.set_item("total_bytes", stats.total_bytes())?;
+        if let Some(clusters) = stats.clusters {
+            let c = distribution_dict(py, &clusters.distribution)?;
+            c.set_item("empty", clusters.empty)?;
+            c.set_item("imbalance", clusters.imbalance)?;
+            c.set_item("sizes", clusters.sizes.into_pyarray(py))?;
+            dict.set_item("clusters", c)?;
+        }
+        if let Some(graph) = stats.graph {
+            let g = PyDict::new(py);
+            g.set_item("entry_point", graph.entry_point)?;
+            let levels = graph
+                .levels
+                .iter()
+                .map(|level| distribution_dict(py, level))
+                .collect::<PyResult<Vec<_>>>()?;
+            g.set_item("levels", levels)?;
+            dict.set_item("graph", g)?;
+        }
+        Ok(dict)
+    }
+
+    /// Write the index and its `.meta` sidecar.
+    fn save(&self, py: Python<'_>, path: PathBuf) -> PyResult<()> {
+        let index = &*self.0;
+        py.detach(|| index.save(&path)).map_err(to_py_err)
+    }
+}
+
+/// IVF index with RaBitQ-quantized clusters, L2 only.
+#[pyclass(name = "Ivf", module = "rabitq", extends = PyIndex)]
+struct PyIvf;
+
+#[pymethods]
+impl PyIvf {
+    #[new]
+    #[pyo3(signature = (
+        data,
+        num_clusters = 4096,
+        total_bits = 7,
+        faster_quant = false,
+        kmeans_iters = 10,
+        seed = 42,
+        num_threads = 0,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        py: Python<'_>,
+        data: PyReadonlyArray2<f32>,
+        num_clusters: usize,
+        total_bits: usize,
+        faster_quant: bool,
+        kmeans_iters: usize,
+        seed: u64,
+        num_threads: usize,
+    ) -> PyResult<(Self, PyIndex)> {
+        let (data, _, dim) = rows(&data)?;
+        let params = IvfParams {
+            num_clusters,
+            total_bits,
+            faster_quant,
+            kmeans_iters,
+            seed,
+            rotator: None,
+            monitor: BuildMonitor::default(),
+        };
+        let ivf = py
+            .detach(|| index::with_threads(num_threads, || Ivf::build(data, dim, &params)))
+            .map_err(to_py_err)?;
+        Ok((Self, PyIndex(Box::new(ivf))))
+    }
+
+    #[staticmethod]
+    fn load(py: Python<'_>, path: PathBuf) -> PyResult<Py<Self>> {
+        let ivf = py.detach(|| Ivf::load(&path)).map_err(to_py_err)?;
+        Py::new(py, (Self, PyIndex(Box::new(ivf))))
+    }
+}
+
+/// HNSW graph over RaBitQ codes.
+#[pyclass(name = "Hnsw", module = "rabitq", extends = PyIndex)]
+struct PyHnsw;
+
+#[pymethods]
+impl PyHnsw {
+    #[new]
+    #[pyo3(signature = (
+        data,
+        m = 16,
+        ef_construction = 200,
+        total_bits = 7,
+        metric = "l2",
+        num_clusters = 16,
+        faster_quant = false,
+        seed = 42,
+        num_threads = 0,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        py: Python<'_>,
+        data: PyReadonlyArray2<f32>,
+        m: usize,
+        ef_construction: usize,
+        total_bits: usize,
+        metric: &str,
+        num_clusters: usize,
+        faster_quant: bool,
+        seed: u64,
+        num_threads: usize,
+    ) -> PyResult<(Self, PyIndex)> {
+        let (data, _, dim) = rows(&data)?;
+        let params = HnswParams {
+            m,
+            ef_construction,
+            total_bits,
+            metric: parse_metric(metric)?,
+            num_clusters,
+            faster_quant,
+            seed,
+            num_threads,
+            rotator: None,
+            monitor: BuildMonitor::default(),
+        };
+        let hnsw = py
+            .detach(|| Hnsw::build(data, dim, &params))
+            .map_err(to_py_err)?;
+        Ok((Self, PyIndex(Box::new(hnsw))))
+    }
+
+    /// `metric` is only needed when the index has no `.meta` sidecar.
+    #[staticmethod]
+    #[pyo3(signature = (path, metric = None))]
+    fn load(py: Python<'_>, path: PathBuf, metric: Option<&str>) -> PyResult<Py<Self>> {
+        let metric = metric.map(parse_metric).transpose()?;
+        let hnsw = py
+            .detach(|| match metric {
+                Some(metric) => Hnsw::load_with_metric(&path, metric),
+                None => Hnsw::load(&path),
+            })
+            .map_err(to_py_err)?;
+        Py::new(py, (Self, PyIndex(Box::new(hnsw))))
+    }
+}
+
+/// SymphonyQG graph, `metric` is `"l2"` or `"ip"`.
+#[pyclass(name = "Qg", module = "rabitq", extends = PyIndex)]
+struct PyQg;
+
+#[pymethods]
+impl PyQg {
+    #[new]
+    #[pyo3(signature = (
+        data,
+        degree = 32,
+        ef_build = 400,
+        num_iter = 3,
+        metric = "l2",
+        seed = 42,
+        num_threads = 0,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        py: Python<'_>,
+        data: PyReadonlyArray2<f32>,
+        degree: usize,
+        ef_build: usize,
+        num_iter: usize,
+        metric: &str,
+        seed: u64,
+        num_threads: usize,
+    ) -> PyResult<(Self, PyIndex)> {
+        let (data, _, dim) = rows(&data)?;
+        let params = QgParams {
+            degree,
+            ef_build,
+            num_threads,
+            num_iter,
+            metric: parse_metric(metric)?,
+            seed,
+            rotator: None,
+            monitor: BuildMonitor::default(),
+        };
+        let qg = py
+            .detach(|| Qg::build(data, dim, &params))
+            .map_err(to_py_err)?;
+        Ok((Self, PyIndex(Box::new(qg))))
+    }
+
+    #[staticmethod]
+    fn load(py: Python<'_>, path: PathBuf) -> PyResult<Py<Self>> {
+        let qg = py.detach(|| Qg::load(&path)).map_err(to_py_err)?;
+        Py::new(py, (Self, PyIndex(Box::new(qg))))
+    }
+}
+
+/// Load any index saved with its `.meta` sidecar.
+#[pyfunction]
+fn open(py: Python<'_>, path: PathBuf) -> PyResult<PyIndex> {
+    py.detach(|| index::open(&path))
+        .map(PyIndex)
+        .map_err(to_py_err)
+}
+
+#[pymodule]
+fn rabitq(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyRotator>()?;
+    m.add_class::<PyRabitqConfig>()?;
+    m.add_function(wrap_pyfunction!(quantize, m)?)?;
+    m.add_function(wrap_pyfunction!(quantize_split, m)?)?;
+    m.add_function(wrap_pyfunction!(reconstruct, m)?)?;
+    m.add_class::<PySingleEstimator>()?;
+    m.add_class::<PySingleCentroidEstimator>()?;
+    m.add_class::<PyBatchBinEstimator>()?;
+    m.add_class::<PySplitBatchEstimator>()?;
+    m.add_class::<PyIndex>()?;
+    m.add_class::<PyIvf>()?;
+    m.add_class::<PyHnsw>()?;
+    m.add_class::<PyQg>()?;
+    m.add_function(wrap_pyfunction!(open, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,87 @@
+import numpy as np
+import pytest
+
+import rabitq
+
+
+def dataset(n=2000, nq=20, dim=64, seed=0):
+    rng = np.random.default_rng(seed)
+    data = rng.standard_normal((n, dim), dtype=np.float32)
+    queries = rng.standard_normal((nq, dim), dtype=np.float32)
+    dists = ((queries[:, None, :] - data[None, :, :]) ** 2).sum(-1)
+    return data, queries, np.argsort(dists, axis=1)[:, :10]
+
+
+def recall(found, gt):
+    return np.mean([len(set(f) & set(g)) / len(g) for f, g in zip(found, gt)])
+
+
+def test_rotator_preserves_norms(tmp_path):
+    rotator = rabitq.Rotator(100)
+    assert (rotator.dim, rotator.padded_dim) == (100, 128)
+    x = np.random.default_rng(1).standard_normal((5, 100), dtype=np.float32)
+    y = rotator.rotate(x)
+    assert y.shape == (5, 128)
+    np.testing.assert_allclose(np.linalg.norm(y, axis=1), np.linalg.norm(x, axis=1), rtol=1e-4)
+    np.testing.assert_allclose(rotator.rotate(x[0]), y[0], rtol=1e-5)
+
+    path = str(tmp_path / "rotator.bin")
+    rotator.save(path)
+    np.testing.assert_allclose(rabitq.Rotator.load(path, 100).rotate(x), y)
+    with pytest.raises(ValueError):
+        rabitq.Rotator(10)
+
+
+def test_quantize_reconstruct():
+    x = np.random.default_rng(2).standard_normal(128, dtype=np.float32)
+    codes, f_add, f_rescale, f_error = rabitq.quantize(x, 7)
+    assert codes.dtype == np.uint8 and codes.shape == (128,)
+    assert rabitq.reconstruct(codes, f_add, f_rescale).shape == (128,)
+    with pytest.raises(ValueError):
+        rabitq.quantize(x, 7, metric="cosine")
+
+
+@pytest.mark.parametrize(
+    "build",
+    [
+        lambda data: rabitq.Ivf(data, num_clusters=16),
+        lambda data: rabitq.Hnsw(data),
+        lambda data: rabitq.Qg(data),
+    ],
+)
+def test_index_build_search_save(tmp_path, build):
+    data, queries, gt = dataset()
+    index = build(data)
+    assert len(index) == len(data) and index.dim == data.shape[1]
+    ids = index.search(queries, 10, nprobe=16, ef=200)
+    assert ids.shape == (len(queries), 10) and ids.dtype == np.uint32
+    assert recall(ids, gt) > 0.8
+    exact = index.search(queries, 10, nprobe=16, ef=200, query_bits="exact")
+    assert recall(exact, gt) > 0.8
+    with pytest.raises(ValueError):
+        index.search(queries, 10, query_bits=0)
+
+    path = str(tmp_path / f"index.{index.kind}")
+    index.save(path)
+    reopened = rabitq.open(path)
+    assert reopened.kind == index.kind
+    assert recall(reopened.search(queries, 10, nprobe=16, ef=200), gt) > 0.8
+    with pytest.raises(ValueError):
+        index.search(np.ascontiguousarray(queries[:, :32]), 10)
+
+
+def test_qg_inner_product():
+    data, queries, _ = dataset()
+    gt = np.argsort(-queries @ data.T, axis=1)[:, :10]
+    index = rabitq.Qg(data, metric="ip")
+    assert index.metric == "ip"
+    assert recall(index.search(queries, 10, ef=200), gt) > 0.8
+
+
+def test_stats():
+    data, _, _ = dataset(n=1000)
+    stats = rabitq.Ivf(data, num_clusters=8).stats()
+    assert stats["clusters"]["sizes"].sum() == 1000
+    assert stats["total_bytes"] == sum(stats["memory"].values())
+    graph = rabitq.Hnsw(data).stats()["graph"]
+    assert graph["levels"][0]["count"] == 1000
@@ -0,0 +1,702 @@
+//! `rabitq` command-line tool: build, search, evaluate and inspect indexes.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use clap::{Args, Parser, Subcommand};
+use rabitq_rs::eval::{self, EvalConfig};
+use rabitq_rs::groundtruth;
+use rabitq_rs::index::{
+    self, BuildMonitor, Hnsw, HnswParams, Ivf, IvfParams, Qg, QgParams, QuantizedBuildParams,
+    StreamBuildParams,
+};
+use rabitq_rs::io::{FileElement, Matrix, VectorFile, read_bin, read_vecs, write_vecs};
+use rabitq_rs::tune::{self, TuneConfig};
+use rabitq_rs::{
+    AnnIndex, IndexKind, IndexMeta, MetricType, QueryPrecision, Rotator, SearchBudget,
+    SearchParams, VectorElement,
+};
+
+#[derive(Parser)]
+#[command(name = "rabitq", version, about = "Build and query RaBitQ indexes")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Build an index from a vector file
+    Build(BuildArgs),
+    /// Search a batch of queries and write the result ids as ivecs
+    Search(SearchArgs),
+    /// Measure recall@k and QPS against a ground truth file
+    Eval(EvalArgs),
+    /// Print the metadata and header of an index
+    Info(InfoArgs),
+    /// Compute exact nearest neighbours, streaming the base vectors from disk
+    Groundtruth(GroundtruthArgs),
+    /// Find the smallest nprobe / ef reaching a target recall and store it as the default
+    Tune(TuneArgs),
+}
+
+#[derive(Args)]
+struct BuildArgs {
+    /// Base vectors (.fvecs, .bvecs, .fbin, .u8bin or .i8bin)
+    #[arg(long)]
+    data: PathBuf,
+    /// Output index file, metadata goes to `<output>.meta`
+    #[arg(long, short)]
+    output: PathBuf,
+    #[arg(long, default_value = "ivf")]
+    kind: IndexKind,
+    /// Bits per dimension (ivf, hnsw)
+    #[arg(long, default_value_t = 7)]
+    bits: usize,
+    /// Distance metric, inner product needs `--kind hnsw` or `--kind qg`
+    #[arg(long, default_value = "l2")]
+    metric: MetricType,
+    /// Number of k-means clusters (ivf, hnsw)
+    #[arg(long)]
+    clusters: Option<usize>,
+    /// Graph degree (qg, multiple of 32)
+    #[arg(long, default_value_t = 32)]
+    degree: usize,
+    /// Max out-degree on the upper levels (hnsw)
+    #[arg(long, default_value_t = 16)]
+    m: usize,
+    /// Candidate list size during construction (hnsw, qg)
+    #[arg(long)]
+    ef_construction: Option<usize>,
+    /// Faster but slightly less accurate quantization
+    #[arg(long)]
+    faster: bool,
+    /// Stream the data file instead of loading it: hnsw builds the graph on quantized
+    /// vectors, ivf reads the file twice to size and then fill the clusters
+    #[arg(long)]
+    quantized: bool,
+    /// Raw vectors read at a time with --quantized
+    #[arg(long, default_value_t = QuantizedBuildParams::default().chunk_rows)]
+    chunk_rows: usize,
+    /// Fraction of insertions ranking their candidates on raw vectors with --quantized
+    #[arg(long, default_value_t = QuantizedBuildParams::default().exact_sample)]
+    exact_sample: f32,
+    /// Quantize a PCA projection to this many dimensions instead of the full vectors
+    #[arg(long)]
+    pca: Option<usize>,
+    /// Learn the rotation from the data in this many steps instead of drawing a random one
+    #[arg(long, conflicts_with = "pca")]
+    learn_rotation: Option<usize>,
+    /// Vectors --pca or --learn-rotation is trained on, evenly spaced over the data
+    #[arg(long, default_value_t = 100_000)]
+    train_sample: usize,
+    /// Seed of k-means, the rotation and random edges, builds repeat for a seed and thread
+    /// count (hnsw: one thread)
+    #[arg(long, default_value_t = 42)]
+    seed: u64,
+    #[arg(long, default_value_t = 0)]
+    threads: usize,
+    /// Print the progress of the construction to stderr
+    #[arg(long)]
+    progress: bool,
+}
+
+#[derive(Args)]
+struct OpenArgs {
+    /// Index file
+    #[arg(long, short)]
+    index: PathBuf,
+    /// Index type, only needed when there is no metadata file
+    #[arg(long)]
+    kind: Option<IndexKind>,
+    /// Metric of an HNSW index without a metadata file
+    #[arg(long, default_value = "l2")]
+    metric: MetricType,
+}
+
+#[derive(Args)]
+struct QueryArgs {
+    /// Query vectors (.fvecs, .bvecs, .fbin, .u8bin or .i8bin)
+    #[arg(long, short)]
+    queries: PathBuf,
+    #[arg(long, short, default_value_t = 10)]
+    k: usize,
+    /// Clusters probed per query (ivf), defaults to the tuned value in the metadata
+    #[arg(long)]
+    nprobe: Option<usize>,
+    /// Candidate list size (hnsw, qg), defaults to the tuned value in the metadata
+    #[arg(long)]
+    ef: Option<usize>,
+    /// Query precision in bits or `exact`: 8 or 16 (ivf, qg), 1 to 16 (hnsw)
+    #[arg(long)]
+    query_bits: Option<QueryPrecision>,
+    /// Stop a query after this many microseconds, keeping the results found so far
+    #[arg(long)]
+    time_budget_us: Option<u64>,
+    /// Stop a query after this many distance estimates
+    #[arg(long)]
+    max_distances: Option<usize>,
+    /// 0 uses all cores
+    #[arg(long, default_value_t = 0)]
+    threads: usize,
+}
+
+impl QueryArgs {
+    fn params(&self, index: &dyn AnnIndex) -> io::Result<SearchParams> {
+        let defaults = index.meta().default_search_params();
+        let params = SearchParams {
+            nprobe: self.nprobe.unwrap_or(defaults.nprobe),
+            ef: self.ef.unwrap_or(defaults.ef),
+            query_precision: self.query_bits.or(defaults.query_precision),
+            budget: SearchBudget {
+                time: self.time_budget_us.map(Duration::from_micros),
+                max_distances: self.max_distances,
+                ..SearchBudget::default()
+            },
+            ..defaults
+        };
+        params.validate(index.kind())?;
+        Ok(params)
+    }
+}
+
+#[derive(Args)]
+struct SearchArgs {
+    #[command(flatten)]
+    open: OpenArgs,
+    #[command(flatten)]
+    query: QueryArgs,
+    /// Output ivecs file, missing results are written as -1
+    #[arg(long, short)]
+    output: PathBuf,
+}
+
+#[derive(Args)]
+struct EvalArgs {
+    #[command(flatten)]
+    open: OpenArgs,
+    #[command(flatten)]
+    query: QueryArgs,
+    /// Ground truth ids (.ivecs)
+    #[arg(long, short)]
+    groundtruth: PathBuf,
+    /// Sweep nprobe (ivf) or ef (hnsw, qg) over these comma separated values, or over the
+    /// default list, stopping once recall saturates, when given without values
+    #[arg(long, num_args = 0.., value_delimiter = ',')]
+    sweep: Option<Vec<usize>>,
+    /// Passes over the queries per value
+    #[arg(long, default_value_t = 1)]
+    rounds: usize,
+    /// Write the results as JSON, or as CSV if the file ends in .csv
+    #[arg(long)]
+    report: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct InfoArgs {
+    #[command(flatten)]
+    open: OpenArgs,
+    /// Load the index and print its memory use and cluster / degree distributions
+    #[arg(long)]
+    stats: bool,
+}
+
+#[derive(Args)]
+struct GroundtruthArgs {
+    /// Base vectors (.fvecs, .bvecs, .fbin, .u8bin or .i8bin)
+    #[arg(long)]
+    base: PathBuf,
+    /// Query vectors, same formats
+    #[arg(long, short)]
+    queries: PathBuf,
+    #[arg(long, short, default_value_t = 100)]
+    k: usize,
+    #[arg(long, default_value = "l2")]
+    metric: MetricType,
+    /// Output ids (.ivecs)
+    #[arg(long, short)]
+    output: PathBuf,
+    /// Also write the distances (.fvecs), inner products for `--metric ip`
+    #[arg(long)]
+    distances: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct TuneArgs {
+    #[command(flatten)]
+    open: OpenArgs,
+    /// Base vectors the index was built from, to compute the ground truth
+    #[arg(long)]
+    base: PathBuf,
+    /// Held-out queries (.fvecs, .bvecs, .fbin, .u8bin or .i8bin)
+    #[arg(long, short)]
+    queries: PathBuf,
+    /// Recall@k to reach
+    #[arg(long, default_value_t = TuneConfig::default().target_recall)]
+    target: f64,
+    #[arg(long, short, default_value_t = TuneConfig::default().k)]
+    k: usize,
+    /// Number of queries sampled from the query file
+    #[arg(long, default_value_t = TuneConfig::default().num_queries)]
+    sample: usize,
+    #[arg(long, default_value_t = 0)]
+    threads: usize,
+}
+
+/// Vectors of any supported element type, picked by file extension
+enum Vectors {
+    F32(Matrix<f32>),
+    U8(Matrix<u8>),
+    I8(Matrix<i8>),
+}
+
+/// Run `$body` with `$m` bound to the typed matrix inside a [`Vectors`]
+macro_rules! with_vectors {
+    ($v:expr, $m:ident => $body:expr) => {
+        match $v {
+            Vectors::F32($m) => $body,
+            Vectors::U8($m) => $body,
+            Vectors::I8($m) => $body,
+        }
+    };
+}
+
+impl Vectors {
+    fn read(path: &Path) -> io::Result<Self> {
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        Ok(match ext {
+            "fvecs" => Vectors::F32(read_vecs(path)?),
+            "bvecs" => Vectors::U8(read_vecs(path)?),
+            "fbin" => Vectors::F32(read_bin(path)?),
+            "u8bin" => Vectors::U8(read_bin(path)?),
+            "i8bin" => Vectors::I8(read_bin(path)?),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{}: unknown vector file extension", path.display()),
+                ));
+            }
+        })
+    }
+
+    fn rows(&self) -> usize {
+        with_vectors!(self, m => m.rows())
+    }
+
+    fn cols(&self) -> usize {
+        with_vectors!(self, m => m.cols())
+    }
+}
+
+fn build_typed<T: VectorElement>(
+    data: &Matrix<T>,
+    args: &BuildArgs,
+) -> io::Result<Box<dyn AnnIndex>> {
+    let (n, dim) = (data.rows(), data.cols());
+    if args.metric != MetricType::L2 && args.kind == IndexKind::Ivf {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} only supports the l2 metric", args.kind),
+        ));
+    }
+    let rotator = trained_rotator(args, n, dim, |ids| {
+        Ok(ids
+            .iter()
+            .flat_map(|&i| data.row(i as usize).iter().copied())
+            .collect())
+    })?;
+    Ok(match args.kind {
+        IndexKind::Ivf => {
+            let params = ivf_params(args, n, rotator);
+            let data = data.as_slice();
+            let index = index::with_threads(args.threads, || Ivf::build(data, dim, &params))?;
+            Box::new(index)
+        }
+        IndexKind::Hnsw => Box::new(Hnsw::build(
+            data.as_slice(),
+            dim,
+            &hnsw_params(args, rotator),
+        )?),
+        IndexKind::Qg => {
+            let defaults = QgParams::default();
+            let params = QgParams {
+                degree: args.degree,
+                ef_build: args.ef_construction.unwrap_or(defaults.ef_build),
+                num_threads: args.threads,
+                metric: args.metric,
+                seed: args.seed,
+                rotator,
+                monitor: build_monitor(args),
+                ..defaults
+            };
+            Box::new(Qg::build(data.as_slice(), dim, &params)?)
+        }
+    })
+}
+
+/// Rotator learned on evenly spaced rows for `--pca` or `--learn-rotation`, `None` for the
+/// default random rotation
+fn trained_rotator<T: VectorElement>(
+    args: &BuildArgs,
+    n: usize,
+    dim: usize,
+    read_rows: impl FnOnce(&[u32]) -> io::Result<Vec<T>>,
+) -> io::Result<Option<Arc<Rotator>>> {
+    let sample = || {
+        let step = n.div_ceil(args.train_sample.max(1)).max(1);
+        let ids: Vec<u32> = (0..n).step_by(step).map(|i| i as u32).collect();
+        read_rows(&ids)
+    };
+    let rotator = if let Some(out_dim) = args.pca {
+        let rotator = Rotator::pca(&sample()?, dim, out_dim, args.seed)?;
+        println!(
+            "PCA to {out_dim} dimensions keeps {:.1}% of the variance",
+            100.0 * rotator.explained_variance().unwrap_or(1.0)
+        );
+        rotator
+    } else if let Some(iters) = args.learn_rotation {
+        let rotator = Rotator::learned(&sample()?, dim, iters, args.seed)?;
+        println!("learned the rotation in {iters} steps");
+        rotator
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(Arc::new(rotator)))
+}
+
+fn ivf_params(args: &BuildArgs, n: usize, rotator: Option<Arc<Rotator>>) -> IvfParams {
+    IvfParams {
+        // about sqrt(n) * 4 clusters, like the python script suggests
+        num_clusters: args
+            .clusters
+            .unwrap_or(((n as f64).sqrt() * 4.0) as usize)
+            .clamp(1, n.max(1)),
+        total_bits: args.bits,
+        faster_quant: args.faster,
+        seed: args.seed,
+        rotator,
+        monitor: build_monitor(args),
+        ..IvfParams::default()
+    }
+}
+
+fn hnsw_params(args: &BuildArgs, rotator: Option<Arc<Rotator>>) -> HnswParams {
+    let defaults = HnswParams::default();
+    HnswParams {
+        m: args.m,
+        ef_construction: args.ef_construction.unwrap_or(defaults.ef_construction),
+        total_bits: args.bits,
+        metric: args.metric,
+        num_clusters: args.clusters.unwrap_or(defaults.num_clusters),
+        faster_quant: args.faster,
+        seed: args.seed,
+        num_threads: args.threads,
+        rotator,
+        monitor: build_monitor(args),
+    }
+}
+
+fn build_monitor(args: &BuildArgs) -> BuildMonitor {
+    if !args.progress {
+        return BuildMonitor::default();
+    }
+    BuildMonitor::default().with_progress(|phase, done, total| {
+        eprintln!("{phase}: {done}/{total}");
+    })
+}
+
+fn build_quantized_typed<T: FileElement + VectorElement>(
+    args: &BuildArgs,
+) -> io::Result<Box<dyn AnnIndex>> {
+    let file = VectorFile::<T>::open(&args.data)?;
+    println!(
+        "streaming {} vectors of dimension {}",
+        file.rows(),
+        file.cols()
+    );
+    let rotator = trained_rotator(args, file.rows(), file.cols(), |ids| {
+        let mut rows = Vec::with_capacity(ids.len() * file.cols());
+        file.read_rows(ids, &mut rows)?;
+        Ok(rows)
+    })?;
+    if args.kind == IndexKind::Ivf {
+        let stream = StreamBuildParams {
+            chunk_rows: args.chunk_rows,
+            ..StreamBuildParams::default()
+        };
+        let params = ivf_params(args, file.rows(), rotator);
+        let index = index::with_threads(args.threads, || {
+            Ivf::build_from_file(&file, &params, &stream)
+        })?;
+        return Ok(Box::new(index));
+    }
+    let build = QuantizedBuildParams {
+        chunk_rows: args.chunk_rows,
+        exact_sample: args.exact_sample,
+        ..QuantizedBuildParams::default()
+    };
+    Ok(Box::new(Hnsw::build_quantized(
+        &file,
+        &hnsw_params(args, rotator),
+        &build,
+    )?))
+}
+
+/// Build an HNSW or IVF index without loading the data file, the element type picked by
+/// extension
+fn build_quantized(args: &BuildArgs) -> io::Result<Box<dyn AnnIndex>> {
+    if args.kind == IndexKind::Qg || (args.kind == IndexKind::Ivf && args.metric != MetricType::L2)
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--quantized needs --kind hnsw, or ivf with the l2 metric",
+        ));
+    }
+    let ext = args.data.extension().and_then(|e| e.to_str()).unwrap_or("");
+    Ok(match ext {
+        "fvecs" | "fbin" => build_quantized_typed::<f32>(args)?,
+        "bvecs" | "u8bin" => build_quantized_typed::<u8>(args)?,
+        "i8bin" => build_quantized_typed::<i8>(args)?,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}: unknown vector file extension", args.data.display()),
+            ));
+        }
+    })
+}
+
+fn open(args: &OpenArgs) -> io::Result<Box<dyn AnnIndex>> {
+    match args.kind {
+        Some(kind) => index::open_as(&args.index, kind, args.metric),
+        None => index::open(&args.index),
+    }
+}
+
+/// Search all queries, returning the ids and the elapsed seconds
+fn run_queries(index: &dyn AnnIndex, args: &QueryArgs) -> io::Result<(Matrix<u32>, f64)> {
+    let queries = Vectors::read(&args.queries)?;
+    if queries.cols() != index.dim() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "queries have dimension {}, index has {}",
+                queries.cols(),
+                index.dim()
+            ),
+        ));
+    }
+    let params = args.params(index)?;
+    let start = Instant::now();
+    let ids = with_vectors!(&queries, m => {
+        index::search_batch(index, m.as_slice(), args.k, &params, args.threads)
+    });
+    let secs = start.elapsed().as_secs_f64();
+    Ok((Matrix::new(ids, queries.rows(), args.k), secs))
+}
+
+fn build(args: BuildArgs) -> io::Result<()> {
+    let mut start = Instant::now();
+    let index = if args.quantized {
+        build_quantized(&args)?
+    } else {
+        let data = Vectors::read(&args.data)?;
+        println!(
+            "loaded {} vectors of dimension {}",
+            data.rows(),
+            data.cols()
+        );
+        start = Instant::now();
+        with_vectors!(&data, m => build_typed(m, &args))?
+    };
+    println!(
+        "built {} index in {:.2}s",
+        args.kind,
+        start.elapsed().as_secs_f64()
+    );
+    index.save(&args.output)?;
+    println!("saved to {}", args.output.display());
+    Ok(())
+}
+
+fn search(args: SearchArgs) -> io::Result<()> {
+    let index = open(&args.open)?;
+    let (ids, secs) = run_queries(index.as_ref(), &args.query)?;
+    let ids = Matrix::new(
+        ids.as_slice().iter().map(|&id| id as i32).collect(),
+        ids.rows(),
+        ids.cols(),
+    );
+    write_vecs(&args.output, &ids)?;
+    println!(
+        "searched {} queries in {secs:.3}s, results written to {}",
+        ids.rows(),
+        args.output.display()
+    );
+    Ok(())
+}
+
+fn eval(args: EvalArgs) -> io::Result<()> {
+    let index = open(&args.open)?;
+    let queries = Vectors::read(&args.query.queries)?;
+    let gt = read_vecs::<u32>(&args.groundtruth)?;
+    let base_params = args.query.params(index.as_ref())?;
+    let single = match index.kind() {
+        IndexKind::Ivf => base_params.nprobe,
+        IndexKind::Hnsw | IndexKind::Qg => base_params.ef,
+    };
+    let config = EvalConfig {
+        k: args.query.k,
+        num_threads: args.query.threads,
+        rounds: args.rounds,
+        stop_recall: match &args.sweep {
+            Some(values) if values.is_empty() => EvalConfig::default().stop_recall,
+            _ => None,
+        },
+        values: args.sweep.clone().unwrap_or_else(|| vec![single]),
+        base_params,
+    };
+    let report = with_vectors!(&queries, m => eval::evaluate(index.as_ref(), m, &gt, &config))?;
+
+    println!(
+        "{:>8} {:>10} {:>10} {:>10} {:>10} {:>10}",
+        report.param,
+        format!("recall@{}", report.k),
+        "qps",
+        "p50_us",
+        "p99_us",
+        "max_us"
+    );
+    for p in &report.points {
+        println!(
+            "{:>8} {:>10.4} {:>10.1} {:>10.1} {:>10.1} {:>10.1}",
+            p.value, p.recall, p.qps, p.p50_us, p.p99_us, p.max_us
+        );
+    }
+    if let Some(path) = &args.report {
+        report.save(path)?;
+        println!("report written to {}", path.display());
+    }
+    Ok(())
+}
+
+fn info(args: InfoArgs) -> io::Result<()> {
+    let path = &args.open.index;
+    let size = fs::metadata(path)?.len();
+    let meta = IndexMeta::load(path)?;
+    let kind = match (&meta, args.open.kind) {
+        (_, Some(kind)) => kind,
+        (Some(meta), None) => meta.kind,
+        (None, None) => {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} has no metadata file, pass --kind", path.display()),
+            ));
+        }
+    };
+
+    println!("file: {} ({size} bytes)", path.display());
+    if let Some(meta) = &meta {
+        println!("\n[meta] {}", IndexMeta::path_for(path).display());
+        println!("kind = {}", meta.kind);
+        println!("metric = {}", meta.metric);
+        println!("num = {}", meta.num);
+        println!("dim = {}", meta.dim);
+        println!("total_bits = {}", meta.total_bits);
+        for (key, value) in &meta.params {
+            println!("{key} = {value}");
+        }
+    }
+    println!("\n[header] {kind}");
+    for (name, value) in index::read_header(path, kind)? {
+        println!("{name} = {value}");
+    }
+    if args.stats {
+        let index = open(&args.open)?;
+        print!("\n[stats] {}", index.stats());
+    }
+    Ok(())
+}
+
+fn groundtruth(args: GroundtruthArgs) -> io::Result<()> {
+    let queries = Vectors::read(&args.queries)?;
+    let dim = queries.cols();
+    let ext = args.base.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let start = Instant::now();
+    let gt = with_vectors!(&queries, q => {
+        let q = q.as_slice();
+        match ext {
+            "fvecs" | "fbin" => groundtruth::compute_file::<f32, _>(&args.base, q, dim, args.k, args.metric),
+            "bvecs" | "u8bin" => groundtruth::compute_file::<u8, _>(&args.base, q, dim, args.k, args.metric),
+            "i8bin" => groundtruth::compute_file::<i8, _>(&args.base, q, dim, args.k, args.metric),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}: unknown vector file extension", args.base.display()),
+            )),
+        }
+    })?;
+    gt.save(&args.output, args.distances.as_deref())?;
+    println!(
+        "top-{} of {} queries computed in {:.2}s, written to {}",
+        args.k,
+        queries.rows(),
+        start.elapsed().as_secs_f64(),
+        args.output.display()
+    );
+    Ok(())
+}
+
+fn tune(args: TuneArgs) -> io::Result<()> {
+    let mut index = open(&args.open)?;
+    let base = Vectors::read(&args.base)?;
+    let queries = Vectors::read(&args.queries)?;
+    let config = TuneConfig {
+        target_recall: args.target,
+        k: args.k,
+        num_queries: args.sample,
+        num_threads: args.threads,
+        ..TuneConfig::default()
+    };
+    let result = with_vectors!(&base, b => with_vectors!(&queries, q => {
+        tune::tune(index.as_ref(), b.as_slice(), q.as_slice(), &config)
+    }))?;
+    if !result.reached {
+        eprintln!(
+            "warning: recall@{} only reaches {:.4} at the largest {}",
+            args.k, result.recall, result.param
+        );
+    }
+    result.apply(index.as_mut(), &config);
+    index.meta().save(&args.open.index)?;
+    println!(
+        "{}={} recall@{}={:.4}, stored in {}",
+        result.param,
+        result.value,
+        args.k,
+        result.recall,
+        IndexMeta::path_for(&args.open.index).display()
+    );
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let result = match Cli::parse().command {
+        Command::Build(args) => build(args),
+        Command::Search(args) => search(args),
+        Command::Eval(args) => eval(args),
+        Command::Info(args) => info(args),
+        Command::Groundtruth(args) => groundtruth(args),
+        Command::Tune(args) => tune(args),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//! Element types accepted for raw vectors.
+//!
+//! The C++ kernels only understand `f32`. Queries, insertions and the k-means sample are
+//! widened one vector at a time right before they are handed over, and IVF builds widen one
+//! chunk at a time, so these never need a full `f32` copy of a dataset stored as fp16 or
+//! int8. HNSW, QG and multi-vector builds hand the whole dataset to C++ at once and widen
+//! non-`f32` input into a full `f32` copy first.
+
+use std::borrow::Cow;
+
+pub use half::{bf16, f16};
+
+/// A scalar type a raw vector can be made of.
+pub trait VectorElement: Copy + Send + Sync + 'static {
+    /// Widen a single value to `f32`.
+    fn to_f32(self) -> f32;
+
+    /// Widen a whole vector. `f32` input is borrowed as is.
+    fn slice_to_f32(x: &[Self]) -> Cow<'_, [f32]> {
+        Cow::Owned(x.iter().map(|v| v.to_f32()).collect())
+    }
+
+    /// The vector itself if it is already `f32`, `None` if it has to be widened.
+    fn as_f32(_x: &[Self]) -> Option<&[f32]> {
+        None
+    }
+}
+
+impl VectorElement for f32 {
+    #[inline]
+    fn to_f32(self) -> f32 {
+        self
+    }
+
+    #[inline]
+    fn slice_to_f32(x: &[Self]) -> Cow<'_, [f32]> {
+        Cow::Borrowed(x)
+    }
+
+    #[inline]
+    fn as_f32(x: &[Self]) -> Option<&[f32]> {
+        Some(x)
+    }
+}
+
+impl VectorElement for f16 {
+    #[inline]
+    fn to_f32(self) -> f32 {
+        f16::to_f32(self)
+    }
+}
+
+impl VectorElement for bf16 {
+    #[inline]
+    fn to_f32(self) -> f32 {
+        bf16::to_f32(self)
+    }
+}
+
+impl VectorElement for i8 {
+    #[inline]
+    fn to_f32(self) -> f32 {
+        self as f32
+    }
+}
+
+impl VectorElement for u8 {
+    #[inline]
+    fn to_f32(self) -> f32 {
+        self as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_f32_is_borrowed() {
+        let x = [1.0f32, -2.5, 3.0];
+        assert!(matches!(f32::slice_to_f32(&x), Cow::Borrowed(_)));
+        assert_eq!(f32::as_f32(&x), Some(&x[..]));
+        assert_eq!(f16::as_f32(&[f16::ONE]), None);
+    }
+
+    #[test]
+    fn test_widening() {
+        let h: Vec<f16> = [0.5f32, -1.0, 1024.0].iter().map(|&v| f16::from_f32(v)).collect();
+        assert_eq!(&*f16::slice_to_f32(&h), &[0.5, -1.0, 1024.0]);
+
+        let b: Vec<bf16> = [0.25f32, -3.0].iter().map(|&v| bf16::from_f32(v)).collect();
+        assert_eq!(&*bf16::slice_to_f32(&b), &[0.25, -3.0]);
+
+        assert_eq!(&*i8::slice_to_f32(&[-128, 0, 127]), &[-128.0, 0.0, 127.0]);
+        assert_eq!(&*u8::slice_to_f32(&[0, 255]), &[0.0, 255.0]);
+    }
+}
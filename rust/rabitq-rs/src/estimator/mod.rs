@@ -1,34 +1,174 @@
+use std::fmt;
+use std::str::FromStr;
+
 use rabitq_sys::*;
 
 use crate::RabitqConfig;
+use crate::element::VectorElement;
+
+/// Confidence multiplier baked into `f_error` at quantization time (`kConstEpsilon` in C++).
+pub const DEFAULT_EPSILON0: f32 = 1.9;
+
+/// Precision of the query in distance estimation.
+///
+/// Single-vector estimators quantize the query to 1 to 16 bits (4 by default), FastScan
+/// quantizes its lookup tables to 8 or 16 bits. `Exact` keeps the query in float, which
+/// removes the query-side error at the cost of slower scans.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QueryPrecision {
+    Bits(usize),
+    Exact,
+}
+
+impl QueryPrecision {
+    /// Default of [`SingleEstimator`] and [`SingleCentroidEstimator`]
+    pub const SINGLE_DEFAULT: Self = Self::Bits(4);
+    /// Default of [`BatchBinEstimator`] and [`SplitBatchEstimator`] without `use_hacc`
+    pub const BATCH_DEFAULT: Self = Self::Bits(8);
+
+    /// True if usable by the single-vector estimators
+    pub fn valid_single(self) -> bool {
+        match self {
+            Self::Bits(bits) => (1..=16).contains(&bits),
+            Self::Exact => true,
+        }
+    }
+
+    /// True if usable by the FastScan estimators
+    pub fn valid_batch(self) -> bool {
+        matches!(self, Self::Bits(8 | 16) | Self::Exact)
+    }
+
+    /// Bits passed to C++, where 0 means exact
+    pub(crate) fn code(self) -> usize {
+        match self {
+            Self::Bits(bits) => bits,
+            Self::Exact => 0,
+        }
+    }
+}
+
+impl fmt::Display for QueryPrecision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bits(bits) => write!(f, "{bits}"),
+            Self::Exact => write!(f, "exact"),
+        }
+    }
+}
+
+impl FromStr for QueryPrecision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("exact") {
+            return Ok(Self::Exact);
+        }
+        match s.parse::<usize>() {
+            Ok(bits) if (1..=16).contains(&bits) => Ok(Self::Bits(bits)),
+            _ => Err(format!(
+                "invalid query precision `{s}`, expected 1 to 16 bits or exact"
+            )),
+        }
+    }
+}
+
+/// A distance estimate with its error bound at a given confidence multiplier `epsilon0`.
+///
+/// RaBitQ guarantees the true distance lies in `[lower, upper]` with a failure probability
+/// that decays exponentially in `epsilon0`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DistanceBound {
+    pub estimate: f32,
+    pub lower: f32,
+    pub upper: f32,
+}
+
+impl DistanceBound {
+    /// Rescale the bound reported by the C++ kernels (always at [`DEFAULT_EPSILON0`]).
+    fn from_low(estimate: f32, low: f32, epsilon0: f32) -> Self {
+        let half_width = (estimate - low) * (epsilon0 / DEFAULT_EPSILON0);
+        Self {
+            estimate,
+            lower: estimate - half_width,
+            upper: estimate + half_width,
+        }
+    }
+
+    /// True if `self` is closer than `other` for every distance inside both bounds.
+    pub fn certainly_less(&self, other: &DistanceBound) -> bool {
+        self.upper < other.lower
+    }
+}
 
 pub fn select_excode_ipfunc(ex_bits: usize) -> ex_ipfunc {
     unsafe { rabitq_select_excode_ipfunc(ex_bits) }
 }
 
+/// Number of vectors in one FastScan batch
+pub const K_BATCH_SIZE: usize = 32;
+
+type IpFunc = Option<unsafe extern "C" fn(*const f32, *const u8, usize) -> f32>;
+
+/// Query object for FastScan batches. The C++ side keeps a pointer to the rotated query,
+/// so the estimator owns its own copy of it.
 pub struct SplitBatchEstimator {
     ptr: *mut SplitBatchQuery,
+    rotated_query: Box<[f32]>,
     padded_dim: usize,
+    precision: QueryPrecision,
 }
 
 impl SplitBatchEstimator {
-    pub fn new(
-        rotated_query: &[f32],
+    pub fn new<T: VectorElement>(
+        rotated_query: &[T],
         padded_dim: usize,
         ex_bits: usize,
         metric_type: MetricType,
         use_hacc: bool,
     ) -> Self {
+        let precision = QueryPrecision::Bits(if use_hacc { 16 } else { 8 });
+        Self::with_precision(rotated_query, padded_dim, ex_bits, metric_type, precision)
+    }
+
+    /// Lookup tables at `precision`, 8 or 16 bits or exact. [`Self::estdist`] ignores its
+    /// `use_hacc` argument for exact tables.
+    pub fn with_precision<T: VectorElement>(
+        rotated_query: &[T],
+        padded_dim: usize,
+        ex_bits: usize,
+        metric_type: MetricType,
+        precision: QueryPrecision,
+    ) -> Self {
+        assert!(
+            precision.valid_batch(),
+            "FastScan tables must have 8 or 16 bits or be exact, got {precision}"
+        );
+        let rotated_query: Box<[f32]> = T::slice_to_f32(rotated_query).into();
+        assert_eq!(rotated_query.len(), padded_dim);
         let ptr = unsafe {
             rabitq_split_batch_query_new(
                 rotated_query.as_ptr(),
                 padded_dim,
                 ex_bits,
                 metric_type,
-                use_hacc,
+                precision.code(),
             )
         };
-        Self { ptr, padded_dim }
+        Self {
+            ptr,
+            rotated_query,
+            padded_dim,
+            precision,
+        }
+    }
+
+    pub fn precision(&self) -> QueryPrecision {
+        self.precision
+    }
+
+    pub fn rotated_query(&self) -> &[f32] {
+        &self.rotated_query
     }
 
     pub fn set_g_add(&mut self, norm: f32, ip: f32) {
@@ -37,18 +177,25 @@ impl SplitBatchEstimator {
         }
     }
 
-    pub fn estdist(&self, batch_data: &[u8], use_hacc: bool) -> (f32, f32, f32) {
-        let mut est_distance = 0.0;
-        let mut low_distance = 0.0;
-        let mut ip_x0_qr = 0.0;
+    /// Estimate the distances of one batch of [`K_BATCH_SIZE`] vectors, returning
+    /// `(est_distance, low_distance, ip_x0_qr)` for every vector of the batch.
+    #[allow(clippy::type_complexity)]
+    pub fn estdist(
+        &self,
+        batch_data: &[u8],
+        use_hacc: bool,
+    ) -> ([f32; K_BATCH_SIZE], [f32; K_BATCH_SIZE], [f32; K_BATCH_SIZE]) {
+        let mut est_distance = [0.0; K_BATCH_SIZE];
+        let mut low_distance = [0.0; K_BATCH_SIZE];
+        let mut ip_x0_qr = [0.0; K_BATCH_SIZE];
         unsafe {
             rabitq_split_batch_estdist(
-                batch_data.as_ptr() as *const i8,
+                batch_data.as_ptr() as *const _,
                 self.ptr,
                 self.padded_dim,
-                &mut est_distance,
-                &mut low_distance,
-                &mut ip_x0_qr,
+                est_distance.as_mut_ptr(),
+                low_distance.as_mut_ptr(),
+                ip_x0_qr.as_mut_ptr(),
                 use_hacc,
             );
         }
@@ -58,13 +205,13 @@ impl SplitBatchEstimator {
     pub fn distance_boosting(
         &self,
         ex_data: &[u8],
-        ip_func: Option<unsafe extern "C" fn(*const f32, *const u8, usize) -> f32>,
+        ip_func: IpFunc,
         ex_bits: usize,
         ip_x0_qr: f32,
     ) -> f32 {
         unsafe {
             rabitq_split_distance_boosting_with_batch_query(
-                ex_data.as_ptr() as *const i8,
+                ex_data.as_ptr() as *const _,
                 ip_func,
                 self.ptr,
                 self.padded_dim,
@@ -75,6 +222,19 @@ impl SplitBatchEstimator {
     }
 }
 
+impl Clone for SplitBatchEstimator {
+    fn clone(&self) -> Self {
+        let rotated_query = self.rotated_query.clone();
+        let ptr = unsafe { rabitq_split_batch_query_clone(self.ptr, rotated_query.as_ptr()) };
+        Self {
+            ptr,
+            rotated_query,
+            padded_dim: self.padded_dim,
+            precision: self.precision,
+        }
+    }
+}
+
 impl Drop for SplitBatchEstimator {
     fn drop(&mut self) {
         unsafe {
@@ -83,16 +243,47 @@ impl Drop for SplitBatchEstimator {
     }
 }
 
+// The C++ query is only read through `&self`, and owns (or points into memory owned by)
+// this value.
+unsafe impl Send for SplitBatchEstimator {}
+unsafe impl Sync for SplitBatchEstimator {}
+
 pub struct BatchBinEstimator {
     ptr: *mut BatchQuery,
     padded_dim: usize,
+    precision: QueryPrecision,
 }
 
 impl BatchBinEstimator {
-    pub const K_BATCH_SIZE: usize = 32;
-    pub fn new(rotated_query: &[f32], padded_dim: usize) -> Self {
-        let ptr = unsafe { rabitq_batch_query_new(rotated_query.as_ptr(), padded_dim) };
-        Self { ptr, padded_dim }
+    pub const K_BATCH_SIZE: usize = K_BATCH_SIZE;
+    pub fn new<T: VectorElement>(rotated_query: &[T], padded_dim: usize) -> Self {
+        Self::with_precision(rotated_query, padded_dim, QueryPrecision::BATCH_DEFAULT)
+    }
+
+    /// Lookup table at `precision`, 8 or 16 bits or exact
+    pub fn with_precision<T: VectorElement>(
+        rotated_query: &[T],
+        padded_dim: usize,
+        precision: QueryPrecision,
+    ) -> Self {
+        assert!(
+            precision.valid_batch(),
+            "FastScan tables must have 8 or 16 bits or be exact, got {precision}"
+        );
+        let rotated_query = T::slice_to_f32(rotated_query);
+        assert_eq!(rotated_query.len(), padded_dim);
+        // BatchQuery only reads the query while building its LUT, no copy needs to be kept
+        let ptr =
+            unsafe { rabitq_batch_query_new(rotated_query.as_ptr(), padded_dim, precision.code()) };
+        Self {
+            ptr,
+            padded_dim,
+            precision,
+        }
+    }
+
+    pub fn precision(&self) -> QueryPrecision {
+        self.precision
     }
 
     pub fn delta(&self) -> f32 {
@@ -115,9 +306,14 @@ impl BatchBinEstimator {
         unsafe { rabitq_batch_query_set_g_add(self.ptr, sqr_norm) }
     }
 
+    /// Quantized lookup table, empty for exact and 16-bit tables
     pub fn lut(&self) -> &[u8] {
+        if self.precision != QueryPrecision::Bits(8) {
+            return &[];
+        }
         let data = unsafe { rabitq_batch_query_lut(self.ptr) };
-        let len = (1 << 16) * std::mem::size_of::<f32>();
+        // one 16-entry u8 table per 4 dimensions
+        let len = self.padded_dim << 2;
         unsafe { std::slice::from_raw_parts(data, len) }
     }
 
@@ -130,7 +326,7 @@ impl BatchBinEstimator {
         );
         unsafe {
             rabitq_qg_batch_estdist(
-                batch_data.as_ptr() as *const i8,
+                batch_data.as_ptr() as *const _,
                 self.ptr,
                 self.padded_dim,
                 est_distance.as_mut_ptr(),
@@ -139,6 +335,17 @@ impl BatchBinEstimator {
     }
 }
 
+impl Clone for BatchBinEstimator {
+    fn clone(&self) -> Self {
+        let ptr = unsafe { rabitq_batch_query_clone(self.ptr) };
+        Self {
+            ptr,
+            padded_dim: self.padded_dim,
+            precision: self.precision,
+        }
+    }
+}
+
 impl Drop for BatchBinEstimator {
     fn drop(&mut self) {
         unsafe {
@@ -147,21 +354,53 @@ impl Drop for BatchBinEstimator {
     }
 }
 
+unsafe impl Send for BatchBinEstimator {}
+unsafe impl Sync for BatchBinEstimator {}
+
 pub struct SingleEstimator {
     ptr: *mut SplitSingleQuery,
+    rotated_query: Box<[f32]>,
     padded_dim: usize,
     ex_bits: usize,
-    ip_func: Option<unsafe extern "C" fn(*const f32, *const u8, usize) -> f32>,
+    ip_func: IpFunc,
+    epsilon0: f32,
+    precision: QueryPrecision,
 }
 
 impl SingleEstimator {
-    pub fn new(
-        rotated_query: &[f32],
+    pub fn new<T: VectorElement>(
+        rotated_query: &[T],
         padded_dim: usize,
         ex_bits: usize,
         config: &RabitqConfig,
         metric_type: MetricType,
     ) -> Self {
+        Self::with_precision(
+            rotated_query,
+            padded_dim,
+            ex_bits,
+            config,
+            metric_type,
+            QueryPrecision::SINGLE_DEFAULT,
+        )
+    }
+
+    /// Query quantized to `precision`, 1 to 16 bits or exact. `config` is used to quantize
+    /// the query.
+    pub fn with_precision<T: VectorElement>(
+        rotated_query: &[T],
+        padded_dim: usize,
+        ex_bits: usize,
+        config: &RabitqConfig,
+        metric_type: MetricType,
+        precision: QueryPrecision,
+    ) -> Self {
+        assert!(
+            precision.valid_single(),
+            "query must have 1 to 16 bits or be exact, got {precision}"
+        );
+        let rotated_query: Box<[f32]> = T::slice_to_f32(rotated_query).into();
+        assert_eq!(rotated_query.len(), padded_dim);
         let ptr = unsafe {
             rabitq_split_single_query_new(
                 rotated_query.as_ptr(),
@@ -169,19 +408,31 @@ impl SingleEstimator {
                 ex_bits,
                 config.ptr as *const _,
                 metric_type,
+                precision.code(),
             )
         };
         Self {
             ptr,
+            rotated_query,
             padded_dim,
             ex_bits,
             ip_func: select_excode_ipfunc(ex_bits),
+            epsilon0: DEFAULT_EPSILON0,
+            precision,
         }
     }
 
+    pub fn precision(&self) -> QueryPrecision {
+        self.precision
+    }
+
+    /// Bit planes of the quantized query, empty for exact queries
     pub fn query_bin(&self) -> &[u64] {
         let data = unsafe { rabitq_split_single_query_query_bin(self.ptr) };
-        let len = self.padded_dim / 8; // a u64 is 8 bytes
+        let len = self.padded_dim * self.precision.code() / 64;
+        if len == 0 {
+            return &[];
+        }
         unsafe { std::slice::from_raw_parts(data, len) }
     }
 
@@ -193,7 +444,11 @@ impl SingleEstimator {
         unsafe { rabitq_split_single_query_vl(self.ptr) }
     }
 
-    pub fn set_g_add(&self, norm: f32, ip: f32) {
+    pub fn rotated_query(&self) -> &[f32] {
+        &self.rotated_query
+    }
+
+    pub fn set_g_add(&mut self, norm: f32, ip: f32) {
         unsafe { rabitq_split_single_query_set_g_add(self.ptr, norm, ip) };
     }
 
@@ -203,7 +458,7 @@ impl SingleEstimator {
         let mut low_dist: f32 = 0.0;
         unsafe {
             rabitq_split_single_estdist(
-                bin_data.as_ptr() as *const i8,
+                bin_data.as_ptr() as *const _,
                 self.ptr,
                 self.padded_dim,
                 &mut ip_x0_qr,
@@ -228,8 +483,8 @@ impl SingleEstimator {
         let mut low_dist: f32 = 0.0;
         unsafe {
             rabitq_split_single_fulldist(
-                bin_data.as_ptr() as *const i8,
-                ex_data.as_ptr() as *const i8,
+                bin_data.as_ptr() as *const _,
+                ex_data.as_ptr() as *const _,
                 self.ip_func,
                 self.ptr,
                 self.padded_dim,
@@ -247,7 +502,7 @@ impl SingleEstimator {
     pub fn distance_boosting(&self, ex_data: &[u8], ip_x0_qr: f32) -> f32 {
         unsafe {
             rabitq_split_distance_boosting_with_single_query(
-                ex_data.as_ptr() as *const i8,
+                ex_data.as_ptr() as *const _,
                 self.ip_func,
                 self.ptr,
                 self.padded_dim,
@@ -257,25 +512,110 @@ impl SingleEstimator {
         }
     }
 
-    
+    /// Confidence multiplier used by the `*_bound` methods
+    pub fn epsilon0(&self) -> f32 {
+        self.epsilon0
+    }
+
+    /// Set the confidence multiplier used by the `*_bound` methods
+    pub fn set_epsilon0(&mut self, epsilon0: f32) {
+        assert!(epsilon0 >= 0.0, "epsilon0 must be non-negative");
+        self.epsilon0 = epsilon0;
+    }
+
+    /// 1-bit estimate with its bound at `epsilon0`
+    pub fn est_dist_bound(&self, bin_data: &[u8], g_add: f32, g_error: f32) -> DistanceBound {
+        let (est_dist, low_dist, _) = self.est_dist(bin_data, g_add, g_error);
+        DistanceBound::from_low(est_dist, low_dist, self.epsilon0)
+    }
+
+    /// Full-bit estimate with its bound at `epsilon0`
+    pub fn full_dist_bound(
+        &self,
+        bin_data: &[u8],
+        ex_data: &[u8],
+        g_add: f32,
+        g_error: f32,
+    ) -> DistanceBound {
+        let (est_dist, low_dist, _) = self.full_dist(bin_data, ex_data, g_add, g_error);
+        DistanceBound::from_low(est_dist, low_dist, self.epsilon0)
+    }
+}
+
+impl Clone for SingleEstimator {
+    fn clone(&self) -> Self {
+        let rotated_query = self.rotated_query.clone();
+        let ptr = unsafe { rabitq_split_single_query_clone(self.ptr, rotated_query.as_ptr()) };
+        Self {
+            ptr,
+            rotated_query,
+            padded_dim: self.padded_dim,
+            ex_bits: self.ex_bits,
+            ip_func: self.ip_func,
+            epsilon0: self.epsilon0,
+            precision: self.precision,
+        }
+    }
+}
+
+impl Drop for SingleEstimator {
+    fn drop(&mut self) {
+        unsafe {
+            rabitq_split_single_query_free(self.ptr);
+        }
+    }
 }
 
+unsafe impl Send for SingleEstimator {}
+unsafe impl Sync for SingleEstimator {}
+
 pub struct SingleCentroidEstimator {
     ptr: *mut SingleCentroidQuery,
+    rotated_query: Box<[f32]>,
     padded_dim: usize,
     ex_bits: usize,
-    ip_func: Option<unsafe extern "C" fn(*const f32, *const u8, usize) -> f32>,
+    ip_func: IpFunc,
+    epsilon0: f32,
+    precision: QueryPrecision,
 }
 
 impl SingleCentroidEstimator {
-    pub fn new(
-        rotated_query: &[f32],
+    pub fn new<T: VectorElement>(
+        rotated_query: &[T],
+        centroid: &[f32],
+        padded_dim: usize,
+        ex_bits: usize,
+        config: &RabitqConfig,
+        metric_type: MetricType,
+    ) -> Self {
+        Self::with_precision(
+            rotated_query,
+            centroid,
+            padded_dim,
+            ex_bits,
+            config,
+            metric_type,
+            QueryPrecision::SINGLE_DEFAULT,
+        )
+    }
+
+    /// Residual query quantized to `precision`, 1 to 16 bits or exact
+    pub fn with_precision<T: VectorElement>(
+        rotated_query: &[T],
         centroid: &[f32],
         padded_dim: usize,
         ex_bits: usize,
         config: &RabitqConfig,
         metric_type: MetricType,
+        precision: QueryPrecision,
     ) -> Self {
+        assert!(
+            precision.valid_single(),
+            "query must have 1 to 16 bits or be exact, got {precision}"
+        );
+        let rotated_query: Box<[f32]> = T::slice_to_f32(rotated_query).into();
+        assert_eq!(rotated_query.len(), padded_dim);
+        assert_eq!(centroid.len(), padded_dim);
         let ptr = unsafe {
             rabitq_single_centroid_query_new(
                 rotated_query.as_ptr(),
@@ -284,27 +624,38 @@ impl SingleCentroidEstimator {
                 ex_bits,
                 config.ptr,
                 metric_type,
+                precision.code(),
             )
         };
-        
+
         Self {
             ptr,
+            rotated_query,
             padded_dim,
             ex_bits,
             ip_func: select_excode_ipfunc(ex_bits),
+            epsilon0: DEFAULT_EPSILON0,
+            precision,
         }
     }
 
+    pub fn precision(&self) -> QueryPrecision {
+        self.precision
+    }
+
+    /// Bit planes of the quantized residual query, empty for exact queries
     pub fn query_bin(&self) -> &[u64] {
         let data = unsafe { rabitq_single_centroid_query_query_bin(self.ptr) };
-        // From C++: QueryBin_(padded_dim * kNumBits / 64, 0) where kNumBits = 4
-        let len = self.padded_dim * 4 / 64;
+        // From C++: QueryBin_(padded_dim * query_bits / 64, 0)
+        let len = self.padded_dim * self.precision.code() / 64;
+        if len == 0 {
+            return &[];
+        }
         unsafe { std::slice::from_raw_parts(data, len) }
     }
 
     pub fn rotated_query(&self) -> &[f32] {
-        let data = unsafe { rabitq_single_centroid_query_rotated_query(self.ptr) };
-        unsafe { std::slice::from_raw_parts(data, self.padded_dim) }
+        &self.rotated_query
     }
 
     pub fn delta(&self) -> f32 {
@@ -331,11 +682,11 @@ impl SingleCentroidEstimator {
         unsafe { rabitq_single_centroid_query_g_error(self.ptr) }
     }
 
-    pub fn set_g_add(&self, norm: f32, ip: f32) {
+    pub fn set_g_add(&mut self, norm: f32, ip: f32) {
         unsafe { rabitq_single_centroid_query_set_g_add(self.ptr, norm, ip) }
     }
 
-    pub fn set_g_error(&self, norm: f32) {
+    pub fn set_g_error(&mut self, norm: f32) {
         unsafe { rabitq_single_centroid_query_set_g_error(self.ptr, norm) }
     }
 
@@ -345,7 +696,7 @@ impl SingleCentroidEstimator {
         let mut low_dist = 0.0;
         unsafe {
             rabitq_single_centroid_estdist(
-                bin_data.as_ptr() as *const i8,
+                bin_data.as_ptr() as *const _,
                 self.ptr,
                 self.padded_dim,
                 &mut ip_x0_qr,
@@ -361,7 +712,7 @@ impl SingleCentroidEstimator {
     pub fn distance_boosting(&self, ex_data: &[u8], ip_x0_qr: f32) -> f32 {
         unsafe {
             rabitq_split_distance_boosting_with_centroid_query(
-                ex_data.as_ptr() as *const i8,
+                ex_data.as_ptr() as *const _,
                 self.ip_func,
                 self.ptr,
                 self.padded_dim,
@@ -383,9 +734,9 @@ impl SingleCentroidEstimator {
         let mut low_dist: f32 = 0.0;
         unsafe {
             rabitq_single_centroid_fulldist(
-                self.ptr as *mut rabitq_sys::SingleCentroidQuery,
-                bin_data.as_ptr() as *const i8,
-                ex_data.as_ptr() as *const i8,
+                self.ptr,
+                bin_data.as_ptr() as *const _,
+                ex_data.as_ptr() as *const _,
                 self.ip_func,
                 self.padded_dim,
                 self.ex_bits,
@@ -393,11 +744,57 @@ impl SingleCentroidEstimator {
                 &mut low_dist,
                 &mut ip_x0_qr,
                 g_add,
-                g_error
+                g_error,
             );
         }
         (est_dist, low_dist, ip_x0_qr)
     }
+
+    /// Confidence multiplier used by the `*_bound` methods
+    pub fn epsilon0(&self) -> f32 {
+        self.epsilon0
+    }
+
+    /// Set the confidence multiplier used by the `*_bound` methods
+    pub fn set_epsilon0(&mut self, epsilon0: f32) {
+        assert!(epsilon0 >= 0.0, "epsilon0 must be non-negative");
+        self.epsilon0 = epsilon0;
+    }
+
+    /// 1-bit estimate with its bound at `epsilon0`
+    pub fn est_dist_bound(&self, bin_data: &[u8], g_add: f32, g_error: f32) -> DistanceBound {
+        let (est_dist, low_dist, _) = self.est_dist(bin_data, g_add, g_error);
+        DistanceBound::from_low(est_dist, low_dist, self.epsilon0)
+    }
+
+    /// Full-bit estimate with its bound at `epsilon0`
+    pub fn full_dist_bound(
+        &self,
+        bin_data: &[u8],
+        ex_data: &[u8],
+        g_add: f32,
+        g_error: f32,
+    ) -> DistanceBound {
+        let (est_dist, low_dist, _) = self.full_dist(bin_data, ex_data, g_add, g_error);
+        DistanceBound::from_low(est_dist, low_dist, self.epsilon0)
+    }
+}
+
+impl Clone for SingleCentroidEstimator {
+    fn clone(&self) -> Self {
+        let rotated_query = self.rotated_query.clone();
+        let ptr =
+            unsafe { rabitq_single_centroid_query_clone(self.ptr, rotated_query.as_ptr()) };
+        Self {
+            ptr,
+            rotated_query,
+            padded_dim: self.padded_dim,
+            ex_bits: self.ex_bits,
+            ip_func: self.ip_func,
+            epsilon0: self.epsilon0,
+            precision: self.precision,
+        }
+    }
 }
 
 impl Drop for SingleCentroidEstimator {
@@ -407,3 +804,93 @@ impl Drop for SingleCentroidEstimator {
         }
     }
 }
+
+unsafe impl Send for SingleCentroidEstimator {}
+unsafe impl Sync for SingleCentroidEstimator {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MetricType;
+    use crate::quantizer::{quantize_full_single, quantize_split_single};
+
+    // The kernel picked for the build (AVX-512, NEON or scalar) reads the packed ex-codes, the
+    // reference takes the low bits of the unpacked total code
+    #[test]
+    fn test_excode_ipfunc_matches_unpacked() {
+        let dim = 192;
+        let data: Vec<f32> = (0..dim)
+            .map(|i| ((i * 37) % 101) as f32 / 25.0 - 2.0)
+            .collect();
+        let query: Vec<f32> = (0..dim)
+            .map(|i| ((i * 53) % 97) as f32 / 16.0 - 3.0)
+            .collect();
+        let centroid = vec![0.0f32; dim];
+        for ex_bits in 1..=8 {
+            let config = RabitqConfig::faster_config(dim, ex_bits + 1);
+            let (_, ex_data) =
+                quantize_split_single(&data, &centroid, ex_bits, MetricType::L2, &config);
+            let codes: Vec<u8> = if ex_bits == 8 {
+                // 8-bit codes are stored unpacked
+                ex_data[..dim].to_vec()
+            } else {
+                let (total_code, ..) =
+                    quantize_full_single(&data, ex_bits + 1, MetricType::L2, &config);
+                total_code
+                    .iter()
+                    .map(|c| c & ((1 << ex_bits) - 1))
+                    .collect()
+            };
+            let ip_func = select_excode_ipfunc(ex_bits).unwrap();
+            let ip = |q: &[f32]| unsafe { ip_func(q.as_ptr(), ex_data.as_ptr(), dim) };
+
+            // a unit query reads back one code
+            let mut unit = vec![0.0f32; dim];
+            for (i, &code) in codes.iter().enumerate() {
+                unit[i] = 1.0;
+                assert_eq!(ip(&unit), f32::from(code), "ex_bits {ex_bits}, dim {i}");
+                unit[i] = 0.0;
+            }
+            let expected: f32 = query
+                .iter()
+                .zip(&codes)
+                .map(|(q, &c)| q * f32::from(c))
+                .sum();
+            let got = ip(&query);
+            assert!(
+                (got - expected).abs() <= 1e-4 * expected.abs().max(1.0),
+                "ex_bits {ex_bits}: {got} vs {expected}"
+            );
+        }
+    }
+
+    // the distance kernels of the build (AVX-512, AVX2, NEON or scalar), every dim up to two
+    // full registers plus a tail
+    #[test]
+    fn test_distance_kernels_match_naive() {
+        for dim in 1..=40 {
+            let x: Vec<f32> = (0..dim)
+                .map(|i| ((i * 37) % 101) as f32 / 25.0 - 2.0)
+                .collect();
+            let y: Vec<f32> = (0..dim)
+                .map(|i| ((i * 53) % 97) as f32 / 16.0 - 3.0)
+                .collect();
+            let l2: f32 = x.iter().zip(&y).map(|(a, b)| (a - b) * (a - b)).sum();
+            let ip: f32 = x.iter().zip(&y).map(|(a, b)| a * b).sum();
+            let (got_l2, got_ip) = unsafe {
+                (
+                    rabitq_euclidean_sqr(x.as_ptr(), y.as_ptr(), dim),
+                    rabitq_dot_product(x.as_ptr(), y.as_ptr(), dim),
+                )
+            };
+            assert!(
+                (got_l2 - l2).abs() <= 1e-4 * l2.max(1.0),
+                "dim {dim}: {got_l2} vs {l2}"
+            );
+            assert!(
+                (got_ip - ip).abs() <= 1e-4 * l2.max(1.0),
+                "dim {dim}: {got_ip} vs {ip}"
+            );
+        }
+    }
+}
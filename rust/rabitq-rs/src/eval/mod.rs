@@ -0,0 +1,416 @@
+//! Recall / QPS evaluation with parameter sweeps.
+//!
+//! The Rust counterpart of the `get_nprobes` loop in `sample/ivf_rabitq_querying.cpp`: search
+//! all queries once per `nprobe` (IVF) or `ef` (HNSW, QG) value, compare against the ground
+//! truth and record recall@k, QPS and per-query latency percentiles.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Instant;
+
+use rayon::prelude::*;
+
+use crate::element::VectorElement;
+use crate::index::{self, AnnIndex, INVALID_ID, IndexKind, SearchParams};
+use crate::io::Matrix;
+
+/// Settings of an evaluation run
+#[derive(Clone, Debug)]
+pub struct EvalConfig {
+    /// Number of neighbours searched and compared, recall@k
+    pub k: usize,
+    /// Threads searching queries in parallel, 0 uses all cores
+    pub num_threads: usize,
+    /// Passes over the queries per value, QPS and latencies are taken over all of them
+    pub rounds: usize,
+    /// Values of `nprobe` (IVF) or `ef` (HNSW, QG), [`default_sweep`] if empty. They are
+    /// evaluated in ascending order, each once.
+    pub values: Vec<usize>,
+    /// Stop the sweep once recall exceeds this or stops improving, like the C++ sample
+    pub stop_recall: Option<f64>,
+    /// Parameters not being swept, e.g. `use_hacc`
+    pub base_params: SearchParams,
+}
+
+impl Default for EvalConfig {
+    fn default() -> Self {
+        Self {
+            k: 10,
+            num_threads: 0,
+            rounds: 1,
+            values: Vec::new(),
+            stop_recall: Some(0.997),
+            base_params: SearchParams::default(),
+        }
+    }
+}
+
+/// Measurements for one swept value
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvalPoint {
+    /// `nprobe` or `ef`
+    pub value: usize,
+    pub recall: f64,
+    pub qps: f64,
+    pub mean_us: f64,
+    pub p50_us: f64,
+    pub p90_us: f64,
+    pub p95_us: f64,
+    pub p99_us: f64,
+    pub max_us: f64,
+}
+
+/// Result of [`evaluate`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvalReport {
+    pub kind: IndexKind,
+    /// `"nprobe"` or `"ef"`
+    pub param: &'static str,
+    pub k: usize,
+    pub num_queries: usize,
+    pub num_threads: usize,
+    pub points: Vec<EvalPoint>,
+}
+
+const CSV_HEADER: &str =
+    "kind,param,value,k,num_queries,threads,recall,qps,mean_us,p50_us,p90_us,p95_us,p99_us,max_us";
+
+impl EvalReport {
+    pub fn to_csv(&self) -> String {
+        let mut out = format!("{CSV_HEADER}\n");
+        for p in &self.points {
+            let _ = writeln!(
+                out,
+                "{},{},{},{},{},{},{:.6},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2},{:.2}",
+                self.kind,
+                self.param,
+                p.value,
+                self.k,
+                self.num_queries,
+                self.num_threads,
+                p.recall,
+                p.qps,
+                p.mean_us,
+                p.p50_us,
+                p.p90_us,
+                p.p95_us,
+                p.p99_us,
+                p.max_us
+            );
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let points: Vec<String> = self
+            .points
+            .iter()
+            .map(|p| {
+                format!(
+                    "{{\"{}\": {}, \"recall\": {:.6}, \"qps\": {:.2}, \"latency_us\": \
+                     {{\"mean\": {:.2}, \"p50\": {:.2}, \"p90\": {:.2}, \"p95\": {:.2}, \
+                     \"p99\": {:.2}, \"max\": {:.2}}}}}",
+                    self.param,
+                    p.value,
+                    p.recall,
+                    p.qps,
+                    p.mean_us,
+                    p.p50_us,
+                    p.p90_us,
+                    p.p95_us,
+                    p.p99_us,
+                    p.max_us
+                )
+            })
+            .collect();
+        format!(
+            "{{\n  \"kind\": \"{}\",\n  \"param\": \"{}\",\n  \"k\": {},\n  \"num_queries\": {},\n  \
+             \"threads\": {},\n  \"points\": [\n    {}\n  ]\n}}\n",
+            self.kind,
+            self.param,
+            self.k,
+            self.num_queries,
+            self.num_threads,
+            points.join(",\n    ")
+        )
+    }
+
+    /// Write the report as CSV if `path` ends in `.csv`, as JSON otherwise.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let is_csv = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("csv"));
+        fs::write(
+            path,
+            if is_csv {
+                self.to_csv()
+            } else {
+                self.to_json()
+            },
+        )
+    }
+}
+
+/// Values tried when none are given: the `nprobe` list of the C++ IVF sample, or a
+/// similar progression of `ef` for the graph indexes.
+pub fn default_sweep(kind: IndexKind) -> Vec<usize> {
+    match kind {
+        IndexKind::Ivf => std::iter::once(5)
+            .chain((10..200).step_by(10))
+            .chain((200..400).step_by(40))
+            .chain((400..=1500).step_by(100))
+            .chain((2000..=4000).step_by(500))
+            .chain([6000, 10000, 15000])
+            .collect(),
+        IndexKind::Hnsw | IndexKind::Qg => (10..100)
+            .step_by(10)
+            .chain((100..400).step_by(50))
+            .chain((400..=1600).step_by(200))
+            .chain([2000, 3000])
+            .collect(),
+    }
+}
+
+/// Fraction of the first `k` ground truth ids of every query found in `results` (`k` ids per
+/// query). `groundtruth` needs at least `k` columns.
+pub fn recall_at_k(results: &[u32], groundtruth: &Matrix<u32>, k: usize) -> f64 {
+    assert!(
+        groundtruth.cols() >= k,
+        "ground truth has fewer than k columns"
+    );
+    let nq = results.len() / k.max(1);
+    assert!(
+        groundtruth.rows() >= nq,
+        "ground truth has fewer rows than queries"
+    );
+    if nq == 0 || k == 0 {
+        return 0.0;
+    }
+    let hits: usize = results
+        .chunks_exact(k)
+        .zip(groundtruth.iter_rows())
+        .map(|(found, truth)| {
+            let truth = &truth[..k];
+            found
+                .iter()
+                .filter(|&&id| id != INVALID_ID && truth.contains(&id))
+                .count()
+        })
+        .sum();
+    hits as f64 / (nq * k) as f64
+}
+
+/// Nearest-rank percentile of sorted values
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Search all queries with `params` and measure one point
+fn measure<T: VectorElement>(
+    index: &dyn AnnIndex,
+    queries: &Matrix<T>,
+    groundtruth: &Matrix<u32>,
+    params: &SearchParams,
+    value: usize,
+    config: &EvalConfig,
+) -> EvalPoint {
+    let k = config.k;
+    let nq = queries.rows();
+    let mut latencies = Vec::with_capacity(nq * config.rounds.max(1));
+    let mut results = Vec::new();
+    let mut total_secs = 0.0;
+
+    for _ in 0..config.rounds.max(1) {
+        let start = Instant::now();
+        let round: Vec<(Vec<u32>, f64)> = index::with_threads(config.num_threads, || {
+            queries
+                .as_slice()
+                .par_chunks_exact(queries.cols().max(1))
+                .map(|query| {
+                    let query = T::slice_to_f32(query);
+                    let t = Instant::now();
+                    let ids = index.search(&query, k, params);
+                    (ids, t.elapsed().as_secs_f64() * 1e6)
+                })
+                .collect()
+        });
+        total_secs += start.elapsed().as_secs_f64();
+        results.clear();
+        for (ids, us) in round {
+            results.extend_from_slice(&ids);
+            latencies.push(us);
+        }
+    }
+
+    latencies.sort_by(f64::total_cmp);
+    let mean_us = latencies.iter().sum::<f64>() / latencies.len().max(1) as f64;
+    EvalPoint {
+        value,
+        recall: recall_at_k(&results, groundtruth, k),
+        qps: latencies.len() as f64 / total_secs.max(f64::MIN_POSITIVE),
+        mean_us,
+        p50_us: percentile(&latencies, 50.0),
+        p90_us: percentile(&latencies, 90.0),
+        p95_us: percentile(&latencies, 95.0),
+        p99_us: percentile(&latencies, 99.0),
+        max_us: latencies.last().copied().unwrap_or(0.0),
+    }
+}
+
+/// Sweep `nprobe` (IVF) or `ef` (HNSW, QG) over `config.values` and measure every point.
+pub fn evaluate<T: VectorElement>(
+    index: &dyn AnnIndex,
+    queries: &Matrix<T>,
+    groundtruth: &Matrix<u32>,
+    config: &EvalConfig,
+) -> io::Result<EvalReport> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidInput, msg);
+    if queries.cols() != index.dim() {
+        return Err(invalid(format!(
+            "queries have dimension {}, index has {}",
+            queries.cols(),
+            index.dim()
+        )));
+    }
+    if groundtruth.cols() < config.k || groundtruth.rows() < queries.rows() {
+        return Err(invalid(format!(
+            "ground truth is {}x{}, need at least {}x{}",
+            groundtruth.rows(),
+            groundtruth.cols(),
+            queries.rows(),
+            config.k
+        )));
+    }
+
+    let kind = index.kind();
+    let mut values = if config.values.is_empty() {
+        default_sweep(kind)
+    } else {
+        config.values.clone()
+    };
+    if kind == IndexKind::Ivf {
+        // nprobe beyond the number of clusters repeats the same search
+        if let Some(clusters) = index.meta().param::<usize>("clusters") {
+            for v in &mut values {
+                *v = (*v).min(clusters);
+            }
+        }
+    }
+    values.sort_unstable();
+    values.dedup();
+
+    let mut points: Vec<EvalPoint> = Vec::with_capacity(values.len());
+    for value in values {
+        let params = match kind {
+            IndexKind::Ivf => SearchParams {
+                nprobe: value,
+                ..config.base_params
+            },
+            IndexKind::Hnsw | IndexKind::Qg => SearchParams {
+                ef: value,
+                ..config.base_params
+            },
+        };
+        let point = measure(index, queries, groundtruth, &params, value, config);
+        let prev_recall = points.last().map(|p| p.recall);
+        let done = config.stop_recall.is_some_and(|stop| {
+            point.recall > stop || prev_recall.is_some_and(|r| point.recall - r < 1e-5)
+        });
+        points.push(point);
+        if done {
+            break;
+        }
+    }
+
+    Ok(EvalReport {
+        kind,
+        param: if kind == IndexKind::Ivf {
+            "nprobe"
+        } else {
+            "ef"
+        },
+        k: config.k,
+        num_queries: queries.rows(),
+        num_threads: match config.num_threads {
+            0 => rayon::current_num_threads(),
+            t => t,
+        },
+        points,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recall_at_k() {
+        let gt = Matrix::new(vec![1, 2, 3, 9, 4, 5, 6, 9], 2, 4);
+        // query 0 finds 1 and 3, query 1 finds 6 only
+        let results = [3, 1, 7, 6, INVALID_ID, 8];
+        let r = recall_at_k(&results, &gt, 3);
+        assert!((r - 3.0 / 6.0).abs() < 1e-12);
+        // ids beyond the first k ground truth entries do not count
+        assert_eq!(recall_at_k(&[9, 9], &gt, 1), 0.0);
+    }
+
+    #[test]
+    fn test_percentile() {
+        let v: Vec<f64> = (1..=100).map(f64::from).collect();
+        assert_eq!(percentile(&v, 50.0), 50.0);
+        assert_eq!(percentile(&v, 99.0), 99.0);
+        assert_eq!(percentile(&v, 100.0), 100.0);
+        assert_eq!(percentile(&[4.0], 90.0), 4.0);
+    }
+
+    #[test]
+    fn test_default_sweep_matches_sample() {
+        let ivf = default_sweep(IndexKind::Ivf);
+        assert_eq!(&ivf[..3], &[5, 10, 20]);
+        assert_eq!(ivf.last(), Some(&15000));
+        assert!(ivf.windows(2).all(|w| w[0] < w[1]));
+        assert!(default_sweep(IndexKind::Qg).windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_report_formats() {
+        let report = EvalReport {
+            kind: IndexKind::Hnsw,
+            param: "ef",
+            k: 10,
+            num_queries: 100,
+            num_threads: 4,
+            points: vec![EvalPoint {
+                value: 50,
+                recall: 0.95,
+                qps: 1234.5,
+                mean_us: 800.0,
+                p50_us: 700.0,
+                p90_us: 1000.0,
+                p95_us: 1100.0,
+                p99_us: 1500.0,
+                max_us: 2000.0,
+            }],
+        };
+        let csv = report.to_csv();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some(CSV_HEADER));
+        assert!(
+            lines
+                .next()
+                .unwrap()
+                .starts_with("hnsw,ef,50,10,100,4,0.950000,1234.50,")
+        );
+
+        let json = report.to_json();
+        assert!(json.contains("\"kind\": \"hnsw\""));
+        assert!(json.contains("{\"ef\": 50, \"recall\": 0.950000, \"qps\": 1234.50"));
+        assert!(json.contains("\"p99\": 1500.00"));
+    }
+}
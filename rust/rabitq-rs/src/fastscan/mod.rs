@@ -0,0 +1,664 @@
+//! Pure-Rust FastScan kernels.
+//!
+//! This is a port of `fastscan/fastscan.hpp`, `fastscan/highacc_fastscan.hpp` and the
+//! batch estimators in `index/estimator.hpp`. Packed codes use the same layout as
+//! `BatchDataMap` / `QGBatchDataMap`, so batches written by the C++ library can be scanned
+//! here and vice versa. SIMD paths are selected at runtime on x86_64 (AVX-512BW, AVX2) and
+//! at compile time on aarch64 (NEON), with a scalar fallback everywhere else.
+
+#[cfg(target_arch = "aarch64")]
+mod neon;
+#[cfg(target_arch = "x86_64")]
+mod x86;
+
+use crate::MetricType;
+
+/// Number of vectors in each packed batch
+pub const BATCH_SIZE: usize = 32;
+
+// position of the lowest set bit for each 4-bit string, used to build the lookup table
+const POS: [usize; 16] = [3, 3, 2, 3, 1, 3, 2, 3, 0, 3, 2, 3, 1, 3, 2, 3];
+
+// data order of packed quantization code, vector `PERM0[j]` is stored in byte `j`
+const PERM0: [usize; 16] = [0, 8, 1, 9, 2, 10, 3, 11, 4, 12, 5, 13, 6, 14, 7, 15];
+
+const NUM_BITS: u32 = 8;
+const NUM_BITS_HACC: u32 = 16;
+
+/// Bytes taken by the packed 1-bit codes of one batch
+pub fn batch_code_bytes(padded_dim: usize) -> usize {
+    padded_dim * BATCH_SIZE / 8
+}
+
+/// Bytes of one `BatchDataMap` (packed codes, f_add, f_rescale, f_error)
+pub fn batch_data_bytes(padded_dim: usize) -> usize {
+    batch_code_bytes(padded_dim) + std::mem::size_of::<f32>() * BATCH_SIZE * 3
+}
+
+/// Bytes of one `QGBatchDataMap` (packed codes, f_add, f_rescale)
+pub fn qg_batch_data_bytes(padded_dim: usize) -> usize {
+    batch_code_bytes(padded_dim) + std::mem::size_of::<f32>() * BATCH_SIZE * 2
+}
+
+/// Pack 1-bit quantization codes (`padded_dim / 8` bytes per vector, MSB first) into
+/// batches of 32 vectors. If `num` is not a multiple of 32 the last batch is zero padded.
+pub fn pack_codes(padded_dim: usize, codes: &[u8], num: usize, blocks: &mut [u8]) {
+    let cols = padded_dim / 8;
+    let num_rd = num.div_ceil(BATCH_SIZE) * BATCH_SIZE;
+    assert!(codes.len() >= num * cols);
+    assert!(blocks.len() >= num_rd * cols);
+
+    let mut out = 0;
+    for row in (0..num_rd).step_by(BATCH_SIZE) {
+        for i in 0..cols {
+            let mut col = [0u8; BATCH_SIZE];
+            for (k, c) in col.iter_mut().enumerate() {
+                if row + k < num {
+                    *c = codes[(row + k) * cols + i];
+                }
+            }
+            for j in 0..16 {
+                // the lower 4 bits represent vector 0 to 15
+                // the upper 4 bits represent vector 16 to 31
+                let (v0, v1) = (col[PERM0[j]], col[PERM0[j] + 16]);
+                blocks[out + j] = (v0 >> 4) | ((v1 >> 4) << 4);
+                blocks[out + j + 16] = (v0 & 15) | ((v1 & 15) << 4);
+            }
+            out += 32;
+        }
+    }
+}
+
+/// Build the float lookup table, for each 4 dims there are 16 entries
+pub fn pack_lut(padded_dim: usize, query: &[f32], lut: &mut [f32]) {
+    assert_eq!(padded_dim % 4, 0);
+    for (q, table) in query[..padded_dim]
+        .chunks_exact(4)
+        .zip(lut.chunks_exact_mut(16))
+    {
+        table[0] = 0.0;
+        for j in 1..16 {
+            table[j] = table[j - (j & j.wrapping_neg())] + q[POS[j]];
+        }
+    }
+}
+
+/// Split a u16 lookup table into lower and upper byte tables. The layout matches the one
+/// produced by the AVX-512 build of `transfer_lut_hacc`: for every 4 codebooks, 64 bytes of
+/// lower halves followed by 64 bytes of upper halves.
+pub fn transfer_lut_hacc(lut: &[u16], padded_dim: usize, hc_lut: &mut [u8]) {
+    let num_codebook = padded_dim >> 2;
+    for (i, table) in lut.chunks_exact(16).take(num_codebook).enumerate() {
+        let fill_lo = (i / 4 * 128) + (i % 4) * 16;
+        let fill_hi = fill_lo + 64;
+        for (j, &v) in table.iter().enumerate() {
+            hc_lut[fill_lo + j] = v as u8;
+            hc_lut[fill_hi + j] = (v >> 8) as u8;
+        }
+    }
+}
+
+/// Accumulate the 8-bit lookup table over one packed batch, `padded_dim % 16 == 0`
+pub fn accumulate(codes: &[u8], lut: &[u8], result: &mut [u16; BATCH_SIZE], padded_dim: usize) {
+    assert_eq!(padded_dim % 16, 0);
+    assert!(codes.len() >= batch_code_bytes(padded_dim));
+    assert!(lut.len() >= padded_dim << 2);
+
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx512bw") {
+            return unsafe { x86::accumulate_avx512bw(codes, lut, result, padded_dim) };
+        }
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { x86::accumulate_avx2(codes, lut, result, padded_dim) };
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        return unsafe { neon::accumulate_neon(codes, lut, result, padded_dim) };
+    }
+    #[allow(unreachable_code)]
+    accumulate_scalar(codes, lut, result, padded_dim)
+}
+
+/// Accumulate the split 16-bit lookup table over one packed batch, `padded_dim % 16 == 0`
+pub fn accumulate_hacc(
+    codes: &[u8],
+    hc_lut: &[u8],
+    result: &mut [i32; BATCH_SIZE],
+    padded_dim: usize,
+) {
+    assert_eq!(padded_dim % 16, 0);
+    assert!(codes.len() >= batch_code_bytes(padded_dim));
+    assert!(hc_lut.len() >= padded_dim << 3);
+
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx512bw") {
+            return unsafe { x86::accumulate_hacc_avx512bw(codes, hc_lut, result, padded_dim) };
+        }
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { x86::accumulate_hacc_avx2(codes, hc_lut, result, padded_dim) };
+        }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        return unsafe { neon::accumulate_hacc_neon(codes, hc_lut, result, padded_dim) };
+    }
+    #[allow(unreachable_code)]
+    accumulate_hacc_scalar(codes, hc_lut, result, padded_dim)
+}
+
+fn accumulate_scalar(codes: &[u8], lut: &[u8], result: &mut [u16; BATCH_SIZE], padded_dim: usize) {
+    result.fill(0);
+    let num_codebook = padded_dim >> 2;
+    for (c, table) in codes
+        .chunks_exact(16)
+        .zip(lut.chunks_exact(16))
+        .take(num_codebook)
+    {
+        for j in 0..16 {
+            let lo = table[(c[j] & 15) as usize] as u16;
+            let hi = table[(c[j] >> 4) as usize] as u16;
+            result[PERM0[j]] = result[PERM0[j]].wrapping_add(lo);
+            result[PERM0[j] + 16] = result[PERM0[j] + 16].wrapping_add(hi);
+        }
+    }
+}
+
+fn accumulate_hacc_scalar(
+    codes: &[u8],
+    hc_lut: &[u8],
+    result: &mut [i32; BATCH_SIZE],
+    padded_dim: usize,
+) {
+    let mut res_lo = [0u16; BATCH_SIZE];
+    let mut res_hi = [0u16; BATCH_SIZE];
+    let num_codebook = padded_dim >> 2;
+    for m in 0..num_codebook {
+        let c = &codes[m * 16..m * 16 + 16];
+        let fill_lo = (m / 4 * 128) + (m % 4) * 16;
+        let table_lo = &hc_lut[fill_lo..fill_lo + 16];
+        let table_hi = &hc_lut[fill_lo + 64..fill_lo + 80];
+        for j in 0..16 {
+            let (lo, hi) = ((c[j] & 15) as usize, (c[j] >> 4) as usize);
+            res_lo[PERM0[j]] = res_lo[PERM0[j]].wrapping_add(table_lo[lo] as u16);
+            res_lo[PERM0[j] + 16] = res_lo[PERM0[j] + 16].wrapping_add(table_lo[hi] as u16);
+            res_hi[PERM0[j]] = res_hi[PERM0[j]].wrapping_add(table_hi[lo] as u16);
+            res_hi[PERM0[j] + 16] = res_hi[PERM0[j] + 16].wrapping_add(table_hi[hi] as u16);
+        }
+    }
+    for i in 0..BATCH_SIZE {
+        result[i] = res_lo[i] as i32 + ((res_hi[i] as i32) << 8);
+    }
+}
+
+// Fold SIMD accumulators back into per-vector sums. Each accumulator is a sequence of
+// 128-bit lanes (8 x u16); in every u16 the low byte belongs to vector `t` and the high
+// byte to vector `t + 8`. `accu[1]`/`accu[3]` hold the high bytes shifted down, so they
+// are used both as results and to remove the carry polluting `accu[0]`/`accu[2]`.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+fn merge_lanes(accu: [&[u16]; 4], result: &mut [u16; BATCH_SIZE]) {
+    result.fill(0);
+    for lane in 0..accu[0].len() / 8 {
+        for t in 0..8 {
+            let k = lane * 8 + t;
+            let a0 = accu[0][k].wrapping_sub(accu[1][k] << 8);
+            let a2 = accu[2][k].wrapping_sub(accu[3][k] << 8);
+            result[t] = result[t].wrapping_add(a0);
+            result[t + 8] = result[t + 8].wrapping_add(accu[1][k]);
+            result[t + 16] = result[t + 16].wrapping_add(a2);
+            result[t + 24] = result[t + 24].wrapping_add(accu[3][k]);
+        }
+    }
+}
+
+/// Quantized lookup table of a rotated query, same as `Lut` in `index/lut.hpp`
+#[derive(Clone, Debug)]
+pub struct Lut {
+    table: Vec<u8>,
+    delta: f32,
+    sum_vl: f32,
+    use_hacc: bool,
+    padded_dim: usize,
+}
+
+impl Lut {
+    pub fn new(rotated_query: &[f32], padded_dim: usize, use_hacc: bool) -> Self {
+        assert_eq!(padded_dim % 16, 0);
+        assert!(rotated_query.len() >= padded_dim);
+
+        let table_length = padded_dim << 2;
+        let mut lut_float = vec![0.0f32; table_length];
+        pack_lut(padded_dim, rotated_query, &mut lut_float);
+
+        let vl = lut_float.iter().copied().fold(f32::INFINITY, f32::min);
+        let vr = lut_float.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+
+        let num_bits = if use_hacc { NUM_BITS_HACC } else { NUM_BITS };
+        let delta = (vr - vl) / ((1u32 << num_bits) - 1) as f32;
+        let one_over_delta = 1.0 / delta;
+        let quantize = |v: f32| ((v - vl) * one_over_delta).round_ties_even();
+
+        let table = if use_hacc {
+            let lut_u16: Vec<u16> = lut_float.iter().map(|&v| quantize(v) as u16).collect();
+            let mut table = vec![0u8; table_length * 2];
+            transfer_lut_hacc(&lut_u16, padded_dim, &mut table);
+            table
+        } else {
+            lut_float.iter().map(|&v| quantize(v) as u8).collect()
+        };
+
+        let num_table = table_length / 16;
+        Self {
+            table,
+            delta,
+            sum_vl: vl * num_table as f32,
+            use_hacc,
+            padded_dim,
+        }
+    }
+
+    pub fn table(&self) -> &[u8] {
+        &self.table
+    }
+
+    pub fn delta(&self) -> f32 {
+        self.delta
+    }
+
+    pub fn sum_vl(&self) -> f32 {
+        self.sum_vl
+    }
+
+    pub fn use_hacc(&self) -> bool {
+        self.use_hacc
+    }
+
+    pub fn padded_dim(&self) -> usize {
+        self.padded_dim
+    }
+
+    /// Raw accumulation of this table over the packed codes of one batch
+    pub fn accumulate(&self, codes: &[u8], result: &mut [i32; BATCH_SIZE]) {
+        if self.use_hacc {
+            accumulate_hacc(codes, &self.table, result, self.padded_dim);
+        } else {
+            let mut accu = [0u16; BATCH_SIZE];
+            accumulate(codes, &self.table, &mut accu, self.padded_dim);
+            for (r, a) in result.iter_mut().zip(accu) {
+                *r = a as i32;
+            }
+        }
+    }
+}
+
+fn read_factors(bytes: &[u8]) -> [f32; BATCH_SIZE] {
+    let mut out = [0.0f32; BATCH_SIZE];
+    for (o, b) in out.iter_mut().zip(bytes.chunks_exact(4)) {
+        *o = f32::from_ne_bytes([b[0], b[1], b[2], b[3]]);
+    }
+    out
+}
+
+/// Query object for scanning `BatchDataMap` batches, same as `SplitBatchQuery`
+#[derive(Clone, Debug)]
+pub struct SplitBatchQuery {
+    lut: Lut,
+    g_add: f32,
+    g_error: f32,
+    k1xsumq: f32,
+    kbxsumq: f32,
+    metric_type: MetricType,
+}
+
+impl SplitBatchQuery {
+    pub fn new(
+        rotated_query: &[f32],
+        padded_dim: usize,
+        ex_bits: usize,
+        metric_type: MetricType,
+        use_hacc: bool,
+    ) -> Self {
+        let lut = Lut::new(rotated_query, padded_dim, use_hacc);
+        let c_1 = -(((1 << 1) - 1) as f32) / 2.0;
+        let c_b = -(((1 << (ex_bits + 1)) - 1) as f32) / 2.0;
+        let sumq: f32 = rotated_query[..padded_dim].iter().sum();
+        Self {
+            lut,
+            g_add: 0.0,
+            g_error: 0.0,
+            k1xsumq: sumq * c_1,
+            kbxsumq: sumq * c_b,
+            metric_type,
+        }
+    }
+
+    pub fn set_g_add(&mut self, norm: f32, ip: f32) {
+        match self.metric_type {
+            MetricType::L2 => self.g_add = norm * norm,
+            MetricType::IP => self.g_add = -ip,
+        }
+        self.g_error = norm;
+    }
+
+    pub fn lut(&self) -> &Lut {
+        &self.lut
+    }
+
+    pub fn delta(&self) -> f32 {
+        self.lut.delta()
+    }
+
+    pub fn sum_vl_lut(&self) -> f32 {
+        self.lut.sum_vl()
+    }
+
+    pub fn k1xsumq(&self) -> f32 {
+        self.k1xsumq
+    }
+
+    pub fn kbxsumq(&self) -> f32 {
+        self.kbxsumq
+    }
+
+    pub fn g_add(&self) -> f32 {
+        self.g_add
+    }
+
+    pub fn g_error(&self) -> f32 {
+        self.g_error
+    }
+}
+
+/// Estimate distances for one `BatchDataMap` batch, returns estimated distances, lower
+/// bounds and the intermediate `ip_x0_qr` used for re-ranking
+pub fn split_batch_estdist(
+    batch_data: &[u8],
+    q_obj: &SplitBatchQuery,
+    est_distance: &mut [f32],
+    low_distance: &mut [f32],
+    ip_x0_qr: &mut [f32],
+) {
+    let padded_dim = q_obj.lut.padded_dim();
+    assert!(batch_data.len() >= batch_data_bytes(padded_dim));
+
+    let code_bytes = batch_code_bytes(padded_dim);
+    let mut accu = [0i32; BATCH_SIZE];
+    q_obj.lut.accumulate(&batch_data[..code_bytes], &mut accu);
+
+    let factors = &batch_data[code_bytes..];
+    let f_add = read_factors(factors);
+    let f_rescale = read_factors(&factors[BATCH_SIZE * 4..]);
+    let f_error = read_factors(&factors[BATCH_SIZE * 8..]);
+
+    for i in 0..BATCH_SIZE {
+        ip_x0_qr[i] = q_obj.delta() * accu[i] as f32 + q_obj.sum_vl_lut();
+        est_distance[i] = f_add[i] + q_obj.g_add + f_rescale[i] * (ip_x0_qr[i] + q_obj.k1xsumq);
+        low_distance[i] = est_distance[i] - f_error[i] * q_obj.g_error;
+    }
+}
+
+/// Query object for scanning `QGBatchDataMap` batches, same as `BatchQuery`
+#[derive(Clone, Debug)]
+pub struct BatchQuery {
+    lut: Lut,
+    g_add: f32,
+    k1xsumq: f32,
+}
+
+impl BatchQuery {
+    pub fn new(rotated_query: &[f32], padded_dim: usize) -> Self {
+        let lut = Lut::new(rotated_query, padded_dim, false);
+        let c_1 = -(((1 << 1) - 1) as f32) / 2.0;
+        let sumq: f32 = rotated_query[..padded_dim].iter().sum();
+        Self {
+            lut,
+            g_add: 0.0,
+            k1xsumq: sumq * c_1,
+        }
+    }
+
+    pub fn set_g_add(&mut self, sqr_norm: f32) {
+        self.g_add = sqr_norm;
+    }
+
+    pub fn lut(&self) -> &Lut {
+        &self.lut
+    }
+
+    pub fn delta(&self) -> f32 {
+        self.lut.delta()
+    }
+
+    pub fn sum_vl_lut(&self) -> f32 {
+        self.lut.sum_vl()
+    }
+
+    pub fn k1xsumq(&self) -> f32 {
+        self.k1xsumq
+    }
+
+    pub fn g_add(&self) -> f32 {
+        self.g_add
+    }
+}
+
+/// Estimate distances for one `QGBatchDataMap` batch
+pub fn qg_batch_estdist(batch_data: &[u8], q_obj: &BatchQuery, est_distance: &mut [f32]) {
+    let padded_dim = q_obj.lut.padded_dim();
+    assert!(batch_data.len() >= qg_batch_data_bytes(padded_dim));
+
+    let code_bytes = batch_code_bytes(padded_dim);
+    let mut accu = [0i32; BATCH_SIZE];
+    q_obj.lut.accumulate(&batch_data[..code_bytes], &mut accu);
+
+    let factors = &batch_data[code_bytes..];
+    let f_add = read_factors(factors);
+    let f_rescale = read_factors(&factors[BATCH_SIZE * 4..]);
+
+    for i in 0..BATCH_SIZE {
+        est_distance[i] = f_add[i]
+            + q_obj.g_add
+            + f_rescale[i] * (q_obj.delta() * accu[i] as f32 + q_obj.sum_vl_lut() + q_obj.k1xsumq);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    fn random_codes(padded_dim: usize, num: usize) -> Vec<u8> {
+        let mut rng = rand::rng();
+        (0..num * padded_dim / 8).map(|_| rng.random()).collect()
+    }
+
+    // sum the lookup table directly from the unpacked codes
+    fn naive_accumulate(codes: &[u8], lut: &[u16], padded_dim: usize, num: usize) -> Vec<u32> {
+        let cols = padded_dim / 8;
+        (0..num)
+            .map(|v| {
+                (0..padded_dim / 4)
+                    .map(|m| {
+                        let byte = codes[v * cols + m / 2];
+                        let nibble = if m % 2 == 0 { byte >> 4 } else { byte & 15 };
+                        lut[m * 16 + nibble as usize] as u32
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+
+    fn batch_bytes(
+        padded_dim: usize,
+        codes: &[u8],
+        num: usize,
+        num_factors: usize,
+    ) -> (Vec<u8>, Vec<f32>) {
+        let mut rng = rand::rng();
+        let mut data = vec![0u8; batch_code_bytes(padded_dim)];
+        pack_codes(padded_dim, codes, num, &mut data);
+        let factors: Vec<f32> = (0..BATCH_SIZE * num_factors)
+            .map(|_| rng.random_range(-1.0..1.0))
+            .collect();
+        for f in &factors {
+            data.extend_from_slice(&f.to_ne_bytes());
+        }
+        (data, factors)
+    }
+
+    #[test]
+    fn test_accumulate_matches_naive() {
+        let mut rng = rand::rng();
+        for padded_dim in [16, 64, 128, 256] {
+            let codes = random_codes(padded_dim, 27);
+            let mut blocks = vec![0u8; batch_code_bytes(padded_dim)];
+            pack_codes(padded_dim, &codes, 27, &mut blocks);
+
+            let lut: Vec<u8> = (0..padded_dim * 4).map(|_| rng.random()).collect();
+            let lut16: Vec<u16> = lut.iter().map(|&v| v as u16).collect();
+            let expected = naive_accumulate(&codes, &lut16, padded_dim, 27);
+
+            let mut res = [0u16; BATCH_SIZE];
+            let mut res_scalar = [0u16; BATCH_SIZE];
+            accumulate(&blocks, &lut, &mut res, padded_dim);
+            accumulate_scalar(&blocks, &lut, &mut res_scalar, padded_dim);
+            assert_eq!(res, res_scalar);
+            for i in 0..27 {
+                assert_eq!(res[i] as u32, expected[i]);
+            }
+            // padded vectors have all-zero codes
+            let zero_code: u16 = lut.chunks(16).map(|t| t[0] as u16).sum();
+            assert!(res[27..].iter().all(|&r| r == zero_code));
+        }
+    }
+
+    #[test]
+    fn test_accumulate_hacc_matches_naive() {
+        let mut rng = rand::rng();
+        for padded_dim in [16, 64, 128, 256] {
+            let codes = random_codes(padded_dim, BATCH_SIZE);
+            let mut blocks = vec![0u8; batch_code_bytes(padded_dim)];
+            pack_codes(padded_dim, &codes, BATCH_SIZE, &mut blocks);
+
+            let lut16: Vec<u16> = (0..padded_dim * 4).map(|_| rng.random()).collect();
+            let mut hc_lut = vec![0u8; padded_dim * 8];
+            transfer_lut_hacc(&lut16, padded_dim, &mut hc_lut);
+            let expected = naive_accumulate(&codes, &lut16, padded_dim, BATCH_SIZE);
+
+            let mut res = [0i32; BATCH_SIZE];
+            let mut res_scalar = [0i32; BATCH_SIZE];
+            accumulate_hacc(&blocks, &hc_lut, &mut res, padded_dim);
+            accumulate_hacc_scalar(&blocks, &hc_lut, &mut res_scalar, padded_dim);
+            assert_eq!(res, res_scalar);
+            if padded_dim <= 64 {
+                // larger dims overflow the 16-bit partial sums exactly like the C++ kernel
+                for i in 0..BATCH_SIZE {
+                    assert_eq!(res[i] as u32, expected[i]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_lut_matches_ffi() {
+        let padded_dim = 128;
+        let mut rng = rand::rng();
+        let query: Vec<f32> = (0..padded_dim)
+            .map(|_| rng.random_range(-1.0..1.0))
+            .collect();
+        let lut = Lut::new(&query, padded_dim, false);
+        unsafe {
+            let q = rabitq_sys::rabitq_batch_query_new(query.as_ptr(), padded_dim, 8);
+            let table =
+                std::slice::from_raw_parts(rabitq_sys::rabitq_batch_query_lut(q), padded_dim * 4);
+            assert_eq!(lut.table(), table);
+            assert_eq!(lut.delta(), rabitq_sys::rabitq_batch_query_delta(q));
+            assert_eq!(lut.sum_vl(), rabitq_sys::rabitq_batch_query_sum_vl_lut(q));
+            rabitq_sys::rabitq_batch_query_free(q);
+        }
+    }
+
+    #[test]
+    fn test_qg_batch_estdist_matches_ffi() {
+        let padded_dim = 256;
+        let mut rng = rand::rng();
+        let query: Vec<f32> = (0..padded_dim)
+            .map(|_| rng.random_range(-1.0..1.0))
+            .collect();
+        let codes = random_codes(padded_dim, BATCH_SIZE);
+        let (data, _) = batch_bytes(padded_dim, &codes, BATCH_SIZE, 2);
+
+        let mut q_obj = BatchQuery::new(&query, padded_dim);
+        q_obj.set_g_add(3.5);
+        let mut est = [0.0f32; BATCH_SIZE];
+        qg_batch_estdist(&data, &q_obj, &mut est);
+
+        let mut expected = [0.0f32; BATCH_SIZE];
+        unsafe {
+            let q = rabitq_sys::rabitq_batch_query_new(query.as_ptr(), padded_dim, 8);
+            rabitq_sys::rabitq_batch_query_set_g_add(q, 3.5);
+            rabitq_sys::rabitq_qg_batch_estdist(
+                data.as_ptr() as *const _,
+                q,
+                padded_dim,
+                expected.as_mut_ptr(),
+            );
+            rabitq_sys::rabitq_batch_query_free(q);
+        }
+        for i in 0..BATCH_SIZE {
+            assert!((est[i] - expected[i]).abs() <= 1e-4 * expected[i].abs().max(1.0));
+        }
+    }
+
+    #[test]
+    fn test_split_batch_estdist_matches_ffi() {
+        let padded_dim = 128;
+        let mut rng = rand::rng();
+        let query: Vec<f32> = (0..padded_dim)
+            .map(|_| rng.random_range(-1.0..1.0))
+            .collect();
+        let codes = random_codes(padded_dim, BATCH_SIZE);
+        let (data, _) = batch_bytes(padded_dim, &codes, BATCH_SIZE, 3);
+
+        for use_hacc in [false, true] {
+            let mut q_obj = SplitBatchQuery::new(&query, padded_dim, 4, MetricType::L2, use_hacc);
+            q_obj.set_g_add(1.5, 0.0);
+            let mut est = [0.0f32; BATCH_SIZE];
+            let mut low = [0.0f32; BATCH_SIZE];
+            let mut ip = [0.0f32; BATCH_SIZE];
+            split_batch_estdist(&data, &q_obj, &mut est, &mut low, &mut ip);
+
+            let mut expected_est = [0.0f32; BATCH_SIZE];
+            let mut expected_low = [0.0f32; BATCH_SIZE];
+            let mut expected_ip = [0.0f32; BATCH_SIZE];
+            unsafe {
+                let q = rabitq_sys::rabitq_split_batch_query_new(
+                    query.as_ptr(),
+                    padded_dim,
+                    4,
+                    MetricType::L2 as u32,
+                    if use_hacc { 16 } else { 8 },
+                );
+                rabitq_sys::rabitq_split_batch_query_set_g_add(q, 1.5, 0.0);
+                rabitq_sys::rabitq_split_batch_estdist(
+                    data.as_ptr() as *const _,
+                    q,
+                    padded_dim,
+                    expected_est.as_mut_ptr(),
+                    expected_low.as_mut_ptr(),
+                    expected_ip.as_mut_ptr(),
+                    use_hacc,
+                );
+                rabitq_sys::rabitq_split_batch_query_free(q);
+            }
+            for i in 0..BATCH_SIZE {
+                let tol = |x: f32| 1e-4 * x.abs().max(1.0);
+                assert!((est[i] - expected_est[i]).abs() <= tol(expected_est[i]));
+                assert!((low[i] - expected_low[i]).abs() <= tol(expected_low[i]));
+                assert!((ip[i] - expected_ip[i]).abs() <= tol(expected_ip[i]));
+            }
+        }
+    }
+}
@@ -0,0 +1,82 @@
+//! NEON accumulation kernels. `vqtbl1q_u8` plays the role of `_mm_shuffle_epi8`, so each
+//! 128-bit register handles one codebook (4 dims) of 32 vectors.
+
+use std::arch::aarch64::*;
+
+use super::{BATCH_SIZE, merge_lanes};
+
+#[target_feature(enable = "neon")]
+pub(super) unsafe fn accumulate_neon(
+    codes: &[u8],
+    lut: &[u8],
+    result: &mut [u16; BATCH_SIZE],
+    padded_dim: usize,
+) {
+    let code_length = padded_dim << 2;
+    let lo_mask = vdupq_n_u8(0x0f);
+    let mut accu = [vdupq_n_u16(0); 4];
+
+    for i in (0..code_length).step_by(16) {
+        let c = unsafe { vld1q_u8(codes.as_ptr().add(i)) };
+        let table = unsafe { vld1q_u8(lut.as_ptr().add(i)) };
+        let lo = vandq_u8(c, lo_mask);
+        let hi = vshrq_n_u8::<4>(c);
+
+        let res_lo = vreinterpretq_u16_u8(vqtbl1q_u8(table, lo));
+        let res_hi = vreinterpretq_u16_u8(vqtbl1q_u8(table, hi));
+
+        accu[0] = vaddq_u16(accu[0], res_lo);
+        accu[1] = vaddq_u16(accu[1], vshrq_n_u16::<8>(res_lo));
+        accu[2] = vaddq_u16(accu[2], res_hi);
+        accu[3] = vaddq_u16(accu[3], vshrq_n_u16::<8>(res_hi));
+    }
+
+    let mut lanes = [[0u16; 8]; 4];
+    for (dst, reg) in lanes.iter_mut().zip(accu) {
+        unsafe { vst1q_u16(dst.as_mut_ptr(), reg) };
+    }
+    merge_lanes(lanes.each_ref().map(|l| &l[..]), result);
+}
+
+#[target_feature(enable = "neon")]
+pub(super) unsafe fn accumulate_hacc_neon(
+    codes: &[u8],
+    hc_lut: &[u8],
+    result: &mut [i32; BATCH_SIZE],
+    padded_dim: usize,
+) {
+    let num_codebook = padded_dim >> 2;
+    let lo_mask = vdupq_n_u8(0x0f);
+    // accu[0] for the lower 8-bit tables, accu[1] for the upper 8-bit tables
+    let mut accu = [[vdupq_n_u16(0); 4]; 2];
+
+    for m in 0..num_codebook {
+        let c = unsafe { vld1q_u8(codes.as_ptr().add(m * 16)) };
+        let lo = vandq_u8(c, lo_mask);
+        let hi = vshrq_n_u8::<4>(c);
+        let fill_lo = (m / 4 * 128) + (m % 4) * 16;
+
+        for (half, a) in accu.iter_mut().enumerate() {
+            let table = unsafe { vld1q_u8(hc_lut.as_ptr().add(fill_lo + half * 64)) };
+            let res_lo = vreinterpretq_u16_u8(vqtbl1q_u8(table, lo));
+            let res_hi = vreinterpretq_u16_u8(vqtbl1q_u8(table, hi));
+
+            a[0] = vaddq_u16(a[0], res_lo);
+            a[1] = vaddq_u16(a[1], vshrq_n_u16::<8>(res_lo));
+            a[2] = vaddq_u16(a[2], res_hi);
+            a[3] = vaddq_u16(a[3], vshrq_n_u16::<8>(res_hi));
+        }
+    }
+
+    let mut res = [[0u16; BATCH_SIZE]; 2];
+    for (r, a) in res.iter_mut().zip(accu) {
+        let mut lanes = [[0u16; 8]; 4];
+        for (dst, reg) in lanes.iter_mut().zip(a) {
+            unsafe { vst1q_u16(dst.as_mut_ptr(), reg) };
+        }
+        merge_lanes(lanes.each_ref().map(|l| &l[..]), r);
+    }
+    for i in 0..BATCH_SIZE {
+        result[i] = res[0][i] as i32 + ((res[1][i] as i32) << 8);
+    }
+}
@@ -0,0 +1,194 @@
+//! AVX2 / AVX-512BW accumulation kernels, following `fastscan::accumulate` and
+//! `fastscan::accumulate_hacc` in the C++ library.
+
+use std::arch::x86_64::*;
+
+use super::{BATCH_SIZE, merge_lanes};
+
+#[target_feature(enable = "avx512bw")]
+pub(super) unsafe fn accumulate_avx512bw(
+    codes: &[u8],
+    lut: &[u8],
+    result: &mut [u16; BATCH_SIZE],
+    padded_dim: usize,
+) {
+    let code_length = padded_dim << 2;
+    let lo_mask = _mm512_set1_epi8(0x0f);
+    let mut accu = [_mm512_setzero_si512(); 4];
+
+    // each 128-bit lane handles one codebook (4 dims) of 32 vectors
+    for i in (0..code_length).step_by(64) {
+        let c = unsafe { _mm512_loadu_si512(codes.as_ptr().add(i).cast()) };
+        let table = unsafe { _mm512_loadu_si512(lut.as_ptr().add(i).cast()) };
+        let lo = _mm512_and_si512(c, lo_mask); // code of vector 0 to 15
+        let hi = _mm512_and_si512(_mm512_srli_epi16::<4>(c), lo_mask); // vector 16 to 31
+
+        let res_lo = _mm512_shuffle_epi8(table, lo);
+        let res_hi = _mm512_shuffle_epi8(table, hi);
+
+        accu[0] = _mm512_add_epi16(accu[0], res_lo);
+        accu[1] = _mm512_add_epi16(accu[1], _mm512_srli_epi16::<8>(res_lo));
+        accu[2] = _mm512_add_epi16(accu[2], res_hi);
+        accu[3] = _mm512_add_epi16(accu[3], _mm512_srli_epi16::<8>(res_hi));
+    }
+
+    let mut lanes = [[0u16; 32]; 4];
+    for (dst, reg) in lanes.iter_mut().zip(accu) {
+        unsafe { _mm512_storeu_si512(dst.as_mut_ptr().cast(), reg) };
+    }
+    merge_lanes(lanes.each_ref().map(|l| &l[..]), result);
+}
+
+#[target_feature(enable = "avx2")]
+pub(super) unsafe fn accumulate_avx2(
+    codes: &[u8],
+    lut: &[u8],
+    result: &mut [u16; BATCH_SIZE],
+    padded_dim: usize,
+) {
+    let code_length = padded_dim << 2;
+    let lo_mask = _mm256_set1_epi8(0x0f);
+    let mut accu = [_mm256_setzero_si256(); 4];
+
+    for i in (0..code_length).step_by(32) {
+        let c = unsafe { _mm256_loadu_si256(codes.as_ptr().add(i).cast()) };
+        let table = unsafe { _mm256_loadu_si256(lut.as_ptr().add(i).cast()) };
+        let lo = _mm256_and_si256(c, lo_mask);
+        let hi = _mm256_and_si256(_mm256_srli_epi16::<4>(c), lo_mask);
+
+        let res_lo = _mm256_shuffle_epi8(table, lo);
+        let res_hi = _mm256_shuffle_epi8(table, hi);
+
+        accu[0] = _mm256_add_epi16(accu[0], res_lo);
+        accu[1] = _mm256_add_epi16(accu[1], _mm256_srli_epi16::<8>(res_lo));
+        accu[2] = _mm256_add_epi16(accu[2], res_hi);
+        accu[3] = _mm256_add_epi16(accu[3], _mm256_srli_epi16::<8>(res_hi));
+    }
+
+    let mut lanes = [[0u16; 16]; 4];
+    for (dst, reg) in lanes.iter_mut().zip(accu) {
+        unsafe { _mm256_storeu_si256(dst.as_mut_ptr().cast(), reg) };
+    }
+    merge_lanes(lanes.each_ref().map(|l| &l[..]), result);
+}
+
+#[target_feature(enable = "avx512bw")]
+pub(super) unsafe fn accumulate_hacc_avx512bw(
+    codes: &[u8],
+    hc_lut: &[u8],
+    result: &mut [i32; BATCH_SIZE],
+    padded_dim: usize,
+) {
+    let num_codebook = padded_dim >> 2;
+    let lo_mask = _mm512_set1_epi8(0x0f);
+    // accu[0] for the lower 8-bit tables, accu[1] for the upper 8-bit tables
+    let mut accu = [[_mm512_setzero_si512(); 4]; 2];
+
+    for m in (0..num_codebook).step_by(4) {
+        let c = unsafe { _mm512_loadu_si512(codes.as_ptr().add(m * 16).cast()) };
+        let lo = _mm512_and_si512(c, lo_mask);
+        let hi = _mm512_and_si512(_mm512_srli_epi16::<4>(c), lo_mask);
+
+        for (half, a) in accu.iter_mut().enumerate() {
+            let table =
+                unsafe { _mm512_loadu_si512(hc_lut.as_ptr().add(m * 32 + half * 64).cast()) };
+            let res_lo = _mm512_shuffle_epi8(table, lo);
+            let res_hi = _mm512_shuffle_epi8(table, hi);
+
+            a[0] = _mm512_add_epi16(a[0], res_lo);
+            a[1] = _mm512_add_epi16(a[1], _mm512_srli_epi16::<8>(res_lo));
+            a[2] = _mm512_add_epi16(a[2], res_hi);
+            a[3] = _mm512_add_epi16(a[3], _mm512_srli_epi16::<8>(res_hi));
+        }
+    }
+
+    let mut res = [[0u16; BATCH_SIZE]; 2];
+    for (r, a) in res.iter_mut().zip(accu) {
+        let mut lanes = [[0u16; 32]; 4];
+        for (dst, reg) in lanes.iter_mut().zip(a) {
+            unsafe { _mm512_storeu_si512(dst.as_mut_ptr().cast(), reg) };
+        }
+        merge_lanes(lanes.each_ref().map(|l| &l[..]), r);
+    }
+    for i in 0..BATCH_SIZE {
+        result[i] = res[0][i] as i32 + ((res[1][i] as i32) << 8);
+    }
+}
+
+#[target_feature(enable = "avx2")]
+pub(super) unsafe fn accumulate_hacc_avx2(
+    codes: &[u8],
+    hc_lut: &[u8],
+    result: &mut [i32; BATCH_SIZE],
+    padded_dim: usize,
+) {
+    let num_codebook = padded_dim >> 2;
+    let lo_mask = _mm256_set1_epi8(0x0f);
+    let mut accu = [[_mm256_setzero_si256(); 4]; 2];
+
+    // the split table is laid out for 512-bit registers, so every group of 4 codebooks is
+    // consumed as two 256-bit halves
+    for m in (0..num_codebook).step_by(2) {
+        let c = unsafe { _mm256_loadu_si256(codes.as_ptr().add(m * 16).cast()) };
+        let lo = _mm256_and_si256(c, lo_mask);
+        let hi = _mm256_and_si256(_mm256_srli_epi16::<4>(c), lo_mask);
+        let fill_lo = (m / 4 * 128) + (m % 4) * 16;
+
+        for (half, a) in accu.iter_mut().enumerate() {
+            let table =
+                unsafe { _mm256_loadu_si256(hc_lut.as_ptr().add(fill_lo + half * 64).cast()) };
+            let res_lo = _mm256_shuffle_epi8(table, lo);
+            let res_hi = _mm256_shuffle_epi8(table, hi);
+
+            a[0] = _mm256_add_epi16(a[0], res_lo);
+            a[1] = _mm256_add_epi16(a[1], _mm256_srli_epi16::<8>(res_lo));
+            a[2] = _mm256_add_epi16(a[2], res_hi);
+            a[3] = _mm256_add_epi16(a[3], _mm256_srli_epi16::<8>(res_hi));
+        }
+    }
+
+    let mut res = [[0u16; BATCH_SIZE]; 2];
+    for (r, a) in res.iter_mut().zip(accu) {
+        let mut lanes = [[0u16; 16]; 4];
+        for (dst, reg) in lanes.iter_mut().zip(a) {
+            unsafe { _mm256_storeu_si256(dst.as_mut_ptr().cast(), reg) };
+        }
+        merge_lanes(lanes.each_ref().map(|l| &l[..]), r);
+    }
+    for i in 0..BATCH_SIZE {
+        result[i] = res[0][i] as i32 + ((res[1][i] as i32) << 8);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fastscan::{accumulate_hacc_scalar, accumulate_scalar, batch_code_bytes};
+    use rand::Rng;
+
+    // the dispatching tests only cover the widest kernel available, check AVX2 explicitly
+    #[test]
+    fn test_avx2_matches_scalar() {
+        if !is_x86_feature_detected!("avx2") {
+            return;
+        }
+        let mut rng = rand::rng();
+        let padded_dim = 192;
+        let codes: Vec<u8> = (0..batch_code_bytes(padded_dim))
+            .map(|_| rng.random())
+            .collect();
+        let lut: Vec<u8> = (0..padded_dim * 8).map(|_| rng.random()).collect();
+
+        let mut res = [0u16; BATCH_SIZE];
+        let mut expected = [0u16; BATCH_SIZE];
+        unsafe { accumulate_avx2(&codes, &lut, &mut res, padded_dim) };
+        accumulate_scalar(&codes, &lut, &mut expected, padded_dim);
+        assert_eq!(res, expected);
+
+        let mut res = [0i32; BATCH_SIZE];
+        let mut expected = [0i32; BATCH_SIZE];
+        unsafe { accumulate_hacc_avx2(&codes, &lut, &mut res, padded_dim) };
+        accumulate_hacc_scalar(&codes, &lut, &mut expected, padded_dim);
+        assert_eq!(res, expected);
+    }
+}
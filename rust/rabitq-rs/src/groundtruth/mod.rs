@@ -0,0 +1,276 @@
+//! Exact k-nearest-neighbour ground truth.
+//!
+//! The base set is scanned in blocks, every query keeping its own top-k heap, so the base
+//! vectors can be streamed from disk ([`compute_file`]) instead of being held in memory.
+//! Queries are processed in parallel; distances use the same Eigen kernels as the indexes.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::io;
+use std::path::Path;
+
+use rabitq_sys as ffi;
+use rayon::prelude::*;
+
+use crate::MetricType;
+use crate::element::VectorElement;
+use crate::io::{FileElement, Format, Matrix, read_blocks, write_vecs};
+
+/// Number of base vectors scanned per block
+pub const DEFAULT_BLOCK_ROWS: usize = 16384;
+
+/// Exact neighbours of every query, best first
+#[derive(Clone, Debug, PartialEq)]
+pub struct GroundTruth {
+    pub ids: Matrix<u32>,
+    /// Squared L2 distances, or inner products for [`MetricType::IP`]
+    pub distances: Matrix<f32>,
+}
+
+impl GroundTruth {
+    /// Write the ids as `.ivecs` and, if `distances_path` is given, the distances as `.fvecs`.
+    pub fn save(&self, ids_path: &Path, distances_path: Option<&Path>) -> io::Result<()> {
+        write_vecs(ids_path, &self.ids)?;
+        if let Some(path) = distances_path {
+            write_vecs(path, &self.distances)?;
+        }
+        Ok(())
+    }
+}
+
+/// Heap entry, ordered so that the worst candidate is on top. `score` is smaller-is-better.
+#[derive(Clone, Copy)]
+struct Candidate {
+    score: f32,
+    id: u32,
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .total_cmp(&other.score)
+            .then(self.id.cmp(&other.id))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+/// Incremental top-k search over base vectors fed block by block
+pub struct Accumulator {
+    queries: Vec<f32>,
+    dim: usize,
+    k: usize,
+    metric: MetricType,
+    heaps: Vec<BinaryHeap<Candidate>>,
+    num_base: usize,
+}
+
+impl Accumulator {
+    /// `queries` holds `nq * dim` values.
+    pub fn new<Q: VectorElement>(queries: &[Q], dim: usize, k: usize, metric: MetricType) -> Self {
+        assert!(dim > 0 && queries.len().is_multiple_of(dim));
+        let nq = queries.len() / dim;
+        Self {
+            queries: Q::slice_to_f32(queries).into_owned(),
+            dim,
+            k,
+            metric,
+            heaps: (0..nq).map(|_| BinaryHeap::with_capacity(k + 1)).collect(),
+            num_base: 0,
+        }
+    }
+
+    /// Scan the next `block.len() / dim` base vectors. Their ids continue from the vectors
+    /// already added.
+    pub fn add_block<T: VectorElement>(&mut self, block: &[T]) {
+        let dim = self.dim;
+        assert!(block.len().is_multiple_of(dim));
+        let block = T::slice_to_f32(block);
+        let first_id = self.num_base;
+        let (k, metric) = (self.k, self.metric);
+
+        self.heaps
+            .par_iter_mut()
+            .zip(self.queries.par_chunks_exact(dim))
+            .for_each(|(heap, query)| {
+                for (i, x) in block.chunks_exact(dim).enumerate() {
+                    let score = unsafe {
+                        match metric {
+                            MetricType::L2 => {
+                                ffi::rabitq_euclidean_sqr(query.as_ptr(), x.as_ptr(), dim)
+                            }
+                            MetricType::IP => {
+                                -ffi::rabitq_dot_product(query.as_ptr(), x.as_ptr(), dim)
+                            }
+                        }
+                    };
+                    let candidate = Candidate {
+                        score,
+                        id: (first_id + i) as u32,
+                    };
+                    if heap.len() < k {
+                        heap.push(candidate);
+                    } else if heap.peek().is_some_and(|worst| candidate < *worst) {
+                        heap.pop();
+                        heap.push(candidate);
+                    }
+                }
+            });
+        self.num_base += block.len() / dim;
+    }
+
+    /// Number of base vectors scanned so far
+    pub fn num_base(&self) -> usize {
+        self.num_base
+    }
+
+    /// Sort the neighbours of every query. Fails if fewer than `k` base vectors were added.
+    pub fn finish(self) -> io::Result<GroundTruth> {
+        if self.num_base < self.k {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("k = {} but only {} base vectors", self.k, self.num_base),
+            ));
+        }
+        let nq = self.heaps.len();
+        let mut ids = Vec::with_capacity(nq * self.k);
+        let mut distances = Vec::with_capacity(nq * self.k);
+        for heap in self.heaps {
+            for c in heap.into_sorted_vec() {
+                ids.push(c.id);
+                distances.push(match self.metric {
+                    MetricType::L2 => c.score,
+                    MetricType::IP => -c.score,
+                });
+            }
+        }
+        Ok(GroundTruth {
+            ids: Matrix::new(ids, nq, self.k),
+            distances: Matrix::new(distances, nq, self.k),
+        })
+    }
+}
+
+/// Exact top-`k` of every query among `base`, both row-major with dimension `dim`.
+pub fn compute<T: VectorElement, Q: VectorElement>(
+    base: &[T],
+    queries: &[Q],
+    dim: usize,
+    k: usize,
+    metric: MetricType,
+) -> io::Result<GroundTruth> {
+    let mut acc = Accumulator::new(queries, dim, k, metric);
+    for block in base.chunks(DEFAULT_BLOCK_ROWS * dim) {
+        acc.add_block(block);
+    }
+    acc.finish()
+}
+
+/// Like [`compute`] but streams the base vectors from a `*vecs` / `*bin` file of `T`.
+pub fn compute_file<T: VectorElement + FileElement, Q: VectorElement>(
+    base_path: &Path,
+    queries: &[Q],
+    dim: usize,
+    k: usize,
+    metric: MetricType,
+) -> io::Result<GroundTruth> {
+    let format = Format::from_path(base_path).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{}: unknown vector file extension", base_path.display()),
+        )
+    })?;
+    let mut acc = Accumulator::new(queries, dim, k, metric);
+    read_blocks::<T>(base_path, format, DEFAULT_BLOCK_ROWS, |block, _| {
+        if block.cols() != dim {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("base has dimension {}, queries have {dim}", block.cols()),
+            ));
+        }
+        acc.add_block(block.as_slice());
+        Ok(())
+    })?;
+    acc.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::write_bin;
+    use tempfile::NamedTempFile;
+
+    fn brute_force(base: &[f32], query: &[f32], k: usize, metric: MetricType) -> Vec<u32> {
+        let mut scored: Vec<(f32, u32)> = base
+            .chunks_exact(query.len())
+            .enumerate()
+            .map(|(i, x)| {
+                let s = match metric {
+                    MetricType::L2 => query.iter().zip(x).map(|(a, b)| (a - b) * (a - b)).sum(),
+                    MetricType::IP => -query.iter().zip(x).map(|(a, b)| a * b).sum::<f32>(),
+                };
+                (s, i as u32)
+            })
+            .collect();
+        scored.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        scored[..k].iter().map(|&(_, id)| id).collect()
+    }
+
+    #[test]
+    fn test_matches_brute_force_across_blocks() {
+        let dim = 16;
+        let base: Vec<f32> = (0..500 * dim)
+            .map(|i| ((i * 7919) % 1013) as f32 / 100.0)
+            .collect();
+        let queries = &base[3 * dim..8 * dim];
+        for metric in [MetricType::L2, MetricType::IP] {
+            let mut acc = Accumulator::new(queries, dim, 10, metric);
+            // uneven blocks to check the running ids
+            for block in base.chunks(37 * dim) {
+                acc.add_block(block);
+            }
+            assert_eq!(acc.num_base(), 500);
+            let gt = acc.finish().unwrap();
+            for (q, query) in queries.chunks_exact(dim).enumerate() {
+                assert_eq!(gt.ids.row(q), brute_force(&base, query, 10, metric));
+            }
+            let d = gt.distances.row(0);
+            match metric {
+                MetricType::L2 => assert!(d.windows(2).all(|w| w[0] <= w[1])),
+                MetricType::IP => assert!(d.windows(2).all(|w| w[0] >= w[1])),
+            }
+        }
+    }
+
+    #[test]
+    fn test_compute_file_matches_compute() {
+        let dim = 8;
+        let base: Vec<u8> = (0..300 * dim).map(|i| ((i * 31) % 251) as u8).collect();
+        let queries: Vec<f32> = (0..4 * dim).map(|i| (i % 200) as f32).collect();
+        let file = NamedTempFile::with_suffix(".u8bin").unwrap();
+        write_bin(file.path(), &Matrix::new(base.clone(), 300, dim)).unwrap();
+
+        let in_memory = compute(&base, &queries, dim, 5, MetricType::L2).unwrap();
+        let streamed =
+            compute_file::<u8, f32>(file.path(), &queries, dim, 5, MetricType::L2).unwrap();
+        assert_eq!(in_memory, streamed);
+        let widened = u8::slice_to_f32(&base);
+        assert_eq!(
+            in_memory.ids.row(0),
+            brute_force(&widened, &queries[..dim], 5, MetricType::L2)
+        );
+        assert!(compute(&base[..4 * dim], &queries, dim, 5, MetricType::L2).is_err());
+    }
+}
@@ -0,0 +1,548 @@
+use std::io;
+use std::os::raw::{c_int, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+use std::sync::{Arc, Mutex, RwLock};
+
+use rabitq_sys as ffi;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::index::sample;
+
+use super::{
+    AnnIndex, BuildMonitor, Distribution, GraphStats, IndexKind, IndexMeta, IndexStats,
+    SearchParams, SearchResult, SearchStats, c_path, invalid_input, num_rows, rotator_error,
+};
+use crate::element::VectorElement;
+use crate::estimator::QueryPrecision;
+use crate::io::{FileElement, VectorFile};
+use crate::kmeans::KMeans;
+use crate::{MetricType, Rotator};
+
+/// Build parameters of an [`Hnsw`] index
+#[derive(Clone, Debug)]
+pub struct HnswParams {
+    /// Max out-degree on the upper levels, twice that on level 0
+    pub m: usize,
+    pub ef_construction: usize,
+    /// Bits per dimension, 1 to 9
+    pub total_bits: usize,
+    pub metric: MetricType,
+    /// Vectors are quantized against the nearest of these k-means centroids
+    pub num_clusters: usize,
+    pub faster_quant: bool,
+    /// Draws the k-means initialization, the rotation and the levels. The graph is the
+    /// same for a seed with `num_threads = 1`, parallel insertions depend on scheduling.
+    pub seed: u64,
+    /// 0 uses all cores
+    pub num_threads: usize,
+    /// Rotation to use instead of a random one, e.g. shared by the shards of a
+    /// [`ShardedIndex`](super::ShardedIndex)
+    pub rotator: Option<Arc<Rotator>>,
+    /// Progress and cancellation of the insertions, also of [`Hnsw::build_quantized`]
+    pub monitor: BuildMonitor,
+}
+
+impl Default for HnswParams {
+    fn default() -> Self {
+        Self {
+            m: 16,
+            ef_construction: 200,
+            total_bits: 7,
+            metric: MetricType::L2,
+            num_clusters: 16,
+            faster_quant: false,
+            seed: 42,
+            num_threads: 0,
+            rotator: None,
+            monitor: BuildMonitor::default(),
+        }
+    }
+}
+
+/// Settings of [`Hnsw::build_quantized`]
+#[derive(Clone, Debug)]
+pub struct QuantizedBuildParams {
+    /// Raw vectors read and quantized at a time
+    pub chunk_rows: usize,
+    /// Fraction of the insertions that re-read the raw vectors of their level-0 candidates
+    /// to rank them exactly, in `0..=1`
+    pub exact_sample: f32,
+    /// Vectors sampled to train the k-means centroids
+    pub train_rows: usize,
+}
+
+impl Default for QuantizedBuildParams {
+    fn default() -> Self {
+        Self {
+            chunk_rows: 100_000,
+            exact_sample: 0.0,
+            train_rows: 100_000,
+        }
+    }
+}
+
+/// HNSW graph over RaBitQ codes, supports L2 and inner product.
+pub struct Hnsw {
+    ptr: *mut ffi::HnswIndex,
+    meta: IndexMeta,
+    /// Shared by insertions, held exclusively by `save` and `stats` which walk the graph
+    writers: RwLock<()>,
+}
+
+// SAFETY: the C++ index locks between searches and insertions, whole-graph readers take
+// `writers`, other mutation needs `&mut self`
+unsafe impl Send for Hnsw {}
+unsafe impl Sync for Hnsw {}
+
+impl Hnsw {
+    /// Cluster `data` (`n * dim`) with k-means and build the graph. The C++ construction
+    /// needs all raw vectors at once, so non-`f32` input is widened into a full `f32` copy
+    /// first; [`build_quantized`](Self::build_quantized) avoids that for data in a file.
+    pub fn build<T: VectorElement>(
+        data: &[T],
+        dim: usize,
+        params: &HnswParams,
+    ) -> io::Result<Self> {
+        let n = num_rows(data, dim)?;
+        if n == 0 || params.m < 2 {
+            return Err(invalid_input(
+                "need at least one vector and m >= 2".to_string(),
+            ));
+        }
+        let num_clusters = params.num_clusters.clamp(1, n);
+        let mut kmeans = KMeans::new(num_clusters);
+        kmeans.seed = params.seed;
+        let mut clustering = kmeans.fit(data, dim);
+        let data = T::slice_to_f32(data);
+
+        let index = Self::new(n, dim, num_clusters, params)?;
+        let ret = unsafe {
+            ffi::rabitq_hnsw_construct(
+                index.ptr,
+                num_clusters,
+                clustering.centroids.as_ptr(),
+                n,
+                data.as_ptr(),
+                clustering.assignments.as_mut_ptr(),
+                resolve_threads(params.num_threads),
+                params.faster_quant,
+                &params.monitor.to_ffi(),
+            )
+        };
+        if ret != 0 {
+            return Err(params.monitor.build_error("HNSW construction"));
+        }
+        Ok(index)
+    }
+
+    /// Build from a vector file without loading it. Raw vectors are read `chunk_rows` at a
+    /// time to be quantized, and neighbours are chosen on distances estimated from the
+    /// RaBitQ codes instead of the raw vectors, so memory holds the index and one chunk.
+    /// K-means runs on a random sample of `train_rows` vectors.
+    pub fn build_quantized<T: FileElement + VectorElement>(
+        file: &VectorFile<T>,
+        params: &HnswParams,
+        build: &QuantizedBuildParams,
+    ) -> io::Result<Self> {
+        let (n, dim) = (file.rows(), file.cols());
+        if n == 0 || params.m < 2 {
+            return Err(invalid_input(
+                "need at least one vector and m >= 2".to_string(),
+            ));
+        }
+        if build.chunk_rows == 0 || !(0.0..=1.0).contains(&build.exact_sample) {
+            return Err(invalid_input(format!(
+                "chunk_rows must be positive and exact_sample in 0..=1, got {} and {}",
+                build.chunk_rows, build.exact_sample
+            )));
+        }
+        let num_clusters = params.num_clusters.clamp(1, n);
+        let mut rng = StdRng::seed_from_u64(params.seed);
+        let mut train_ids: Vec<u32> = sample(&mut rng, n, build.train_rows.clamp(1, n))
+            .into_iter()
+            .map(|i| i as u32)
+            .collect();
+        train_ids.sort_unstable();
+        let mut train = Vec::with_capacity(train_ids.len() * dim);
+        file.read_rows(&train_ids, &mut train)?;
+        let mut kmeans = KMeans::new(num_clusters);
+        kmeans.seed = params.seed;
+        let clustering = kmeans.fit(&train, dim);
+        drop(train);
+
+        let index = Self::new(n, dim, num_clusters, params)?;
+        let source = RowSource {
+            file,
+            error: Mutex::new(None),
+        };
+        let ret = unsafe {
+            ffi::rabitq_hnsw_construct_quantized(
+                index.ptr,
+                num_clusters,
+                clustering.centroids.as_ptr(),
+                n,
+                Some(fetch_rows::<T>),
+                &source as *const RowSource<T> as *mut c_void,
+                build.chunk_rows,
+                build.exact_sample,
+                resolve_threads(params.num_threads),
+                params.faster_quant,
+                &params.monitor.to_ffi(),
+            )
+        };
+        if let Some(err) = source.error.into_inner().unwrap_or_else(|e| e.into_inner()) {
+            return Err(err);
+        }
+        if ret != 0 {
+            return Err(params.monitor.build_error("HNSW construction"));
+        }
+        Ok(index)
+    }
+
+    fn new(n: usize, dim: usize, num_clusters: usize, params: &HnswParams) -> io::Result<Self> {
+        let ptr = unsafe {
+            ffi::rabitq_hnsw_new(
+                n,
+                dim,
+                params.total_bits,
+                params.m,
+                params.ef_construction,
+                params.seed as usize,
+                params.metric as ffi::MetricType,
+            )
+        };
+        if ptr.is_null() {
+            return Err(invalid_input(format!(
+                "total_bits must be in 1..=9, got {}",
+                params.total_bits
+            )));
+        }
+        let meta = IndexMeta::new(IndexKind::Hnsw, params.metric, n, dim, params.total_bits)
+            .with_param("clusters", num_clusters)
+            .with_param("m", params.m)
+            .with_param("ef_construction", params.ef_construction);
+        let index = Self::from_raw(ptr, meta);
+        if let Some(rotator) = &params.rotator
+            && unsafe { ffi::rabitq_hnsw_set_rotator(index.ptr, rotator.as_ptr()) } != 0
+        {
+            return Err(rotator_error(rotator, dim));
+        }
+        Ok(index)
+    }
+
+    fn from_raw(ptr: *mut ffi::HnswIndex, meta: IndexMeta) -> Self {
+        Self {
+            ptr,
+            meta,
+            writers: RwLock::new(()),
+        }
+    }
+
+    /// Load an index saved by [`AnnIndex::save`], taking the metric from its metadata file.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let metric = match IndexMeta::load(path)? {
+            Some(meta) => meta.metric,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!(
+                        "{} has no metadata file, use load_with_metric",
+                        path.display()
+                    ),
+                ));
+            }
+        };
+        Self::load_with_metric(path, metric)
+    }
+
+    /// Load an index file, which does not record the metric it was built with.
+    pub fn load_with_metric(path: &Path, metric: MetricType) -> io::Result<Self> {
+        let c_path = c_path(path)?;
+        let ptr = unsafe { ffi::rabitq_hnsw_load(c_path.as_ptr(), metric as ffi::MetricType) };
+        if ptr.is_null() {
+            return Err(io::Error::other(format!(
+                "failed to load {}",
+                path.display()
+            )));
+        }
+        let meta = match IndexMeta::load(path)? {
+            Some(meta) => meta,
+            None => unsafe {
+                IndexMeta::new(
+                    IndexKind::Hnsw,
+                    metric,
+                    ffi::rabitq_hnsw_num(ptr),
+                    ffi::rabitq_hnsw_dim(ptr),
+                    ffi::rabitq_hnsw_ex_bits(ptr) + 1,
+                )
+                .with_param("clusters", ffi::rabitq_hnsw_num_clusters(ptr))
+                .with_param("m", ffi::rabitq_hnsw_max_degree(ptr))
+                .with_param("ef_construction", ffi::rabitq_hnsw_ef_construction(ptr))
+            },
+        };
+        Ok(Self::from_raw(ptr, meta))
+    }
+
+    /// Add a vector after construction, quantized against the existing centroids and linked
+    /// on distances between the stored codes. Needs [`reserve`](Self::reserve) first, then
+    /// can run concurrently with searches and other insertions within its capacity.
+    pub fn insert<T: VectorElement>(&self, vector: &[T], label: u32) -> io::Result<()> {
+        if vector.len() != self.dim() {
+            return Err(invalid_input(format!(
+                "expected a vector of dim {}, got {}",
+                self.dim(),
+                vector.len()
+            )));
+        }
+        if self.len() >= self.capacity() {
+            return Err(invalid_input(format!(
+                "index is full with {} vectors, reserve room first",
+                self.capacity()
+            )));
+        }
+        let vector = T::slice_to_f32(vector);
+        let _guard = self.writers.read().unwrap_or_else(|e| e.into_inner());
+        if unsafe { ffi::rabitq_hnsw_insert(self.ptr, vector.as_ptr(), label) } != 0 {
+            return Err(invalid_input(format!(
+                "label {label} already exists or the index is full"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Make room for at least `additional` more vectors than [`len`](AnnIndex::len). Opens
+    /// the index for insertion, searches then lock the neighbour lists they read.
+    pub fn reserve(&mut self, additional: usize) -> io::Result<()> {
+        let capacity = self.len() + additional;
+        if unsafe { ffi::rabitq_hnsw_reserve(self.ptr, capacity) } != 0 {
+            return Err(io::Error::other(format!(
+                "failed to reserve room for {capacity} vectors"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Vectors the index holds without [`reserve`](Self::reserve), the build size at first
+    pub fn capacity(&self) -> usize {
+        unsafe { ffi::rabitq_hnsw_capacity(self.ptr) }
+    }
+
+    /// Search with distances, `k` (id, distance) pairs per query padded with `u32::MAX` and
+    /// infinity. Uses `params.ef`, `params.query_precision` and `params.budget` for each
+    /// query. `num_threads == 0` uses all cores.
+    ///
+    /// # Panics
+    ///
+    /// If `queries` is not a whole number of rows of the index dimension, or
+    /// `params.query_precision` is neither exact nor 1 to 16 bits.
+    pub fn search_with_distances(
+        &self,
+        queries: &[f32],
+        k: usize,
+        params: &SearchParams,
+        num_threads: usize,
+    ) -> (Vec<u32>, Vec<f32>) {
+        let results = self.search_all(queries, k, params, num_threads);
+        (results.ids, results.distances)
+    }
+
+    /// [`search_with_distances`](Self::search_with_distances) that also tells which queries
+    /// ran out of their budget and, if `params.stats` is set, what each did
+    fn search_all(
+        &self,
+        queries: &[f32],
+        k: usize,
+        params: &SearchParams,
+        num_threads: usize,
+    ) -> BatchResults {
+        let nq = queries.len() / self.dim();
+        assert_eq!(nq * self.dim(), queries.len());
+        let precision = params
+            .query_precision
+            .unwrap_or(QueryPrecision::SINGLE_DEFAULT);
+        assert!(
+            precision.valid_single(),
+            "HNSW queries must have 1 to 16 bits or be exact, got {precision}"
+        );
+        let threads = resolve_threads(num_threads);
+        let mut ids = vec![0u32; nq * k];
+        let mut dists = vec![0f32; nq * k];
+        let budget = params.budget.to_ffi();
+        let mut partial = vec![false; nq];
+        let mut stats = params
+            .stats
+            .then(|| vec![ffi::RabitqSearchStats::from(SearchStats::default()); nq]);
+        unsafe {
+            ffi::rabitq_hnsw_search(
+                self.ptr,
+                queries.as_ptr(),
+                nq,
+                k,
+                params.ef,
+                threads,
+                precision.code(),
+                ids.as_mut_ptr(),
+                dists.as_mut_ptr(),
+                budget.as_ref().map_or(ptr::null(), |b| b as *const _),
+                partial.as_mut_ptr(),
+                stats.as_mut().map_or(ptr::null_mut(), |s| s.as_mut_ptr()),
+            );
+        }
+        BatchResults {
+            ids,
+            distances: dists,
+            partial,
+            stats,
+        }
+    }
+
+    pub fn padded_dim(&self) -> usize {
+        unsafe { ffi::rabitq_hnsw_padded_dim(self.ptr) }
+    }
+
+    pub fn max_degree(&self) -> usize {
+        unsafe { ffi::rabitq_hnsw_max_degree(self.ptr) }
+    }
+
+    pub fn ex_bits(&self) -> usize {
+        unsafe { ffi::rabitq_hnsw_ex_bits(self.ptr) }
+    }
+}
+
+/// Flat results of a batch of queries
+struct BatchResults {
+    ids: Vec<u32>,
+    distances: Vec<f32>,
+    partial: Vec<bool>,
+    stats: Option<Vec<ffi::RabitqSearchStats>>,
+}
+
+fn resolve_threads(num_threads: usize) -> usize {
+    match num_threads {
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        t => t,
+    }
+}
+
+/// Context of [`fetch_rows`], keeps the first read error for [`Hnsw::build_quantized`]
+struct RowSource<'a, T> {
+    file: &'a VectorFile<T>,
+    error: Mutex<Option<io::Error>>,
+}
+
+unsafe extern "C" fn fetch_rows<T: FileElement + VectorElement>(
+    ctx: *mut c_void,
+    ids: *const u32,
+    count: usize,
+    out: *mut f32,
+) -> c_int {
+    // SAFETY: ctx is the RowSource passed by build_quantized, alive during construction
+    let source = unsafe { &*(ctx as *const RowSource<T>) };
+    let (ids, out) = unsafe {
+        (
+            std::slice::from_raw_parts(ids, count),
+            std::slice::from_raw_parts_mut(out, count * source.file.cols()),
+        )
+    };
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut rows = Vec::with_capacity(out.len());
+        source.file.read_rows(ids, &mut rows)?;
+        out.copy_from_slice(&T::slice_to_f32(&rows));
+        Ok(())
+    }))
+    .unwrap_or_else(|_| Err(io::Error::other("panic while reading raw vectors")));
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            let mut error = source.error.lock().unwrap_or_else(|e| e.into_inner());
+            error.get_or_insert(err);
+            -1
+        }
+    }
+}
+
+impl AnnIndex for Hnsw {
+    fn meta(&self) -> &IndexMeta {
+        &self.meta
+    }
+
+    fn meta_mut(&mut self) -> &mut IndexMeta {
+        &mut self.meta
+    }
+
+    fn search_result(&self, query: &[f32], k: usize, params: &SearchParams) -> SearchResult {
+        assert_eq!(query.len(), self.dim());
+        let results = self.search_all(query, k, params, 1);
+        SearchResult {
+            ids: results.ids,
+            distances: results.distances,
+            partial: results.partial[0],
+            stats: results.stats.map(|s| s[0].into()),
+        }
+    }
+
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let c_path = c_path(path)?;
+        let _guard = self.writers.write().unwrap_or_else(|e| e.into_inner());
+        if unsafe { ffi::rabitq_hnsw_save(self.ptr, c_path.as_ptr()) } != 0 {
+            return Err(io::Error::other(format!(
+                "failed to write {}",
+                path.display()
+            )));
+        }
+        let mut meta = self.meta.clone();
+        meta.num = self.len();
+        meta.save(path)
+    }
+
+    fn len(&self) -> usize {
+        unsafe { ffi::rabitq_hnsw_num(self.ptr) }
+    }
+
+    fn stats(&self) -> IndexStats {
+        let _guard = self.writers.write().unwrap_or_else(|e| e.into_inner());
+        let num_levels = unsafe { ffi::rabitq_hnsw_num_levels(self.ptr) };
+        let mut degrees = vec![0u32; self.len()];
+        let levels = (0..num_levels)
+            .map(|level| {
+                let count = unsafe {
+                    ffi::rabitq_hnsw_level_degrees(self.ptr, level, degrees.as_mut_ptr())
+                };
+                let level_degrees: Vec<usize> =
+                    degrees[..count].iter().map(|&d| d as usize).collect();
+                Distribution::exact(&level_degrees)
+            })
+            .collect();
+        let mut bytes = [0usize; 6];
+        unsafe { ffi::rabitq_hnsw_memory(self.ptr, bytes.as_mut_ptr()) };
+        IndexStats {
+            kind: IndexKind::Hnsw,
+            num: self.len(),
+            dim: self.dim(),
+            memory: [
+                "links",
+                "labels",
+                "bin_data",
+                "ex_data",
+                "upper_links",
+                "centroids",
+            ]
+            .into_iter()
+            .zip(bytes)
+            .collect(),
+            clusters: None,
+            graph: Some(GraphStats {
+                entry_point: unsafe { ffi::rabitq_hnsw_entry_point(self.ptr) },
+                levels,
+            }),
+        }
+    }
+}
+
+impl Drop for Hnsw {
+    fn drop(&mut self) {
+        unsafe { ffi::rabitq_hnsw_free(self.ptr) };
+    }
+}
@@ -0,0 +1,544 @@
+use std::io;
+use std::path::Path;
+use std::ptr;
+use std::sync::Arc;
+
+use rabitq_sys as ffi;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::index::sample;
+
+use super::{
+    AnnIndex, BuildMonitor, ClusterStats, IndexKind, IndexMeta, IndexStats, SearchParams,
+    SearchResult, SearchStats, c_path, invalid_input, num_rows, rotator_error,
+};
+use crate::element::VectorElement;
+use crate::estimator::QueryPrecision;
+use crate::io::{FileElement, VectorFile};
+use crate::kmeans::{self, KMeans};
+use crate::{MetricType, Rotator};
+
+/// Build parameters of an [`Ivf`] index
+#[derive(Clone, Debug)]
+pub struct IvfParams {
+    pub num_clusters: usize,
+    /// Bits per dimension, 1 to 9
+    pub total_bits: usize,
+    /// Use the faster but slightly less accurate quantization
+    pub faster_quant: bool,
+    pub kmeans_iters: usize,
+    /// Draws the k-means initialization and the rotation, the index is the same for a seed
+    pub seed: u64,
+    /// Rotation to use instead of a random one, e.g. shared by the shards of a
+    /// [`ShardedIndex`](super::ShardedIndex)
+    pub rotator: Option<Arc<Rotator>>,
+    /// Progress and cancellation of the quantization of the clusters, or of the vectors for
+    /// a build from non-`f32` input or through an [`IvfBuilder`]
+    pub monitor: BuildMonitor,
+}
+
+impl Default for IvfParams {
+    fn default() -> Self {
+        Self {
+            num_clusters: 4096,
+            total_bits: 7,
+            faster_quant: false,
+            kmeans_iters: 10,
+            seed: 42,
+            rotator: None,
+            monitor: BuildMonitor::default(),
+        }
+    }
+}
+
+/// Settings of the streaming builds, [`Ivf::build_from_file`] and [`Ivf::build_streaming`]
+#[derive(Clone, Debug)]
+pub struct StreamBuildParams {
+    /// Vectors read at a time from a file
+    pub chunk_rows: usize,
+    /// Vectors the k-means centroids are trained on
+    pub train_rows: usize,
+}
+
+impl Default for StreamBuildParams {
+    fn default() -> Self {
+        Self {
+            chunk_rows: 100_000,
+            train_rows: 100_000,
+        }
+    }
+}
+
+/// IVF index with RaBitQ-quantized clusters, L2 only.
+pub struct Ivf {
+    ptr: *mut ffi::IvfIndex,
+    meta: IndexMeta,
+}
+
+// SAFETY: search only reads the index, mutation needs `&mut self`
+unsafe impl Send for Ivf {}
+unsafe impl Sync for Ivf {}
+
+impl Ivf {
+    /// Cluster `data` (`n * dim`) with k-means and build the index. Non-`f32` input is
+    /// quantized through an [`IvfBuilder`], widened `StreamBuildParams::chunk_rows` vectors at
+    /// a time; its progress is then reported in the `"quantize vectors"` phase.
+    pub fn build<T: VectorElement>(data: &[T], dim: usize, params: &IvfParams) -> io::Result<Self> {
+        let n = num_rows(data, dim)?;
+        if params.num_clusters == 0 || params.num_clusters > n {
+            return Err(invalid_input(format!(
+                "num_clusters must be in 1..={n}, got {}",
+                params.num_clusters
+            )));
+        }
+        let mut kmeans = KMeans::new(params.num_clusters);
+        kmeans.iters = params.kmeans_iters;
+        kmeans.seed = params.seed;
+        let clustering = kmeans.fit(data, dim);
+        if let Some(data) = T::as_f32(data) {
+            return Self::from_clusters(
+                data,
+                dim,
+                &clustering.centroids,
+                &clustering.assignments,
+                params,
+            );
+        }
+
+        let mut sizes = vec![0usize; params.num_clusters];
+        for &c in &clustering.assignments {
+            sizes[c as usize] += 1;
+        }
+        let chunk_rows = StreamBuildParams::default().chunk_rows;
+        let mut builder = IvfBuilder::new(dim, &clustering.centroids, &sizes, params)?;
+        for (chunk, ids) in data
+            .chunks(chunk_rows * dim)
+            .zip(clustering.assignments.chunks(chunk_rows))
+        {
+            builder.add(chunk, ids)?;
+        }
+        builder.finish()
+    }
+
+    /// Build the index from an existing partition, e.g. the output of `python/ivf.py`.
+    pub fn from_clusters(
+        data: &[f32],
+        dim: usize,
+        centroids: &[f32],
+        cluster_ids: &[u32],
+        params: &IvfParams,
+    ) -> io::Result<Self> {
+        let n = num_rows(data, dim)?;
+        if centroids.len() != params.num_clusters * dim || cluster_ids.len() != n {
+            return Err(invalid_input(format!(
+                "expected {} centroids and {n} cluster ids",
+                params.num_clusters
+            )));
+        }
+        let index = Self::new(n, dim, params)?;
+        let ret = unsafe {
+            ffi::rabitq_ivf_construct(
+                index.ptr,
+                data.as_ptr(),
+                centroids.as_ptr(),
+                cluster_ids.as_ptr(),
+                params.faster_quant,
+                &params.monitor.to_ffi(),
+            )
+        };
+        if ret != 0 {
+            return Err(params.monitor.build_error("IVF construction"));
+        }
+        Ok(index)
+    }
+
+    /// Build from a vector file without loading it. K-means runs on a random sample of
+    /// `train_rows` vectors, then the file is read twice `chunk_rows` vectors at a time:
+    /// once to assign the vectors to clusters and count the cluster sizes, once to quantize
+    /// them into storage allocated for those sizes. Memory holds the index, one chunk and a
+    /// cluster id per vector.
+    pub fn build_from_file<T: FileElement + VectorElement>(
+        file: &VectorFile<T>,
+        params: &IvfParams,
+        stream: &StreamBuildParams,
+    ) -> io::Result<Self> {
+        let (n, dim) = (file.rows(), file.cols());
+        if n == 0 || stream.chunk_rows == 0 {
+            return Err(invalid_input(
+                "need at least one vector and chunk_rows positive".to_string(),
+            ));
+        }
+        let mut rng = StdRng::seed_from_u64(params.seed);
+        let mut train_ids: Vec<u32> = sample(&mut rng, n, stream.train_rows.clamp(1, n))
+            .into_iter()
+            .map(|i| i as u32)
+            .collect();
+        train_ids.sort_unstable();
+        let mut train = Vec::with_capacity(train_ids.len() * dim);
+        file.read_rows(&train_ids, &mut train)?;
+        let centroids = Self::train(&train, dim, params)?;
+        drop(train);
+
+        let chunks = move || {
+            (0..n).step_by(stream.chunk_rows).map(move |start| {
+                let end = n.min(start + stream.chunk_rows);
+                let ids: Vec<u32> = (start as u32..end as u32).collect();
+                let mut rows = Vec::with_capacity(ids.len() * dim);
+                file.read_rows(&ids, &mut rows)?;
+                Ok(rows)
+            })
+        };
+        Self::build_two_pass(dim, &centroids, chunks, n, params)
+    }
+
+    /// Build from chunks of vectors (`rows * dim` each) too large to hold at once. `source`
+    /// is called three times and must yield the same vectors each time: k-means trains on
+    /// the first `train_rows` vectors, so shuffled data gives better clusters, then one pass
+    /// assigns the vectors to clusters and counts the cluster sizes and the last one
+    /// quantizes them. Vectors get their ids in the order they come. The clustering pass
+    /// reports a total of 0 since the number of vectors is not known yet.
+    pub fn build_streaming<T, I>(
+        dim: usize,
+        mut source: impl FnMut() -> I,
+        params: &IvfParams,
+        stream: &StreamBuildParams,
+    ) -> io::Result<Self>
+    where
+        T: VectorElement,
+        I: IntoIterator<Item = io::Result<Vec<T>>>,
+    {
+        let mut train = Vec::new();
+        for chunk in source() {
+            if train.len() >= stream.train_rows.max(1) * dim {
+                break;
+            }
+            train.extend(chunk?);
+        }
+        train.truncate(stream.train_rows.max(1) * dim);
+        let centroids = Self::train(&train, dim, params)?;
+        drop(train);
+        Self::build_two_pass(dim, &centroids, source, 0, params)
+    }
+
+    /// K-means centroids of the sample `train`
+    fn train<T: VectorElement>(
+        train: &[T],
+        dim: usize,
+        params: &IvfParams,
+    ) -> io::Result<Vec<f32>> {
+        let n = num_rows(train, dim)?;
+        if params.num_clusters == 0 || params.num_clusters > n {
+            return Err(invalid_input(format!(
+                "num_clusters must be in 1..={n}, the training vectors, got {}",
+                params.num_clusters
+            )));
+        }
+        let mut kmeans = KMeans::new(params.num_clusters);
+        kmeans.iters = params.kmeans_iters;
+        kmeans.seed = params.seed;
+        Ok(kmeans.fit(train, dim).centroids)
+    }
+
+    /// Assign and count in a first pass over `source`, quantize in a second. `total` is the
+    /// number of vectors reported in the first pass, 0 if unknown.
+    fn build_two_pass<T, I>(
+        dim: usize,
+        centroids: &[f32],
+        mut source: impl FnMut() -> I,
+        total: usize,
+        params: &IvfParams,
+    ) -> io::Result<Self>
+    where
+        T: VectorElement,
+        I: IntoIterator<Item = io::Result<Vec<T>>>,
+    {
+        let mut cluster_ids = Vec::new();
+        let mut sizes = vec![0usize; params.num_clusters];
+        for chunk in source() {
+            params.monitor.check("IVF construction")?;
+            let chunk = chunk?;
+            let rows = num_rows(&chunk, dim)?;
+            let start = cluster_ids.len();
+            cluster_ids.resize(start + rows, 0);
+            kmeans::assign(&chunk, dim, centroids, &mut cluster_ids[start..]);
+            for &c in &cluster_ids[start..] {
+                sizes[c as usize] += 1;
+            }
+            params
+                .monitor
+                .report("assign clusters", cluster_ids.len(), total);
+        }
+
+        let mut builder = IvfBuilder::new(dim, centroids, &sizes, params)?;
+        for chunk in source() {
+            let chunk = chunk?;
+            let start = builder.added();
+            let rows = num_rows(&chunk, dim)?;
+            let Some(ids) = cluster_ids.get(start..start + rows) else {
+                return Err(invalid_input(
+                    "the second pass over the vectors yielded more than the first".to_string(),
+                ));
+            };
+            builder.add(&chunk, ids)?;
+        }
+        builder.finish()
+    }
+
+    /// Index with storage and rotator, not constructed yet
+    fn new(n: usize, dim: usize, params: &IvfParams) -> io::Result<Self> {
+        let ptr = unsafe {
+            ffi::rabitq_ivf_new(n, dim, params.num_clusters, params.total_bits, params.seed)
+        };
+        if ptr.is_null() {
+            return Err(invalid_input(format!(
+                "total_bits must be in 1..=9, got {}",
+                params.total_bits
+            )));
+        }
+        let meta = IndexMeta::new(IndexKind::Ivf, MetricType::L2, n, dim, params.total_bits)
+            .with_param("clusters", params.num_clusters);
+        let index = Self { ptr, meta };
+        if let Some(rotator) = &params.rotator
+            && unsafe { ffi::rabitq_ivf_set_rotator(index.ptr, rotator.as_ptr()) } != 0
+        {
+            return Err(rotator_error(rotator, dim));
+        }
+        Ok(index)
+    }
+
+    /// Load an index saved by [`AnnIndex::save`] or by the C++ samples.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let c_path = c_path(path)?;
+        let ptr = unsafe { ffi::rabitq_ivf_load(c_path.as_ptr()) };
+        if ptr.is_null() {
+            return Err(io::Error::other(format!(
+                "failed to load {}",
+                path.display()
+            )));
+        }
+        let meta = match IndexMeta::load(path)? {
+            Some(meta) => meta,
+            None => unsafe {
+                IndexMeta::new(
+                    IndexKind::Ivf,
+                    MetricType::L2,
+                    ffi::rabitq_ivf_num(ptr),
+                    ffi::rabitq_ivf_dim(ptr),
+                    ffi::rabitq_ivf_ex_bits(ptr) + 1,
+                )
+                .with_param("clusters", ffi::rabitq_ivf_num_clusters(ptr))
+            },
+        };
+        Ok(Self { ptr, meta })
+    }
+
+    pub fn padded_dim(&self) -> usize {
+        unsafe { ffi::rabitq_ivf_padded_dim(self.ptr) }
+    }
+
+    pub fn num_clusters(&self) -> usize {
+        unsafe { ffi::rabitq_ivf_num_clusters(self.ptr) }
+    }
+
+    pub fn ex_bits(&self) -> usize {
+        unsafe { ffi::rabitq_ivf_ex_bits(self.ptr) }
+    }
+}
+
+/// Streaming construction of an [`Ivf`] from a known partition: the storage of every cluster
+/// is allocated from the cluster sizes up front, then vectors are added in chunks of any
+/// size and quantized into their clusters. Vectors get their ids in the order they are
+/// added. The index is the one [`Ivf::from_clusters`] builds from the same vectors and
+/// cluster ids.
+pub struct IvfBuilder {
+    /// `None` once finished
+    index: Option<Ivf>,
+    ptr: *mut ffi::IvfBuilder,
+    /// Boxed so the pointer the C++ builder keeps stays valid when `self` moves
+    monitor: Box<BuildMonitor>,
+    num_clusters: usize,
+    added: usize,
+}
+
+// SAFETY: the builder and its index are only used through `&mut self`
+unsafe impl Send for IvfBuilder {}
+
+impl IvfBuilder {
+    /// `cluster_sizes[i]` vectors will be added to cluster `i`, whose centroid is row `i` of
+    /// `centroids`. Progress of the `"quantize vectors"` phase goes to `params.monitor`.
+    pub fn new(
+        dim: usize,
+        centroids: &[f32],
+        cluster_sizes: &[usize],
+        params: &IvfParams,
+    ) -> io::Result<Self> {
+        let n = cluster_sizes.iter().sum();
+        if n == 0
+            || cluster_sizes.len() != params.num_clusters
+            || centroids.len() != params.num_clusters * dim
+        {
+            return Err(invalid_input(format!(
+                "expected {} centroids and cluster sizes, summing to at least one vector",
+                params.num_clusters
+            )));
+        }
+        let index = Ivf::new(n, dim, params)?;
+        let monitor = Box::new(params.monitor.clone());
+        let ptr = unsafe {
+            ffi::rabitq_ivf_builder_new(
+                index.ptr,
+                centroids.as_ptr(),
+                cluster_sizes.as_ptr(),
+                params.faster_quant,
+                &monitor.to_ffi(),
+            )
+        };
+        if ptr.is_null() {
+            return Err(io::Error::other("IVF construction failed"));
+        }
+        Ok(Self {
+            index: Some(index),
+            ptr,
+            monitor,
+            num_clusters: params.num_clusters,
+            added: 0,
+        })
+    }
+
+    /// Quantize the vectors of `data` (`rows * dim`), `cluster_ids` giving the cluster of
+    /// each. Nothing is added if a cluster id is out of range or a cluster would get more
+    /// vectors than its size.
+    pub fn add<T: VectorElement>(&mut self, data: &[T], cluster_ids: &[u32]) -> io::Result<()> {
+        let index = self.index.as_ref().expect("builder is not finished");
+        let rows = num_rows(data, index.dim())?;
+        if cluster_ids.len() != rows {
+            return Err(invalid_input(format!(
+                "expected {rows} cluster ids, got {}",
+                cluster_ids.len()
+            )));
+        }
+        let data = T::slice_to_f32(data);
+        let ret = unsafe {
+            ffi::rabitq_ivf_builder_add(self.ptr, data.as_ptr(), cluster_ids.as_ptr(), rows)
+        };
+        if ret != 0 {
+            self.monitor.check("IVF construction")?;
+            return Err(invalid_input(format!(
+                "cluster ids must be below {} and clusters must not exceed their sizes",
+                self.num_clusters
+            )));
+        }
+        self.added += rows;
+        Ok(())
+    }
+
+    /// Number of vectors added so far
+    pub fn added(&self) -> usize {
+        self.added
+    }
+
+    /// Complete the index, every cluster must have got all its vectors.
+    pub fn finish(mut self) -> io::Result<Ivf> {
+        let n = self.index.as_ref().map_or(0, Ivf::len);
+        if self.added != n || unsafe { ffi::rabitq_ivf_builder_finish(self.ptr) } != 0 {
+            return Err(invalid_input(format!(
+                "{} of the {n} vectors were added",
+                self.added
+            )));
+        }
+        Ok(self.index.take().expect("builder is not finished"))
+    }
+}
+
+impl Drop for IvfBuilder {
+    fn drop(&mut self) {
+        // the C++ builder refers to the index, free it first
+        unsafe { ffi::rabitq_ivf_builder_free(self.ptr) };
+    }
+}
+
+impl AnnIndex for Ivf {
+    fn meta(&self) -> &IndexMeta {
+        &self.meta
+    }
+
+    fn meta_mut(&mut self) -> &mut IndexMeta {
+        &mut self.meta
+    }
+
+    fn search_result(&self, query: &[f32], k: usize, params: &SearchParams) -> SearchResult {
+        assert_eq!(query.len(), self.dim());
+        let mut results = vec![0u32; k];
+        let mut distances = vec![0f32; k];
+        let budget = params.budget.to_ffi();
+        let mut partial = false;
+        let mut stats = params
+            .stats
+            .then(|| ffi::RabitqSearchStats::from(SearchStats::default()));
+        let nprobe = params.nprobe.clamp(1, self.num_clusters());
+        let precision = params
+            .query_precision
+            .unwrap_or(QueryPrecision::Bits(if params.use_hacc { 16 } else { 8 }));
+        assert!(
+            precision.valid_batch(),
+            "IVF tables must have 8 or 16 bits or be exact, got {precision}"
+        );
+        unsafe {
+            ffi::rabitq_ivf_search(
+                self.ptr,
+                query.as_ptr(),
+                k,
+                nprobe,
+                results.as_mut_ptr(),
+                precision.code(),
+                distances.as_mut_ptr(),
+                budget.as_ref().map_or(ptr::null(), |b| b as *const _),
+                &mut partial,
+                stats.as_mut().map_or(ptr::null_mut(), |s| s as *mut _),
+            );
+        }
+        SearchResult {
+            ids: results,
+            distances,
+            partial,
+            stats: stats.map(SearchStats::from),
+        }
+    }
+
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let c_path = c_path(path)?;
+        if unsafe { ffi::rabitq_ivf_save(self.ptr, c_path.as_ptr()) } != 0 {
+            return Err(io::Error::other(format!(
+                "failed to write {}",
+                path.display()
+            )));
+        }
+        self.meta.save(path)
+    }
+
+    fn stats(&self) -> IndexStats {
+        let mut sizes = vec![0usize; self.num_clusters()];
+        let mut bytes = [0usize; 4];
+        unsafe {
+            ffi::rabitq_ivf_cluster_sizes(self.ptr, sizes.as_mut_ptr());
+            ffi::rabitq_ivf_memory(self.ptr, bytes.as_mut_ptr());
+        }
+        IndexStats {
+            kind: IndexKind::Ivf,
+            num: self.len(),
+            dim: self.dim(),
+            memory: ["batch_data", "ex_data", "ids", "centroids"]
+                .into_iter()
+                .zip(bytes)
+                .collect(),
+            clusters: Some(ClusterStats::new(sizes)),
+            graph: None,
+        }
+    }
+}
+
+impl Drop for Ivf {
+    fn drop(&mut self) {
+        unsafe { ffi::rabitq_ivf_free(self.ptr) };
+    }
+}
@@ -0,0 +1,213 @@
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use rabitq_sys as ffi;
+
+use super::{SearchParams, c_path, invalid_input, num_rows, rotator_error};
+use crate::Rotator;
+use crate::element::VectorElement;
+use crate::estimator::QueryPrecision;
+
+/// Build parameters of a [`MultiVectorIndex`]
+#[derive(Clone, Debug)]
+pub struct MultiVectorParams {
+    /// Bits per dimension, 1 to 9
+    pub total_bits: usize,
+    /// Use the faster but slightly less accurate quantization
+    pub faster_quant: bool,
+    /// Draws the rotation
+    pub seed: u64,
+    /// Rotation to use instead of a random one
+    pub rotator: Option<Arc<Rotator>>,
+}
+
+impl Default for MultiVectorParams {
+    fn default() -> Self {
+        Self {
+            total_bits: 7,
+            faster_quant: false,
+            seed: 42,
+            rotator: None,
+        }
+    }
+}
+
+/// Late-interaction (ColBERT-style) index over documents made of several token vectors.
+///
+/// A query is a set of tokens too, and a document scores the MaxSim of the two: the sum over
+/// query tokens of the best inner product with one of its tokens. Tokens are quantized for
+/// inner products, every document is scored from FastScan estimates and the best
+/// [`SearchParams::ef`] documents are re-scored with the remaining bits.
+///
+/// It is searched by token sets rather than single vectors, so it is not an [`AnnIndex`]
+/// and saves no `.meta` file; reopen it with [`MultiVectorIndex::load`].
+///
+/// [`AnnIndex`]: super::AnnIndex
+pub struct MultiVectorIndex {
+    ptr: *mut ffi::MultiVectorIndex,
+}
+
+// SAFETY: search only reads the index, mutation needs `&mut self`
+unsafe impl Send for MultiVectorIndex {}
+unsafe impl Sync for MultiVectorIndex {}
+
+impl MultiVectorIndex {
+    /// Build the index from the tokens of all documents (`sum(doc_lens) * dim`), stored
+    /// document after document. Every document needs at least one token. Non-`f32` tokens
+    /// are widened into a full `f32` copy first.
+    pub fn build<T: VectorElement>(
+        tokens: &[T],
+        dim: usize,
+        doc_lens: &[usize],
+        params: &MultiVectorParams,
+    ) -> io::Result<Self> {
+        let n = num_rows(tokens, dim)?;
+        if doc_lens.is_empty() || doc_lens.contains(&0) {
+            return Err(invalid_input(
+                "need at least one document and no empty ones".to_string(),
+            ));
+        }
+        let mut doc_offsets = Vec::with_capacity(doc_lens.len() + 1);
+        doc_offsets.push(0);
+        for len in doc_lens {
+            doc_offsets.push(doc_offsets.last().unwrap() + len);
+        }
+        if doc_offsets.last() != Some(&n) {
+            return Err(invalid_input(format!(
+                "documents hold {} tokens but {n} were given",
+                doc_offsets.last().unwrap()
+            )));
+        }
+        let ptr = unsafe { ffi::rabitq_maxsim_new(dim, params.total_bits, params.seed) };
+        if ptr.is_null() {
+            return Err(invalid_input(format!(
+                "total_bits must be in 1..=9, got {}",
+                params.total_bits
+            )));
+        }
+        let index = Self { ptr };
+        if let Some(rotator) = &params.rotator
+            && unsafe { ffi::rabitq_maxsim_set_rotator(index.ptr, rotator.as_ptr()) } != 0
+        {
+            return Err(rotator_error(rotator, dim));
+        }
+        let tokens = T::slice_to_f32(tokens);
+        let ret = unsafe {
+            ffi::rabitq_maxsim_construct(
+                index.ptr,
+                tokens.as_ptr(),
+                doc_offsets.as_ptr(),
+                doc_lens.len(),
+                params.faster_quant,
+            )
+        };
+        if ret != 0 {
+            return Err(io::Error::other("multi-vector construction failed"));
+        }
+        Ok(index)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let c_path = c_path(path)?;
+        let ptr = unsafe { ffi::rabitq_maxsim_load(c_path.as_ptr()) };
+        if ptr.is_null() {
+            return Err(io::Error::other(format!(
+                "failed to load {}",
+                path.display()
+            )));
+        }
+        Ok(Self { ptr })
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let c_path = c_path(path)?;
+        if unsafe { ffi::rabitq_maxsim_save(self.ptr, c_path.as_ptr()) } != 0 {
+            return Err(io::Error::other(format!(
+                "failed to write {}",
+                path.display()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Top `k` documents for the query tokens (`m * dim`) and their estimated MaxSim, best
+    /// first. `params.ef` documents are re-scored with the ex-bit codes, `use_hacc` and
+    /// `query_precision` pick the lookup tables as for IVF. Missing results are
+    /// [`INVALID_ID`](super::INVALID_ID) with a score of negative infinity.
+    ///
+    /// # Panics
+    ///
+    /// If `query_tokens` is empty or not a whole number of rows of the index dimension, or
+    /// `params.query_precision` is neither exact nor 8 or 16 bits.
+    pub fn search(
+        &self,
+        query_tokens: &[f32],
+        k: usize,
+        params: &SearchParams,
+    ) -> (Vec<u32>, Vec<f32>) {
+        assert!(
+            !query_tokens.is_empty() && query_tokens.len().is_multiple_of(self.dim()),
+            "query tokens must be a non-empty multiple of dim {}",
+            self.dim()
+        );
+        let precision = params
+            .query_precision
+            .unwrap_or(QueryPrecision::Bits(if params.use_hacc { 16 } else { 8 }));
+        assert!(
+            precision.valid_batch(),
+            "multi-vector tables must have 8 or 16 bits or be exact, got {precision}"
+        );
+        let mut results = vec![0u32; k];
+        let mut scores = vec![0f32; k];
+        unsafe {
+            ffi::rabitq_maxsim_search(
+                self.ptr,
+                query_tokens.as_ptr(),
+                query_tokens.len() / self.dim(),
+                k,
+                params.ef,
+                precision.code(),
+                results.as_mut_ptr(),
+                scores.as_mut_ptr(),
+            );
+        }
+        (results, scores)
+    }
+
+    pub fn num_docs(&self) -> usize {
+        unsafe { ffi::rabitq_maxsim_num_docs(self.ptr) }
+    }
+
+    pub fn num_tokens(&self) -> usize {
+        unsafe { ffi::rabitq_maxsim_num_tokens(self.ptr) }
+    }
+
+    pub fn dim(&self) -> usize {
+        unsafe { ffi::rabitq_maxsim_dim(self.ptr) }
+    }
+
+    pub fn padded_dim(&self) -> usize {
+        unsafe { ffi::rabitq_maxsim_padded_dim(self.ptr) }
+    }
+
+    pub fn ex_bits(&self) -> usize {
+        unsafe { ffi::rabitq_maxsim_ex_bits(self.ptr) }
+    }
+
+    /// Bytes held by the batch data, ex data and centroids
+    pub fn memory(&self) -> Vec<(&'static str, usize)> {
+        let mut bytes = [0usize; 3];
+        unsafe { ffi::rabitq_maxsim_memory(self.ptr, bytes.as_mut_ptr()) };
+        ["batch_data", "ex_data", "centroids"]
+            .into_iter()
+            .zip(bytes)
+            .collect()
+    }
+}
+
+impl Drop for MultiVectorIndex {
+    fn drop(&mut self) {
+        unsafe { ffi::rabitq_maxsim_free(self.ptr) };
+    }
+}
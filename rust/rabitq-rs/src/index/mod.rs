@@ -0,0 +1,602 @@
+//! Safe wrappers around the IVF, HNSW and SymphonyQG indexes, [`ShardedIndex`] over
+//! several of them, and the multi-vector [`MultiVectorIndex`].
+//!
+//! The C++ index files carry no type tag and HNSW does not store its metric, so every index
+//! saved from Rust also gets a small `<index>.meta` text file next to it (see [`IndexMeta`]).
+//! Indexes built by the C++ samples can still be opened with [`open_as`].
+
+mod hnsw;
+mod ivf;
+mod maxsim;
+mod monitor;
+mod qg;
+mod sharded;
+mod stats;
+
+pub use hnsw::{Hnsw, HnswParams, QuantizedBuildParams};
+pub use ivf::{Ivf, IvfBuilder, IvfParams, StreamBuildParams};
+pub use maxsim::{MultiVectorIndex, MultiVectorParams};
+pub use monitor::{BuildMonitor, CancelToken, ProgressFn};
+pub use qg::{Qg, QgParams};
+pub use sharded::{Routing, ShardParams, ShardedIndex, ShardedParams};
+pub use stats::{ClusterStats, Distribution, GraphStats, IndexStats, SearchStats};
+
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use rabitq_sys as ffi;
+use rayon::prelude::*;
+
+use crate::MetricType;
+use crate::Rotator;
+use crate::element::VectorElement;
+use crate::estimator::QueryPrecision;
+
+/// Id written for result slots that could not be filled
+pub const INVALID_ID: u32 = u32::MAX;
+
+/// Metadata keys of the default search parameters, see [`IndexMeta::default_search_params`]
+pub const DEFAULT_NPROBE: &str = "default_nprobe";
+pub const DEFAULT_EF: &str = "default_ef";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexKind {
+    Ivf,
+    Hnsw,
+    Qg,
+}
+
+impl fmt::Display for IndexKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexKind::Ivf => f.write_str("ivf"),
+            IndexKind::Hnsw => f.write_str("hnsw"),
+            IndexKind::Qg => f.write_str("qg"),
+        }
+    }
+}
+
+impl FromStr for IndexKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ivf" => Ok(IndexKind::Ivf),
+            "hnsw" => Ok(IndexKind::Hnsw),
+            "qg" | "symqg" => Ok(IndexKind::Qg),
+            _ => Err(format!(
+                "unknown index kind `{s}`, expected ivf, hnsw or qg"
+            )),
+        }
+    }
+}
+
+/// Per-query search knobs. `nprobe` is used by IVF, `ef` by HNSW and QG and as the number of
+/// documents re-scored by [`MultiVectorIndex`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SearchParams {
+    pub nprobe: usize,
+    pub ef: usize,
+    pub use_hacc: bool,
+    /// Query precision, `None` keeps the index default: 16-bit (`use_hacc`) or 8-bit tables
+    /// for IVF, a 4-bit query for HNSW and 8-bit tables for QG. IVF and QG accept 8, 16 or
+    /// exact, HNSW 1 to 16 bits or exact. Searches panic on others, [`validate`] checks
+    /// them up front.
+    ///
+    /// [`validate`]: SearchParams::validate
+    pub query_precision: Option<QueryPrecision>,
+    /// Limits on the work of each query, unlimited by default
+    pub budget: SearchBudget,
+    /// Count what each query did into [`SearchResult::stats`]
+    pub stats: bool,
+}
+
+impl Default for SearchParams {
+    fn default() -> Self {
+        Self {
+            nprobe: 64,
+            ef: 100,
+            use_hacc: true,
+            query_precision: None,
+            budget: SearchBudget::default(),
+            stats: false,
+        }
+    }
+}
+
+/// Limits on the work of one search, `None` is unlimited. A search that reaches one stops,
+/// keeps the best results found so far and reports them as partial. [`ShardedIndex`] gives
+/// every shard the whole budget.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SearchBudget {
+    /// Wall-clock time from the start of the search, checked between clusters, batches and
+    /// graph vertices
+    pub time: Option<Duration>,
+    /// Clusters probed by IVF
+    pub max_clusters: Option<usize>,
+    /// Vectors whose distance is estimated or computed. QG counts `1 + degree` per vertex.
+    pub max_distances: Option<usize>,
+    /// Ex-bit refinements of an estimate by IVF and HNSW, past it the 1-bit estimates
+    /// stand in
+    pub max_boosts: Option<usize>,
+}
+
+impl SearchBudget {
+    pub fn is_unlimited(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The C side reads 0 as unlimited, so a zero limit becomes 1
+    pub(crate) fn to_ffi(self) -> Option<ffi::RabitqSearchBudget> {
+        if self.is_unlimited() {
+            return None;
+        }
+        let limit = |value: Option<usize>| value.map_or(0, |v| v.max(1));
+        Some(ffi::RabitqSearchBudget {
+            time_us: self.time.map_or(0, |t| {
+                u64::try_from(t.as_micros()).unwrap_or(u64::MAX).max(1)
+            }),
+            max_clusters: limit(self.max_clusters),
+            max_distances: limit(self.max_distances),
+            max_boosts: limit(self.max_boosts),
+        })
+    }
+}
+
+/// Results of one search
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchResult {
+    /// `k` ids padded with [`INVALID_ID`]
+    pub ids: Vec<u32>,
+    /// Distances of the ids padded with infinity
+    pub distances: Vec<f32>,
+    /// The budget ran out, the results are the best found until then
+    pub partial: bool,
+    /// What the search did, if [`SearchParams::stats`] was set
+    pub stats: Option<SearchStats>,
+}
+
+impl SearchParams {
+    /// Check that `query_precision` is supported by indexes of `kind`, searching with an
+    /// unsupported one panics.
+    pub fn validate(&self, kind: IndexKind) -> io::Result<()> {
+        let Some(precision) = self.query_precision else {
+            return Ok(());
+        };
+        let valid = match kind {
+            IndexKind::Ivf | IndexKind::Qg => precision.valid_batch(),
+            IndexKind::Hnsw => precision.valid_single(),
+        };
+        if valid {
+            Ok(())
+        } else {
+            let expected = match kind {
+                IndexKind::Ivf | IndexKind::Qg => "8, 16 or exact",
+                IndexKind::Hnsw => "1 to 16 or exact",
+            };
+            Err(invalid_input(format!(
+                "{kind} index does not support query precision {precision}, expected {expected}"
+            )))
+        }
+    }
+}
+
+/// Metadata saved next to an index as `<index>.meta`, one `key = value` per line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexMeta {
+    pub kind: IndexKind,
+    pub metric: MetricType,
+    pub num: usize,
+    pub dim: usize,
+    pub total_bits: usize,
+    /// Build parameters, e.g. `clusters`, `m`, `ef_construction`, `degree`
+    pub params: BTreeMap<String, String>,
+}
+
+impl IndexMeta {
+    pub fn new(
+        kind: IndexKind,
+        metric: MetricType,
+        num: usize,
+        dim: usize,
+        total_bits: usize,
+    ) -> Self {
+        Self {
+            kind,
+            metric,
+            num,
+            dim,
+            total_bits,
+            params: BTreeMap::new(),
+        }
+    }
+
+    /// Record a build parameter
+    pub fn with_param(mut self, key: &str, value: impl ToString) -> Self {
+        self.params.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Look up a build parameter
+    pub fn param<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.params.get(key).and_then(|v| v.parse().ok())
+    }
+
+    /// Search parameters stored by the tuner, falling back to [`SearchParams::default`]
+    pub fn default_search_params(&self) -> SearchParams {
+        let defaults = SearchParams::default();
+        SearchParams {
+            nprobe: self.param(DEFAULT_NPROBE).unwrap_or(defaults.nprobe),
+            ef: self.param(DEFAULT_EF).unwrap_or(defaults.ef),
+            ..defaults
+        }
+    }
+
+    /// Path of the metadata file belonging to `index_path`
+    pub fn path_for(index_path: &Path) -> PathBuf {
+        let mut path = index_path.as_os_str().to_owned();
+        path.push(".meta");
+        PathBuf::from(path)
+    }
+
+    pub fn save(&self, index_path: &Path) -> io::Result<()> {
+        let mut text = format!(
+            "kind = {}\nmetric = {}\nnum = {}\ndim = {}\ntotal_bits = {}\n",
+            self.kind, self.metric, self.num, self.dim, self.total_bits
+        );
+        for (key, value) in &self.params {
+            text.push_str(&format!("{key} = {value}\n"));
+        }
+        fs::write(Self::path_for(index_path), text)
+    }
+
+    /// Read the metadata of `index_path`, `Ok(None)` if there is none.
+    pub fn load(index_path: &Path) -> io::Result<Option<Self>> {
+        let path = Self::path_for(index_path);
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let bad = |msg: String| invalid_data(format!("{}: {msg}", path.display()));
+
+        let mut entries = BTreeMap::new();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| bad(format!("malformed line `{line}`")))?;
+            entries.insert(key.trim().to_string(), value.trim().to_string());
+        }
+        let mut take = |key: &str| {
+            entries
+                .remove(key)
+                .ok_or_else(|| bad(format!("missing `{key}`")))
+        };
+        let kind = take("kind")?.parse().map_err(bad)?;
+        let metric = take("metric")?.parse().map_err(bad)?;
+        let num = take("num")?.parse().map_err(|e| bad(format!("num: {e}")))?;
+        let dim = take("dim")?.parse().map_err(|e| bad(format!("dim: {e}")))?;
+        let total_bits = take("total_bits")?
+            .parse()
+            .map_err(|e| bad(format!("total_bits: {e}")))?;
+        Ok(Some(Self {
+            kind,
+            metric,
+            num,
+            dim,
+            total_bits,
+            params: entries,
+        }))
+    }
+}
+
+/// Operations shared by all index types
+pub trait AnnIndex: Send + Sync {
+    fn meta(&self) -> &IndexMeta;
+
+    /// Metadata written by the next [`save`](AnnIndex::save), e.g. to store tuned defaults
+    fn meta_mut(&mut self) -> &mut IndexMeta;
+
+    /// Search one raw (unrotated) query within `params.budget`. Distances are squared L2,
+    /// or 1 minus the inner product for [`MetricType::IP`], estimated from the codes by IVF
+    /// and HNSW and exact for QG.
+    ///
+    /// # Panics
+    ///
+    /// As for [`search`](AnnIndex::search).
+    fn search_result(&self, query: &[f32], k: usize, params: &SearchParams) -> SearchResult;
+
+    /// Search one raw (unrotated) query, returning `k` ids padded with [`INVALID_ID`] and
+    /// their distances padded with infinity, see [`search_result`](AnnIndex::search_result).
+    ///
+    /// # Panics
+    ///
+    /// As for [`search`](AnnIndex::search).
+    fn search_scored(
+        &self,
+        query: &[f32],
+        k: usize,
+        params: &SearchParams,
+    ) -> (Vec<u32>, Vec<f32>) {
+        let result = self.search_result(query, k, params);
+        (result.ids, result.distances)
+    }
+
+    /// Search one raw (unrotated) query, returning `k` ids padded with [`INVALID_ID`].
+    ///
+    /// # Panics
+    ///
+    /// If `query` does not have the index dimension, or `params.query_precision` is not
+    /// supported by the index, see [`SearchParams::validate`].
+    fn search(&self, query: &[f32], k: usize, params: &SearchParams) -> Vec<u32> {
+        self.search_scored(query, k, params).0
+    }
+
+    /// Write the index and its metadata file
+    fn save(&self, path: &Path) -> io::Result<()>;
+
+    /// Memory use per section and cluster or degree distributions
+    fn stats(&self) -> IndexStats;
+
+    fn kind(&self) -> IndexKind {
+        self.meta().kind
+    }
+
+    fn len(&self) -> usize {
+        self.meta().num
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn dim(&self) -> usize {
+        self.meta().dim
+    }
+}
+
+/// Search every row of `queries` (`nq * dim`), returning `nq * k` ids. `num_threads == 0`
+/// uses all cores.
+///
+/// # Panics
+///
+/// If `queries` is not a whole number of rows of the index dimension, or
+/// `params.query_precision` is not supported by the index, see [`SearchParams::validate`].
+pub fn search_batch<T: VectorElement>(
+    index: &dyn AnnIndex,
+    queries: &[T],
+    k: usize,
+    params: &SearchParams,
+    num_threads: usize,
+) -> Vec<u32> {
+    let dim = index.dim();
+    assert!(
+        queries.len().is_multiple_of(dim),
+        "queries do not match the index dimension"
+    );
+    let mut results = vec![INVALID_ID; queries.len() / dim * k];
+    if k == 0 {
+        return results;
+    }
+    let run = || {
+        results
+            .par_chunks_mut(k)
+            .zip(queries.par_chunks_exact(dim))
+            .for_each(|(out, query)| {
+                out.copy_from_slice(&index.search(&T::slice_to_f32(query), k, params));
+            });
+    };
+    with_threads(num_threads, run);
+    results
+}
+
+/// Run `f` on a rayon pool of `num_threads` threads (the global pool if 0).
+pub fn with_threads<R: Send>(num_threads: usize, f: impl FnOnce() -> R + Send) -> R {
+    if num_threads == 0 {
+        return f();
+    }
+    match rayon::ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .build()
+    {
+        Ok(pool) => pool.install(f),
+        Err(_) => f(),
+    }
+}
+
+/// Open an index saved from Rust, using its metadata file to pick the type.
+pub fn open(path: &Path) -> io::Result<Box<dyn AnnIndex>> {
+    let meta = IndexMeta::load(path)?.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "{} has no metadata file, open it with an explicit index kind",
+                path.display()
+            ),
+        )
+    })?;
+    if meta.params.contains_key(sharded::SHARDS) {
+        return Ok(Box::new(ShardedIndex::load(path)?));
+    }
+    open_as(path, meta.kind, meta.metric)
+}
+
+/// Open an index of a known type, e.g. one written by the C++ samples. The metric is only
+/// used by HNSW, which does not store it.
+pub fn open_as(path: &Path, kind: IndexKind, metric: MetricType) -> io::Result<Box<dyn AnnIndex>> {
+    Ok(match kind {
+        IndexKind::Ivf => Box::new(Ivf::load(path)?),
+        IndexKind::Hnsw => Box::new(Hnsw::load_with_metric(path, metric)?),
+        IndexKind::Qg => Box::new(Qg::load(path)?),
+    })
+}
+
+/// Fixed-size fields at the start of a C++ index file, read without loading the index.
+pub fn read_header(path: &Path, kind: IndexKind) -> io::Result<Vec<(&'static str, u64)>> {
+    // (name, size in bytes) of the leading fields written by save()
+    const IVF: &[(&str, usize)] = &[
+        ("num", 8),
+        ("dim", 8),
+        ("num_clusters", 8),
+        ("ex_bits", 8),
+        ("rotator_type", 1),
+    ];
+    // after the magic, whose absence marks a file saved before the version and rotator type
+    const HNSW: &[(&str, usize)] = &[
+        ("version", 4),
+        ("max_elements", 8),
+        ("num", 8),
+        ("dim", 8),
+        ("padded_dim", 8),
+        ("num_clusters", 8),
+        ("ex_bits", 8),
+        ("size_bin_data", 8),
+        ("size_ex_data", 8),
+        ("size_links_level0", 8),
+        ("offset_bin_data", 8),
+        ("offset_ex_data", 8),
+        ("label_offset", 4),
+        ("size_data_per_element", 8),
+        ("size_links_per_element", 8),
+        ("max_level", 4),
+        ("entry_point", 4),
+        ("m", 8),
+        ("max_m", 8),
+        ("max_m0", 8),
+    ];
+    // after the magic, whose absence marks a file saved before the version and metric
+    const QG: &[(&str, usize)] = &[
+        ("version", 4),
+        ("num", 8),
+        ("degree_bound", 8),
+        ("dim", 8),
+        ("padded_dim", 8),
+        ("entry_point", 4),
+        ("rotator_type", 1),
+        ("metric", 1),
+    ];
+    const HNSW_MAGIC: u64 = u64::from_le_bytes(*b"RABITQH\0");
+    const QG_MAGIC: u64 = u64::from_le_bytes(*b"RABITQG\0");
+
+    let mut file = fs::File::open(path)?;
+    let mut read_field = |size: usize| -> io::Result<u64> {
+        let mut buf = [0u8; 8];
+        file.read_exact(&mut buf[..size])?;
+        Ok(u64::from_le_bytes(buf))
+    };
+    let mut values = Vec::new();
+    let fields = match kind {
+        IndexKind::Ivf => IVF,
+        IndexKind::Hnsw => {
+            let magic = read_field(8)?;
+            if magic != HNSW_MAGIC {
+                // legacy file: the magic was max_elements
+                values.extend([("version", 0), ("max_elements", magic)]);
+                for &(name, size) in &HNSW[2..] {
+                    values.push((name, read_field(size)?));
+                }
+                return Ok(values);
+            }
+            HNSW
+        }
+        IndexKind::Qg => {
+            let magic = read_field(8)?;
+            if magic != QG_MAGIC {
+                // legacy file: the magic was the num, and the metric is L2
+                values.extend([("version", 0), ("num", magic)]);
+                for &(name, size) in &QG[2..QG.len() - 1] {
+                    values.push((name, read_field(size)?));
+                }
+                values.push(("metric", 0));
+                return Ok(values);
+            }
+            QG
+        }
+    };
+    for &(name, size) in fields {
+        values.push((name, read_field(size)?));
+    }
+    Ok(values)
+}
+
+pub(crate) fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+pub(crate) fn invalid_input(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+/// Error for a shared rotator that does not match the dimension of an index
+pub(crate) fn rotator_error(rotator: &Rotator, dim: usize) -> io::Error {
+    invalid_input(format!(
+        "rotator from dim {} to {} does not fit an index of dim {dim}",
+        rotator.dim(),
+        rotator.padded_dim()
+    ))
+}
+
+pub(crate) fn c_path(path: &Path) -> io::Result<CString> {
+    let s = path
+        .to_str()
+        .ok_or_else(|| invalid_input(format!("{} is not valid UTF-8", path.display())))?;
+    CString::new(s).map_err(|e| invalid_input(e.to_string()))
+}
+
+/// Check a `n * dim` data slice and return `n`
+pub(crate) fn num_rows<T>(data: &[T], dim: usize) -> io::Result<usize> {
+    // the FHT-Kac rotator used by all indexes exits the process outside of this range
+    if !(64..4096).contains(&dim) {
+        return Err(invalid_input(format!("dim must be in 64..4096, got {dim}")));
+    }
+    if !data.len().is_multiple_of(dim) {
+        return Err(invalid_input(format!(
+            "data of length {} is not a multiple of dim {dim}",
+            data.len()
+        )));
+    }
+    Ok(data.len() / dim)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_meta_round_trip() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.index");
+        let meta = IndexMeta::new(IndexKind::Hnsw, MetricType::IP, 1000, 96, 5)
+            .with_param("m", 16)
+            .with_param("ef_construction", 200);
+        meta.save(&path).unwrap();
+        assert!(dir.path().join("test.index.meta").exists());
+
+        let loaded = IndexMeta::load(&path).unwrap().unwrap();
+        assert_eq!(loaded, meta);
+        assert_eq!(loaded.param::<usize>("m"), Some(16));
+        assert!(
+            IndexMeta::load(&dir.path().join("other"))
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_meta_rejects_garbage() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("bad.index");
+        fs::write(IndexMeta::path_for(&path), "kind = btree\n").unwrap();
+        assert!(IndexMeta::load(&path).is_err());
+    }
+}
@@ -0,0 +1,143 @@
+use std::ffi::{CStr, c_char, c_void};
+use std::fmt;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::Arc;
+
+use rabitq_sys as ffi;
+
+/// Flag that aborts the builds watching it, like an `Arc<AtomicBool>`: clones share the flag
+/// and it can be set from any thread.
+#[derive(Clone)]
+pub struct CancelToken(Arc<TokenPtr>);
+
+struct TokenPtr(*mut ffi::RabitqCancelToken);
+
+// SAFETY: the token is an atomic flag
+unsafe impl Send for TokenPtr {}
+unsafe impl Sync for TokenPtr {}
+
+impl Drop for TokenPtr {
+    fn drop(&mut self) {
+        unsafe { ffi::rabitq_cancel_token_free(self.0) }
+    }
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        let token = unsafe { ffi::rabitq_cancel_token_new() };
+        Self(Arc::new(TokenPtr(token)))
+    }
+
+    /// Builds watching the token stop at their next batch with an
+    /// [`Interrupted`](io::ErrorKind::Interrupted) error
+    pub fn cancel(&self) {
+        unsafe { ffi::rabitq_cancel_token_cancel(self.0.0) }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        unsafe { ffi::rabitq_cancel_token_is_cancelled(self.0.0) }
+    }
+}
+
+impl Default for CancelToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for CancelToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CancelToken")
+            .field(&self.is_cancelled())
+            .finish()
+    }
+}
+
+/// Receives `(phase, done, total)`: items done out of total in the named phase
+pub type ProgressFn = dyn Fn(&str, usize, usize) + Send + Sync;
+
+/// Progress reporting and cancellation of an index build.
+///
+/// Builds run their items in batches, thousands of vectors or a few clusters per thread.
+/// Between two batches the progress closure is called on the thread running the build and
+/// the cancellation token is checked.
+#[derive(Clone, Default)]
+pub struct BuildMonitor {
+    pub progress: Option<Arc<ProgressFn>>,
+    pub cancel: Option<CancelToken>,
+}
+
+impl BuildMonitor {
+    pub fn with_progress(
+        mut self,
+        progress: impl Fn(&str, usize, usize) + Send + Sync + 'static,
+    ) -> Self {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+
+    pub fn with_cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Monitor for the FFI builds, borrows `self`
+    pub(crate) fn to_ffi(&self) -> ffi::RabitqBuildMonitor {
+        ffi::RabitqBuildMonitor {
+            progress: self.progress.as_ref().map(|_| report as _),
+            ctx: self as *const Self as *mut c_void,
+            cancel: self
+                .cancel
+                .as_ref()
+                .map_or(ptr::null(), |token| token.0.0.cast_const()),
+        }
+    }
+
+    /// Error of a failed build, [`Interrupted`](io::ErrorKind::Interrupted) if it was
+    /// cancelled
+    pub(crate) fn build_error(&self, what: &str) -> io::Error {
+        match self.check(what) {
+            Err(err) => err,
+            Ok(()) => io::Error::other(format!("{what} failed")),
+        }
+    }
+
+    /// [`Interrupted`](io::ErrorKind::Interrupted) error once the token is set, for the
+    /// build phases run in Rust
+    pub(crate) fn check(&self, what: &str) -> io::Result<()> {
+        if self.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+            return Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                format!("{what} cancelled"),
+            ));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn report(&self, phase: &str, done: usize, total: usize) {
+        if let Some(progress) = &self.progress {
+            progress(phase, done, total);
+        }
+    }
+}
+
+impl fmt::Debug for BuildMonitor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BuildMonitor")
+            .field("progress", &self.progress.is_some())
+            .field("cancel", &self.cancel)
+            .finish()
+    }
+}
+
+unsafe extern "C" fn report(ctx: *mut c_void, phase: *const c_char, done: usize, total: usize) {
+    // SAFETY: ctx is the BuildMonitor of to_ffi, alive during the build
+    let monitor = unsafe { &*(ctx as *const BuildMonitor) };
+    let phase = unsafe { CStr::from_ptr(phase) }.to_string_lossy();
+    if let Some(progress) = &monitor.progress {
+        // a panic must not unwind into C++
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| progress(&phase, done, total)));
+    }
+}
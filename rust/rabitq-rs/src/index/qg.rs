@@ -0,0 +1,308 @@
+use std::io;
+use std::path::Path;
+use std::ptr;
+use std::sync::{Arc, RwLock};
+
+use rabitq_sys as ffi;
+
+use super::{
+    AnnIndex, BuildMonitor, Distribution, GraphStats, IndexKind, IndexMeta, IndexStats,
+    SearchParams, SearchResult, SearchStats, c_path, invalid_input, num_rows, rotator_error,
+};
+use crate::element::VectorElement;
+use crate::estimator::QueryPrecision;
+use crate::{MetricType, Rotator};
+
+/// Build parameters of a [`Qg`] index
+#[derive(Clone, Debug)]
+pub struct QgParams {
+    /// Out-degree of every vertex, a multiple of 32
+    pub degree: usize,
+    pub ef_build: usize,
+    /// 0 uses all cores
+    pub num_threads: usize,
+    /// Graph refinement rounds, at least 2
+    pub num_iter: usize,
+    pub metric: MetricType,
+    /// Draws the rotation and the random edges, the graph is the same for a seed and
+    /// `num_threads`
+    pub seed: u64,
+    /// Rotation to use instead of a random one, e.g. shared by the shards of a
+    /// [`ShardedIndex`](super::ShardedIndex)
+    pub rotator: Option<Arc<Rotator>>,
+    /// Progress and cancellation of the refinement rounds
+    pub monitor: BuildMonitor,
+}
+
+impl Default for QgParams {
+    fn default() -> Self {
+        Self {
+            degree: 32,
+            ef_build: 400,
+            num_threads: 0,
+            num_iter: 3,
+            metric: MetricType::L2,
+            seed: 42,
+            rotator: None,
+            monitor: BuildMonitor::default(),
+        }
+    }
+}
+
+/// SymphonyQG graph, codes stored next to the adjacency lists. Supports L2 and inner
+/// product, 1 bit per dimension for the neighbour codes plus the raw vectors for re-ranking.
+pub struct Qg {
+    ptr: *mut ffi::QgIndex,
+    meta: IndexMeta,
+    /// Shared by insertions, held exclusively by `save` and `stats` which walk the graph
+    writers: RwLock<()>,
+}
+
+// SAFETY: the C++ graph locks rows between searches and insertions, whole-graph readers
+// take `writers`, other mutation needs `&mut self`
+unsafe impl Send for Qg {}
+unsafe impl Sync for Qg {}
+
+impl Qg {
+    /// Build the graph over `data` (`n * dim`). The graph rows hold the raw vectors and the
+    /// C++ builder reads them all at once, so non-`f32` input is widened into a full `f32`
+    /// copy first.
+    pub fn build<T: VectorElement>(data: &[T], dim: usize, params: &QgParams) -> io::Result<Self> {
+        let n = num_rows(data, dim)?;
+        if params.num_iter < 2 {
+            return Err(invalid_input("num_iter must be at least 2".to_string()));
+        }
+        if n <= params.degree {
+            return Err(invalid_input(format!(
+                "need more than degree = {} vectors, got {n}",
+                params.degree
+            )));
+        }
+        let data = T::slice_to_f32(data);
+        let ptr = unsafe {
+            ffi::rabitq_qg_new(
+                n,
+                dim,
+                params.degree,
+                params.metric as ffi::MetricType,
+                params.seed,
+            )
+        };
+        if ptr.is_null() {
+            return Err(invalid_input(format!(
+                "degree must be a positive multiple of 32, got {}",
+                params.degree
+            )));
+        }
+        let meta = IndexMeta::new(IndexKind::Qg, params.metric, n, dim, 1)
+            .with_param("degree", params.degree)
+            .with_param("ef_build", params.ef_build);
+        let index = Self::from_raw(ptr, meta);
+        if let Some(rotator) = &params.rotator
+            && unsafe { ffi::rabitq_qg_set_rotator(index.ptr, rotator.as_ptr()) } != 0
+        {
+            return Err(rotator_error(rotator, dim));
+        }
+        let ret = unsafe {
+            ffi::rabitq_qg_build(
+                index.ptr,
+                data.as_ptr(),
+                params.ef_build,
+                params.num_threads,
+                params.num_iter,
+                params.seed,
+                &params.monitor.to_ffi(),
+            )
+        };
+        if ret != 0 {
+            return Err(params.monitor.build_error("SymphonyQG construction"));
+        }
+        Ok(index)
+    }
+
+    /// Load an index saved by [`AnnIndex::save`] or by the C++ samples.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let c_path = c_path(path)?;
+        let ptr = unsafe { ffi::rabitq_qg_load(c_path.as_ptr()) };
+        if ptr.is_null() {
+            return Err(io::Error::other(format!(
+                "failed to load {}",
+                path.display()
+            )));
+        }
+        let meta = match IndexMeta::load(path)? {
+            Some(meta) => meta,
+            None => unsafe {
+                let metric = if ffi::rabitq_qg_metric(ptr) == MetricType::IP as ffi::MetricType {
+                    MetricType::IP
+                } else {
+                    MetricType::L2
+                };
+                IndexMeta::new(
+                    IndexKind::Qg,
+                    metric,
+                    ffi::rabitq_qg_num(ptr),
+                    ffi::rabitq_qg_dim(ptr),
+                    1,
+                )
+                .with_param("degree", ffi::rabitq_qg_degree_bound(ptr))
+            },
+        };
+        Ok(Self::from_raw(ptr, meta))
+    }
+
+    fn from_raw(ptr: *mut ffi::QgIndex, meta: IndexMeta) -> Self {
+        Self {
+            ptr,
+            meta,
+            writers: RwLock::new(()),
+        }
+    }
+
+    /// Add a vector linked to neighbours found with a search pool of `ef_build`, returning
+    /// its id, which is the next position. Neighbours whose pruned lists take the vector
+    /// get their codes recomputed. Needs [`reserve`](Self::reserve) first, then can run
+    /// concurrently with searches, insertions run one at a time within its capacity.
+    pub fn insert<T: VectorElement>(&self, vector: &[T]) -> io::Result<u32> {
+        if vector.len() != self.dim() {
+            return Err(invalid_input(format!(
+                "expected a vector of dim {}, got {}",
+                self.dim(),
+                vector.len()
+            )));
+        }
+        let ef = self
+            .meta
+            .param("ef_build")
+            .unwrap_or(QgParams::default().ef_build);
+        let vector = T::slice_to_f32(vector);
+        let _guard = self.writers.read().unwrap_or_else(|e| e.into_inner());
+        let mut id = 0u32;
+        if unsafe { ffi::rabitq_qg_insert(self.ptr, vector.as_ptr(), ef, &mut id) } != 0 {
+            return Err(invalid_input(format!(
+                "graph is full with {} vectors, reserve room first",
+                self.capacity()
+            )));
+        }
+        Ok(id)
+    }
+
+    /// Make room for at least `additional` more vectors than [`len`](AnnIndex::len). Opens
+    /// the graph for insertion, searches then lock the rows they read.
+    pub fn reserve(&mut self, additional: usize) -> io::Result<()> {
+        let capacity = self.len() + additional;
+        if unsafe { ffi::rabitq_qg_reserve(self.ptr, capacity) } != 0 {
+            return Err(io::Error::other(format!(
+                "failed to reserve room for {capacity} vectors"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Vectors the graph holds without [`reserve`](Self::reserve), the build size at first
+    pub fn capacity(&self) -> usize {
+        unsafe { ffi::rabitq_qg_capacity(self.ptr) }
+    }
+
+    pub fn degree_bound(&self) -> usize {
+        unsafe { ffi::rabitq_qg_degree_bound(self.ptr) }
+    }
+}
+
+impl AnnIndex for Qg {
+    fn meta(&self) -> &IndexMeta {
+        &self.meta
+    }
+
+    fn meta_mut(&mut self) -> &mut IndexMeta {
+        &mut self.meta
+    }
+
+    fn search_result(&self, query: &[f32], k: usize, params: &SearchParams) -> SearchResult {
+        assert_eq!(query.len(), self.dim());
+        let mut results = vec![0u32; k];
+        let mut distances = vec![0f32; k];
+        let budget = params.budget.to_ffi();
+        let mut partial = false;
+        let mut stats = params
+            .stats
+            .then(|| ffi::RabitqSearchStats::from(SearchStats::default()));
+        let ef = params.ef.max(k);
+        let precision = params
+            .query_precision
+            .unwrap_or(QueryPrecision::BATCH_DEFAULT);
+        assert!(
+            precision.valid_batch(),
+            "SymphonyQG tables must have 8 or 16 bits or be exact, got {precision}"
+        );
+        unsafe {
+            ffi::rabitq_qg_search(
+                self.ptr,
+                query.as_ptr(),
+                k,
+                ef,
+                precision.code(),
+                results.as_mut_ptr(),
+                distances.as_mut_ptr(),
+                budget.as_ref().map_or(ptr::null(), |b| b as *const _),
+                &mut partial,
+                stats.as_mut().map_or(ptr::null_mut(), |s| s as *mut _),
+            );
+        }
+        SearchResult {
+            ids: results,
+            distances,
+            partial,
+            stats: stats.map(SearchStats::from),
+        }
+    }
+
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let c_path = c_path(path)?;
+        let _guard = self.writers.write().unwrap_or_else(|e| e.into_inner());
+        if unsafe { ffi::rabitq_qg_save(self.ptr, c_path.as_ptr()) } != 0 {
+            return Err(io::Error::other(format!(
+                "failed to write {}",
+                path.display()
+            )));
+        }
+        let mut meta = self.meta.clone();
+        meta.num = self.len();
+        meta.save(path)
+    }
+
+    fn len(&self) -> usize {
+        unsafe { ffi::rabitq_qg_num(self.ptr) }
+    }
+
+    fn stats(&self) -> IndexStats {
+        let _guard = self.writers.write().unwrap_or_else(|e| e.into_inner());
+        let mut degrees = vec![0u32; self.len()];
+        let mut bytes = [0usize; 3];
+        unsafe {
+            ffi::rabitq_qg_degrees(self.ptr, degrees.as_mut_ptr());
+            ffi::rabitq_qg_memory(self.ptr, bytes.as_mut_ptr());
+        }
+        let degrees: Vec<usize> = degrees.into_iter().map(|d| d as usize).collect();
+        IndexStats {
+            kind: IndexKind::Qg,
+            num: self.len(),
+            dim: self.dim(),
+            memory: ["vectors", "batch_data", "neighbors"]
+                .into_iter()
+                .zip(bytes)
+                .collect(),
+            clusters: None,
+            graph: Some(GraphStats {
+                entry_point: unsafe { ffi::rabitq_qg_entry_point(self.ptr) },
+                levels: vec![Distribution::exact(&degrees)],
+            }),
+        }
+    }
+}
+
+impl Drop for Qg {
+    fn drop(&mut self) {
+        unsafe { ffi::rabitq_qg_free(self.ptr) };
+    }
+}
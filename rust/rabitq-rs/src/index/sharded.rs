@@ -0,0 +1,612 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+
+use rabitq_sys as ffi;
+use rayon::prelude::*;
+
+use super::{
+    AnnIndex, ClusterStats, Hnsw, HnswParams, INVALID_ID, IndexKind, IndexMeta, IndexStats, Ivf,
+    IvfParams, Qg, QgParams, SearchParams, SearchResult, SearchStats, invalid_data, invalid_input,
+    num_rows,
+};
+use crate::element::VectorElement;
+use crate::io::{FileElement, VectorFile};
+use crate::kmeans::KMeans;
+use crate::{MetricType, Rotator};
+
+/// Metadata key holding the number of shards, present only for sharded indexes
+pub(crate) const SHARDS: &str = "shards";
+const ROUTING: &str = "routing";
+
+/// How vectors are assigned to shards
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Routing {
+    /// By a hash of the vector id, which balances the shards whatever the ids are
+    Hash,
+    /// To the shard of the nearest of `num_shards` k-means centroids under the index metric,
+    /// the one with the largest inner product for [`MetricType::IP`]
+    Centroid,
+}
+
+/// Index built for every shard
+#[derive(Clone, Debug)]
+pub enum ShardParams {
+    Ivf(IvfParams),
+    Hnsw(HnswParams),
+    Qg(QgParams),
+}
+
+/// Build parameters of a [`ShardedIndex`]
+#[derive(Clone, Debug)]
+pub struct ShardedParams {
+    pub num_shards: usize,
+    pub routing: Routing,
+    /// Parameters of every shard. Without a `rotator` in them, one rotator drawn from `seed`
+    /// is created and shared by all shards.
+    pub shard: ShardParams,
+    /// Seed of the shared rotator and of the k-means run of [`Routing::Centroid`]
+    pub seed: u64,
+}
+
+impl ShardParams {
+    fn metric(&self) -> MetricType {
+        match self {
+            ShardParams::Ivf(_) => MetricType::L2,
+            ShardParams::Hnsw(params) => params.metric,
+            ShardParams::Qg(params) => params.metric,
+        }
+    }
+}
+
+enum Shard {
+    Ivf(Ivf),
+    Hnsw(Hnsw),
+    Qg(Qg),
+}
+
+impl Shard {
+    fn build<T: VectorElement>(
+        data: &[T],
+        dim: usize,
+        params: &ShardParams,
+        rotator: &Arc<Rotator>,
+    ) -> io::Result<Self> {
+        Ok(match params {
+            ShardParams::Ivf(params) => {
+                let params = IvfParams {
+                    rotator: Some(params.rotator.clone().unwrap_or_else(|| rotator.clone())),
+                    ..params.clone()
+                };
+                Shard::Ivf(Ivf::build(data, dim, &params)?)
+            }
+            ShardParams::Hnsw(params) => {
+                let params = HnswParams {
+                    rotator: Some(params.rotator.clone().unwrap_or_else(|| rotator.clone())),
+                    ..params.clone()
+                };
+                Shard::Hnsw(Hnsw::build(data, dim, &params)?)
+            }
+            ShardParams::Qg(params) => {
+                let params = QgParams {
+                    rotator: Some(params.rotator.clone().unwrap_or_else(|| rotator.clone())),
+                    ..params.clone()
+                };
+                Shard::Qg(Qg::build(data, dim, &params)?)
+            }
+        })
+    }
+
+    fn load(path: &Path, kind: IndexKind) -> io::Result<Self> {
+        Ok(match kind {
+            IndexKind::Ivf => Shard::Ivf(Ivf::load(path)?),
+            IndexKind::Hnsw => Shard::Hnsw(Hnsw::load(path)?),
+            IndexKind::Qg => Shard::Qg(Qg::load(path)?),
+        })
+    }
+
+    fn index(&self) -> &dyn AnnIndex {
+        match self {
+            Shard::Ivf(index) => index,
+            Shard::Hnsw(index) => index,
+            Shard::Qg(index) => index,
+        }
+    }
+
+    /// Add `vector` as shard-local id `local`, the next id of the shard
+    fn insert(&self, vector: &[f32], local: u32) -> io::Result<()> {
+        match self {
+            Shard::Ivf(_) => Err(ivf_unsupported()),
+            Shard::Hnsw(index) => index.insert(vector, local),
+            Shard::Qg(index) => {
+                let id = index.insert(vector)?;
+                debug_assert_eq!(id, local, "SymphonyQG ids are insertion positions");
+                Ok(())
+            }
+        }
+    }
+
+    fn reserve(&mut self, additional: usize) -> io::Result<()> {
+        match self {
+            Shard::Ivf(_) => Err(ivf_unsupported()),
+            Shard::Hnsw(index) => index.reserve(additional),
+            Shard::Qg(index) => index.reserve(additional),
+        }
+    }
+}
+
+fn ivf_unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "IVF shards do not support insertion",
+    )
+}
+
+/// Several indexes of one kind searched as one. Shards share a rotator, queries run on all
+/// of them in parallel and the per-shard top-k are merged by distance, as returned by
+/// [`AnnIndex::search_scored`] or recomputed from raw vectors by
+/// [`search_reranked`](Self::search_reranked).
+///
+/// Ids are global: the row numbers of the build data, then the ids given to
+/// [`insert`](Self::insert), which must not repeat. Saved as `<path>` holding the id maps
+/// and routing centroids, `<path>.<i>` for shard `i` and the metadata file.
+pub struct ShardedIndex {
+    shards: Vec<Shard>,
+    /// Global id of every shard-local id
+    ids: Vec<RwLock<Vec<u32>>>,
+    /// Held while inserting into a shard so its local ids follow `ids`
+    inserting: Vec<Mutex<()>>,
+    /// `num_shards * dim` routing centroids, empty for [`Routing::Hash`]
+    centroids: Vec<f32>,
+    meta: IndexMeta,
+}
+
+impl ShardedIndex {
+    /// Route every row of `data` (`n * dim`) to a shard and build the shards one after the
+    /// other. Shard `i` holds the rows routed to it, by row number for [`Routing::Hash`].
+    /// The rows of one shard at a time are copied out of `data`, in their own element type,
+    /// and widened as the shard's build does.
+    pub fn build<T: VectorElement>(
+        data: &[T],
+        dim: usize,
+        params: &ShardedParams,
+    ) -> io::Result<Self> {
+        let n = num_rows(data, dim)?;
+        let num_shards = params.num_shards;
+        if num_shards == 0 || num_shards > n {
+            return Err(invalid_input(format!(
+                "num_shards must be in 1..={n}, got {num_shards}"
+            )));
+        }
+        let (centroids, assignments) = match params.routing {
+            Routing::Hash => (
+                Vec::new(),
+                (0..n as u32).map(|id| hash_shard(id, num_shards)).collect(),
+            ),
+            Routing::Centroid => {
+                let mut kmeans = KMeans::new(num_shards);
+                kmeans.seed = params.seed;
+                let clustering = kmeans.fit(data, dim);
+                let assignments = match params.shard.metric() {
+                    MetricType::L2 => clustering
+                        .assignments
+                        .iter()
+                        .map(|&s| s as usize)
+                        .collect::<Vec<_>>(),
+                    // k-means runs on L2, rows go where route() sends insertions
+                    MetricType::IP => data
+                        .par_chunks_exact(dim)
+                        .map(|x| {
+                            let x = T::slice_to_f32(x);
+                            nearest_centroid(&clustering.centroids, MetricType::IP, &x)
+                        })
+                        .collect(),
+                };
+                (clustering.centroids, assignments)
+            }
+        };
+        let mut ids = vec![Vec::new(); num_shards];
+        for (row, &shard) in assignments.iter().enumerate() {
+            ids[shard].push(row as u32);
+        }
+        if let Some(empty) = ids.iter().position(Vec::is_empty) {
+            return Err(invalid_input(format!(
+                "shard {empty} got no vectors, use fewer shards"
+            )));
+        }
+
+        let rotator = Rotator::with_seed(dim, dim.next_multiple_of(64), params.seed)
+            .map(Arc::new)
+            .ok_or_else(|| io::Error::other("failed to create the shared rotator"))?;
+        let mut shards = Vec::with_capacity(num_shards);
+        let mut rows = Vec::new();
+        for shard_ids in &ids {
+            rows.clear();
+            for &id in shard_ids {
+                let start = id as usize * dim;
+                rows.extend_from_slice(&data[start..start + dim]);
+            }
+            shards.push(Shard::build(&rows, dim, &params.shard, &rotator)?);
+        }
+
+        let first = shards[0].index().meta();
+        let mut meta = IndexMeta::new(first.kind, first.metric, n, dim, first.total_bits);
+        meta.params = first.params.clone();
+        let meta = meta
+            .with_param(SHARDS, num_shards)
+            .with_param(ROUTING, routing_name(params.routing));
+        Ok(Self::from_parts(shards, ids, centroids, meta))
+    }
+
+    fn from_parts(
+        shards: Vec<Shard>,
+        ids: Vec<Vec<u32>>,
+        centroids: Vec<f32>,
+        meta: IndexMeta,
+    ) -> Self {
+        Self {
+            inserting: shards.iter().map(|_| Mutex::new(())).collect(),
+            shards,
+            ids: ids.into_iter().map(RwLock::new).collect(),
+            centroids,
+            meta,
+        }
+    }
+
+    /// Load an index saved by [`AnnIndex::save`].
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let meta = IndexMeta::load(path)?
+            .ok_or_else(|| invalid_data(format!("{} has no metadata file", path.display())))?;
+        let num_shards: usize = meta
+            .param(SHARDS)
+            .ok_or_else(|| invalid_data(format!("{} is not a sharded index", path.display())))?;
+
+        let mut reader = BufReader::new(File::open(path)?);
+        if read_u64(&mut reader)? != num_shards as u64 {
+            return Err(invalid_data(format!(
+                "{}: shard count does not match the metadata",
+                path.display()
+            )));
+        }
+        let mut ids = Vec::with_capacity(num_shards);
+        for _ in 0..num_shards {
+            let count = read_u64(&mut reader)? as usize;
+            let mut buf = vec![0u8; count * 4];
+            reader.read_exact(&mut buf)?;
+            ids.push(
+                buf.chunks_exact(4)
+                    .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+                    .collect::<Vec<_>>(),
+            );
+        }
+        let count = read_u64(&mut reader)? as usize;
+        let mut buf = vec![0u8; count * 4];
+        reader.read_exact(&mut buf)?;
+        let centroids = buf
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect::<Vec<_>>();
+
+        let mut shards = Vec::with_capacity(num_shards);
+        for (i, shard_ids) in ids.iter().enumerate() {
+            let shard = Shard::load(&shard_path(path, i), meta.kind)?;
+            if shard.index().len() != shard_ids.len() {
+                return Err(invalid_data(format!(
+                    "{}: shard {i} holds {} vectors but has {} ids",
+                    path.display(),
+                    shard.index().len(),
+                    shard_ids.len()
+                )));
+            }
+            shards.push(shard);
+        }
+        Ok(Self::from_parts(shards, ids, centroids, meta))
+    }
+
+    pub fn num_shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Shard `i`, searched with its own local ids
+    pub fn shard(&self, i: usize) -> &dyn AnnIndex {
+        self.shards[i].index()
+    }
+
+    /// Shard that `vector` with global id `id` belongs to
+    pub fn route(&self, id: u32, vector: &[f32]) -> usize {
+        if self.centroids.is_empty() {
+            return hash_shard(id, self.shards.len());
+        }
+        nearest_centroid(&self.centroids, self.meta.metric, vector)
+    }
+
+    /// Add a vector under global id `id` to the shard it routes to. HNSW and QG shards only,
+    /// within the room made by [`reserve`](Self::reserve). Can run concurrently with
+    /// searches and other insertions.
+    pub fn insert<T: VectorElement>(&self, vector: &[T], id: u32) -> io::Result<()> {
+        if vector.len() != self.dim() {
+            return Err(invalid_input(format!(
+                "expected a vector of dim {}, got {}",
+                self.dim(),
+                vector.len()
+            )));
+        }
+        let vector = T::slice_to_f32(vector);
+        let s = self.route(id, &vector);
+        let _guard = self.inserting[s].lock().unwrap_or_else(|e| e.into_inner());
+        let local = {
+            let mut ids = self.ids[s].write().unwrap_or_else(|e| e.into_inner());
+            ids.push(id);
+            ids.len() - 1
+        };
+        if let Err(e) = self.shards[s].insert(&vector, local as u32) {
+            self.ids[s].write().unwrap_or_else(|e| e.into_inner()).pop();
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Make room for `additional` more vectors in every shard.
+    pub fn reserve(&mut self, additional: usize) -> io::Result<()> {
+        for shard in &mut self.shards {
+            shard.reserve(additional)?;
+        }
+        Ok(())
+    }
+
+    /// Like [`AnnIndex::search_scored`], but take the best `candidates` of every shard and
+    /// rank them on exact distances to their rows of `raw`, the vectors the index was built
+    /// from indexed by global id.
+    ///
+    /// # Panics
+    ///
+    /// If `query` does not have the index dimension, or `params.query_precision` is not
+    /// supported by the shards, see [`SearchParams::validate`].
+    pub fn search_reranked<T: FileElement + VectorElement>(
+        &self,
+        query: &[f32],
+        k: usize,
+        params: &SearchParams,
+        raw: &VectorFile<T>,
+        candidates: usize,
+    ) -> io::Result<(Vec<u32>, Vec<f32>)> {
+        let dim = self.dim();
+        assert_eq!(query.len(), dim);
+        if raw.cols() != dim {
+            return Err(invalid_input(format!(
+                "raw vectors have dim {}, the index {dim}",
+                raw.cols()
+            )));
+        }
+        let mut ids = self.search_shards(query, candidates.max(k), params).ids;
+        ids.retain(|&id| id != INVALID_ID);
+        ids.sort_unstable();
+        let mut rows = Vec::with_capacity(ids.len() * dim);
+        raw.read_rows(&ids, &mut rows)?;
+        let rows = T::slice_to_f32(&rows);
+        let scored = ids
+            .iter()
+            .zip(rows.chunks_exact(dim))
+            .map(|(&id, x)| (id, exact_distance(self.meta.metric, query, x)))
+            .collect::<Vec<_>>();
+        Ok(top_k(scored, k))
+    }
+
+    /// Merged top `k` of every shard, with global ids, partial if any shard was and the sum
+    /// of their stats
+    fn search_shards(&self, query: &[f32], k: usize, params: &SearchParams) -> SearchResult {
+        let per_shard = self
+            .shards
+            .par_iter()
+            .zip(&self.ids)
+            .map(|(shard, global)| {
+                let result = shard.index().search_result(query, k, params);
+                let global = global.read().unwrap_or_else(|e| e.into_inner());
+                let found = result
+                    .ids
+                    .into_iter()
+                    .zip(result.distances)
+                    .filter(|&(id, _)| id != INVALID_ID)
+                    .map(|(id, dist)| (global[id as usize], dist))
+                    .collect::<Vec<_>>();
+                (found, result.partial, result.stats)
+            })
+            .collect::<Vec<_>>();
+        let partial = per_shard.iter().any(|(_, partial, _)| *partial);
+        let stats = params.stats.then(|| {
+            let mut total = SearchStats::default();
+            for (_, _, stats) in &per_shard {
+                total += stats.unwrap_or_default();
+            }
+            total
+        });
+        let (ids, distances) = top_k(
+            per_shard
+                .into_iter()
+                .flat_map(|(found, ..)| found)
+                .collect(),
+            k,
+        );
+        SearchResult {
+            ids,
+            distances,
+            partial,
+            stats,
+        }
+    }
+}
+
+impl AnnIndex for ShardedIndex {
+    fn meta(&self) -> &IndexMeta {
+        &self.meta
+    }
+
+    fn meta_mut(&mut self) -> &mut IndexMeta {
+        &mut self.meta
+    }
+
+    fn search_result(&self, query: &[f32], k: usize, params: &SearchParams) -> SearchResult {
+        assert_eq!(query.len(), self.dim());
+        self.search_shards(query, k, params)
+    }
+
+    fn save(&self, path: &Path) -> io::Result<()> {
+        // keep insertions out so that the id maps match the saved shards
+        let _guards = self
+            .inserting
+            .iter()
+            .map(|m| m.lock().unwrap_or_else(|e| e.into_inner()))
+            .collect::<Vec<_>>();
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&(self.shards.len() as u64).to_le_bytes())?;
+        for ids in &self.ids {
+            let ids = ids.read().unwrap_or_else(|e| e.into_inner());
+            writer.write_all(&(ids.len() as u64).to_le_bytes())?;
+            for id in ids.iter() {
+                writer.write_all(&id.to_le_bytes())?;
+            }
+        }
+        writer.write_all(&(self.centroids.len() as u64).to_le_bytes())?;
+        for value in &self.centroids {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        writer.flush()?;
+        for (i, shard) in self.shards.iter().enumerate() {
+            shard.index().save(&shard_path(path, i))?;
+        }
+        let mut meta = self.meta.clone();
+        meta.num = self.len();
+        meta.save(path)
+    }
+
+    /// Memory summed over the shards plus the id maps, and the clusters of all IVF shards.
+    /// Graph connectivity is per shard, see [`ShardedIndex::shard`].
+    fn stats(&self) -> IndexStats {
+        let shard_stats = self
+            .shards
+            .iter()
+            .map(|s| s.index().stats())
+            .collect::<Vec<_>>();
+        let mut memory = shard_stats[0].memory.clone();
+        for stats in &shard_stats[1..] {
+            for (total, &(_, bytes)) in memory.iter_mut().zip(&stats.memory) {
+                total.1 += bytes;
+            }
+        }
+        let id_bytes = self
+            .ids
+            .iter()
+            .map(|ids| ids.read().unwrap_or_else(|e| e.into_inner()).len() * 4)
+            .sum::<usize>();
+        memory.push(("shard_ids", id_bytes));
+        memory.push(("routing_centroids", self.centroids.len() * 4));
+        let clusters = (self.kind() == IndexKind::Ivf).then(|| {
+            ClusterStats::new(
+                shard_stats
+                    .iter()
+                    .filter_map(|s| s.clusters.as_ref())
+                    .flat_map(|c| c.sizes.iter().copied())
+                    .collect(),
+            )
+        });
+        IndexStats {
+            kind: self.kind(),
+            num: self.len(),
+            dim: self.dim(),
+            memory,
+            clusters,
+            graph: None,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.shards.iter().map(|s| s.index().len()).sum()
+    }
+}
+
+/// Best `k` of `(id, distance)` candidates, padded with [`INVALID_ID`] and infinity
+fn top_k(mut candidates: Vec<(u32, f32)>, k: usize) -> (Vec<u32>, Vec<f32>) {
+    candidates.sort_unstable_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+    candidates.truncate(k);
+    candidates.resize(k, (INVALID_ID, f32::INFINITY));
+    candidates.into_iter().unzip()
+}
+
+fn exact_distance(metric: MetricType, x: &[f32], y: &[f32]) -> f32 {
+    unsafe {
+        match metric {
+            MetricType::L2 => ffi::rabitq_euclidean_sqr(x.as_ptr(), y.as_ptr(), x.len()),
+            MetricType::IP => 1.0 - ffi::rabitq_dot_product(x.as_ptr(), y.as_ptr(), x.len()),
+        }
+    }
+}
+
+/// Centroid of `centroids` nearest to `vector` under `metric`
+fn nearest_centroid(centroids: &[f32], metric: MetricType, vector: &[f32]) -> usize {
+    centroids
+        .chunks_exact(vector.len())
+        .map(|c| exact_distance(metric, vector, c))
+        .enumerate()
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map_or(0, |(i, _)| i)
+}
+
+/// Shard of `id` under [`Routing::Hash`], with the MurmurHash3 finalizer so that
+/// consecutive ids spread evenly
+fn hash_shard(id: u32, num_shards: usize) -> usize {
+    let mut h = id as u64;
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^= h >> 33;
+    (h % num_shards as u64) as usize
+}
+
+fn routing_name(routing: Routing) -> &'static str {
+    match routing {
+        Routing::Hash => "hash",
+        Routing::Centroid => "centroid",
+    }
+}
+
+fn shard_path(path: &Path, i: usize) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(format!(".{i}"));
+    PathBuf::from(path)
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_top_k_merges_and_pads() {
+        let (ids, distances) = top_k(vec![(7, 3.0), (2, 1.0), (9, 2.0)], 2);
+        assert_eq!(ids, [2, 9]);
+        assert_eq!(distances, [1.0, 2.0]);
+
+        let (ids, distances) = top_k(vec![(4, 0.5)], 3);
+        assert_eq!(ids, [4, INVALID_ID, INVALID_ID]);
+        assert!(distances[1..].iter().all(|d| d.is_infinite()));
+    }
+
+    #[test]
+    fn test_hash_routing_is_balanced() {
+        let mut counts = [0usize; 4];
+        for id in 0..4000 {
+            counts[hash_shard(id, 4)] += 1;
+        }
+        assert!(
+            counts.iter().all(|&c| (900..1100).contains(&c)),
+            "{counts:?}"
+        );
+    }
+}
@@ -0,0 +1,260 @@
+//! Index statistics returned by [`AnnIndex::stats`](super::AnnIndex::stats).
+//!
+//! Memory is reported per in-memory section as the C++ side allocates it, so the numbers
+//! match the index in RAM rather than its file. IVF adds the cluster size distribution and
+//! its imbalance, the graph indexes their out-degrees per level.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::AddAssign;
+
+use rabitq_sys as ffi;
+
+use super::IndexKind;
+
+/// Summary of a set of counts, such as cluster sizes or vertex degrees
+#[derive(Clone, Debug, PartialEq)]
+pub struct Distribution {
+    /// Number of values summarized
+    pub count: usize,
+    pub min: usize,
+    pub max: usize,
+    pub mean: f64,
+    /// `(lower bound, count)` of the non-empty buckets, in increasing order. Buckets hold a
+    /// single value for degrees and powers of two (`0`, `1`, `2..4`, `4..8`, ...) for
+    /// cluster sizes.
+    pub histogram: Vec<(usize, usize)>,
+}
+
+impl Distribution {
+    /// One bucket per distinct value
+    pub(crate) fn exact(values: &[usize]) -> Self {
+        Self::bucketed(values, |v| v)
+    }
+
+    /// Power-of-two buckets
+    pub(crate) fn log2(values: &[usize]) -> Self {
+        Self::bucketed(values, |v| if v == 0 { 0 } else { 1 << v.ilog2() })
+    }
+
+    fn bucketed(values: &[usize], bucket: impl Fn(usize) -> usize) -> Self {
+        let mut histogram = BTreeMap::new();
+        for &v in values {
+            *histogram.entry(bucket(v)).or_insert(0) += 1;
+        }
+        let sum: usize = values.iter().sum();
+        Self {
+            count: values.len(),
+            min: values.iter().copied().min().unwrap_or(0),
+            max: values.iter().copied().max().unwrap_or(0),
+            mean: if values.is_empty() {
+                0.0
+            } else {
+                sum as f64 / values.len() as f64
+            },
+            histogram: histogram.into_iter().collect(),
+        }
+    }
+}
+
+/// Cluster balance of an IVF index
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClusterStats {
+    /// Number of vectors of every cluster
+    pub sizes: Vec<usize>,
+    pub distribution: Distribution,
+    pub empty: usize,
+    /// `k * sum(size^2) / n^2`, 1 for perfectly balanced clusters. Search cost on skewed
+    /// clusters grows with it.
+    pub imbalance: f64,
+}
+
+impl ClusterStats {
+    pub(crate) fn new(sizes: Vec<usize>) -> Self {
+        let n: usize = sizes.iter().sum();
+        let sum_sqr: f64 = sizes.iter().map(|&s| (s as f64) * (s as f64)).sum();
+        let imbalance = if n == 0 {
+            0.0
+        } else {
+            sizes.len() as f64 * sum_sqr / (n as f64 * n as f64)
+        };
+        Self {
+            distribution: Distribution::log2(&sizes),
+            empty: sizes.iter().filter(|&&s| s == 0).count(),
+            imbalance,
+            sizes,
+        }
+    }
+}
+
+/// Connectivity of a graph index
+#[derive(Clone, Debug, PartialEq)]
+pub struct GraphStats {
+    pub entry_point: u32,
+    /// Out-degrees per level, bottom level first. SymphonyQG has a single level.
+    pub levels: Vec<Distribution>,
+}
+
+/// Shape and memory use of an index, see [`AnnIndex::stats`](super::AnnIndex::stats)
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexStats {
+    pub kind: IndexKind,
+    pub num: usize,
+    pub dim: usize,
+    /// Bytes of every in-memory section, e.g. `batch_data`, `ex_data`, `ids`
+    pub memory: Vec<(&'static str, usize)>,
+    /// IVF only
+    pub clusters: Option<ClusterStats>,
+    /// HNSW and SymphonyQG only
+    pub graph: Option<GraphStats>,
+}
+
+impl IndexStats {
+    pub fn total_bytes(&self) -> usize {
+        self.memory.iter().map(|&(_, bytes)| bytes).sum()
+    }
+}
+
+/// What one search did, returned when [`SearchParams::stats`](super::SearchParams::stats) is
+/// set. Counters that do not apply to an index stay 0, [`ShardedIndex`](super::ShardedIndex)
+/// sums those of its shards.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    /// Clusters probed (IVF)
+    pub clusters: usize,
+    /// FastScan batches of 32 codes scanned (IVF, QG)
+    pub batches: usize,
+    /// Vectors whose distance was estimated, plus the exact distances computed by QG
+    pub distances: usize,
+    /// Estimates dropped without boosting because their lower bound was above the current
+    /// k-th distance (IVF, HNSW), or neighbors kept out of the full search pool (QG)
+    pub pruned: usize,
+    /// Ex-bit refinements of an estimate (IVF, HNSW)
+    pub boosts: usize,
+    /// Graph vertices whose neighbors were scanned (HNSW, QG)
+    pub hops: usize,
+    /// Graph vertices marked visited (HNSW, QG)
+    pub visited: usize,
+}
+
+impl From<ffi::RabitqSearchStats> for SearchStats {
+    fn from(stats: ffi::RabitqSearchStats) -> Self {
+        Self {
+            clusters: stats.clusters,
+            batches: stats.batches,
+            distances: stats.distances,
+            pruned: stats.pruned,
+            boosts: stats.boosts,
+            hops: stats.hops,
+            visited: stats.visited,
+        }
+    }
+}
+
+impl From<SearchStats> for ffi::RabitqSearchStats {
+    fn from(stats: SearchStats) -> Self {
+        Self {
+            clusters: stats.clusters,
+            batches: stats.batches,
+            distances: stats.distances,
+            pruned: stats.pruned,
+            boosts: stats.boosts,
+            hops: stats.hops,
+            visited: stats.visited,
+        }
+    }
+}
+
+impl AddAssign for SearchStats {
+    fn add_assign(&mut self, other: Self) {
+        self.clusters += other.clusters;
+        self.batches += other.batches;
+        self.distances += other.distances;
+        self.pruned += other.pruned;
+        self.boosts += other.boosts;
+        self.hops += other.hops;
+        self.visited += other.visited;
+    }
+}
+
+impl fmt::Display for SearchStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "clusters {} / batches {} / distances {} / pruned {} / boosts {} / hops {} / \
+             visited {}",
+            self.clusters,
+            self.batches,
+            self.distances,
+            self.pruned,
+            self.boosts,
+            self.hops,
+            self.visited
+        )
+    }
+}
+
+impl fmt::Display for Distribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "min {} / mean {:.1} / max {} over {}",
+            self.min, self.mean, self.max, self.count
+        )
+    }
+}
+
+impl fmt::Display for IndexStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} index, {} x {}", self.kind, self.num, self.dim)?;
+        for (section, bytes) in &self.memory {
+            writeln!(f, "  {section:<12} {bytes:>14} bytes")?;
+        }
+        writeln!(f, "  {:<12} {:>14} bytes", "total", self.total_bytes())?;
+        if let Some(clusters) = &self.clusters {
+            writeln!(f, "clusters: size {}", clusters.distribution)?;
+            writeln!(
+                f,
+                "  empty {}, imbalance {:.3}",
+                clusters.empty, clusters.imbalance
+            )?;
+            for (lower, count) in &clusters.distribution.histogram {
+                writeln!(f, "  size >= {lower:<10} {count}")?;
+            }
+        }
+        if let Some(graph) = &self.graph {
+            writeln!(
+                f,
+                "graph: {} level(s), entry point {}",
+                graph.levels.len(),
+                graph.entry_point
+            )?;
+            for (level, degrees) in graph.levels.iter().enumerate() {
+                writeln!(f, "  level {level}: degree {degrees}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distributions() {
+        let exact = Distribution::exact(&[3, 1, 3, 2]);
+        assert_eq!((exact.min, exact.max, exact.mean), (1, 3, 2.25));
+        assert_eq!(exact.histogram, vec![(1, 1), (2, 1), (3, 2)]);
+
+        let clusters = ClusterStats::new(vec![0, 1, 5, 6, 20]);
+        assert_eq!(clusters.empty, 1);
+        assert_eq!(
+            clusters.distribution.histogram,
+            vec![(0, 1), (1, 1), (4, 2), (16, 1)]
+        );
+        assert!((ClusterStats::new(vec![4; 8]).imbalance - 1.0).abs() < 1e-12);
+        assert!(clusters.imbalance > 1.0);
+        assert_eq!(Distribution::exact(&[]).mean, 0.0);
+    }
+}
@@ -0,0 +1,495 @@
+//! Readers and writers for the usual ANN benchmark file formats.
+//!
+//! * `.fvecs` / `.ivecs` / `.bvecs`: every row is a little-endian `u32` dimension followed by
+//!   that many `f32` / `i32` / `u8` values (same layout as `load_vecs` in `utils/io.hpp`).
+//! * `.fbin` / `.ibin` / `.u8bin` / `.i8bin` (also fp16 / bf16 variants): a `u32` row count
+//!   and a `u32` column count, then the rows back to back (same layout as `load_bin`).
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::slice::ChunksExact;
+use std::sync::Mutex;
+
+use half::{bf16, f16};
+
+/// A scalar that can be stored in a vector file.
+pub trait FileElement: Copy + Default + 'static {
+    /// Size in bytes of one stored value.
+    const SIZE: usize;
+
+    /// Decode a value from `SIZE` little-endian bytes.
+    fn from_le_slice(bytes: &[u8]) -> Self;
+
+    /// Append the little-endian encoding of `self`.
+    fn write_le(self, out: &mut Vec<u8>);
+}
+
+macro_rules! impl_file_element {
+    ($($t:ty),*) => {
+        $(
+            impl FileElement for $t {
+                const SIZE: usize = std::mem::size_of::<$t>();
+
+                #[inline]
+                fn from_le_slice(bytes: &[u8]) -> Self {
+                    <$t>::from_le_bytes(bytes.try_into().unwrap())
+                }
+
+                #[inline]
+                fn write_le(self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+impl_file_element!(f32, i32, u32, u8, i8, f16, bf16);
+
+/// A dense row-major matrix, one vector per row.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Matrix<T> {
+    data: Vec<T>,
+    rows: usize,
+    cols: usize,
+}
+
+impl<T> Matrix<T> {
+    /// Wrap row-major `data` holding `rows` vectors of `cols` elements.
+    pub fn new(data: Vec<T>, rows: usize, cols: usize) -> Self {
+        assert_eq!(data.len(), rows * cols, "data does not match {rows}x{cols}");
+        Self { data, rows, cols }
+    }
+
+    /// Number of vectors
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Dimension of every vector
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// The `i`-th vector
+    pub fn row(&self, i: usize) -> &[T] {
+        &self.data[i * self.cols..(i + 1) * self.cols]
+    }
+
+    /// Iterate over all vectors
+    pub fn iter_rows(&self) -> ChunksExact<'_, T> {
+        self.data.chunks_exact(self.cols.max(1))
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        self.data
+    }
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn decode_into<T: FileElement>(bytes: &[u8], out: &mut Vec<T>) {
+    out.extend(bytes.chunks_exact(T::SIZE).map(T::from_le_slice));
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+/// Read a `.fvecs` / `.ivecs` / `.bvecs` file. All rows must share the same dimension.
+pub fn read_vecs<T: FileElement>(path: &Path) -> io::Result<Matrix<T>> {
+    let file = File::open(path)?;
+    let file_len = file.metadata()?.len() as usize;
+    let mut reader = BufReader::new(file);
+    if file_len == 0 {
+        return Ok(Matrix::new(Vec::new(), 0, 0));
+    }
+
+    let cols = read_u32(&mut reader)? as usize;
+    let row_bytes = 4 + cols * T::SIZE;
+    if !file_len.is_multiple_of(row_bytes) {
+        return Err(invalid_data(format!(
+            "{}: size {file_len} is not a multiple of the row size {row_bytes}",
+            path.display()
+        )));
+    }
+    let rows = file_len / row_bytes;
+
+    let mut data = Vec::with_capacity(rows * cols);
+    let mut buf = vec![0u8; cols * T::SIZE];
+    for i in 0..rows {
+        if i > 0 {
+            let d = read_u32(&mut reader)? as usize;
+            if d != cols {
+                return Err(invalid_data(format!(
+                    "{}: row {i} has dimension {d}, expected {cols}",
+                    path.display()
+                )));
+            }
+        }
+        reader.read_exact(&mut buf)?;
+        decode_into(&buf, &mut data);
+    }
+    Ok(Matrix::new(data, rows, cols))
+}
+
+/// Write a `.fvecs` / `.ivecs` / `.bvecs` file.
+pub fn write_vecs<T: FileElement>(path: &Path, matrix: &Matrix<T>) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let mut buf = Vec::with_capacity(4 + matrix.cols() * T::SIZE);
+    for row in matrix.iter_rows().take(matrix.rows()) {
+        buf.clear();
+        buf.extend_from_slice(&(matrix.cols() as u32).to_le_bytes());
+        for &v in row {
+            v.write_le(&mut buf);
+        }
+        writer.write_all(&buf)?;
+    }
+    writer.flush()
+}
+
+/// Read a `.fbin` / `.ibin` / `.u8bin` / `.i8bin` file.
+pub fn read_bin<T: FileElement>(path: &Path) -> io::Result<Matrix<T>> {
+    let file = File::open(path)?;
+    let file_len = file.metadata()?.len() as usize;
+    let mut reader = BufReader::new(file);
+    let rows = read_u32(&mut reader)? as usize;
+    let cols = read_u32(&mut reader)? as usize;
+    let expected = 8 + rows * cols * T::SIZE;
+    if file_len != expected {
+        return Err(invalid_data(format!(
+            "{}: header says {rows}x{cols} ({expected} bytes), file has {file_len} bytes",
+            path.display()
+        )));
+    }
+
+    let mut data = Vec::with_capacity(rows * cols);
+    let mut buf = vec![0u8; cols * T::SIZE];
+    for _ in 0..rows {
+        reader.read_exact(&mut buf)?;
+        decode_into(&buf, &mut data);
+    }
+    Ok(Matrix::new(data, rows, cols))
+}
+
+/// Write a `.fbin` / `.ibin` / `.u8bin` / `.i8bin` file.
+pub fn write_bin<T: FileElement>(path: &Path, matrix: &Matrix<T>) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(&(matrix.rows() as u32).to_le_bytes())?;
+    writer.write_all(&(matrix.cols() as u32).to_le_bytes())?;
+    let mut buf = Vec::with_capacity(matrix.cols() * T::SIZE);
+    for row in matrix.iter_rows().take(matrix.rows()) {
+        buf.clear();
+        for &v in row {
+            v.write_le(&mut buf);
+        }
+        writer.write_all(&buf)?;
+    }
+    writer.flush()
+}
+
+/// On-disk layout of a vector file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// `.fvecs` / `.ivecs` / `.bvecs`, dimension before every row
+    Vecs,
+    /// `.fbin` / `.u8bin` / ..., one header for the whole file
+    Bin,
+}
+
+impl Format {
+    /// Guess the format from the extension: `*vecs` or `*bin`.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        if ext.ends_with("vecs") {
+            Some(Format::Vecs)
+        } else if ext.ends_with("bin") {
+            Some(Format::Bin)
+        } else {
+            None
+        }
+    }
+}
+
+/// Rows and columns from the header of a vector file, `None` for an empty `.fvecs` file.
+/// Leaves `reader` after the header of the first row.
+fn read_shape<T: FileElement>(
+    reader: &mut impl Read,
+    file_len: usize,
+    format: Format,
+    path: &Path,
+) -> io::Result<Option<(usize, usize)>> {
+    match format {
+        Format::Vecs => {
+            if file_len == 0 {
+                return Ok(None);
+            }
+            let cols = read_u32(reader)? as usize;
+            let row_bytes = 4 + cols * T::SIZE;
+            if !file_len.is_multiple_of(row_bytes) {
+                return Err(invalid_data(format!(
+                    "{}: size {file_len} is not a multiple of the row size {row_bytes}",
+                    path.display()
+                )));
+            }
+            Ok(Some((file_len / row_bytes, cols)))
+        }
+        Format::Bin => {
+            let rows = read_u32(reader)? as usize;
+            let cols = read_u32(reader)? as usize;
+            if file_len != 8 + rows * cols * T::SIZE {
+                return Err(invalid_data(format!(
+                    "{}: header says {rows}x{cols}, file has {file_len} bytes",
+                    path.display()
+                )));
+            }
+            Ok(Some((rows, cols)))
+        }
+    }
+}
+
+/// Stream a vector file in blocks of at most `block_rows` vectors, so files larger than memory
+/// can be processed. `f` receives each block and the index of its first row.
+pub fn read_blocks<T: FileElement>(
+    path: &Path,
+    format: Format,
+    block_rows: usize,
+    mut f: impl FnMut(Matrix<T>, usize) -> io::Result<()>,
+) -> io::Result<()> {
+    assert!(block_rows > 0);
+    let file = File::open(path)?;
+    let file_len = file.metadata()?.len() as usize;
+    let mut reader = BufReader::new(file);
+    let Some((rows, cols)) = read_shape::<T>(&mut reader, file_len, format, path)? else {
+        return Ok(());
+    };
+
+    let mut buf = vec![0u8; cols * T::SIZE];
+    let mut start = 0;
+    while start < rows {
+        let n = block_rows.min(rows - start);
+        let mut data = Vec::with_capacity(n * cols);
+        for i in start..start + n {
+            if format == Format::Vecs && i > 0 {
+                let d = read_u32(&mut reader)? as usize;
+                if d != cols {
+                    return Err(invalid_data(format!(
+                        "{}: row {i} has dimension {d}, expected {cols}",
+                        path.display()
+                    )));
+                }
+            }
+            reader.read_exact(&mut buf)?;
+            decode_into(&buf, &mut data);
+        }
+        f(Matrix::new(data, n, cols), start)?;
+        start += n;
+    }
+    Ok(())
+}
+
+/// Random access to the rows of a vector file, for builds that cannot hold the whole file
+/// in memory. Reads may come from several threads.
+pub struct VectorFile<T> {
+    file: Mutex<File>,
+    path: PathBuf,
+    format: Format,
+    rows: usize,
+    cols: usize,
+    _element: PhantomData<T>,
+}
+
+impl<T: FileElement> VectorFile<T> {
+    /// Open `path`, taking the format from its extension.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let format = Format::from_path(path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}: unknown vector file extension", path.display()),
+            )
+        })?;
+        let mut file = File::open(path)?;
+        let file_len = file.metadata()?.len() as usize;
+        let (rows, cols) = read_shape::<T>(&mut file, file_len, format, path)?.unwrap_or((0, 0));
+        Ok(Self {
+            file: Mutex::new(file),
+            path: path.to_path_buf(),
+            format,
+            rows,
+            cols,
+            _element: PhantomData,
+        })
+    }
+
+    /// Number of vectors
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Dimension of every vector
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    fn row_bytes(&self) -> usize {
+        match self.format {
+            Format::Vecs => 4 + self.cols * T::SIZE,
+            Format::Bin => self.cols * T::SIZE,
+        }
+    }
+
+    /// Append the rows `ids` to `out`, in the given order. Runs of consecutive ids are read
+    /// with a single seek.
+    pub fn read_rows(&self, ids: &[u32], out: &mut Vec<T>) -> io::Result<()> {
+        let row_bytes = self.row_bytes();
+        let header = match self.format {
+            Format::Vecs => 0,
+            Format::Bin => 8,
+        };
+        let mut buf = Vec::new();
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let mut start = 0;
+        while start < ids.len() {
+            let mut end = start + 1;
+            while end < ids.len() && ids[end] == ids[end - 1].wrapping_add(1) {
+                end += 1;
+            }
+            let first = ids[start] as usize;
+            if first + (end - start) > self.rows {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "{}: row {} out of range, file has {} rows",
+                        self.path.display(),
+                        first + (end - start) - 1,
+                        self.rows
+                    ),
+                ));
+            }
+            buf.resize((end - start) * row_bytes, 0);
+            file.seek(SeekFrom::Start((header + first * row_bytes) as u64))?;
+            file.read_exact(&mut buf)?;
+            for (i, row) in buf.chunks_exact(row_bytes).enumerate() {
+                let values = match self.format {
+                    Format::Vecs => {
+                        let d = u32::from_le_bytes(row[..4].try_into().unwrap()) as usize;
+                        if d != self.cols {
+                            return Err(invalid_data(format!(
+                                "{}: row {} has dimension {d}, expected {}",
+                                self.path.display(),
+                                first + i,
+                                self.cols
+                            )));
+                        }
+                        &row[4..]
+                    }
+                    Format::Bin => row,
+                };
+                decode_into(values, out);
+            }
+            start = end;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_bvecs_round_trip() {
+        let m = Matrix::new((0..=255u8).collect(), 4, 64);
+        let file = NamedTempFile::new().unwrap();
+        write_vecs(file.path(), &m).unwrap();
+        assert_eq!(std::fs::metadata(file.path()).unwrap().len(), 4 * (4 + 64));
+        assert_eq!(read_vecs::<u8>(file.path()).unwrap(), m);
+    }
+
+    #[test]
+    fn test_fvecs_round_trip() {
+        let m = Matrix::new((0..30).map(|i| i as f32 * 0.5).collect(), 3, 10);
+        let file = NamedTempFile::new().unwrap();
+        write_vecs(file.path(), &m).unwrap();
+        let back = read_vecs::<f32>(file.path()).unwrap();
+        assert_eq!(back.rows(), 3);
+        assert_eq!(back.row(2), m.row(2));
+    }
+
+    #[test]
+    fn test_bin_round_trip() {
+        let m = Matrix::new((-64..64i8).collect(), 8, 16);
+        let file = NamedTempFile::new().unwrap();
+        write_bin(file.path(), &m).unwrap();
+        assert_eq!(read_bin::<i8>(file.path()).unwrap(), m);
+
+        let h = Matrix::new((0..12).map(|i| f16::from_f32(i as f32)).collect(), 2, 6);
+        write_bin(file.path(), &h).unwrap();
+        assert_eq!(read_bin::<f16>(file.path()).unwrap(), h);
+    }
+
+    #[test]
+    fn test_read_blocks() {
+        let m = Matrix::new((0..70).map(|i| i as f32).collect(), 7, 10);
+        let file = NamedTempFile::new().unwrap();
+        for format in [Format::Vecs, Format::Bin] {
+            match format {
+                Format::Vecs => write_vecs(file.path(), &m).unwrap(),
+                Format::Bin => write_bin(file.path(), &m).unwrap(),
+            }
+            let mut blocks = Vec::new();
+            read_blocks::<f32>(file.path(), format, 3, |block, start| {
+                blocks.push((start, block.rows()));
+                assert_eq!(block.row(0), m.row(start));
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(blocks, [(0, 3), (3, 3), (6, 1)]);
+        }
+        assert_eq!(Format::from_path(Path::new("a.u8bin")), Some(Format::Bin));
+        assert_eq!(Format::from_path(Path::new("a.fvecs")), Some(Format::Vecs));
+        assert_eq!(Format::from_path(Path::new("a.txt")), None);
+    }
+
+    #[test]
+    fn test_vector_file_random_access() {
+        let m = Matrix::new((0..70).map(|i| i as f32).collect(), 7, 10);
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a.fvecs", "a.fbin"] {
+            let path = dir.path().join(name);
+            match Format::from_path(&path).unwrap() {
+                Format::Vecs => write_vecs(&path, &m).unwrap(),
+                Format::Bin => write_bin(&path, &m).unwrap(),
+            }
+            let file = VectorFile::<f32>::open(&path).unwrap();
+            assert_eq!((file.rows(), file.cols()), (7, 10));
+            let mut rows = Vec::new();
+            file.read_rows(&[5, 6, 0, 2, 3], &mut rows).unwrap();
+            let expected: Vec<f32> = [5, 6, 0, 2, 3]
+                .into_iter()
+                .flat_map(|i| m.row(i).to_vec())
+                .collect();
+            assert_eq!(rows, expected);
+            assert!(file.read_rows(&[6, 7], &mut rows).is_err());
+        }
+    }
+
+    #[test]
+    fn test_wrong_element_size_is_rejected() {
+        let m = Matrix::new(vec![1.0f32; 20], 2, 10);
+        let file = NamedTempFile::new().unwrap();
+        write_bin(file.path(), &m).unwrap();
+        assert!(read_bin::<u8>(file.path()).is_err());
+    }
+}
@@ -0,0 +1,211 @@
+//! Lloyd's k-means, used to partition the data for IVF and for the HNSW centroids.
+//!
+//! This replaces `python/ivf.py` for the Rust tooling. Training runs on a random sample of at
+//! most `max_points_per_centroid * k` vectors, then every vector is assigned to its nearest
+//! centroid.
+
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::index::sample;
+use rayon::prelude::*;
+
+use crate::element::VectorElement;
+
+/// Parameters of a k-means run
+#[derive(Clone, Debug)]
+pub struct KMeans {
+    /// Number of clusters
+    pub k: usize,
+    /// Number of Lloyd iterations
+    pub iters: usize,
+    /// Cap on the training sample size, per centroid
+    pub max_points_per_centroid: usize,
+    pub seed: u64,
+}
+
+/// Result of [`KMeans::fit`]
+#[derive(Clone, Debug)]
+pub struct Clustering {
+    /// `k * dim` row-major centroids
+    pub centroids: Vec<f32>,
+    /// Nearest centroid of every input vector
+    pub assignments: Vec<u32>,
+}
+
+impl KMeans {
+    pub fn new(k: usize) -> Self {
+        Self {
+            k,
+            iters: 10,
+            max_points_per_centroid: 256,
+            seed: 42,
+        }
+    }
+
+    /// Cluster `data` (`n * dim`, row-major). Only the training sample is widened to `f32`.
+    pub fn fit<T: VectorElement>(&self, data: &[T], dim: usize) -> Clustering {
+        assert!(dim > 0 && data.len().is_multiple_of(dim));
+        let n = data.len() / dim;
+        assert!(self.k > 0 && self.k <= n, "k must be in 1..={n}");
+
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let num_train = n.min(self.k * self.max_points_per_centroid.max(1));
+        let train: Vec<f32> = if num_train == n {
+            data.iter().map(|v| v.to_f32()).collect()
+        } else {
+            sample(&mut rng, n, num_train)
+                .into_iter()
+                .flat_map(|i| &data[i * dim..(i + 1) * dim])
+                .map(|v| v.to_f32())
+                .collect()
+        };
+
+        // random distinct training points as initial centroids
+        let mut centroids: Vec<f32> = sample(&mut rng, num_train, self.k)
+            .into_iter()
+            .flat_map(|i| &train[i * dim..(i + 1) * dim])
+            .copied()
+            .collect();
+
+        let mut assignments = vec![0u32; num_train];
+        for _ in 0..self.iters {
+            assign(&train, dim, &centroids, &mut assignments);
+            update_centroids(&train, dim, &assignments, &mut centroids);
+        }
+
+        let mut assignments = vec![0u32; n];
+        assign(data, dim, &centroids, &mut assignments);
+        Clustering {
+            centroids,
+            assignments,
+        }
+    }
+}
+
+#[inline]
+fn l2_sqr<T: VectorElement>(a: &[T], b: &[f32]) -> f32 {
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| (x.to_f32() - y) * (x.to_f32() - y))
+        .sum()
+}
+
+/// Nearest centroid of every vector of `data`
+pub(crate) fn assign<T: VectorElement>(
+    data: &[T],
+    dim: usize,
+    centroids: &[f32],
+    assignments: &mut [u32],
+) {
+    assignments
+        .par_iter_mut()
+        .zip(data.par_chunks_exact(dim))
+        .for_each(|(a, x)| {
+            let mut best = (f32::INFINITY, 0);
+            for (c, centroid) in centroids.chunks_exact(dim).enumerate() {
+                let d = l2_sqr(x, centroid);
+                if d < best.0 {
+                    best = (d, c);
+                }
+            }
+            *a = best.1 as u32;
+        });
+}
+
+fn update_centroids(data: &[f32], dim: usize, assignments: &[u32], centroids: &mut [f32]) {
+    let k = centroids.len() / dim;
+    let mut sums = vec![0.0f64; k * dim];
+    let mut counts = vec![0usize; k];
+    for (x, &a) in data.chunks_exact(dim).zip(assignments) {
+        let a = a as usize;
+        counts[a] += 1;
+        for (s, &v) in sums[a * dim..(a + 1) * dim].iter_mut().zip(x) {
+            *s += v as f64;
+        }
+    }
+
+    for c in 0..k {
+        if counts[c] == 0 {
+            continue;
+        }
+        let inv = 1.0 / counts[c] as f64;
+        for (dst, &s) in centroids[c * dim..(c + 1) * dim]
+            .iter_mut()
+            .zip(&sums[c * dim..(c + 1) * dim])
+        {
+            *dst = (s * inv) as f32;
+        }
+    }
+
+    // an empty cluster takes over half of the largest one, split along a tiny perturbation
+    for c in 0..k {
+        if counts[c] != 0 {
+            continue;
+        }
+        let largest = (0..k).max_by_key(|&i| counts[i]).unwrap();
+        let (src, dst) = if largest < c {
+            let (lo, hi) = centroids.split_at_mut(c * dim);
+            (&mut lo[largest * dim..(largest + 1) * dim], &mut hi[..dim])
+        } else {
+            let (lo, hi) = centroids.split_at_mut(largest * dim);
+            (&mut hi[..dim], &mut lo[c * dim..(c + 1) * dim])
+        };
+        for (j, (s, d)) in src.iter_mut().zip(dst.iter_mut()).enumerate() {
+            let eps = if j % 2 == 0 {
+                1.0 / 1024.0
+            } else {
+                -1.0 / 1024.0
+            };
+            *d = *s * (1.0 + eps);
+            *s *= 1.0 - eps;
+        }
+        counts[c] = counts[largest] / 2;
+        counts[largest] -= counts[c];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_separated_blobs() {
+        // 4 well separated blobs of 50 points
+        let dim = 8;
+        let mut data = Vec::new();
+        for b in 0..4 {
+            for i in 0..50 {
+                for j in 0..dim {
+                    let jitter = ((i * 7 + j * 3) % 11) as f32 * 0.01;
+                    data.push(b as f32 * 100.0 + jitter);
+                }
+            }
+        }
+        let mut km = KMeans::new(4);
+        km.seed = 7;
+        let result = km.fit(&data, dim);
+        assert_eq!(result.centroids.len(), 4 * dim);
+        for b in 0..4 {
+            let first = result.assignments[b * 50];
+            assert!(
+                result.assignments[b * 50..(b + 1) * 50]
+                    .iter()
+                    .all(|&a| a == first)
+            );
+        }
+        let mut ids = result.assignments.clone();
+        ids.sort_unstable();
+        ids.dedup();
+        assert_eq!(ids.len(), 4);
+    }
+
+    #[test]
+    fn test_deterministic_for_a_seed() {
+        let dim = 4;
+        let data: Vec<f32> = (0..400).map(|i| ((i * 37) % 101) as f32).collect();
+        let a = KMeans::new(5).fit(&data, dim);
+        let b = KMeans::new(5).fit(&data, dim);
+        assert_eq!(a.assignments, b.assignments);
+        assert_eq!(a.centroids, b.centroids);
+    }
+}
@@ -1,9 +1,26 @@
 //! Rabitq Rust wrapper
 
+pub mod element;
 pub mod estimator;
+pub mod eval;
+pub mod fastscan;
+pub mod groundtruth;
+pub mod index;
+pub mod io;
+pub mod kmeans;
+pub mod logging;
 pub mod quantizer;
 pub mod rotator;
+pub mod tune;
 
-pub use estimator::{BatchBinEstimator, SingleEstimator, SplitBatchEstimator};
+pub use element::{VectorElement, bf16, f16};
+pub use estimator::{
+    BatchBinEstimator, DEFAULT_EPSILON0, DistanceBound, QueryPrecision, SingleEstimator,
+    SplitBatchEstimator,
+};
+pub use index::{
+    AnnIndex, BuildMonitor, CancelToken, IndexKind, IndexMeta, SearchBudget, SearchParams,
+    SearchResult,
+};
 pub use quantizer::{MetricType, RabitqConfig, quantize_full_single, reconstruct_vec};
 pub use rotator::Rotator;
@@ -0,0 +1,102 @@
+//! Bridge from the messages of the C++ library to the [`log`] crate.
+//!
+//! The library prints nothing until [`init`] is called. Records are emitted with the
+//! [`TARGET`] target, so they can be filtered like any other module; `tracing` subscribers
+//! receive them through `tracing-log`.
+
+use std::ffi::{CStr, c_char};
+use std::panic::{self, AssertUnwindSafe};
+
+use log::{Level, LevelFilter};
+use rabitq_sys as ffi;
+
+/// Target of the records forwarded from the library
+pub const TARGET: &str = "rabitq";
+
+unsafe extern "C" fn forward(level: ffi::RabitqLogLevel, message: *const c_char) {
+    let level = match level {
+        ffi::RabitqLogLevel_RABITQ_LOG_ERROR => Level::Error,
+        ffi::RabitqLogLevel_RABITQ_LOG_WARN => Level::Warn,
+        ffi::RabitqLogLevel_RABITQ_LOG_INFO => Level::Info,
+        _ => Level::Debug,
+    };
+    // SAFETY: the library passes a NUL-terminated string alive during the call
+    let message = unsafe { CStr::from_ptr(message) }.to_string_lossy();
+    // a panicking logger must not unwind into C++
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        log::log!(target: TARGET, level, "{message}");
+    }));
+}
+
+/// Forward the messages of the library to the installed logger.
+///
+/// Messages above [`log::max_level`] are dropped before the library formats them, call this
+/// again after raising the max level.
+pub fn init() {
+    let max_level = match log::max_level() {
+        LevelFilter::Off => return disable(),
+        LevelFilter::Error => ffi::RabitqLogLevel_RABITQ_LOG_ERROR,
+        LevelFilter::Warn => ffi::RabitqLogLevel_RABITQ_LOG_WARN,
+        LevelFilter::Info => ffi::RabitqLogLevel_RABITQ_LOG_INFO,
+        LevelFilter::Debug | LevelFilter::Trace => ffi::RabitqLogLevel_RABITQ_LOG_DEBUG,
+    };
+    unsafe { ffi::rabitq_set_log_callback(Some(forward), max_level) }
+}
+
+/// Silence the library again, the default
+pub fn disable() {
+    unsafe { ffi::rabitq_set_log_callback(None, ffi::RabitqLogLevel_RABITQ_LOG_ERROR) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::index::{Ivf, IvfParams};
+
+    static RECORDS: Mutex<Vec<(Level, String)>> = Mutex::new(Vec::new());
+
+    struct Capture;
+
+    impl log::Log for Capture {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.target() == TARGET
+        }
+
+        fn log(&self, record: &log::Record) {
+            if self.enabled(record.metadata()) {
+                let message = record.args().to_string();
+                RECORDS.lock().unwrap().push((record.level(), message));
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn test_log_bridge() {
+        log::set_logger(&Capture).unwrap();
+        log::set_max_level(LevelFilter::Info);
+
+        let data: Vec<f32> = (0..200 * 64).map(|i| ((i * 37) % 101) as f32).collect();
+        let params = IvfParams {
+            num_clusters: 4,
+            ..Default::default()
+        };
+        let build = || Ivf::build(&data, 64, &params).unwrap();
+
+        build();
+        assert!(RECORDS.lock().unwrap().is_empty(), "silent by default");
+
+        init();
+        build();
+        disable();
+        let records = std::mem::take(&mut *RECORDS.lock().unwrap());
+        assert!(records.contains(&(Level::Info, "Start IVF construction...".to_string())));
+        assert!(records.iter().all(|(level, _)| *level <= Level::Info));
+
+        build();
+        assert!(RECORDS.lock().unwrap().is_empty());
+    }
+}
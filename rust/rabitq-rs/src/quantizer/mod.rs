@@ -1,12 +1,34 @@
+use crate::element::VectorElement;
 use rabitq_sys as ffi;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(u32)]
 pub enum MetricType {
     L2 = 0,
     IP = 1,
 }
 
+impl std::fmt::Display for MetricType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MetricType::L2 => f.write_str("l2"),
+            MetricType::IP => f.write_str("ip"),
+        }
+    }
+}
+
+impl std::str::FromStr for MetricType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "l2" => Ok(MetricType::L2),
+            "ip" => Ok(MetricType::IP),
+            _ => Err(format!("unknown metric `{s}`, expected `l2` or `ip`")),
+        }
+    }
+}
+
 pub struct RabitqConfig {
     pub ptr: *mut ffi::RabitqConfig,
 }
@@ -23,18 +45,29 @@ impl RabitqConfig {
     }
 }
 
+impl Default for RabitqConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Drop for RabitqConfig {
     fn drop(&mut self) {
         unsafe { ffi::rabitq_config_free(self.ptr) };
     }
 }
 
-pub fn quantize_full_single(
-    data: &[f32],
+// The config is never modified after construction.
+unsafe impl Send for RabitqConfig {}
+unsafe impl Sync for RabitqConfig {}
+
+pub fn quantize_full_single<T: VectorElement>(
+    data: &[T],
     total_bits: usize,
     metric_type: MetricType,
     config: &RabitqConfig,
 ) -> (Vec<u8>, f32, f32, f32) {
+    let data = T::slice_to_f32(data);
     let dim = data.len();
     let mut total_code = vec![0u8; dim];
     let mut f_add = 0.0;
@@ -57,13 +90,14 @@ pub fn quantize_full_single(
     (total_code, f_add, f_rescale, f_error)
 }
 
-pub fn quantize_split_single(
-    data: &[f32],
+pub fn quantize_split_single<T: VectorElement>(
+    data: &[T],
     centroid: &[f32],
     ex_bits: usize,
     metric_type: MetricType,
     config: &RabitqConfig,
 ) -> (Vec<u8>, Vec<u8>) {
+    let data = T::slice_to_f32(data);
     let padded_dim = data.len();
     let mut bin_codes = vec![0u8; padded_dim / 8 + 12];
     let mut ex_codes = vec![0u8; padded_dim * ex_bits / 8 + 8];
@@ -74,8 +108,8 @@ pub fn quantize_split_single(
             centroid.as_ptr(),
             padded_dim,
             ex_bits,
-            bin_codes.as_mut_ptr() as *mut i8,
-            ex_codes.as_mut_ptr() as *mut i8,
+            bin_codes.as_mut_ptr() as *mut _,
+            ex_codes.as_mut_ptr() as *mut _,
             metric_type as u32,
             config.ptr,
         );
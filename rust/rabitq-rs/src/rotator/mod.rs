@@ -1,5 +1,7 @@
+use crate::element::VectorElement;
 use rabitq_sys as ffi;
 use std::ffi::CString;
+use std::fmt;
 use std::os::raw::c_char;
 use std::path::Path;
 
@@ -20,9 +22,106 @@ impl Rotator {
         }
     }
 
-    /// Rotate a raw vector
-    pub fn rotate(&self, x: &[f32], y: &mut [f32]) {
+    /// Create a Rotator that is the same for the same `seed`
+    pub fn with_seed(dim: usize, padded_dim: usize, seed: u64) -> Option<Self> {
+        let ptr = unsafe { ffi::rabitq_rotator_new_seeded(dim, padded_dim, seed) };
+        if ptr.is_null() {
+            None
+        } else {
+            Some(Self { ptr })
+        }
+    }
+
+    /// Learn a PCA from `sample` (`n * dim`, at least 2 rows) down to `out_dim` components,
+    /// followed by a rotation of `out_dim` padded to a multiple of 64 drawn from `seed`. Given as the
+    /// `rotator` of an index's params, it makes the index quantize the projected vectors
+    /// while data and queries keep their original dimension.
+    pub fn pca<T: VectorElement>(
+        sample: &[T],
+        dim: usize,
+        out_dim: usize,
+        seed: u64,
+    ) -> std::io::Result<Self> {
+        if dim == 0 || !sample.len().is_multiple_of(dim) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("sample is not a multiple of dim {dim}"),
+            ));
+        }
+        let n = sample.len() / dim;
+        let sample = T::slice_to_f32(sample);
+        let ptr = unsafe { ffi::rabitq_pca_rotator_new(sample.as_ptr(), n, dim, out_dim, seed) };
+        if ptr.is_null() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("PCA needs 2 samples and out_dim in 1..={dim}, got {n} and {out_dim}"),
+            ));
+        }
+        Ok(Self { ptr })
+    }
+
+    /// Load a rotator saved from [`Rotator::pca`]
+    pub fn load_pca(path: &Path) -> Result<Self, std::io::Error> {
+        let c_path = CString::new(path.to_str().unwrap()).unwrap();
+        let ptr = unsafe { ffi::rabitq_pca_rotator_load(c_path.as_ptr() as *const c_char) };
+        if ptr.is_null() {
+            Err(std::io::Error::other("Failed to load PCA rotator"))
+        } else {
+            Ok(Self { ptr })
+        }
+    }
+
+    /// Learn an orthogonal rotation of `dim` (padded to a multiple of 64) from `sample`
+    /// (`n * dim`, at least 2 rows) in `iters` ITQ-style steps from one drawn from `seed`, each
+    /// of which lowers the 1-bit quantization error of the centered and normalized sample.
+    /// Given as the `rotator` of an index's params, it replaces the random rotation.
+    pub fn learned<T: VectorElement>(
+        sample: &[T],
+        dim: usize,
+        iters: usize,
+        seed: u64,
+    ) -> std::io::Result<Self> {
+        if dim == 0 || !sample.len().is_multiple_of(dim) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("sample is not a multiple of dim {dim}"),
+            ));
+        }
+        let n = sample.len() / dim;
+        let sample = T::slice_to_f32(sample);
+        let ptr = unsafe { ffi::rabitq_learned_rotator_new(sample.as_ptr(), n, dim, iters, seed) };
+        if ptr.is_null() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("learning a rotation needs 2 samples, got {n}"),
+            ));
+        }
+        Ok(Self { ptr })
+    }
+
+    /// Load a rotator of `dim` saved from [`Rotator::learned`]
+    pub fn load_learned(path: &Path, dim: usize) -> Result<Self, std::io::Error> {
+        let c_path = CString::new(path.to_str().unwrap()).unwrap();
+        let ptr =
+            unsafe { ffi::rabitq_learned_rotator_load(c_path.as_ptr() as *const c_char, dim) };
+        if ptr.is_null() {
+            Err(std::io::Error::other("Failed to load learned rotator"))
+        } else {
+            Ok(Self { ptr })
+        }
+    }
+
+    /// Fraction of the sample variance kept by a PCA rotator, `None` for plain rotations
+    pub fn explained_variance(&self) -> Option<f32> {
+        let ratio = unsafe { ffi::rabitq_rotator_explained_variance(self.ptr) };
+        (ratio >= 0.0).then_some(ratio)
+    }
+
+    /// Rotate a raw vector. Non-`f32` inputs are widened to `f32` first.
+    pub fn rotate<T: VectorElement>(&self, x: &[T], y: &mut [f32]) {
+        assert_eq!(x.len(), self.dim());
         assert_eq!(y.len(), self.padded_dim());
+        let x = T::slice_to_f32(x);
         unsafe {
             ffi::rabitq_rotator_rotate(self.ptr, x.as_ptr(), y.as_mut_ptr());
         }
@@ -36,10 +135,7 @@ impl Rotator {
         if result == 0 {
             Ok(())
         } else {
-            Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Failed to load rotator",
-            ))
+            Err(std::io::Error::other("Failed to load rotator"))
         }
     }
 
@@ -51,10 +147,7 @@ impl Rotator {
         if result == 0 {
             Ok(())
         } else {
-            Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Failed to save rotator",
-            ))
+            Err(std::io::Error::other("Failed to save rotator"))
         }
     }
 
@@ -67,6 +160,10 @@ impl Rotator {
     pub fn dim(&self) -> usize {
         unsafe { ffi::rabitq_rotator_dim(self.ptr) }
     }
+
+    pub(crate) fn as_ptr(&self) -> *const ffi::Rotator {
+        self.ptr
+    }
 }
 
 impl Drop for Rotator {
@@ -77,6 +174,15 @@ impl Drop for Rotator {
     }
 }
 
+impl fmt::Debug for Rotator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Rotator")
+            .field("dim", &self.dim())
+            .field("padded_dim", &self.padded_dim())
+            .finish()
+    }
+}
+
 unsafe impl Send for Rotator {}
 unsafe impl Sync for Rotator {}
 
@@ -103,6 +209,67 @@ mod tests {
         assert!(y.iter().any(|&v| v != 0.0));
     }
 
+    // every FHT size and the Kac's walk over the remainder, with the kernels picked for the
+    // build (AVX-512, NEON or scalar)
+    #[test]
+    fn test_rotator_preserves_inner_products() {
+        let dims = [
+            (64, 64),
+            (100, 128),
+            (192, 192),
+            (250, 256),
+            (320, 320),
+            (500, 512),
+            (1000, 1024),
+            (2048, 2048),
+            (2100, 2112),
+        ];
+        for (dim, padded_dim) in dims {
+            let rotator = Rotator::new(dim, padded_dim).unwrap();
+            let x: Vec<f32> = (0..dim)
+                .map(|i| ((i * 37) % 101) as f32 / 50.0 - 1.0)
+                .collect();
+            let y: Vec<f32> = (0..dim)
+                .map(|i| ((i * 53) % 97) as f32 / 48.0 - 1.0)
+                .collect();
+            let (mut rx, mut ry) = (vec![0.0f32; padded_dim], vec![0.0f32; padded_dim]);
+            rotator.rotate(&x, &mut rx);
+            rotator.rotate(&y, &mut ry);
+            let dot = |a: &[f32], b: &[f32]| a.iter().zip(b).map(|(a, b)| a * b).sum::<f32>();
+            for (before, after) in [(dot(&x, &x), dot(&rx, &rx)), (dot(&x, &y), dot(&rx, &ry))] {
+                assert!(
+                    (before - after).abs() <= 1e-3 * dot(&x, &x),
+                    "padded_dim {padded_dim}: {before} vs {after}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_rotator_rotate_u8() {
+        let rotator = Rotator::new(100, 128).unwrap();
+        let x: Vec<u8> = (0..100).map(|i| (i * 7 % 256) as u8).collect();
+        let x_f32: Vec<f32> = x.iter().map(|&v| v as f32).collect();
+        let mut y = vec![0.0f32; 128];
+        let mut y_f32 = vec![0.0f32; 128];
+        rotator.rotate(&x, &mut y);
+        rotator.rotate(&x_f32, &mut y_f32);
+        assert_eq!(y, y_f32);
+    }
+
+    #[test]
+    fn test_rotator_seed() {
+        let x: Vec<f32> = (0..100).map(|i| i as f32).collect();
+        let rotate = |rotator: Rotator| {
+            let mut y = vec![0.0f32; 128];
+            rotator.rotate(&x, &mut y);
+            y
+        };
+        let first = rotate(Rotator::with_seed(100, 128, 7).unwrap());
+        assert_eq!(first, rotate(Rotator::with_seed(100, 128, 7).unwrap()));
+        assert_ne!(first, rotate(Rotator::with_seed(100, 128, 8).unwrap()));
+    }
+
     #[test]
     fn test_rotator_save_load() {
         let rotator = Rotator::new(128, 128).unwrap();
@@ -116,4 +283,106 @@ mod tests {
         let load_result = new_rotator.load(path);
         assert!(load_result.is_ok());
     }
+
+    #[test]
+    fn test_pca_rotator() {
+        // 200 dims of which only the first 20 vary much
+        let (n, dim) = (500, 200);
+        let sample: Vec<f32> = (0..n * dim)
+            .map(|i| {
+                let noise = ((i * 7919) % 1000) as f32 / 1000.0 - 0.5;
+                if i % dim < 20 {
+                    noise * 10.0
+                } else {
+                    noise * 0.1
+                }
+            })
+            .collect();
+        let rotator = Rotator::pca(&sample, dim, 20, 42).unwrap();
+        assert_eq!(rotator.dim(), 200);
+        assert_eq!(rotator.padded_dim(), 64);
+        assert!(rotator.explained_variance().unwrap() > 0.95);
+        assert!(
+            Rotator::new(128, 128)
+                .unwrap()
+                .explained_variance()
+                .is_none()
+        );
+
+        // the projection keeps distances between sample vectors
+        let mut x = vec![0.0f32; 64];
+        let mut y = vec![0.0f32; 64];
+        rotator.rotate(&sample[..dim], &mut x);
+        rotator.rotate(&sample[dim..2 * dim], &mut y);
+        let dist =
+            |a: &[f32], b: &[f32]| a.iter().zip(b).map(|(u, v)| (u - v).powi(2)).sum::<f32>();
+        let raw = dist(&sample[..dim], &sample[dim..2 * dim]);
+        assert!((dist(&x, &y) - raw).abs() < 0.05 * raw);
+
+        let temp_file = NamedTempFile::new().unwrap();
+        rotator.save(temp_file.path()).unwrap();
+        let loaded = Rotator::load_pca(temp_file.path()).unwrap();
+        let mut z = vec![0.0f32; 64];
+        loaded.rotate(&sample[..dim], &mut z);
+        assert_eq!(x, z);
+
+        assert!(Rotator::pca(&sample, dim, 0, 42).is_err());
+        assert!(Rotator::pca(&sample, dim, 201, 42).is_err());
+        assert!(Rotator::pca(&sample[..dim], dim, 10, 42).is_err());
+    }
+
+    #[test]
+    fn test_learned_rotator() {
+        // clustered sample, where a rotation aligned with the data beats a random one
+        let (n, dim) = (1000, 100);
+        let sample: Vec<f32> = (0..n * dim)
+            .map(|i| {
+                let (row, col) = (i / dim, i % dim);
+                let noise = ((i * 7919) % 1000) as f32 / 1000.0 - 0.5;
+                if col % 10 == row % 10 {
+                    3.0 + noise
+                } else {
+                    noise * 0.3
+                }
+            })
+            .collect();
+        // mean <o_bar, o> of the sign codes of the centered sample, what training raises
+        let mean = |j: usize| (0..n).map(|i| sample[i * dim + j]).sum::<f32>() / n as f32;
+        let means: Vec<f32> = (0..dim).map(mean).collect();
+        let quality = |rotator: &Rotator| {
+            let padded = rotator.padded_dim();
+            let mut y = vec![0.0f32; padded];
+            let mut total = 0.0;
+            for row in sample.chunks(dim) {
+                let centered: Vec<f32> = row.iter().zip(&means).map(|(x, m)| x - m).collect();
+                rotator.rotate(&centered, &mut y);
+                let norm = y.iter().map(|v| v * v).sum::<f32>().sqrt();
+                let l1 = y.iter().map(|v| v.abs()).sum::<f32>();
+                total += l1 / (norm * (padded as f32).sqrt());
+            }
+            total / n as f32
+        };
+        let random = Rotator::learned(&sample, dim, 0, 42).unwrap();
+        let rotator = Rotator::learned(&sample, dim, 20, 42).unwrap();
+        assert_eq!(rotator.dim(), 100);
+        assert_eq!(rotator.padded_dim(), 128);
+        assert!(rotator.explained_variance().is_none());
+        assert!(quality(&rotator) > quality(&random) + 0.05);
+
+        // still a rotation
+        let mut x = vec![0.0f32; 128];
+        rotator.rotate(&sample[..dim], &mut x);
+        let norm = |v: &[f32]| v.iter().map(|a| a * a).sum::<f32>();
+        assert!((norm(&x) - norm(&sample[..dim])).abs() < 1e-3 * norm(&sample[..dim]));
+
+        let temp_file = NamedTempFile::new().unwrap();
+        rotator.save(temp_file.path()).unwrap();
+        let loaded = Rotator::load_learned(temp_file.path(), dim).unwrap();
+        let mut z = vec![0.0f32; 128];
+        loaded.rotate(&sample[..dim], &mut z);
+        assert_eq!(x, z);
+
+        assert!(Rotator::load_learned(temp_file.path(), 200).is_err());
+        assert!(Rotator::learned(&sample[..dim], dim, 5, 42).is_err());
+    }
 }
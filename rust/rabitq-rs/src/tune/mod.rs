@@ -0,0 +1,177 @@
+//! Pick the smallest `nprobe` (IVF) or `ef` (HNSW, QG) that reaches a target recall.
+//!
+//! Ground truth for a sample of held-out queries is computed exactly against the base
+//! vectors, then the parameter is found by doubling followed by a binary search, assuming
+//! recall grows with it. [`TuneResult::apply`] records the value in the index metadata, where
+//! [`IndexMeta::default_search_params`](crate::IndexMeta::default_search_params) picks it up.
+
+use std::collections::BTreeMap;
+use std::io;
+
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::index::sample;
+
+use crate::element::VectorElement;
+use crate::eval::recall_at_k;
+use crate::groundtruth;
+use crate::index::{self, AnnIndex, DEFAULT_EF, DEFAULT_NPROBE, IndexKind, SearchParams};
+
+/// Settings of a tuning run
+#[derive(Clone, Debug)]
+pub struct TuneConfig {
+    /// Recall@k to reach, in `0..=1`
+    pub target_recall: f64,
+    pub k: usize,
+    /// Held-out queries used, a random subset if more are given
+    pub num_queries: usize,
+    /// Upper bound of `ef` for the graph indexes, `nprobe` is bounded by the cluster count
+    pub max_ef: usize,
+    /// 0 uses all cores
+    pub num_threads: usize,
+    pub seed: u64,
+}
+
+impl Default for TuneConfig {
+    fn default() -> Self {
+        Self {
+            target_recall: 0.95,
+            k: 10,
+            num_queries: 200,
+            max_ef: 4096,
+            num_threads: 0,
+            seed: 42,
+        }
+    }
+}
+
+/// Outcome of [`tune`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct TuneResult {
+    /// `"nprobe"` or `"ef"`
+    pub param: &'static str,
+    pub value: usize,
+    /// Recall@k measured at `value`
+    pub recall: f64,
+    /// False if even the largest value tried misses the target, `value` is then that value
+    pub reached: bool,
+}
+
+impl TuneResult {
+    /// Store `value` as the index's default search parameter, together with the target
+    /// it was tuned for. Takes effect on disk with the next save.
+    pub fn apply(&self, index: &mut dyn AnnIndex, config: &TuneConfig) {
+        let key = if self.param == "nprobe" {
+            DEFAULT_NPROBE
+        } else {
+            DEFAULT_EF
+        };
+        let params = &mut index.meta_mut().params;
+        params.insert(key.to_string(), self.value.to_string());
+        params.insert(
+            "tuned_recall".to_string(),
+            format!("{}", config.target_recall),
+        );
+        params.insert("tuned_k".to_string(), config.k.to_string());
+    }
+}
+
+/// Find the smallest search parameter of `index` reaching `config.target_recall` on
+/// `queries`, with ground truth computed against `base` (the vectors the index was built
+/// from, in the same order).
+pub fn tune<T: VectorElement, Q: VectorElement>(
+    index: &dyn AnnIndex,
+    base: &[T],
+    queries: &[Q],
+    config: &TuneConfig,
+) -> io::Result<TuneResult> {
+    let dim = index.dim();
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidInput, msg);
+    if base.len() != index.len() * dim || !queries.len().is_multiple_of(dim) {
+        return Err(invalid(format!(
+            "expected {} base vectors and queries of dimension {dim}",
+            index.len()
+        )));
+    }
+    if !(0.0..=1.0).contains(&config.target_recall) || config.k == 0 {
+        return Err(invalid(
+            "target recall must be in 0..=1 and k positive".to_string(),
+        ));
+    }
+
+    let nq = queries.len() / dim;
+    let queries: Vec<f32> = if nq > config.num_queries {
+        let mut rng = StdRng::seed_from_u64(config.seed);
+        let mut picked = sample(&mut rng, nq, config.num_queries).into_vec();
+        picked.sort_unstable();
+        picked
+            .into_iter()
+            .flat_map(|i| Q::slice_to_f32(&queries[i * dim..(i + 1) * dim]).into_owned())
+            .collect()
+    } else {
+        Q::slice_to_f32(queries).into_owned()
+    };
+    let gt = index::with_threads(config.num_threads, || {
+        groundtruth::compute(base, &queries, dim, config.k, index.meta().metric)
+    })?;
+
+    let kind = index.kind();
+    let (param, lo, hi) = match kind {
+        IndexKind::Ivf => {
+            let clusters = index
+                .meta()
+                .param::<usize>("clusters")
+                .unwrap_or(index.len());
+            ("nprobe", 1, clusters.max(1))
+        }
+        IndexKind::Hnsw | IndexKind::Qg => ("ef", config.k, config.max_ef.max(config.k)),
+    };
+    let base_params = index.meta().default_search_params();
+    let mut recalls = BTreeMap::new();
+    let mut recall_at = |value: usize| -> f64 {
+        *recalls.entry(value).or_insert_with(|| {
+            let params = match kind {
+                IndexKind::Ivf => SearchParams {
+                    nprobe: value,
+                    ..base_params
+                },
+                IndexKind::Hnsw | IndexKind::Qg => SearchParams {
+                    ef: value,
+                    ..base_params
+                },
+            };
+            let ids = index::search_batch(index, &queries, config.k, &params, config.num_threads);
+            recall_at_k(&ids, &gt.ids, config.k)
+        })
+    };
+
+    // double until the target is reached, then binary search the last interval
+    let mut failed = lo - 1;
+    let mut ok = lo;
+    while recall_at(ok) < config.target_recall {
+        if ok == hi {
+            return Ok(TuneResult {
+                param,
+                value: hi,
+                recall: recall_at(hi),
+                reached: false,
+            });
+        }
+        failed = ok;
+        ok = (ok * 2).min(hi);
+    }
+    while ok - failed > 1 {
+        let mid = failed + (ok - failed) / 2;
+        if recall_at(mid) >= config.target_recall {
+            ok = mid;
+        } else {
+            failed = mid;
+        }
+    }
+    Ok(TuneResult {
+        param,
+        value: ok,
+        recall: recall_at(ok),
+        reached: true,
+    })
+}
@@ -1,9 +1,14 @@
+// the estimator tests below index their buffers in range loops
+#![allow(clippy::needless_range_loop)]
+
 use ndarray::{Array, Array1, Array2};
 use rabitq_rs::estimator::SingleCentroidEstimator;
 use rabitq_rs::quantizer::{MetricType, quantize_split_single};
 use rabitq_rs::rotator::Rotator;
-use rabitq_rs::{BatchBinEstimator, RabitqConfig, SingleEstimator};
+use rabitq_rs::{BatchBinEstimator, DEFAULT_EPSILON0, RabitqConfig, SingleEstimator};
+use rand::SeedableRng;
 use rand::distr::{Distribution, Uniform};
+use rand::rngs::StdRng;
 
 #[test]
 fn test_quantization_and_estimation() {
@@ -49,7 +54,7 @@ fn test_quantization_and_estimation() {
     );
 
     // 4. Query
-    let query = SingleEstimator::new(
+    let mut query = SingleEstimator::new(
         rotated_query.as_slice().unwrap(),
         rotator.padded_dim(),
         EX_BITS,
@@ -244,7 +249,7 @@ fn test_single_centroid_estimator() {
     );
 
     // 5. Query using SingleEstimator for comparison
-    let single_query = SingleEstimator::new(
+    let mut single_query = SingleEstimator::new(
         rotated_query.as_slice().unwrap(),
         rotator.padded_dim(),
         EX_BITS,
@@ -268,3 +273,1634 @@ fn test_single_centroid_estimator() {
         single_full_dist_res.0, single_full_dist_res.1, single_full_dist_res.2
     );
 }
+
+#[test]
+fn test_single_estimator_bounds() {
+    const DIM: usize = 256;
+    const NUM_VECTORS: usize = 200;
+    const EX_BITS: usize = 3;
+
+    let mut rng = rand::rng();
+    let unif = Uniform::new(-1.0f32, 1.0f32).unwrap();
+    let rotator = Rotator::new(DIM, DIM).expect("Failed to create rotator");
+    let centroid: Vec<f32> = (0..DIM).map(|_| unif.sample(&mut rng) * 0.1).collect();
+    let query: Vec<f32> = (0..DIM).map(|_| unif.sample(&mut rng)).collect();
+    let mut rotated_query = vec![0.0f32; DIM];
+    rotator.rotate(&query, &mut rotated_query);
+
+    let g_add: f32 = rotated_query
+        .iter()
+        .zip(&centroid)
+        .map(|(q, c)| (q - c) * (q - c))
+        .sum();
+    let g_err = g_add.sqrt();
+
+    let mut estimator = SingleEstimator::new(
+        &rotated_query,
+        DIM,
+        EX_BITS,
+        &RabitqConfig::new(),
+        0,
+    );
+    assert_eq!(estimator.epsilon0(), DEFAULT_EPSILON0);
+
+    let config = RabitqConfig::new();
+    let mut covered = 0;
+    for _ in 0..NUM_VECTORS {
+        let x: Vec<f32> = (0..DIM).map(|_| unif.sample(&mut rng)).collect();
+        let mut rotated_x = vec![0.0f32; DIM];
+        rotator.rotate(&x, &mut rotated_x);
+        let exact: f32 = rotated_x
+            .iter()
+            .zip(&rotated_query)
+            .map(|(a, b)| (a - b) * (a - b))
+            .sum();
+        let (bin_codes, ex_codes) =
+            quantize_split_single(&rotated_x, &centroid, EX_BITS, MetricType::L2, &config);
+
+        // the default multiplier reproduces the kernel's lower bound
+        let (est, low, _) = estimator.est_dist(&bin_codes, g_add, g_err);
+        let bound = estimator.est_dist_bound(&bin_codes, g_add, g_err);
+        assert_eq!(bound.estimate, est);
+        assert!((bound.lower - low).abs() <= 1e-3 * est.abs().max(1.0));
+        assert!(bound.lower <= bound.estimate && bound.estimate <= bound.upper);
+        if bound.lower <= exact && exact <= bound.upper {
+            covered += 1;
+        }
+
+        let full = estimator.full_dist_bound(&bin_codes, &ex_codes, g_add, g_err);
+        assert!(full.upper - full.lower <= bound.upper - bound.lower);
+    }
+    assert!(covered * 10 >= NUM_VECTORS * 9, "only {covered} bounds hold");
+
+    // the bound shrinks linearly with epsilon0
+    let x: Vec<f32> = (0..DIM).map(|_| unif.sample(&mut rng)).collect();
+    let mut rotated_x = vec![0.0f32; DIM];
+    rotator.rotate(&x, &mut rotated_x);
+    let (bin_codes, _) =
+        quantize_split_single(&rotated_x, &centroid, EX_BITS, MetricType::L2, &config);
+    let wide = estimator.est_dist_bound(&bin_codes, g_add, g_err);
+    estimator.set_epsilon0(DEFAULT_EPSILON0 / 2.0);
+    let narrow = estimator.est_dist_bound(&bin_codes, g_add, g_err);
+    let ratio = (narrow.upper - narrow.lower) / (wide.upper - wide.lower);
+    assert!((ratio - 0.5).abs() < 1e-3);
+    estimator.set_epsilon0(0.0);
+    let point = estimator.est_dist_bound(&bin_codes, g_add, g_err);
+    assert_eq!(point.lower, point.upper);
+    assert!(!point.certainly_less(&point));
+}
+
+#[test]
+fn test_estimators_are_send_sync_and_clone() {
+    fn assert_send_sync<T: Send + Sync + Clone>() {}
+    assert_send_sync::<SingleEstimator>();
+    assert_send_sync::<SingleCentroidEstimator>();
+    assert_send_sync::<BatchBinEstimator>();
+    assert_send_sync::<rabitq_rs::SplitBatchEstimator>();
+
+    const DIM: usize = 128;
+    const EX_BITS: usize = 2;
+    let mut rng = rand::rng();
+    let unif = Uniform::new(-1.0f32, 1.0f32).unwrap();
+    let centroid = vec![0.0f32; DIM];
+    let config = RabitqConfig::default();
+    let codes: Vec<(Vec<u8>, Vec<u8>)> = (0..64)
+        .map(|_| {
+            let x: Vec<f32> = (0..DIM).map(|_| unif.sample(&mut rng)).collect();
+            quantize_split_single(&x, &centroid, EX_BITS, MetricType::L2, &config)
+        })
+        .collect();
+
+    // the estimator keeps its own copy of the query, the caller's buffer may go away
+    let estimator = {
+        let query: Vec<f32> = (0..DIM).map(|_| unif.sample(&mut rng)).collect();
+        let mut estimator = SingleEstimator::new(&query, DIM, EX_BITS, &config, 0);
+        estimator.set_g_add(1.0, 0.0);
+        estimator
+    };
+    let expected: Vec<f32> = codes
+        .iter()
+        .map(|(bin, ex)| estimator.full_dist(bin, ex, 1.0, 1.0).0)
+        .collect();
+
+    let copy = estimator.clone();
+    drop(estimator);
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for ((bin, ex), want) in codes.iter().zip(&expected) {
+                    assert_eq!(copy.full_dist(bin, ex, 1.0, 1.0).0, *want);
+                }
+            });
+        }
+    });
+}
+
+/// `n` vectors of `dim` uniform values, plus the exact L2 top-`k` of the first `nq` rows
+/// against all of them.
+#[test]
+fn test_query_precision() {
+    use rabitq_rs::{IndexKind, QueryPrecision, SearchParams};
+
+    const DIM: usize = 256;
+    const NUM_VECTORS: usize = 100;
+    const EX_BITS: usize = 3;
+
+    let mut rng = rand::rng();
+    let unif = Uniform::new(-1.0f32, 1.0f32).unwrap();
+    let rotator = Rotator::new(DIM, DIM).expect("Failed to create rotator");
+    let centroid = vec![0.0f32; DIM];
+    let query: Vec<f32> = (0..DIM).map(|_| unif.sample(&mut rng)).collect();
+    let mut rotated_query = vec![0.0f32; DIM];
+    rotator.rotate(&query, &mut rotated_query);
+    let g_add: f32 = rotated_query.iter().map(|q| q * q).sum();
+
+    let config = RabitqConfig::new();
+    let estimator = |precision| {
+        SingleEstimator::with_precision(&rotated_query, DIM, EX_BITS, &config, 0, precision)
+    };
+    let exact = estimator(QueryPrecision::Exact);
+    let coarse = estimator(QueryPrecision::Bits(2));
+    let fine = estimator(QueryPrecision::Bits(8));
+    assert!(exact.query_bin().is_empty());
+    assert_eq!(fine.query_bin().len(), DIM * 8 / 64);
+
+    let (mut coarse_err, mut fine_err) = (0.0f32, 0.0f32);
+    for _ in 0..NUM_VECTORS {
+        let x: Vec<f32> = (0..DIM).map(|_| unif.sample(&mut rng)).collect();
+        let mut rotated_x = vec![0.0f32; DIM];
+        rotator.rotate(&x, &mut rotated_x);
+        let (bin_codes, ex_codes) =
+            quantize_split_single(&rotated_x, &centroid, EX_BITS, MetricType::L2, &config);
+
+        // without query quantization the 1-bit inner product is the one of full_dist
+        let (_, _, ip) = exact.est_dist(&bin_codes, g_add, 0.0);
+        let (_, _, full_ip) = exact.full_dist(&bin_codes, &ex_codes, g_add, 0.0);
+        assert!((ip - full_ip).abs() <= 1e-4 * full_ip.abs().max(1.0));
+        coarse_err += (coarse.est_dist(&bin_codes, g_add, 0.0).2 - ip).abs();
+        fine_err += (fine.est_dist(&bin_codes, g_add, 0.0).2 - ip).abs();
+    }
+    assert!(
+        fine_err < coarse_err,
+        "8-bit error {fine_err}, 2-bit {coarse_err}"
+    );
+
+    assert_eq!("exact".parse(), Ok(QueryPrecision::Exact));
+    assert_eq!("6".parse(), Ok(QueryPrecision::Bits(6)));
+    assert!("0".parse::<QueryPrecision>().is_err());
+    let params = SearchParams {
+        query_precision: Some(QueryPrecision::Bits(4)),
+        ..SearchParams::default()
+    };
+    assert!(params.validate(IndexKind::Hnsw).is_ok());
+    assert!(params.validate(IndexKind::Ivf).is_err());
+}
+
+fn index_test_data(n: usize, nq: usize, dim: usize, k: usize) -> (Vec<f32>, Vec<Vec<u32>>) {
+    // seeded by the shape, so a failing recall check reproduces
+    let mut rng = StdRng::seed_from_u64((n * dim) as u64);
+    let uniform = Uniform::new(-1.0f32, 1.0).unwrap();
+    let data: Vec<f32> = (0..n * dim).map(|_| uniform.sample(&mut rng)).collect();
+    let gt = data
+        .chunks_exact(dim)
+        .take(nq)
+        .map(|q| {
+            let mut dists: Vec<(f32, u32)> = data
+                .chunks_exact(dim)
+                .enumerate()
+                .map(|(i, x)| {
+                    (
+                        q.iter().zip(x).map(|(a, b)| (a - b) * (a - b)).sum(),
+                        i as u32,
+                    )
+                })
+                .collect();
+            dists.sort_by(|a, b| a.0.total_cmp(&b.0));
+            dists[..k].iter().map(|&(_, id)| id).collect()
+        })
+        .collect();
+    (data, gt)
+}
+
+fn recall(found: &[u32], gt: &[Vec<u32>], k: usize) -> f64 {
+    let hits: usize = found
+        .chunks_exact(k)
+        .zip(gt)
+        .map(|(f, g)| f.iter().filter(|id| g.contains(id)).count())
+        .sum();
+    hits as f64 / (gt.len() * k) as f64
+}
+
+#[test]
+fn test_indexes_build_save_load_search() {
+    use rabitq_rs::index::{self, Hnsw, HnswParams, Ivf, IvfParams, Qg, QgParams};
+    use rabitq_rs::{AnnIndex, IndexKind, QueryPrecision, SearchParams};
+
+    let (n, nq, dim, k) = (2000, 50, 64, 10);
+    let (data, gt) = index_test_data(n, nq, dim, k);
+    let queries = &data[..nq * dim];
+    let dir = tempfile::tempdir().unwrap();
+    let params = SearchParams {
+        nprobe: 16,
+        ef: 100,
+        use_hacc: true,
+        ..SearchParams::default()
+    };
+
+    let ivf = IvfParams {
+        num_clusters: 16,
+        ..IvfParams::default()
+    };
+    let hnsw = HnswParams {
+        num_threads: 2,
+        ..HnswParams::default()
+    };
+    let qg = QgParams {
+        num_threads: 2,
+        ef_build: 100,
+        ..QgParams::default()
+    };
+    let built: Vec<Box<dyn AnnIndex>> = vec![
+        Box::new(Ivf::build(&data, dim, &ivf).unwrap()),
+        Box::new(Hnsw::build(&data, dim, &hnsw).unwrap()),
+        Box::new(Qg::build(&data, dim, &qg).unwrap()),
+    ];
+
+    for index in built {
+        assert_eq!(index.len(), n);
+        assert_eq!(index.dim(), dim);
+        let found = index::search_batch(index.as_ref(), queries, k, &params, 2);
+        let r = recall(&found, &gt, k);
+        assert!(r > 0.8, "{} recall {r}", index.kind());
+
+        let path = dir.path().join(format!("{}.index", index.kind()));
+        index.save(&path).unwrap();
+        let loaded = index::open(&path).unwrap();
+        assert_eq!(loaded.meta(), index.meta());
+        let reloaded = index::search_batch(loaded.as_ref(), queries, k, &params, 2);
+        let r2 = recall(&reloaded, &gt, k);
+        assert!(
+            (r - r2).abs() < 0.02,
+            "{} recall {r} before and {r2} after reload",
+            index.kind()
+        );
+
+        for precision in [QueryPrecision::Exact, QueryPrecision::Bits(16)] {
+            let params = SearchParams {
+                query_precision: Some(precision),
+                ..params
+            };
+            params.validate(index.kind()).unwrap();
+            let found = index::search_batch(index.as_ref(), queries, k, &params, 2);
+            let r = recall(&found, &gt, k);
+            assert!(r > 0.8, "{} recall {r} at {precision}", index.kind());
+        }
+
+        let header = index::read_header(&path, index.kind()).unwrap();
+        let header_num = header.iter().find(|(name, _)| *name == "num").unwrap().1;
+        assert_eq!(header_num as usize, n);
+    }
+
+    // HNSW files do not record the metric, the metadata file does
+    let path = dir.path().join("hnsw.index");
+    std::fs::remove_file(rabitq_rs::IndexMeta::path_for(&path)).unwrap();
+    assert!(index::open(&path).is_err());
+    let raw = index::open_as(&path, IndexKind::Hnsw, MetricType::L2).unwrap();
+    assert_eq!(raw.len(), n);
+}
+
+#[test]
+fn test_index_parameter_errors() {
+    use rabitq_rs::index::{Ivf, IvfParams, Qg, QgParams};
+
+    let data = vec![0.5f32; 100 * 64];
+    let bad_bits = IvfParams {
+        num_clusters: 4,
+        total_bits: 12,
+        ..IvfParams::default()
+    };
+    assert!(Ivf::build(&data, 64, &bad_bits).is_err());
+    let too_many_clusters = IvfParams {
+        num_clusters: 101,
+        ..IvfParams::default()
+    };
+    assert!(Ivf::build(&data, 64, &too_many_clusters).is_err());
+    let bad_degree = QgParams {
+        degree: 48,
+        ..QgParams::default()
+    };
+    assert!(Qg::build(&data, 64, &bad_degree).is_err());
+    assert!(Ivf::build(&data[..100], 64, &IvfParams::default()).is_err());
+    // the rotator needs at least 64 dimensions
+    assert!(Ivf::build(&data, 32, &IvfParams::default()).is_err());
+}
+
+#[test]
+fn test_inner_product_graphs() {
+    use rabitq_rs::eval::recall_at_k;
+    use rabitq_rs::groundtruth;
+    use rabitq_rs::index::{self, Hnsw, HnswParams, Qg, QgParams};
+    use rabitq_rs::{AnnIndex, SearchParams};
+
+    let (n, nq, dim, k) = (2000, 50, 64, 10);
+    let (data, _) = index_test_data(n, nq, dim, k);
+    let queries = &data[..nq * dim];
+    let gt = groundtruth::compute(&data, queries, dim, k, MetricType::IP).unwrap();
+    let params = SearchParams {
+        ef: 200,
+        ..SearchParams::default()
+    };
+    let dir = tempfile::tempdir().unwrap();
+
+    let hnsw = HnswParams {
+        metric: MetricType::IP,
+        num_threads: 2,
+        ..HnswParams::default()
+    };
+    let qg = QgParams {
+        metric: MetricType::IP,
+        num_threads: 2,
+        ef_build: 100,
+        ..QgParams::default()
+    };
+    let built: Vec<Box<dyn AnnIndex>> = vec![
+        Box::new(Hnsw::build(&data, dim, &hnsw).unwrap()),
+        Box::new(Qg::build(&data, dim, &qg).unwrap()),
+    ];
+    for index in built {
+        assert_eq!(index.meta().metric, MetricType::IP);
+        let found = index::search_batch(index.as_ref(), queries, k, &params, 2);
+        let r = recall_at_k(&found, &gt.ids, k);
+        assert!(r > 0.8, "{} inner product recall {r}", index.kind());
+    }
+
+    // the metric is in the QG file itself, a missing metadata file does not lose it
+    let path = dir.path().join("ip.qg");
+    let index = Qg::build(&data, dim, &qg).unwrap();
+    index.save(&path).unwrap();
+    std::fs::remove_file(dir.path().join("ip.qg.meta")).unwrap();
+    let reopened = index::open_as(&path, rabitq_rs::IndexKind::Qg, MetricType::L2).unwrap();
+    assert_eq!(reopened.meta().metric, MetricType::IP);
+    let found = index::search_batch(reopened.as_ref(), queries, k, &params, 2);
+    assert!(recall_at_k(&found, &gt.ids, k) > 0.8);
+}
+
+#[test]
+fn test_qg_legacy_file_layout() {
+    use rabitq_rs::index::{self, Qg, QgParams};
+    use rabitq_rs::{AnnIndex, IndexKind, SearchParams};
+
+    let (n, nq, dim, k) = (1000, 20, 64, 10);
+    let (data, _) = index_test_data(n, nq, dim, k);
+    let queries = &data[..nq * dim];
+    let params = QgParams {
+        num_threads: 2,
+        ef_build: 100,
+        ..QgParams::default()
+    };
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("new.qg");
+    let qg = Qg::build(&data, dim, &params).unwrap();
+    qg.save(&path).unwrap();
+
+    // the layout before the magic, version and metric: num, degree_bound, dim, padded_dim,
+    // entry_point, rotator_type, then the rows and the rotator
+    let bytes = std::fs::read(&path).unwrap();
+    assert_eq!(&bytes[..8], b"RABITQG\0");
+    let header = 8 * 4 + 4 + 1;
+    let mut legacy = bytes[12..12 + header].to_vec();
+    legacy.extend_from_slice(&bytes[12 + header + 1..]);
+    let legacy_path = dir.path().join("legacy.qg");
+    std::fs::write(&legacy_path, legacy).unwrap();
+
+    let fields = index::read_header(&legacy_path, IndexKind::Qg).unwrap();
+    let field = |name: &str| fields.iter().find(|(f, _)| *f == name).unwrap().1;
+    assert_eq!(field("version"), 0);
+    assert_eq!(field("num") as usize, n);
+    assert_eq!(field("dim") as usize, dim);
+    assert_eq!(field("metric"), 0);
+
+    let loaded = Qg::load(&legacy_path).unwrap();
+    assert_eq!(loaded.len(), n);
+    let search = SearchParams {
+        ef: 100,
+        ..SearchParams::default()
+    };
+    assert_eq!(
+        index::search_batch(&loaded, queries, k, &search, 2),
+        index::search_batch(&qg, queries, k, &search, 2)
+    );
+}
+
+#[test]
+fn test_hnsw_legacy_file_layout() {
+    use rabitq_rs::index::{self, Hnsw, HnswParams};
+    use rabitq_rs::{AnnIndex, IndexKind, QueryPrecision, SearchParams};
+
+    let (n, nq, dim, k) = (1000, 20, 64, 10);
+    let (data, _) = index_test_data(n, nq, dim, k);
+    let queries = &data[..nq * dim];
+    let params = HnswParams {
+        num_threads: 2,
+        ..HnswParams::default()
+    };
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("new.hnsw");
+    let hnsw = Hnsw::build(&data, dim, &params).unwrap();
+    hnsw.save(&path).unwrap();
+
+    // the layout before the magic and version: max_elements first, and no rotator type
+    // before the FhtKacRotator flips of padded_dim / 2 bytes at the end
+    let bytes = std::fs::read(&path).unwrap();
+    assert_eq!(&bytes[..8], b"RABITQH\0");
+    let type_pos = bytes.len() - hnsw.padded_dim() / 2 - 1;
+    let mut legacy = bytes[12..type_pos].to_vec();
+    legacy.extend_from_slice(&bytes[type_pos + 1..]);
+    let legacy_path = dir.path().join("legacy.hnsw");
+    std::fs::write(&legacy_path, legacy).unwrap();
+
+    let fields = index::read_header(&legacy_path, IndexKind::Hnsw).unwrap();
+    let field = |name: &str| fields.iter().find(|(f, _)| *f == name).unwrap().1;
+    assert_eq!(field("version"), 0);
+    assert_eq!(field("num") as usize, n);
+    assert_eq!(field("dim") as usize, dim);
+
+    let loaded = Hnsw::load_with_metric(&legacy_path, MetricType::L2).unwrap();
+    assert_eq!(loaded.len(), n);
+    // exact queries, quantized ones are randomized
+    let search = SearchParams {
+        ef: 100,
+        query_precision: Some(QueryPrecision::Exact),
+        ..SearchParams::default()
+    };
+    assert_eq!(
+        index::search_batch(&loaded, queries, k, &search, 2),
+        index::search_batch(&hnsw, queries, k, &search, 2)
+    );
+}
+
+#[test]
+fn test_eval_sweep() {
+    use rabitq_rs::eval::{self, EvalConfig};
+    use rabitq_rs::index::{Ivf, IvfParams};
+    use rabitq_rs::io::Matrix;
+
+    let (n, nq, dim, k) = (2000, 50, 64, 10);
+    let (data, gt) = index_test_data(n, nq, dim, k);
+    let queries = Matrix::new(data[..nq * dim].to_vec(), nq, dim);
+    let gt = Matrix::new(gt.concat(), nq, k);
+    let params = IvfParams {
+        num_clusters: 16,
+        ..IvfParams::default()
+    };
+    let ivf = Ivf::build(&data, dim, &params).unwrap();
+
+    let config = EvalConfig {
+        k,
+        num_threads: 2,
+        rounds: 2,
+        values: vec![16, 1, 64, 4],
+        stop_recall: None,
+        ..EvalConfig::default()
+    };
+    let report = eval::evaluate(&ivf, &queries, &gt, &config).unwrap();
+    assert_eq!(report.param, "nprobe");
+    assert_eq!(report.num_threads, 2);
+    // the sweep is sorted, nprobe 64 is clamped to the 16 clusters and merged with 16
+    let values: Vec<usize> = report.points.iter().map(|p| p.value).collect();
+    assert_eq!(values, [1, 4, 16]);
+    let first = &report.points[0];
+    let last = report.points.last().unwrap();
+    assert!(last.recall > first.recall);
+    assert!(last.recall > 0.9, "recall {}", last.recall);
+    for p in &report.points {
+        assert!(p.qps > 0.0);
+        assert!(p.p50_us <= p.p90_us && p.p90_us <= p.p99_us && p.p99_us <= p.max_us);
+    }
+
+    // the default sweep stops once recall saturates
+    let auto = eval::evaluate(&ivf, &queries, &gt, &EvalConfig { k, ..EvalConfig::default() });
+    assert!(auto.unwrap().points.len() <= 4);
+
+    let dir = tempfile::tempdir().unwrap();
+    let csv = dir.path().join("report.csv");
+    report.save(&csv).unwrap();
+    assert_eq!(std::fs::read_to_string(&csv).unwrap().lines().count(), 4);
+    let json = dir.path().join("report.json");
+    report.save(&json).unwrap();
+    assert!(std::fs::read_to_string(&json).unwrap().starts_with('{'));
+}
+
+#[test]
+fn test_tune_stores_default() {
+    use rabitq_rs::eval::recall_at_k;
+    use rabitq_rs::groundtruth;
+    use rabitq_rs::index::{self, Ivf, IvfParams};
+    use rabitq_rs::AnnIndex;
+    use rabitq_rs::tune::{self, TuneConfig};
+
+    let (n, dim, k) = (2000, 64, 10);
+    let (data, _) = index_test_data(n, 0, dim, k);
+    // tuned on the first 300 queries, checked on the other 200
+    let (all_queries, _) = index_test_data(500, 0, dim, k);
+    let (queries, held_out) = all_queries.split_at(300 * dim);
+    let params = IvfParams {
+        num_clusters: 32,
+        ..IvfParams::default()
+    };
+    let mut ivf = Ivf::build(&data, dim, &params).unwrap();
+    let config = TuneConfig {
+        target_recall: 0.9,
+        num_queries: 100,
+        ..TuneConfig::default()
+    };
+    let result = tune::tune(&ivf, &data, queries, &config).unwrap();
+    assert_eq!(result.param, "nprobe");
+    assert!(result.reached);
+    assert!(result.recall >= 0.9);
+    assert!(result.value > 1 && result.value <= 32);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tuned.index");
+    result.apply(&mut ivf, &config);
+    ivf.save(&path).unwrap();
+    let loaded = index::open(&path).unwrap();
+    let defaults = loaded.meta().default_search_params();
+    assert_eq!(defaults.nprobe, result.value);
+    let gt = groundtruth::compute(&data, held_out, dim, k, MetricType::L2).unwrap();
+    let found = index::search_batch(loaded.as_ref(), held_out, k, &defaults, 2);
+    let held_out_recall = recall_at_k(&found, &gt.ids, k);
+    assert!(held_out_recall >= 0.9, "held-out recall {held_out_recall}");
+
+    // an unreachable target reports the largest value
+    let config = TuneConfig {
+        target_recall: 1.0,
+        num_queries: 100,
+        ..TuneConfig::default()
+    };
+    let best = tune::tune(&ivf, &data, queries, &config).unwrap();
+    assert!(!best.reached);
+    assert_eq!(best.value, 32);
+    assert!(best.recall < 1.0);
+}
+
+#[test]
+fn test_index_stats() {
+    use rabitq_rs::AnnIndex;
+    use rabitq_rs::index::{Hnsw, HnswParams, Ivf, IvfParams, Qg, QgParams};
+
+    let (n, dim) = (1000, 64);
+    let (data, _) = index_test_data(n, 0, dim, 10);
+
+    let ivf_params = IvfParams {
+        num_clusters: 8,
+        ..IvfParams::default()
+    };
+    let stats = Ivf::build(&data, dim, &ivf_params).unwrap().stats();
+    let clusters = stats.clusters.as_ref().unwrap();
+    assert_eq!(clusters.sizes.len(), 8);
+    assert_eq!(clusters.sizes.iter().sum::<usize>(), n);
+    assert!(clusters.imbalance >= 1.0);
+    assert!(stats.graph.is_none());
+    let ids = stats.memory.iter().find(|(name, _)| *name == "ids").unwrap();
+    assert_eq!(ids.1, n * 4);
+
+    let hnsw = Hnsw::build(&data, dim, &HnswParams::default()).unwrap();
+    let stats = hnsw.stats();
+    let graph = stats.graph.as_ref().unwrap();
+    assert_eq!(graph.levels[0].count, n);
+    assert!(graph.levels[0].max <= 2 * HnswParams::default().m);
+    assert!(graph.levels.windows(2).all(|w| w[0].count >= w[1].count));
+    assert!((graph.entry_point as usize) < n);
+    assert!(stats.total_bytes() > 0);
+
+    let qg = Qg::build(&data, dim, &QgParams::default()).unwrap();
+    let graph = qg.stats().graph.unwrap();
+    assert_eq!(graph.levels.len(), 1);
+    assert!(graph.levels[0].max <= 32 && graph.levels[0].min > 0);
+}
+
+#[test]
+fn test_hnsw_quantized_build() {
+    use rabitq_rs::eval::recall_at_k;
+    use rabitq_rs::groundtruth;
+    use rabitq_rs::index::{self, Hnsw, HnswParams, QuantizedBuildParams};
+    use rabitq_rs::io::{Matrix, VectorFile, write_vecs};
+    use rabitq_rs::{AnnIndex, SearchParams};
+
+    let (n, nq, dim, k) = (2000, 50, 64, 10);
+    let (data, _) = index_test_data(n, nq, dim, k);
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("base.fvecs");
+    write_vecs(&path, &Matrix::new(data.clone(), n, dim)).unwrap();
+    let file = VectorFile::<f32>::open(&path).unwrap();
+    let search = SearchParams {
+        ef: 100,
+        ..SearchParams::default()
+    };
+
+    let cases = [
+        (MetricType::L2, 0.0),
+        (MetricType::L2, 0.5),
+        (MetricType::IP, 0.0),
+    ];
+    for (metric, exact_sample) in cases {
+        let params = HnswParams {
+            metric,
+            num_threads: 2,
+            ..HnswParams::default()
+        };
+        let build = QuantizedBuildParams {
+            chunk_rows: 300,
+            exact_sample,
+            train_rows: 500,
+        };
+        let hnsw = Hnsw::build_quantized(&file, &params, &build).unwrap();
+        assert_eq!(hnsw.len(), n);
+        let queries = &data[..nq * dim];
+        let gt = groundtruth::compute(&data, queries, dim, k, metric).unwrap();
+        let found = index::search_batch(&hnsw, queries, k, &search, 2);
+        let r = recall_at_k(&found, &gt.ids, k);
+        assert!(r > 0.8, "{metric:?} with exact_sample {exact_sample} recall {r}");
+    }
+
+    let params = HnswParams::default();
+    let bad = QuantizedBuildParams {
+        chunk_rows: 0,
+        ..QuantizedBuildParams::default()
+    };
+    assert!(Hnsw::build_quantized(&file, &params, &bad).is_err());
+}
+
+#[test]
+fn test_graph_insertion() {
+    use rabitq_rs::index::{self, Hnsw, HnswParams, Qg, QgParams};
+    use rabitq_rs::{AnnIndex, SearchParams};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let (n, nq, dim, k) = (2000, 50, 64, 10);
+    let (data, gt) = index_test_data(n, nq, dim, k);
+    let queries = &data[..nq * dim];
+    let built = n - 400;
+    let (base, rest) = data.split_at(built * dim);
+    let params = SearchParams {
+        ef: 200,
+        ..SearchParams::default()
+    };
+    let dir = tempfile::tempdir().unwrap();
+
+    // inserters and a searcher share the graph until every vector is in
+    fn insert_while_searching(
+        index: &dyn AnnIndex,
+        queries: &[f32],
+        k: usize,
+        params: &SearchParams,
+        inserters: Vec<Box<dyn FnOnce() + Send + '_>>,
+    ) {
+        let done = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            let handles: Vec<_> = inserters.into_iter().map(|f| s.spawn(f)).collect();
+            s.spawn(|| {
+                while !done.load(Ordering::Relaxed) {
+                    for q in queries.chunks_exact(index.dim()) {
+                        assert_eq!(index.search(q, k, params).len(), k);
+                    }
+                }
+            });
+            for handle in handles {
+                handle.join().unwrap();
+            }
+            done.store(true, Ordering::Relaxed);
+        });
+    }
+
+    let mut hnsw = Hnsw::build(
+        base,
+        dim,
+        &HnswParams {
+            num_threads: 2,
+            ..HnswParams::default()
+        },
+    )
+    .unwrap();
+    assert!(hnsw.insert(&rest[..dim], built as u32).is_err());
+    hnsw.reserve(n - built).unwrap();
+    assert_eq!(hnsw.capacity(), n);
+    assert!(hnsw.insert(&rest[..dim], 0).is_err());
+    assert!(hnsw.insert(&rest[..dim - 1], built as u32).is_err());
+    let inserters: Vec<Box<dyn FnOnce() + Send + '_>> = (0..2)
+        .map(|t| {
+            let hnsw = &hnsw;
+            Box::new(move || {
+                for i in (t..n - built).step_by(2) {
+                    let label = (built + i) as u32;
+                    hnsw.insert(&rest[i * dim..(i + 1) * dim], label).unwrap();
+                }
+            }) as Box<dyn FnOnce() + Send + '_>
+        })
+        .collect();
+    insert_while_searching(&hnsw, queries, k, &params, inserters);
+    assert_eq!(hnsw.len(), n);
+    let found = index::search_batch(&hnsw, queries, k, &params, 2);
+    let r = recall(&found, &gt, k);
+    assert!(r > 0.8, "HNSW recall after insertion {r}");
+
+    let path = dir.path().join("inserted.hnsw");
+    hnsw.save(&path).unwrap();
+    let reopened = Hnsw::load(&path).unwrap();
+    assert_eq!(reopened.len(), n);
+    assert_eq!(reopened.meta().num, n);
+    let found = index::search_batch(&reopened, queries, k, &params, 2);
+    assert!((recall(&found, &gt, k) - r).abs() < 0.02);
+
+    let qg_params = QgParams {
+        num_threads: 2,
+        ef_build: 100,
+        ..QgParams::default()
+    };
+    let mut qg = Qg::build(base, dim, &qg_params).unwrap();
+    assert!(qg.insert(&rest[..dim]).is_err());
+    qg.reserve(n - built).unwrap();
+    let inserter: Box<dyn FnOnce() + Send + '_> = Box::new(|| {
+        for (i, vector) in rest.chunks_exact(dim).enumerate() {
+            assert_eq!(qg.insert(vector).unwrap() as usize, built + i);
+        }
+    });
+    insert_while_searching(&qg, queries, k, &params, vec![inserter]);
+    assert_eq!(qg.len(), n);
+    let found = index::search_batch(&qg, queries, k, &params, 2);
+    let r = recall(&found, &gt, k);
+    assert!(r > 0.8, "QG recall after insertion {r}");
+    assert!(qg.stats().graph.unwrap().levels[0].min > 0);
+
+    let path = dir.path().join("inserted.qg");
+    qg.save(&path).unwrap();
+    let reopened = Qg::load(&path).unwrap();
+    assert_eq!(reopened.len(), n);
+    assert_eq!(reopened.capacity(), n);
+    let found = index::search_batch(&reopened, queries, k, &params, 2);
+    assert!((recall(&found, &gt, k) - r).abs() < 0.02);
+}
+
+#[test]
+fn test_sharded_index() {
+    use rabitq_rs::index::{
+        self, HnswParams, IvfParams, Routing, ShardParams, ShardedIndex, ShardedParams,
+    };
+    use rabitq_rs::io::{Matrix, VectorFile, write_vecs};
+    use rabitq_rs::{AnnIndex, IndexKind, SearchParams};
+
+    let (n, nq, dim, k) = (2000, 50, 64, 10);
+    let (data, gt) = index_test_data(n, nq, dim, k);
+    let queries = &data[..nq * dim];
+    let params = SearchParams {
+        nprobe: 8,
+        ef: 100,
+        ..SearchParams::default()
+    };
+    let dir = tempfile::tempdir().unwrap();
+
+    let ivf = ShardedParams {
+        num_shards: 4,
+        routing: Routing::Hash,
+        shard: ShardParams::Ivf(IvfParams {
+            num_clusters: 16,
+            ..IvfParams::default()
+        }),
+        seed: 42,
+    };
+    let sharded = ShardedIndex::build(&data, dim, &ivf).unwrap();
+    assert_eq!(sharded.kind(), IndexKind::Ivf);
+    assert_eq!(sharded.len(), n);
+    assert!((0..4).all(|i| (400..600).contains(&sharded.shard(i).len())));
+    let found = index::search_batch(&sharded, queries, k, &params, 2);
+    let r = recall(&found, &gt, k);
+    assert!(r > 0.8, "sharded IVF recall {r}");
+    let (ids, distances) = sharded.search_scored(&queries[..dim], k, &params);
+    assert_eq!(ids[0], 0);
+    assert!(distances.windows(2).all(|w| w[0] <= w[1]));
+    assert!(sharded.insert(&queries[..dim], n as u32).is_err());
+    let stats = sharded.stats();
+    assert_eq!(stats.clusters.unwrap().sizes.len(), 64);
+
+    // exact re-ranking from the raw vectors
+    let raw_path = dir.path().join("base.fvecs");
+    write_vecs(&raw_path, &Matrix::new(data.clone(), n, dim)).unwrap();
+    let raw = VectorFile::<f32>::open(&raw_path).unwrap();
+    let mut reranked = Vec::new();
+    for query in queries.chunks_exact(dim) {
+        let (ids, distances) = sharded
+            .search_reranked(query, k, &params, &raw, 4 * k)
+            .unwrap();
+        let x = &data[ids[1] as usize * dim..][..dim];
+        let exact: f32 = query.iter().zip(x).map(|(a, b)| (a - b) * (a - b)).sum();
+        assert!((distances[1] - exact).abs() <= 1e-3 * exact);
+        reranked.extend(ids);
+    }
+    assert!(recall(&reranked, &gt, k) >= r);
+
+    let path = dir.path().join("sharded.ivf");
+    sharded.save(&path).unwrap();
+    let reopened = index::open(&path).unwrap();
+    assert_eq!(reopened.len(), n);
+    let reloaded = index::search_batch(reopened.as_ref(), queries, k, &params, 2);
+    assert_eq!(reloaded, found);
+
+    // centroid routing over graphs, the last vectors inserted afterwards
+    let built = n - 200;
+    let hnsw = ShardedParams {
+        num_shards: 3,
+        routing: Routing::Centroid,
+        shard: ShardParams::Hnsw(HnswParams {
+            num_threads: 2,
+            ..HnswParams::default()
+        }),
+        seed: 7,
+    };
+    let mut sharded = ShardedIndex::build(&data[..built * dim], dim, &hnsw).unwrap();
+    assert_eq!(sharded.num_shards(), 3);
+    sharded.reserve(n - built).unwrap();
+    for (i, vector) in data[built * dim..].chunks_exact(dim).enumerate() {
+        sharded.insert(vector, (built + i) as u32).unwrap();
+    }
+    assert_eq!(sharded.len(), n);
+    let found = index::search_batch(&sharded, queries, k, &params, 2);
+    let r = recall(&found, &gt, k);
+    assert!(r > 0.8, "sharded HNSW recall {r}");
+
+    let path = dir.path().join("sharded.hnsw");
+    sharded.save(&path).unwrap();
+    let reopened = ShardedIndex::load(&path).unwrap();
+    assert_eq!(reopened.len(), n);
+    assert_eq!(reopened.meta().num, n);
+    let found = index::search_batch(&reopened, queries, k, &params, 2);
+    assert!((recall(&found, &gt, k) - r).abs() < 0.02);
+
+    // under IP, rows and insertions go to the centroid of largest inner product, which
+    // does not change with the norm of the vector
+    let ip = ShardedParams {
+        shard: ShardParams::Hnsw(HnswParams {
+            metric: MetricType::IP,
+            num_threads: 2,
+            ..HnswParams::default()
+        }),
+        ..hnsw
+    };
+    let sharded = ShardedIndex::build(&data, dim, &ip).unwrap();
+    let mut routed = vec![0; 3];
+    for (id, vector) in data.chunks_exact(dim).enumerate() {
+        let s = sharded.route(id as u32, vector);
+        let scaled: Vec<f32> = vector.iter().map(|x| x * 1e-3).collect();
+        assert_eq!(sharded.route(id as u32, &scaled), s);
+        routed[s] += 1;
+    }
+    let sizes: Vec<usize> = (0..3).map(|i| sharded.shard(i).len()).collect();
+    assert_eq!(routed, sizes);
+
+    let too_many = ShardedParams {
+        num_shards: n + 1,
+        ..ivf
+    };
+    assert!(ShardedIndex::build(&data, dim, &too_many).is_err());
+}
+
+#[test]
+fn test_multi_vector_maxsim() {
+    use rabitq_rs::SearchParams;
+    use rabitq_rs::index::{MultiVectorIndex, MultiVectorParams};
+
+    let (num_docs, nq, m, dim, k) = (300, 20, 8, 64, 5);
+    let mut rng = StdRng::seed_from_u64(7);
+    let uniform = Uniform::new(-1.0f32, 1.0).unwrap();
+    let unit = |rng: &mut StdRng| {
+        let v: Vec<f32> = (0..dim).map(|_| uniform.sample(rng)).collect();
+        let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+        v.into_iter().map(|x| x / norm).collect::<Vec<f32>>()
+    };
+    // lengths cross the 32-token FastScan batches
+    let doc_lens: Vec<usize> = (0..num_docs).map(|i| 1 + (i * 7) % 70).collect();
+    let tokens: Vec<f32> = (0..doc_lens.iter().sum::<usize>() * dim)
+        .step_by(dim)
+        .flat_map(|_| unit(&mut rng))
+        .collect();
+    let mut doc_offsets = vec![0];
+    for len in &doc_lens {
+        doc_offsets.push(doc_offsets.last().unwrap() + len);
+    }
+    let doc = |d: usize| &tokens[doc_offsets[d] * dim..doc_offsets[d + 1] * dim];
+    let maxsim = |query: &[f32], d: usize| -> f32 {
+        query
+            .chunks_exact(dim)
+            .map(|q| {
+                doc(d)
+                    .chunks_exact(dim)
+                    .map(|t| q.iter().zip(t).map(|(a, b)| a * b).sum::<f32>())
+                    .fold(f32::MIN, f32::max)
+            })
+            .sum()
+    };
+
+    // queries are noisy tokens of one document
+    let queries: Vec<Vec<f32>> = (0..nq)
+        .map(|i| {
+            let d = (i * 13) % num_docs;
+            (0..m)
+                .flat_map(|j| {
+                    let t = &doc(d).chunks_exact(dim).collect::<Vec<_>>()[j % doc_lens[d]];
+                    let noise = unit(&mut rng);
+                    t.iter()
+                        .zip(noise)
+                        .map(|(a, b)| a + 0.5 * b)
+                        .collect::<Vec<f32>>()
+                })
+                .collect()
+        })
+        .collect();
+
+    let index =
+        MultiVectorIndex::build(&tokens, dim, &doc_lens, &MultiVectorParams::default()).unwrap();
+    assert_eq!(index.num_docs(), num_docs);
+    assert_eq!(index.num_tokens(), doc_offsets[num_docs]);
+    assert_eq!(index.ex_bits(), 6);
+
+    let params = SearchParams {
+        ef: 100,
+        ..SearchParams::default()
+    };
+    let mut hits = 0;
+    for query in &queries {
+        let mut exact: Vec<(f32, u32)> = (0..num_docs)
+            .map(|d| (maxsim(query, d), d as u32))
+            .collect();
+        exact.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let (found, scores) = index.search(query, k, &params);
+        assert!(scores.windows(2).all(|w| w[0] >= w[1]));
+        for (&id, &score) in found.iter().zip(&scores) {
+            assert!((score - maxsim(query, id as usize)).abs() < 0.2 * m as f32);
+        }
+        hits += found
+            .iter()
+            .filter(|id| exact[..k].iter().any(|(_, e)| e == *id))
+            .count();
+    }
+    let recall = hits as f64 / (nq * k) as f64;
+    assert!(recall > 0.9, "recall {recall}");
+
+    // fewer documents than k pads the results
+    let (found, scores) = index.search(&queries[0], num_docs + 2, &params);
+    assert_eq!(found[num_docs..], [u32::MAX; 2]);
+    assert!(scores[num_docs..].iter().all(|s| *s == f32::NEG_INFINITY));
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("maxsim.index");
+    index.save(&path).unwrap();
+    let loaded = MultiVectorIndex::load(&path).unwrap();
+    assert_eq!(loaded.num_tokens(), index.num_tokens());
+    assert_eq!(
+        loaded.search(&queries[1], k, &params),
+        index.search(&queries[1], k, &params)
+    );
+
+    // files saved before the magic and version start with the num of documents
+    let bytes = std::fs::read(&path).unwrap();
+    assert_eq!(&bytes[..8], b"RABITQM\0");
+    let legacy_path = dir.path().join("legacy.index");
+    std::fs::write(&legacy_path, &bytes[12..]).unwrap();
+    let legacy = MultiVectorIndex::load(&legacy_path).unwrap();
+    assert_eq!(
+        legacy.search(&queries[1], k, &params),
+        index.search(&queries[1], k, &params)
+    );
+
+    assert!(
+        MultiVectorIndex::build(&tokens, dim, &[1, 0, 2], &MultiVectorParams::default()).is_err()
+    );
+    assert!(
+        MultiVectorIndex::build(&tokens, dim, &doc_lens[1..], &MultiVectorParams::default())
+            .is_err()
+    );
+}
+
+#[test]
+fn test_pca_rotator_indexes() {
+    use rabitq_rs::index::{self, Hnsw, HnswParams, Ivf, IvfParams, Qg, QgParams};
+    use rabitq_rs::{AnnIndex, SearchParams};
+    use std::sync::Arc;
+
+    // 256-dim vectors spanning a 24-dim subspace plus a little noise
+    let (n, nq, dim, latent, k) = (2000, 50, 256, 24, 10);
+    let mut rng = rand::rng();
+    let uniform = Uniform::new(-1.0f32, 1.0).unwrap();
+    let basis: Vec<f32> = (0..latent * dim)
+        .map(|_| uniform.sample(&mut rng))
+        .collect();
+    let data: Vec<f32> = (0..n)
+        .flat_map(|_| {
+            let z: Vec<f32> = (0..latent).map(|_| uniform.sample(&mut rng)).collect();
+            let noise: Vec<f32> = (0..dim).map(|_| 0.01 * uniform.sample(&mut rng)).collect();
+            (0..dim)
+                .map(|j| noise[j] + (0..latent).map(|l| z[l] * basis[l * dim + j]).sum::<f32>())
+                .collect::<Vec<f32>>()
+        })
+        .collect();
+    let queries = &data[..nq * dim];
+    let gt: Vec<Vec<u32>> = queries
+        .chunks_exact(dim)
+        .map(|q| {
+            let mut dists: Vec<(f32, u32)> = data
+                .chunks_exact(dim)
+                .enumerate()
+                .map(|(i, x)| {
+                    (
+                        q.iter().zip(x).map(|(a, b)| (a - b) * (a - b)).sum(),
+                        i as u32,
+                    )
+                })
+                .collect();
+            dists.sort_by(|a, b| a.0.total_cmp(&b.0));
+            dists[..k].iter().map(|&(_, id)| id).collect()
+        })
+        .collect();
+
+    let pca = Arc::new(Rotator::pca(&data[..1000 * dim], dim, 32, 42).unwrap());
+    assert_eq!(pca.padded_dim(), 64);
+    assert!(pca.explained_variance().unwrap() > 0.99);
+
+    let ivf = Ivf::build(
+        &data,
+        dim,
+        &IvfParams {
+            num_clusters: 16,
+            rotator: Some(pca.clone()),
+            ..IvfParams::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(ivf.padded_dim(), 64);
+    let hnsw = Hnsw::build(
+        &data,
+        dim,
+        &HnswParams {
+            num_threads: 2,
+            rotator: Some(pca.clone()),
+            ..HnswParams::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(hnsw.padded_dim(), 64);
+    let qg = Qg::build(
+        &data,
+        dim,
+        &QgParams {
+            num_threads: 2,
+            ef_build: 100,
+            rotator: Some(pca.clone()),
+            ..QgParams::default()
+        },
+    )
+    .unwrap();
+
+    let params = SearchParams {
+        nprobe: 16,
+        ef: 100,
+        ..SearchParams::default()
+    };
+    let dir = tempfile::tempdir().unwrap();
+    let built: Vec<Box<dyn AnnIndex>> = vec![Box::new(ivf), Box::new(hnsw), Box::new(qg)];
+    for index in built {
+        assert_eq!(index.dim(), dim);
+        let found = index::search_batch(index.as_ref(), queries, k, &params, 2);
+        let r = recall(&found, &gt, k);
+        assert!(r > 0.8, "{} recall {r}", index.kind());
+
+        // the PCA is saved with the index and applied to queries after loading
+        let path = dir.path().join(format!("{}.index", index.kind()));
+        index.save(&path).unwrap();
+        let loaded = index::open(&path).unwrap();
+        let reloaded = index::search_batch(loaded.as_ref(), queries, k, &params, 2);
+        let r2 = recall(&reloaded, &gt, k);
+        assert!(
+            (r - r2).abs() < 0.02,
+            "{} recall {r} before and {r2} after reload",
+            index.kind()
+        );
+    }
+
+    // the input dimension still has to match
+    let other = Arc::new(Rotator::pca(&data[..100 * dim], dim, 32, 42).unwrap());
+    let short: Vec<f32> = data
+        .chunks_exact(dim)
+        .flat_map(|x| x[..128].to_vec())
+        .collect();
+    let params = IvfParams {
+        num_clusters: 16,
+        rotator: Some(other),
+        ..IvfParams::default()
+    };
+    assert!(Ivf::build(&short, 128, &params).is_err());
+}
+
+#[test]
+fn test_learned_rotator_indexes() {
+    use rabitq_rs::index::{self, Hnsw, HnswParams, Ivf, IvfParams, Qg, QgParams};
+    use rabitq_rs::{AnnIndex, SearchParams};
+    use std::sync::Arc;
+
+    let (n, nq, dim, k) = (2000, 50, 100, 10);
+    let (data, gt) = index_test_data(n, nq, dim, k);
+    let queries = &data[..nq * dim];
+
+    let learned = Rotator::learned(&data[..1000 * dim], dim, 10, 42).unwrap();
+    assert_eq!(learned.padded_dim(), 128);
+    let dir = tempfile::tempdir().unwrap();
+    let rotator_path = dir.path().join("learned.rotator");
+    learned.save(&rotator_path).unwrap();
+    let learned = Arc::new(Rotator::load_learned(&rotator_path, dim).unwrap());
+
+    let built: Vec<Box<dyn AnnIndex>> = vec![
+        Box::new(
+            Ivf::build(
+                &data,
+                dim,
+                &IvfParams {
+                    num_clusters: 16,
+                    rotator: Some(learned.clone()),
+                    ..IvfParams::default()
+                },
+            )
+            .unwrap(),
+        ),
+        Box::new(
+            Hnsw::build(
+                &data,
+                dim,
+                &HnswParams {
+                    num_threads: 2,
+                    rotator: Some(learned.clone()),
+                    ..HnswParams::default()
+                },
+            )
+            .unwrap(),
+        ),
+        Box::new(
+            Qg::build(
+                &data,
+                dim,
+                &QgParams {
+                    num_threads: 2,
+                    ef_build: 100,
+                    rotator: Some(learned.clone()),
+                    ..QgParams::default()
+                },
+            )
+            .unwrap(),
+        ),
+    ];
+
+    let params = SearchParams {
+        nprobe: 16,
+        ef: 100,
+        ..SearchParams::default()
+    };
+    for index in built {
+        let found = index::search_batch(index.as_ref(), queries, k, &params, 2);
+        let r = recall(&found, &gt, k);
+        assert!(r > 0.8, "{} recall {r}", index.kind());
+
+        // the learned matrix is saved with the index like a random one
+        let path = dir.path().join(format!("{}.index", index.kind()));
+        index.save(&path).unwrap();
+        let loaded = index::open(&path).unwrap();
+        let reloaded = index::search_batch(loaded.as_ref(), queries, k, &params, 2);
+        let r2 = recall(&reloaded, &gt, k);
+        assert!(
+            (r - r2).abs() < 0.02,
+            "{} recall {r} before and {r2} after reload",
+            index.kind()
+        );
+    }
+}
+
+#[test]
+fn test_seeded_builds_repeat() {
+    use rabitq_rs::index::{Hnsw, HnswParams, Ivf, IvfParams, Qg, QgParams};
+    use rabitq_rs::AnnIndex;
+
+    let (n, dim) = (1500, 100);
+    let (data, _) = index_test_data(n, 0, dim, 10);
+    let dir = tempfile::tempdir().unwrap();
+    let saved = |index: &dyn AnnIndex, name: &str| {
+        let path = dir.path().join(name);
+        index.save(&path).unwrap();
+        std::fs::read(path).unwrap()
+    };
+
+    let ivf = |seed| IvfParams {
+        num_clusters: 16,
+        faster_quant: true,
+        seed,
+        ..IvfParams::default()
+    };
+    let first = saved(&Ivf::build(&data, dim, &ivf(1)).unwrap(), "ivf1");
+    assert_eq!(
+        first,
+        saved(&Ivf::build(&data, dim, &ivf(1)).unwrap(), "ivf2")
+    );
+    assert_ne!(
+        first,
+        saved(&Ivf::build(&data, dim, &ivf(2)).unwrap(), "ivf3")
+    );
+
+    // parallel insertions depend on scheduling, one thread repeats
+    let hnsw = |seed| HnswParams {
+        num_threads: 1,
+        ef_construction: 100,
+        seed,
+        ..HnswParams::default()
+    };
+    let first = saved(&Hnsw::build(&data, dim, &hnsw(1)).unwrap(), "hnsw1");
+    assert_eq!(
+        first,
+        saved(&Hnsw::build(&data, dim, &hnsw(1)).unwrap(), "hnsw2")
+    );
+    assert_ne!(
+        first,
+        saved(&Hnsw::build(&data, dim, &hnsw(2)).unwrap(), "hnsw3")
+    );
+
+    let qg = |seed| QgParams {
+        num_threads: 4,
+        ef_build: 100,
+        seed,
+        ..QgParams::default()
+    };
+    let first = saved(&Qg::build(&data, dim, &qg(1)).unwrap(), "qg1");
+    assert_eq!(first, saved(&Qg::build(&data, dim, &qg(1)).unwrap(), "qg2"));
+    assert_ne!(first, saved(&Qg::build(&data, dim, &qg(2)).unwrap(), "qg3"));
+
+    // learned rotations start from a seeded one
+    let sample = &data[..500 * dim];
+    let rotate = |rotator: Rotator| {
+        let mut y = vec![0.0f32; rotator.padded_dim()];
+        rotator.rotate(&data[..dim], &mut y);
+        y
+    };
+    assert_eq!(
+        rotate(Rotator::learned(sample, dim, 3, 5).unwrap()),
+        rotate(Rotator::learned(sample, dim, 3, 5).unwrap())
+    );
+    assert_eq!(
+        rotate(Rotator::pca(sample, dim, 40, 5).unwrap()),
+        rotate(Rotator::pca(sample, dim, 40, 5).unwrap())
+    );
+}
+
+#[test]
+fn test_search_budgets() {
+    use rabitq_rs::index::{Hnsw, HnswParams, INVALID_ID, Ivf, IvfParams, Qg, QgParams};
+    use rabitq_rs::{AnnIndex, SearchBudget, SearchParams};
+    use std::time::Duration;
+
+    let (n, nq, dim, k) = (2000, 20, 64, 10);
+    let (data, gt) = index_test_data(n, nq, dim, k);
+    let queries = &data[..nq * dim];
+    let built: Vec<Box<dyn AnnIndex>> = vec![
+        Box::new(
+            Ivf::build(
+                &data,
+                dim,
+                &IvfParams {
+                    num_clusters: 16,
+                    ..IvfParams::default()
+                },
+            )
+            .unwrap(),
+        ),
+        Box::new(Hnsw::build(&data, dim, &HnswParams::default()).unwrap()),
+        Box::new(Qg::build(&data, dim, &QgParams::default()).unwrap()),
+    ];
+    let params = SearchParams {
+        nprobe: 16,
+        ef: 100,
+        ..SearchParams::default()
+    };
+    let with = |budget: SearchBudget| SearchParams { budget, ..params };
+    let found = |ids: &[u32]| ids.iter().filter(|&&id| id != INVALID_ID).count();
+
+    for index in &built {
+        let kind = index.kind();
+        let mut ids = Vec::new();
+        for q in queries.chunks_exact(dim) {
+            // a budget that is never reached changes nothing
+            let result = index.search_result(
+                q,
+                k,
+                &with(SearchBudget {
+                    time: Some(Duration::from_secs(60)),
+                    max_distances: Some(usize::MAX),
+                    ..SearchBudget::default()
+                }),
+            );
+            assert!(!result.partial, "{kind}");
+            assert_eq!(result.ids, index.search(q, k, &params), "{kind}");
+            ids.extend(result.ids);
+
+            let result = index.search_result(
+                q,
+                k,
+                &with(SearchBudget {
+                    max_distances: Some(64),
+                    ..SearchBudget::default()
+                }),
+            );
+            assert!(result.partial, "{kind}");
+            assert!(found(&result.ids) > 0, "{kind} found nothing");
+        }
+        assert!(recall(&ids, &gt, k) > 0.8, "{kind}");
+    }
+
+    // IVF stops after its clusters, in probing order
+    let ivf = &built[0];
+    for q in queries.chunks_exact(dim) {
+        let result = ivf.search_result(
+            q,
+            k,
+            &with(SearchBudget {
+                max_clusters: Some(2),
+                ..SearchBudget::default()
+            }),
+        );
+        assert!(result.partial);
+        let nearest = SearchParams {
+            nprobe: 2,
+            ..params
+        };
+        assert_eq!(result.ids, ivf.search(q, k, &nearest));
+    }
+
+    // out of boostings the 1-bit estimates still fill the results
+    for index in &built[..2] {
+        let q = &queries[..dim];
+        let result = index.search_result(
+            q,
+            k,
+            &with(SearchBudget {
+                max_boosts: Some(1),
+                ..SearchBudget::default()
+            }),
+        );
+        assert!(result.partial, "{}", index.kind());
+        assert_eq!(found(&result.ids), k, "{}", index.kind());
+    }
+}
+
+#[test]
+fn test_search_stats() {
+    use rabitq_rs::index::{Hnsw, HnswParams, Ivf, IvfParams, Qg, QgParams};
+    use rabitq_rs::{AnnIndex, IndexKind, SearchParams};
+
+    let (n, dim, k) = (2000, 64, 10);
+    let (data, _) = index_test_data(n, 0, dim, k);
+    let built: Vec<Box<dyn AnnIndex>> = vec![
+        Box::new(
+            Ivf::build(
+                &data,
+                dim,
+                &IvfParams {
+                    num_clusters: 16,
+                    ..IvfParams::default()
+                },
+            )
+            .unwrap(),
+        ),
+        Box::new(Hnsw::build(&data, dim, &HnswParams::default()).unwrap()),
+        Box::new(Qg::build(&data, dim, &QgParams::default()).unwrap()),
+    ];
+    let params = SearchParams {
+        nprobe: 16,
+        ef: 100,
+        ..SearchParams::default()
+    };
+    let counted = SearchParams {
+        stats: true,
+        ..params
+    };
+
+    for index in &built {
+        let q = &data[..dim];
+        let plain = index.search_result(q, k, &params);
+        assert_eq!(plain.stats, None);
+        let result = index.search_result(q, k, &counted);
+        assert_eq!(result.ids, plain.ids, "{}", index.kind());
+        let stats = result.stats.unwrap();
+        match index.kind() {
+            IndexKind::Ivf => {
+                // every cluster is probed, every estimate is either boosted or pruned
+                assert_eq!(stats.clusters, 16);
+                assert_eq!(stats.distances, n);
+                assert!(stats.batches >= n / 32);
+                assert_eq!(stats.boosts + stats.pruned, n);
+                assert_eq!((stats.hops, stats.visited), (0, 0));
+            }
+            IndexKind::Hnsw => {
+                assert!(stats.hops > 0 && stats.visited > k);
+                assert_eq!(stats.boosts + stats.pruned, stats.visited);
+                assert!(stats.distances >= stats.visited);
+                assert_eq!((stats.clusters, stats.batches), (0, 0));
+            }
+            IndexKind::Qg => {
+                assert!(stats.hops > 0 && stats.visited >= stats.hops);
+                // one batch per vertex of degree 32
+                assert_eq!(stats.batches, stats.hops);
+                assert_eq!((stats.clusters, stats.boosts), (0, 0));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_build_progress_and_cancel() {
+    use std::io::ErrorKind;
+    use std::sync::{Arc, Mutex};
+
+    use rabitq_rs::index::{Hnsw, HnswParams, Ivf, IvfParams, Qg, QgParams};
+    use rabitq_rs::{BuildMonitor, CancelToken};
+
+    let (n, dim) = (2000, 64);
+    let (data, _) = index_test_data(n, 0, dim, 10);
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let record = {
+        let reports = reports.clone();
+        BuildMonitor::default().with_progress(move |phase, done, total| {
+            reports
+                .lock()
+                .unwrap()
+                .push((phase.to_string(), done, total));
+        })
+    };
+    let take = || std::mem::take(&mut *reports.lock().unwrap());
+
+    let ivf = IvfParams {
+        num_clusters: 16,
+        monitor: record.clone(),
+        ..IvfParams::default()
+    };
+    Ivf::build(&data, dim, &ivf).unwrap();
+    let ivf_reports = take();
+    assert_eq!(
+        ivf_reports.last(),
+        Some(&("quantize clusters".to_string(), 16, 16))
+    );
+
+    let hnsw = HnswParams {
+        monitor: record.clone(),
+        ..HnswParams::default()
+    };
+    Hnsw::build(&data, dim, &hnsw).unwrap();
+    assert_eq!(take().last(), Some(&("insert vectors".to_string(), n, n)));
+
+    let qg = QgParams {
+        monitor: record.clone(),
+        ..QgParams::default()
+    };
+    Qg::build(&data, dim, &qg).unwrap();
+    let qg_reports = take();
+    let total = qg.num_iter * n;
+    assert!(qg_reports.windows(2).all(|w| w[0].1 < w[1].1));
+    assert_eq!(
+        qg_reports.last(),
+        Some(&("search neighbors".to_string(), total, total))
+    );
+
+    // a token set before the build stops it at the first batch
+    let cancel = CancelToken::new();
+    cancel.cancel();
+    let hnsw = HnswParams {
+        monitor: BuildMonitor::default().with_cancel(cancel),
+        ..HnswParams::default()
+    };
+    let err = Hnsw::build(&data, dim, &hnsw).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::Interrupted);
+
+    // cancelled from the progress closure, after the first round of refinement
+    let cancel = CancelToken::new();
+    let qg = QgParams {
+        monitor: BuildMonitor::default()
+            .with_cancel(cancel.clone())
+            .with_progress(move |_, done, _| {
+                if done >= n {
+                    cancel.cancel();
+                }
+            }),
+        ..QgParams::default()
+    };
+    let err = Qg::build(&data, dim, &qg).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::Interrupted);
+    assert!(take().is_empty());
+}
+
+#[test]
+fn test_ivf_streaming_build() {
+    use std::sync::{Arc, Mutex};
+
+    use rabitq_rs::index::{self, Ivf, IvfBuilder, IvfParams, StreamBuildParams};
+    use rabitq_rs::io::{Matrix, VectorFile, write_bin};
+    use rabitq_rs::kmeans::KMeans;
+    use rabitq_rs::{AnnIndex, BuildMonitor, SearchParams, f16};
+
+    let (n, nq, dim, k) = (2000, 50, 64, 10);
+    let (data, gt) = index_test_data(n, nq, dim, k);
+    let dir = tempfile::tempdir().unwrap();
+    let saved = |index: &Ivf, name: &str| {
+        let path = dir.path().join(name);
+        index.save(&path).unwrap();
+        std::fs::read(path).unwrap()
+    };
+    let params = IvfParams {
+        num_clusters: 16,
+        ..IvfParams::default()
+    };
+    let search = SearchParams {
+        nprobe: 16,
+        ..SearchParams::default()
+    };
+
+    // chunks in any cluster order give the index of the in-memory build
+    let clustering = KMeans::new(16).fit(&data, dim);
+    let mut sizes = vec![0; 16];
+    for &c in &clustering.assignments {
+        sizes[c as usize] += 1;
+    }
+    let mut builder = IvfBuilder::new(dim, &clustering.centroids, &sizes, &params).unwrap();
+    for (rows, ids) in data
+        .chunks(300 * dim)
+        .zip(clustering.assignments.chunks(300))
+    {
+        builder.add(rows, ids).unwrap();
+    }
+    let streamed = builder.finish().unwrap();
+    let in_memory = Ivf::from_clusters(
+        &data,
+        dim,
+        &clustering.centroids,
+        &clustering.assignments,
+        &params,
+    )
+    .unwrap();
+    assert_eq!(saved(&streamed, "streamed"), saved(&in_memory, "in_memory"));
+
+    // non-f32 input is quantized chunk by chunk into the index of its widened copy
+    let half: Vec<f16> = data.iter().map(|&x| f16::from_f32(x)).collect();
+    let widened: Vec<f32> = half.iter().map(|x| x.to_f32()).collect();
+    assert_eq!(
+        saved(&Ivf::build(&half, dim, &params).unwrap(), "f16"),
+        saved(&Ivf::build(&widened, dim, &params).unwrap(), "widened")
+    );
+
+    let mut builder = IvfBuilder::new(dim, &clustering.centroids, &sizes, &params).unwrap();
+    assert!(builder.add(&data[..dim], &[16]).is_err());
+    builder
+        .add(&data[..dim], &clustering.assignments[..1])
+        .unwrap();
+    assert_eq!(builder.added(), 1);
+    assert!(builder.finish().is_err());
+
+    let path = dir.path().join("base.fbin");
+    write_bin(&path, &Matrix::new(data.clone(), n, dim)).unwrap();
+    let file = VectorFile::<f32>::open(&path).unwrap();
+    let stream = StreamBuildParams {
+        chunk_rows: 300,
+        train_rows: 1000,
+    };
+    let ivf = Ivf::build_from_file(&file, &params, &stream).unwrap();
+    assert_eq!(ivf.len(), n);
+    let found = index::search_batch(&ivf, &data[..nq * dim], k, &search, 2);
+    let r = recall(&found, &gt, k);
+    assert!(r > 0.8, "file build recall {r}");
+
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let monitored = IvfParams {
+        monitor: BuildMonitor::default().with_progress({
+            let reports = reports.clone();
+            move |phase, done, total| {
+                reports
+                    .lock()
+                    .unwrap()
+                    .push((phase.to_string(), done, total));
+            }
+        }),
+        ..params.clone()
+    };
+    let chunks = || data.chunks(250 * dim).map(|rows| Ok(rows.to_vec()));
+    let ivf = Ivf::build_streaming(dim, chunks, &monitored, &stream).unwrap();
+    assert_eq!(ivf.len(), n);
+    let found = index::search_batch(&ivf, &data[..nq * dim], k, &search, 2);
+    let r = recall(&found, &gt, k);
+    assert!(r > 0.8, "iterator build recall {r}");
+    let reports = reports.lock().unwrap();
+    assert!(reports.contains(&("assign clusters".to_string(), n, 0)));
+    assert_eq!(
+        reports.last(),
+        Some(&("quantize vectors".to_string(), n, n))
+    );
+}
@@ -3,43 +3,61 @@ extern crate bindgen;
 extern crate cc;
 
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-fn main() {
-    // println!("cargo:rerun-if-changed=../../rabitqlib");
-    // println!("cargo:rerun-if-changed=../../rabitqlib/**/*.hpp");
-    // println!("cargo:rerun-if-changed=../../rabitqlib/**/*.cpp");
-    // println!("cargo:rerun-if-changed=rabitq_wrapper.cpp");
-    // println!("cargo:rerun-if-changed=rabitq.h");
-
-    // 递归检测rabitqlib目录下的所有头文件和源文件
-    let rabitqlib_path = "../../rabitqlib";
-    if let Ok(entries) = std::fs::read_dir(rabitqlib_path) {
-        for entry in entries.flatten() {
-            if let Ok(file_type) = entry.file_type() {
-                if file_type.is_file() {
-                    if let Some(ext) = entry.path().extension() {
-                        if ext == "hpp" || ext == "cpp" {
-                            println!("cargo:rerun-if-changed={}", entry.path().display());
-                        }
-                    }
-                }
-            }
+// third-party headers (Eigen, hnswlib) are vendored and never edited, skip them
+fn watch_sources(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() && entry.file_name() != "third" {
+            watch_sources(&path);
+        } else if file_type.is_file()
+            && let Some(ext) = path.extension()
+            && (ext == "hpp" || ext == "cpp")
+        {
+            println!("cargo:rerun-if-changed={}", path.display());
         }
     }
-    
+}
+
+fn main() {
+    // 递归检测rabitqlib目录下的所有头文件和源文件
+    watch_sources(Path::new("../../rabitqlib"));
+    println!("cargo:rerun-if-changed=rabitq_wrapper.cpp");
+    println!("cargo:rerun-if-changed=rabitq.h");
+
     // Compile the C++ wrapper.
-    cc::Build::new()
+    let mut build = cc::Build::new();
+    build
         .file("rabitq_wrapper.cpp")
-        .include("../../rabitqlib") 
+        .include("../../rabitqlib")
         .cpp(true)
         .flag("-std=c++17")
-        .flag("-march=native")
-        .flag("-mavx512f")
-        .flag("-fopenmp")
-        .compile("rabitq_wrapper");
+        .flag("-fopenmp");
+
+    // The library picks AVX-512 / AVX2 / NEON / scalar kernels from the predefined macros,
+    // so only the target cpu needs to be set here.
+    match env::var("CARGO_CFG_TARGET_ARCH").as_deref() {
+        Ok("x86_64") => {
+            build.flag("-march=native");
+        }
+        Ok("aarch64") => {
+            // NEON is part of armv8-a, no need to probe the build machine
+            build.flag("-march=armv8-a");
+        }
+        _ => {}
+    }
+    build.compile("rabitq_wrapper");
 
     println!("cargo:rustc-link-lib=stdc++");
+    // IVF and SymphonyQG construction run OpenMP loops
+    println!("cargo:rustc-link-lib=gomp");
 
     // Generate bindings for the C header.
     let bindings = bindgen::Builder::default()
@@ -20,6 +20,20 @@ enum MetricType {
     METRIC_IP
 };
 
+enum RabitqLogLevel {
+    RABITQ_LOG_ERROR,
+    RABITQ_LOG_WARN,
+    RABITQ_LOG_INFO,
+    RABITQ_LOG_DEBUG
+};
+
+// Receives each message of the library without a trailing newline, possibly from several
+// threads at once
+typedef void (*rabitq_log_callback)(enum RabitqLogLevel level, const char* message);
+// The library prints nothing by default. NULL silences it again, messages above max_level
+// are dropped before they are formatted.
+void rabitq_set_log_callback(rabitq_log_callback callback, enum RabitqLogLevel max_level);
+
 typedef struct SplitBatchQuery SplitBatchQuery;
 typedef struct SplitSingleQuery SplitSingleQuery;
 typedef struct BatchQuery BatchQuery;
@@ -31,6 +45,8 @@ RabitqConfig* rabitq_faster_config(size_t dim, size_t total_bits);
 
 
 Rotator* rabitq_rotator_new(size_t dim, size_t padded_dim);
+// Same rotation for the same seed
+Rotator* rabitq_rotator_new_seeded(size_t dim, size_t padded_dim, uint64_t seed);
 void rabitq_rotator_free(Rotator* rotator);
 void rabitq_rotator_rotate(const Rotator* rotator, const float* x, float* y);
 
@@ -38,6 +54,27 @@ int rabitq_rotator_load(Rotator* rotator, const char* file_path);
 int rabitq_rotator_save(const Rotator* rotator, const char* file_path);
 size_t rabitq_rotator_size(const Rotator* rotator);
 size_t rabitq_rotator_dim(const Rotator* rotator);
+// PCA from dim to out_dim (1 to dim) learned on num sample rows, followed by a rotation of
+// out_dim padded to a multiple of 64 drawn from seed. NULL if num < 2 or out_dim is out of
+// range.
+Rotator* rabitq_pca_rotator_new(
+    const float* data, size_t num, size_t dim, size_t out_dim, uint64_t seed
+);
+// Loads a PCA rotator written by rabitq_rotator_save, NULL on failure
+Rotator* rabitq_pca_rotator_load(const char* file_path);
+// Rotation of dim padded to a multiple of 64 learned on num sample rows in iters steps to
+// lower the quantization error, starting from one drawn from seed. NULL if num < 2.
+Rotator* rabitq_learned_rotator_new(
+    const float* data, size_t num, size_t dim, size_t iters, uint64_t seed
+);
+// Loads a learned rotator of the given dim written by rabitq_rotator_save, NULL on failure
+Rotator* rabitq_learned_rotator_load(const char* file_path, size_t dim);
+// Fraction of the sample variance kept by a PCA rotator, -1 for other rotators
+float rabitq_rotator_explained_variance(const Rotator* rotator);
+
+// Exact distances between raw vectors
+float rabitq_euclidean_sqr(const float* x, const float* y, size_t dim);
+float rabitq_dot_product(const float* x, const float* y, size_t dim);
 
 void rabitq_quantize_full_single(
     const float* data,
@@ -70,16 +107,23 @@ void rabitq_quantize_split_single(
     const RabitqConfig* config
 );
 
+// Query precision: lut_bits is 8 or 16 (high accuracy FastScan), query_bits is 1..16;
+// 0 keeps the query in float for both
 SplitBatchQuery* rabitq_split_batch_query_new(
     const float* rotated_query,
     size_t padded_dim,
     size_t ex_bits,
     enum MetricType metric_type,
-    bool use_hacc
+    size_t lut_bits
 );
 
 void rabitq_split_batch_query_free(SplitBatchQuery* q_obj);
 
+// copy of `q_obj` that reads the rotated query from `rotated_query`
+SplitBatchQuery* rabitq_split_batch_query_clone(
+    const SplitBatchQuery* q_obj, const float* rotated_query
+);
+
 void rabitq_split_batch_query_set_g_add(SplitBatchQuery* q_obj, float norm, float ip);
 
 void rabitq_split_batch_estdist(
@@ -119,11 +163,16 @@ SplitSingleQuery* rabitq_split_single_query_new(
     size_t padded_dim,
     size_t ex_bits,
     const RabitqConfig* config,
-    enum MetricType metric_type
+    enum MetricType metric_type,
+    size_t query_bits
 );
 
 void rabitq_split_single_query_free(SplitSingleQuery* q_obj);
 
+SplitSingleQuery* rabitq_split_single_query_clone(
+    const SplitSingleQuery* q_obj, const float* rotated_query
+);
+
 const uint64_t* rabitq_split_single_query_query_bin(const SplitSingleQuery* q_obj);
 
 float rabitq_split_single_query_delta(const SplitSingleQuery* q_obj);
@@ -167,8 +216,11 @@ void rabitq_split_single_fulldist(
 );
 
 // Batch Query
-BatchQuery* rabitq_batch_query_new(const float* rotated_query, size_t padded_dim);
+BatchQuery* rabitq_batch_query_new(
+    const float* rotated_query, size_t padded_dim, size_t lut_bits
+);
 void rabitq_batch_query_free(BatchQuery* query);
+BatchQuery* rabitq_batch_query_clone(const BatchQuery* query);
 float rabitq_batch_query_delta(const BatchQuery* query);
 float rabitq_batch_query_sum_vl_lut(const BatchQuery* query);
 float rabitq_batch_query_k1xsumq(const BatchQuery* query);
@@ -190,9 +242,13 @@ SingleCentroidQuery* rabitq_single_centroid_query_new(
     size_t padded_dim,
     size_t ex_bits,
     const RabitqConfig* config,
-    enum MetricType metric_type
+    enum MetricType metric_type,
+    size_t query_bits
 );
 void rabitq_single_centroid_query_free(SingleCentroidQuery* query);
+SingleCentroidQuery* rabitq_single_centroid_query_clone(
+    const SingleCentroidQuery* query, const float* rotated_query
+);
 const uint64_t* rabitq_single_centroid_query_query_bin(const SingleCentroidQuery* query);
 const float* rabitq_single_centroid_query_rotated_query(const SingleCentroidQuery* query);
 float rabitq_single_centroid_query_delta(const SingleCentroidQuery* query);
@@ -228,6 +284,288 @@ void rabitq_single_centroid_fulldist(
     float g_add,
     float g_error
 );
+
+// Indexes. Loaders return NULL when the file cannot be opened, the other functions return
+// 0 on success and -1 on failure.
+typedef struct IvfIndex IvfIndex;
+typedef struct HnswIndex HnswIndex;
+typedef struct QgIndex QgIndex;
+typedef struct MultiVectorIndex MultiVectorIndex;
+
+// Limits on the work of one search, 0 is unlimited. Distances count the vectors whose
+// distance is estimated or computed, boosts the ex-bit refinements of an estimate. A search
+// that reaches one keeps the best results found so far and sets its partial flag.
+typedef struct RabitqSearchBudget {
+    uint64_t time_us;
+    size_t max_clusters;
+    size_t max_distances;
+    size_t max_boosts;
+} RabitqSearchBudget;
+
+// What one search did, see rabitqlib/utils/search_stats.hpp. Fields that do not apply to
+// an index stay 0.
+typedef struct RabitqSearchStats {
+    size_t clusters;
+    size_t batches;
+    size_t distances;
+    size_t pruned;
+    size_t boosts;
+    size_t hops;
+    size_t visited;
+} RabitqSearchStats;
+
+// Flag that cancels the builds watching it, safe to set from any thread
+typedef struct RabitqCancelToken RabitqCancelToken;
+RabitqCancelToken* rabitq_cancel_token_new();
+void rabitq_cancel_token_free(RabitqCancelToken* token);
+void rabitq_cancel_token_cancel(RabitqCancelToken* token);
+bool rabitq_cancel_token_is_cancelled(const RabitqCancelToken* token);
+
+// Progress of a build: items done out of total in the named phase. Called between batches
+// by the thread running the build.
+typedef void (*rabitq_build_progress)(void* ctx, const char* phase, size_t done, size_t total);
+// Passed to the builds, each member may be NULL. A cancelled build returns -1 and leaves
+// an index that has to be built again.
+typedef struct RabitqBuildMonitor {
+    rabitq_build_progress progress;
+    void* ctx;
+    const RabitqCancelToken* cancel;
+} RabitqBuildMonitor;
+
+// IVF
+// Rotator drawn from seed
+IvfIndex* rabitq_ivf_new(
+    size_t num, size_t dim, size_t num_clusters, size_t total_bits, uint64_t seed
+);
+IvfIndex* rabitq_ivf_load(const char* file_path);
+void rabitq_ivf_free(IvfIndex* index);
+int rabitq_ivf_construct(
+    IvfIndex* index,
+    const float* data,
+    const float* centroids,
+    const uint32_t* cluster_ids,
+    bool faster,
+    const RabitqBuildMonitor* monitor
+);
+int rabitq_ivf_save(const IvfIndex* index, const char* file_path);
+// Copies rotator into an index that is not constructed yet, -1 if its dimensions differ
+int rabitq_ivf_set_rotator(IvfIndex* index, const Rotator* rotator);
+// Streaming construction, for data larger than memory: storage for cluster_sizes[i]
+// vectors of cluster i is allocated up front, the sizes summing to the num of the index.
+// Vectors are then added in chunks, getting ids in the order they are added, and the index
+// is complete after finish. NULL if the sizes do not match the index. monitor may be NULL,
+// otherwise it must outlive the builder.
+typedef struct IvfBuilder IvfBuilder;
+IvfBuilder* rabitq_ivf_builder_new(
+    IvfIndex* index,
+    const float* centroids,
+    const size_t* cluster_sizes,
+    bool faster,
+    const RabitqBuildMonitor* monitor
+);
+void rabitq_ivf_builder_free(IvfBuilder* builder);
+// -1 if a cluster id is out of range or a cluster gets more vectors than its size, nothing
+// is added then, or if the build was cancelled
+int rabitq_ivf_builder_add(
+    IvfBuilder* builder, const float* data, const uint32_t* cluster_ids, size_t num
+);
+// -1 if a cluster did not get all its vectors
+int rabitq_ivf_builder_finish(IvfBuilder* builder);
+// distances may be NULL, otherwise it gets k estimated squared distances, missing results
+// are UINT32_MAX and infinity. budget may be NULL, partial then stays false. stats may be
+// NULL.
+void rabitq_ivf_search(
+    const IvfIndex* index,
+    const float* query,
+    size_t k,
+    size_t nprobe,
+    uint32_t* results,
+    size_t lut_bits,
+    float* distances,
+    const RabitqSearchBudget* budget,
+    bool* partial,
+    RabitqSearchStats* stats
+);
+size_t rabitq_ivf_num(const IvfIndex* index);
+size_t rabitq_ivf_dim(const IvfIndex* index);
+size_t rabitq_ivf_padded_dim(const IvfIndex* index);
+size_t rabitq_ivf_num_clusters(const IvfIndex* index);
+size_t rabitq_ivf_ex_bits(const IvfIndex* index);
+// writes num_clusters sizes
+void rabitq_ivf_cluster_sizes(const IvfIndex* index, size_t* sizes);
+// writes the bytes of batch data, ex data, ids and centroids
+void rabitq_ivf_memory(const IvfIndex* index, size_t* bytes);
+
+// HNSW, random_seed draws the rotator and the levels. Builds repeat for a seed with one
+// thread, parallel insertions link vectors in the order threads reach them.
+HnswIndex* rabitq_hnsw_new(
+    size_t max_elements,
+    size_t dim,
+    size_t total_bits,
+    size_t m,
+    size_t ef_construction,
+    size_t random_seed,
+    enum MetricType metric_type
+);
+HnswIndex* rabitq_hnsw_load(const char* file_path, enum MetricType metric_type);
+void rabitq_hnsw_free(HnswIndex* index);
+int rabitq_hnsw_set_rotator(HnswIndex* index, const Rotator* rotator);
+int rabitq_hnsw_construct(
+    HnswIndex* index,
+    size_t num_clusters,
+    const float* centroids,
+    size_t num,
+    const float* data,
+    uint32_t* cluster_ids,
+    size_t num_threads,
+    bool faster,
+    const RabitqBuildMonitor* monitor
+);
+// Raw vector source of rabitq_hnsw_construct_quantized: writes the rows of the count ids
+// to out and returns 0, nonzero on failure. Called from several threads at once when
+// exact_sample > 0.
+typedef int (*rabitq_fetch_rows)(void* ctx, const uint32_t* ids, size_t count, float* out);
+// Chooses edges on distances estimated from the quantized vectors, only chunk_size raw
+// rows resident
+int rabitq_hnsw_construct_quantized(
+    HnswIndex* index,
+    size_t num_clusters,
+    const float* centroids,
+    size_t num,
+    rabitq_fetch_rows fetch_rows,
+    void* ctx,
+    size_t chunk_size,
+    float exact_sample,
+    size_t num_threads,
+    bool faster,
+    const RabitqBuildMonitor* monitor
+);
+// Adds one vector after construction, -1 if the label exists or the index is full. Safe
+// concurrently with searches and other insertions.
+int rabitq_hnsw_insert(HnswIndex* index, const float* vector, uint32_t label);
+// Grows the index to hold capacity vectors, not safe concurrently with anything else
+int rabitq_hnsw_reserve(HnswIndex* index, size_t capacity);
+size_t rabitq_hnsw_capacity(const HnswIndex* index);
+int rabitq_hnsw_save(const HnswIndex* index, const char* file_path);
+// writes k ids and distances per query, missing results are UINT32_MAX. budget may be
+// NULL, otherwise each query gets its own and partial num_queries flags. stats may be NULL
+// or hold num_queries entries.
+void rabitq_hnsw_search(
+    const HnswIndex* index,
+    const float* queries,
+    size_t num_queries,
+    size_t k,
+    size_t ef,
+    size_t num_threads,
+    size_t query_bits,
+    uint32_t* results,
+    float* distances,
+    const RabitqSearchBudget* budget,
+    bool* partial,
+    RabitqSearchStats* stats
+);
+size_t rabitq_hnsw_num(const HnswIndex* index);
+size_t rabitq_hnsw_dim(const HnswIndex* index);
+size_t rabitq_hnsw_padded_dim(const HnswIndex* index);
+size_t rabitq_hnsw_num_clusters(const HnswIndex* index);
+size_t rabitq_hnsw_ex_bits(const HnswIndex* index);
+size_t rabitq_hnsw_max_degree(const HnswIndex* index);
+size_t rabitq_hnsw_ef_construction(const HnswIndex* index);
+size_t rabitq_hnsw_num_levels(const HnswIndex* index);
+uint32_t rabitq_hnsw_entry_point(const HnswIndex* index);
+// writes the out-degree of every element on the level, returns their count (at most num)
+size_t rabitq_hnsw_level_degrees(const HnswIndex* index, size_t level, uint32_t* degrees);
+// writes the bytes of level 0 edges, labels, bin data, ex data, upper level edges and
+// centroids
+void rabitq_hnsw_memory(const HnswIndex* index, size_t* bytes);
+
+// SymphonyQG
+// Rotator drawn from seed
+QgIndex* rabitq_qg_new(
+    size_t num, size_t dim, size_t degree_bound, enum MetricType metric_type, uint64_t seed
+);
+QgIndex* rabitq_qg_load(const char* file_path);
+void rabitq_qg_free(QgIndex* index);
+int rabitq_qg_set_rotator(QgIndex* index, const Rotator* rotator);
+// Random initial and supplementary edges are drawn from seed, the graph is the same for a
+// seed and num_threads
+int rabitq_qg_build(
+    QgIndex* index,
+    const float* data,
+    size_t ef_build,
+    size_t num_threads,
+    size_t num_iter,
+    uint64_t seed,
+    const RabitqBuildMonitor* monitor
+);
+// Adds one vector, writing its id, -1 if the graph is full. Safe concurrently with
+// searches, insertions run one at a time.
+int rabitq_qg_insert(QgIndex* index, const float* vector, size_t ef, uint32_t* id);
+// Grows the graph to hold capacity vectors, not safe concurrently with anything else
+int rabitq_qg_reserve(QgIndex* index, size_t capacity);
+size_t rabitq_qg_capacity(const QgIndex* index);
+int rabitq_qg_save(const QgIndex* index, const char* file_path);
+// distances may be NULL, otherwise it gets k exact distances (1 - inner product for IP).
+// budget may be NULL, a vertex takes 1 + degree_bound distances and there are no clusters
+// or boosts. stats may be NULL.
+void rabitq_qg_search(
+    const QgIndex* index,
+    const float* query,
+    size_t k,
+    size_t ef,
+    size_t lut_bits,
+    uint32_t* results,
+    float* distances,
+    const RabitqSearchBudget* budget,
+    bool* partial,
+    RabitqSearchStats* stats
+);
+size_t rabitq_qg_num(const QgIndex* index);
+size_t rabitq_qg_dim(const QgIndex* index);
+size_t rabitq_qg_degree_bound(const QgIndex* index);
+enum MetricType rabitq_qg_metric(const QgIndex* index);
+uint32_t rabitq_qg_entry_point(const QgIndex* index);
+// writes num degrees
+void rabitq_qg_degrees(const QgIndex* index, uint32_t* degrees);
+// writes the bytes of raw vectors, batch data and neighbors
+void rabitq_qg_memory(const QgIndex* index, size_t* bytes);
+
+// Multi-vector (MaxSim)
+// Rotator drawn from seed
+MultiVectorIndex* rabitq_maxsim_new(size_t dim, size_t total_bits, uint64_t seed);
+MultiVectorIndex* rabitq_maxsim_load(const char* file_path);
+void rabitq_maxsim_free(MultiVectorIndex* index);
+int rabitq_maxsim_set_rotator(MultiVectorIndex* index, const Rotator* rotator);
+// tokens holds doc_offsets[num_docs] rows, document i being rows doc_offsets[i] to
+// doc_offsets[i + 1], -1 if a document is empty
+int rabitq_maxsim_construct(
+    MultiVectorIndex* index,
+    const float* tokens,
+    const size_t* doc_offsets,
+    size_t num_docs,
+    bool faster
+);
+int rabitq_maxsim_save(const MultiVectorIndex* index, const char* file_path);
+// writes k documents and MaxSim scores, the best rerank of them by 1-bit estimates are
+// re-scored with ex-bit codes, missing results are UINT32_MAX and -infinity
+void rabitq_maxsim_search(
+    const MultiVectorIndex* index,
+    const float* query,
+    size_t num_query_tokens,
+    size_t k,
+    size_t rerank,
+    size_t lut_bits,
+    uint32_t* results,
+    float* scores
+);
+size_t rabitq_maxsim_num_docs(const MultiVectorIndex* index);
+size_t rabitq_maxsim_num_tokens(const MultiVectorIndex* index);
+size_t rabitq_maxsim_dim(const MultiVectorIndex* index);
+size_t rabitq_maxsim_padded_dim(const MultiVectorIndex* index);
+size_t rabitq_maxsim_ex_bits(const MultiVectorIndex* index);
+// writes the bytes of batch data, ex data and centroids
+void rabitq_maxsim_memory(const MultiVectorIndex* index, size_t* bytes);
+
 #ifdef __cplusplus
 }
 #endif
@@ -3,10 +3,70 @@
 #include "../../rabitqlib/utils/rotator.hpp"
 #include "../../rabitqlib/index/estimator.hpp"
 #include "../../rabitqlib/index/query.hpp"
+#include "../../rabitqlib/index/ivf/ivf.hpp"
+#include "../../rabitqlib/index/ivf/ivf_builder.hpp"
+#include "../../rabitqlib/index/hnsw/hnsw.hpp"
+#include "../../rabitqlib/index/symqg/qg.hpp"
+#include "../../rabitqlib/index/symqg/qg_builder.hpp"
+#include "../../rabitqlib/index/maxsim/maxsim.hpp"
+#include "../../rabitqlib/utils/build_monitor.hpp"
+#include "../../rabitqlib/utils/log.hpp"
 #include "../../rabitqlib/utils/space.hpp"
 
+#include <algorithm>
+#include <atomic>
+#include <exception>
+#include <limits>
+
+static std::atomic<rabitq_log_callback> log_callback{nullptr};
+
+static void forward_log(rabitqlib::LogLevel level, const char* message) {
+    rabitq_log_callback callback = log_callback.load(std::memory_order_acquire);
+    if (callback != nullptr) {
+        callback(static_cast<RabitqLogLevel>(level), message);
+    }
+}
+
+static rabitqlib::BuildMonitor build_monitor(const RabitqBuildMonitor* monitor) {
+    rabitqlib::BuildMonitor result;
+    if (monitor == nullptr) {
+        return result;
+    }
+    if (monitor->progress != nullptr) {
+        result.progress = [progress = monitor->progress,
+                           ctx = monitor->ctx](const char* phase, size_t done, size_t total) {
+            progress(ctx, phase, done, total);
+        };
+    }
+    result.cancel = reinterpret_cast<const std::atomic<bool>*>(monitor->cancel);
+    return result;
+}
+
 extern "C" {
 
+RabitqCancelToken* rabitq_cancel_token_new() {
+    return reinterpret_cast<RabitqCancelToken*>(new std::atomic<bool>(false));
+}
+
+void rabitq_cancel_token_free(RabitqCancelToken* token) {
+    delete reinterpret_cast<std::atomic<bool>*>(token);
+}
+
+void rabitq_cancel_token_cancel(RabitqCancelToken* token) {
+    reinterpret_cast<std::atomic<bool>*>(token)->store(true, std::memory_order_relaxed);
+}
+
+bool rabitq_cancel_token_is_cancelled(const RabitqCancelToken* token) {
+    return reinterpret_cast<const std::atomic<bool>*>(token)->load(std::memory_order_relaxed);
+}
+
+void rabitq_set_log_callback(rabitq_log_callback callback, enum RabitqLogLevel max_level) {
+    log_callback.store(callback, std::memory_order_release);
+    rabitqlib::set_log_callback(
+        callback == nullptr ? nullptr : forward_log, static_cast<rabitqlib::LogLevel>(max_level)
+    );
+}
+
 RabitqConfig* rabitq_config_new() {
     return reinterpret_cast<RabitqConfig*>(new rabitqlib::quant::RabitqConfig());
 }
@@ -25,6 +85,11 @@ Rotator* rabitq_rotator_new(size_t dim, size_t padded_dim) {
     return reinterpret_cast<Rotator*>(rotator);
 }
 
+Rotator* rabitq_rotator_new_seeded(size_t dim, size_t padded_dim, uint64_t seed) {
+    auto rotator = new rabitqlib::rotator_impl::FhtKacRotator(dim, padded_dim, seed);
+    return reinterpret_cast<Rotator*>(rotator);
+}
+
 void rabitq_rotator_free(Rotator* rotator) {
     delete reinterpret_cast<rabitqlib::Rotator<float>*>(rotator);
 }
@@ -38,7 +103,11 @@ int rabitq_rotator_load(Rotator* rotator, const char* file_path) {
     if (!input.is_open()) {
         return -1;
     }
-    reinterpret_cast<rabitqlib::Rotator<float>*>(rotator)->load(input);
+    try {
+        reinterpret_cast<rabitqlib::Rotator<float>*>(rotator)->load(input);
+    } catch (const std::exception&) {
+        return -1;
+    }
     return 0;
 }
 
@@ -59,6 +128,76 @@ size_t rabitq_rotator_dim(const Rotator* rotator) {
     return reinterpret_cast<const rabitqlib::Rotator<float>*>(rotator)->dim();
 }
 
+Rotator* rabitq_pca_rotator_new(
+    const float* data, size_t num, size_t dim, size_t out_dim, uint64_t seed
+) {
+    try {
+        auto rotator = new rabitqlib::rotator_impl::PcaRotator(data, num, dim, out_dim, seed);
+        return reinterpret_cast<Rotator*>(rotator);
+    } catch (const std::exception&) {
+        return nullptr;
+    }
+}
+
+Rotator* rabitq_pca_rotator_load(const char* file_path) {
+    std::ifstream input(file_path, std::ios::binary);
+    if (!input.is_open()) {
+        return nullptr;
+    }
+    auto rotator = new rabitqlib::rotator_impl::PcaRotator();
+    try {
+        rotator->load(input);
+    } catch (const std::exception&) {
+        delete rotator;
+        return nullptr;
+    }
+    return reinterpret_cast<Rotator*>(rotator);
+}
+
+Rotator* rabitq_learned_rotator_new(
+    const float* data, size_t num, size_t dim, size_t iters, uint64_t seed
+) {
+    try {
+        auto rotator =
+            new rabitqlib::rotator_impl::LearnedRotator(data, num, dim, iters, seed);
+        return reinterpret_cast<Rotator*>(rotator);
+    } catch (const std::exception&) {
+        return nullptr;
+    }
+}
+
+Rotator* rabitq_learned_rotator_load(const char* file_path, size_t dim) {
+    std::ifstream input(file_path, std::ios::binary);
+    if (!input.is_open() || dim == 0) {
+        return nullptr;
+    }
+    auto rotator = new rabitqlib::rotator_impl::LearnedRotator(
+        dim, rabitqlib::round_up_to_multiple(dim, 64)
+    );
+    try {
+        rotator->load(input);
+    } catch (const std::exception&) {
+        delete rotator;
+        return nullptr;
+    }
+    return reinterpret_cast<Rotator*>(rotator);
+}
+
+float rabitq_rotator_explained_variance(const Rotator* rotator) {
+    auto pca = dynamic_cast<const rabitqlib::rotator_impl::PcaRotator*>(
+        reinterpret_cast<const rabitqlib::Rotator<float>*>(rotator)
+    );
+    return pca == nullptr ? -1.0F : pca->explained_variance();
+}
+
+float rabitq_euclidean_sqr(const float* x, const float* y, size_t dim) {
+    return rabitqlib::euclidean_sqr(x, y, dim);
+}
+
+float rabitq_dot_product(const float* x, const float* y, size_t dim) {
+    return rabitqlib::dot_product(x, y, dim);
+}
+
 void rabitq_quantize_full_single(
     const float* data,
     size_t dim,
@@ -120,9 +259,9 @@ SplitBatchQuery* rabitq_split_batch_query_new(
     size_t padded_dim,
     size_t ex_bits,
     MetricType metric_type,
-    bool use_hacc) {
+    size_t lut_bits) {
     auto q = new rabitqlib::SplitBatchQuery<float>(
-        rotated_query, padded_dim, ex_bits, static_cast<rabitqlib::MetricType>(metric_type), use_hacc);
+        rotated_query, padded_dim, ex_bits, static_cast<rabitqlib::MetricType>(metric_type), lut_bits);
     return reinterpret_cast<SplitBatchQuery*>(q);
 }
 
@@ -130,6 +269,14 @@ void rabitq_split_batch_query_free(SplitBatchQuery* q_obj) {
     delete reinterpret_cast<rabitqlib::SplitBatchQuery<float>*>(q_obj);
 }
 
+SplitBatchQuery* rabitq_split_batch_query_clone(
+    const SplitBatchQuery* q_obj, const float* rotated_query
+) {
+    const auto* cpp_q_obj = reinterpret_cast<const rabitqlib::SplitBatchQuery<float>*>(q_obj);
+    auto q = new rabitqlib::SplitBatchQuery<float>(*cpp_q_obj, rotated_query);
+    return reinterpret_cast<SplitBatchQuery*>(q);
+}
+
 void rabitq_split_batch_query_set_g_add(SplitBatchQuery* q_obj, float norm, float ip) {
     reinterpret_cast<rabitqlib::SplitBatchQuery<float>*>(q_obj)->set_g_add(norm, ip);
 }
@@ -168,14 +315,16 @@ SplitSingleQuery* rabitq_split_single_query_new(
     size_t padded_dim,
     size_t ex_bits,
     const RabitqConfig* config,
-    MetricType metric_type
+    MetricType metric_type,
+    size_t query_bits
 ) {
     auto q = new rabitqlib::SplitSingleQuery<float>(
         rotated_query,
         padded_dim,
         ex_bits,
         *reinterpret_cast<const rabitqlib::quant::RabitqConfig*>(config),
-        static_cast<rabitqlib::MetricType>(metric_type)
+        static_cast<rabitqlib::MetricType>(metric_type),
+        query_bits
     );
     return reinterpret_cast<SplitSingleQuery*>(q);
 }
@@ -184,6 +333,14 @@ void rabitq_split_single_query_free(SplitSingleQuery* q_obj) {
     delete reinterpret_cast<rabitqlib::SplitSingleQuery<float>*>(q_obj);
 }
 
+SplitSingleQuery* rabitq_split_single_query_clone(
+    const SplitSingleQuery* q_obj, const float* rotated_query
+) {
+    const auto* cpp_q_obj = reinterpret_cast<const rabitqlib::SplitSingleQuery<float>*>(q_obj);
+    auto q = new rabitqlib::SplitSingleQuery<float>(*cpp_q_obj, rotated_query);
+    return reinterpret_cast<SplitSingleQuery*>(q);
+}
+
 const uint64_t* rabitq_split_single_query_query_bin(const SplitSingleQuery* q_obj) {
     return reinterpret_cast<const rabitqlib::SplitSingleQuery<float>*>(q_obj)->query_bin();
 }
@@ -279,8 +436,10 @@ void rabitq_split_single_fulldist(
 }
 
 // BatchQuery
-BatchQuery* rabitq_batch_query_new(const float* rotated_query, size_t padded_dim) {
-    auto query = new rabitqlib::BatchQuery<float>(rotated_query, padded_dim);
+BatchQuery* rabitq_batch_query_new(
+    const float* rotated_query, size_t padded_dim, size_t lut_bits
+) {
+    auto query = new rabitqlib::BatchQuery<float>(rotated_query, padded_dim, lut_bits);
     return reinterpret_cast<BatchQuery*>(query);
 }
 
@@ -288,6 +447,11 @@ void rabitq_batch_query_free(BatchQuery* query) {
     delete reinterpret_cast<rabitqlib::BatchQuery<float>*>(query);
 }
 
+BatchQuery* rabitq_batch_query_clone(const BatchQuery* query) {
+    const auto* cpp_query = reinterpret_cast<const rabitqlib::BatchQuery<float>*>(query);
+    return reinterpret_cast<BatchQuery*>(new rabitqlib::BatchQuery<float>(*cpp_query));
+}
+
 float rabitq_batch_query_delta(const BatchQuery* query) {
     return reinterpret_cast<const rabitqlib::BatchQuery<float>*>(query)->delta();
 }
@@ -334,7 +498,8 @@ SingleCentroidQuery* rabitq_single_centroid_query_new(
     size_t padded_dim,
     size_t ex_bits,
     const RabitqConfig* config,
-    MetricType metric_type
+    MetricType metric_type,
+    size_t query_bits
 ) {
     const auto* cpp_config = reinterpret_cast<const rabitqlib::quant::RabitqConfig*>(config);
     auto query = new rabitqlib::SingleCentroidQuery<float>(
@@ -343,7 +508,8 @@ SingleCentroidQuery* rabitq_single_centroid_query_new(
         padded_dim,
         ex_bits,
         *cpp_config,
-        static_cast<rabitqlib::MetricType>(metric_type)
+        static_cast<rabitqlib::MetricType>(metric_type),
+        query_bits
     );
     return reinterpret_cast<SingleCentroidQuery*>(query);
 }
@@ -352,6 +518,14 @@ void rabitq_single_centroid_query_free(SingleCentroidQuery* query) {
     delete reinterpret_cast<rabitqlib::SingleCentroidQuery<float>*>(query);
 }
 
+SingleCentroidQuery* rabitq_single_centroid_query_clone(
+    const SingleCentroidQuery* query, const float* rotated_query
+) {
+    const auto* cpp_query = reinterpret_cast<const rabitqlib::SingleCentroidQuery<float>*>(query);
+    auto q = new rabitqlib::SingleCentroidQuery<float>(*cpp_query, rotated_query);
+    return reinterpret_cast<SingleCentroidQuery*>(q);
+}
+
 const uint64_t* rabitq_single_centroid_query_query_bin(const SingleCentroidQuery* query) {
     return reinterpret_cast<const rabitqlib::SingleCentroidQuery<float>*>(query)->query_bin();
 }
@@ -443,4 +617,781 @@ void rabitq_single_centroid_fulldist(
         g_error
     );
 }
-}
\ No newline at end of file
+
+// The index classes report some failures with exit()/assert, so files are checked here
+// before they are handed over.
+static bool readable(const char* file_path) {
+    std::ifstream input(file_path, std::ios::binary);
+    return input.is_open();
+}
+
+static bool writable(const char* file_path) {
+    std::ofstream output(file_path, std::ios::binary | std::ios::app);
+    return output.is_open();
+}
+
+static rabitqlib::SearchBudget search_budget(const RabitqSearchBudget& budget) {
+    return rabitqlib::SearchBudget(
+        budget.time_us, budget.max_clusters, budget.max_distances, budget.max_boosts
+    );
+}
+
+static RabitqSearchStats search_stats(const rabitqlib::SearchStats& stats) {
+    return RabitqSearchStats{
+        stats.clusters,
+        stats.batches,
+        stats.distances,
+        stats.pruned,
+        stats.boosts,
+        stats.hops,
+        stats.visited
+    };
+}
+
+// IVF
+IvfIndex* rabitq_ivf_new(
+    size_t num, size_t dim, size_t num_clusters, size_t total_bits, uint64_t seed
+) {
+    if (total_bits < 1 || total_bits > 9 || num_clusters == 0) {
+        return nullptr;
+    }
+    auto index = new rabitqlib::ivf::IVF(
+        num, dim, num_clusters, total_bits, rabitqlib::RotatorType::FhtKacRotator, seed
+    );
+    return reinterpret_cast<IvfIndex*>(index);
+}
+
+IvfIndex* rabitq_ivf_load(const char* file_path) {
+    if (!readable(file_path)) {
+        return nullptr;
+    }
+    auto index = new rabitqlib::ivf::IVF();
+    try {
+        index->load(file_path);
+    } catch (const std::exception&) {
+        delete index;
+        return nullptr;
+    }
+    return reinterpret_cast<IvfIndex*>(index);
+}
+
+void rabitq_ivf_free(IvfIndex* index) {
+    delete reinterpret_cast<rabitqlib::ivf::IVF*>(index);
+}
+
+int rabitq_ivf_construct(
+    IvfIndex* index,
+    const float* data,
+    const float* centroids,
+    const uint32_t* cluster_ids,
+    bool faster,
+    const RabitqBuildMonitor* monitor
+) {
+    auto* ivf = reinterpret_cast<rabitqlib::ivf::IVF*>(index);
+    for (size_t i = 0; i < ivf->num(); ++i) {
+        if (cluster_ids[i] >= ivf->num_clusters()) {
+            return -1;
+        }
+    }
+    try {
+        ivf->construct(data, centroids, cluster_ids, faster, build_monitor(monitor));
+    } catch (const std::exception&) {
+        return -1;
+    }
+    return 0;
+}
+
+int rabitq_ivf_save(const IvfIndex* index, const char* file_path) {
+    if (!writable(file_path)) {
+        return -1;
+    }
+    reinterpret_cast<const rabitqlib::ivf::IVF*>(index)->save(file_path);
+    return 0;
+}
+
+int rabitq_ivf_set_rotator(IvfIndex* index, const Rotator* rotator) {
+    try {
+        reinterpret_cast<rabitqlib::ivf::IVF*>(index)->set_rotator(
+            *reinterpret_cast<const rabitqlib::Rotator<float>*>(rotator)
+        );
+        return 0;
+    } catch (const std::exception&) {
+        return -1;
+    }
+}
+
+IvfBuilder* rabitq_ivf_builder_new(
+    IvfIndex* index,
+    const float* centroids,
+    const size_t* cluster_sizes,
+    bool faster,
+    const RabitqBuildMonitor* monitor
+) {
+    auto* ivf = reinterpret_cast<rabitqlib::ivf::IVF*>(index);
+    try {
+        auto* builder = new rabitqlib::ivf::IVFBuilder(
+            *ivf,
+            centroids,
+            std::vector<size_t>(cluster_sizes, cluster_sizes + ivf->num_clusters()),
+            faster,
+            build_monitor(monitor)
+        );
+        return reinterpret_cast<IvfBuilder*>(builder);
+    } catch (const std::exception&) {
+        return nullptr;
+    }
+}
+
+void rabitq_ivf_builder_free(IvfBuilder* builder) {
+    delete reinterpret_cast<rabitqlib::ivf::IVFBuilder*>(builder);
+}
+
+int rabitq_ivf_builder_add(
+    IvfBuilder* builder, const float* data, const uint32_t* cluster_ids, size_t num
+) {
+    try {
+        reinterpret_cast<rabitqlib::ivf::IVFBuilder*>(builder)->add(data, cluster_ids, num);
+    } catch (const std::exception&) {
+        return -1;
+    }
+    return 0;
+}
+
+int rabitq_ivf_builder_finish(IvfBuilder* builder) {
+    try {
+        reinterpret_cast<rabitqlib::ivf::IVFBuilder*>(builder)->finish();
+    } catch (const std::exception&) {
+        return -1;
+    }
+    return 0;
+}
+
+void rabitq_ivf_search(
+    const IvfIndex* index,
+    const float* query,
+    size_t k,
+    size_t nprobe,
+    uint32_t* results,
+    size_t lut_bits,
+    float* distances,
+    const RabitqSearchBudget* budget,
+    bool* partial,
+    RabitqSearchStats* stats
+) {
+    std::fill(results, results + k, std::numeric_limits<uint32_t>::max());
+    if (distances != nullptr) {
+        std::fill(distances, distances + k, std::numeric_limits<float>::infinity());
+    }
+    rabitqlib::SearchBudget limits;
+    if (budget != nullptr) {
+        limits = search_budget(*budget);
+    }
+    rabitqlib::SearchStats counts;
+    reinterpret_cast<const rabitqlib::ivf::IVF*>(index)->search(
+        query,
+        k,
+        nprobe,
+        results,
+        lut_bits,
+        distances,
+        budget == nullptr ? nullptr : &limits,
+        stats == nullptr ? nullptr : &counts
+    );
+    if (budget != nullptr) {
+        *partial = limits.partial;
+    }
+    if (stats != nullptr) {
+        *stats = search_stats(counts);
+    }
+}
+
+size_t rabitq_ivf_num(const IvfIndex* index) {
+    return reinterpret_cast<const rabitqlib::ivf::IVF*>(index)->num();
+}
+
+size_t rabitq_ivf_dim(const IvfIndex* index) {
+    return reinterpret_cast<const rabitqlib::ivf::IVF*>(index)->dim();
+}
+
+size_t rabitq_ivf_padded_dim(const IvfIndex* index) {
+    return reinterpret_cast<const rabitqlib::ivf::IVF*>(index)->padded_dim();
+}
+
+size_t rabitq_ivf_num_clusters(const IvfIndex* index) {
+    return reinterpret_cast<const rabitqlib::ivf::IVF*>(index)->num_clusters();
+}
+
+size_t rabitq_ivf_ex_bits(const IvfIndex* index) {
+    return reinterpret_cast<const rabitqlib::ivf::IVF*>(index)->ex_bits();
+}
+
+void rabitq_ivf_cluster_sizes(const IvfIndex* index, size_t* sizes) {
+    auto cluster_sizes = reinterpret_cast<const rabitqlib::ivf::IVF*>(index)->cluster_sizes();
+    std::copy(cluster_sizes.begin(), cluster_sizes.end(), sizes);
+}
+
+void rabitq_ivf_memory(const IvfIndex* index, size_t* bytes) {
+    auto sections = reinterpret_cast<const rabitqlib::ivf::IVF*>(index)->memory_sections();
+    bytes[0] = sections.batch_data;
+    bytes[1] = sections.ex_data;
+    bytes[2] = sections.ids;
+    bytes[3] = sections.centroids;
+}
+
+// HNSW
+HnswIndex* rabitq_hnsw_new(
+    size_t max_elements,
+    size_t dim,
+    size_t total_bits,
+    size_t m,
+    size_t ef_construction,
+    size_t random_seed,
+    MetricType metric_type
+) {
+    if (total_bits < 1 || total_bits > 9) {
+        return nullptr;
+    }
+    try {
+        auto index = new rabitqlib::hnsw::HierarchicalNSW(
+            max_elements,
+            dim,
+            total_bits,
+            m,
+            ef_construction,
+            random_seed,
+            static_cast<rabitqlib::MetricType>(metric_type)
+        );
+        return reinterpret_cast<HnswIndex*>(index);
+    } catch (const std::exception&) {
+        return nullptr;
+    }
+}
+
+HnswIndex* rabitq_hnsw_load(const char* file_path, MetricType metric_type) {
+    if (!readable(file_path)) {
+        return nullptr;
+    }
+    auto index = new rabitqlib::hnsw::HierarchicalNSW();
+    try {
+        index->load(file_path, static_cast<rabitqlib::MetricType>(metric_type));
+    } catch (const std::exception&) {
+        delete index;
+        return nullptr;
+    }
+    return reinterpret_cast<HnswIndex*>(index);
+}
+
+void rabitq_hnsw_free(HnswIndex* index) {
+    delete reinterpret_cast<rabitqlib::hnsw::HierarchicalNSW*>(index);
+}
+
+int rabitq_hnsw_set_rotator(HnswIndex* index, const Rotator* rotator) {
+    try {
+        reinterpret_cast<rabitqlib::hnsw::HierarchicalNSW*>(index)->set_rotator(
+            *reinterpret_cast<const rabitqlib::Rotator<float>*>(rotator)
+        );
+        return 0;
+    } catch (const std::exception&) {
+        return -1;
+    }
+}
+
+int rabitq_hnsw_construct(
+    HnswIndex* index,
+    size_t num_clusters,
+    const float* centroids,
+    size_t num,
+    const float* data,
+    uint32_t* cluster_ids,
+    size_t num_threads,
+    bool faster,
+    const RabitqBuildMonitor* monitor
+) {
+    for (size_t i = 0; i < num; ++i) {
+        if (cluster_ids[i] >= num_clusters) {
+            return -1;
+        }
+    }
+    try {
+        reinterpret_cast<rabitqlib::hnsw::HierarchicalNSW*>(index)->construct(
+            num_clusters,
+            centroids,
+            num,
+            data,
+            cluster_ids,
+            num_threads,
+            faster,
+            build_monitor(monitor)
+        );
+    } catch (const std::exception&) {
+        return -1;
+    }
+    return 0;
+}
+
+int rabitq_hnsw_construct_quantized(
+    HnswIndex* index,
+    size_t num_clusters,
+    const float* centroids,
+    size_t num,
+    rabitq_fetch_rows fetch_rows,
+    void* ctx,
+    size_t chunk_size,
+    float exact_sample,
+    size_t num_threads,
+    bool faster,
+    const RabitqBuildMonitor* monitor
+) {
+    rabitqlib::hnsw::HierarchicalNSW::FetchRows fetch =
+        [=](const uint32_t* ids, size_t count, float* out) {
+            return fetch_rows(ctx, ids, count, out) == 0;
+        };
+    try {
+        reinterpret_cast<rabitqlib::hnsw::HierarchicalNSW*>(index)->construct_quantized(
+            num_clusters,
+            centroids,
+            num,
+            fetch,
+            chunk_size,
+            exact_sample,
+            num_threads,
+            faster,
+            build_monitor(monitor)
+        );
+    } catch (const std::exception&) {
+        return -1;
+    }
+    return 0;
+}
+
+int rabitq_hnsw_insert(HnswIndex* index, const float* vector, uint32_t label) {
+    try {
+        reinterpret_cast<rabitqlib::hnsw::HierarchicalNSW*>(index)->insert(vector, label);
+    } catch (const std::exception&) {
+        return -1;
+    }
+    return 0;
+}
+
+int rabitq_hnsw_reserve(HnswIndex* index, size_t capacity) {
+    try {
+        reinterpret_cast<rabitqlib::hnsw::HierarchicalNSW*>(index)->reserve(capacity);
+    } catch (const std::exception&) {
+        return -1;
+    }
+    return 0;
+}
+
+size_t rabitq_hnsw_capacity(const HnswIndex* index) {
+    return reinterpret_cast<const rabitqlib::hnsw::HierarchicalNSW*>(index)->capacity();
+}
+
+int rabitq_hnsw_save(const HnswIndex* index, const char* file_path) {
+    if (!writable(file_path)) {
+        return -1;
+    }
+    reinterpret_cast<const rabitqlib::hnsw::HierarchicalNSW*>(index)->save(file_path);
+    return 0;
+}
+
+void rabitq_hnsw_search(
+    const HnswIndex* index,
+    const float* queries,
+    size_t num_queries,
+    size_t k,
+    size_t ef,
+    size_t num_threads,
+    size_t query_bits,
+    uint32_t* results,
+    float* distances,
+    const RabitqSearchBudget* budget,
+    bool* partial,
+    RabitqSearchStats* stats
+) {
+    // search() only reads the graph once it is built, see search_knn
+    auto* hnsw =
+        const_cast<rabitqlib::hnsw::HierarchicalNSW*>(
+            reinterpret_cast<const rabitqlib::hnsw::HierarchicalNSW*>(index)
+        );
+    std::vector<rabitqlib::SearchBudget> budgets;
+    if (budget != nullptr) {
+        budgets.assign(num_queries, search_budget(*budget));
+    }
+    std::vector<rabitqlib::SearchStats> counts(stats == nullptr ? 0 : num_queries);
+    auto knns = hnsw->search(
+        queries,
+        num_queries,
+        k,
+        ef,
+        num_threads,
+        query_bits,
+        budget == nullptr ? nullptr : budgets.data(),
+        stats == nullptr ? nullptr : counts.data()
+    );
+    for (size_t i = 0; i < budgets.size(); ++i) {
+        partial[i] = budgets[i].partial;
+    }
+    for (size_t i = 0; i < counts.size(); ++i) {
+        stats[i] = search_stats(counts[i]);
+    }
+    for (size_t i = 0; i < num_queries; ++i) {
+        for (size_t j = 0; j < k; ++j) {
+            bool found = j < knns[i].size();
+            results[i * k + j] = found ? knns[i][j].second : std::numeric_limits<uint32_t>::max();
+            distances[i * k + j] =
+                found ? knns[i][j].first : std::numeric_limits<float>::infinity();
+        }
+    }
+}
+
+size_t rabitq_hnsw_num(const HnswIndex* index) {
+    return reinterpret_cast<const rabitqlib::hnsw::HierarchicalNSW*>(index)->num_points();
+}
+
+size_t rabitq_hnsw_dim(const HnswIndex* index) {
+    return reinterpret_cast<const rabitqlib::hnsw::HierarchicalNSW*>(index)->dim();
+}
+
+size_t rabitq_hnsw_padded_dim(const HnswIndex* index) {
+    return reinterpret_cast<const rabitqlib::hnsw::HierarchicalNSW*>(index)->padded_dim();
+}
+
+size_t rabitq_hnsw_num_clusters(const HnswIndex* index) {
+    return reinterpret_cast<const rabitqlib::hnsw::HierarchicalNSW*>(index)->num_clusters();
+}
+
+size_t rabitq_hnsw_ex_bits(const HnswIndex* index) {
+    return reinterpret_cast<const rabitqlib::hnsw::HierarchicalNSW*>(index)->ex_bits();
+}
+
+size_t rabitq_hnsw_max_degree(const HnswIndex* index) {
+    return reinterpret_cast<const rabitqlib::hnsw::HierarchicalNSW*>(index)->max_degree();
+}
+
+size_t rabitq_hnsw_ef_construction(const HnswIndex* index) {
+    return reinterpret_cast<const rabitqlib::hnsw::HierarchicalNSW*>(index)->ef_construction();
+}
+
+size_t rabitq_hnsw_num_levels(const HnswIndex* index) {
+    return reinterpret_cast<const rabitqlib::hnsw::HierarchicalNSW*>(index)->num_levels();
+}
+
+uint32_t rabitq_hnsw_entry_point(const HnswIndex* index) {
+    return reinterpret_cast<const rabitqlib::hnsw::HierarchicalNSW*>(index)->entry_point();
+}
+
+size_t rabitq_hnsw_level_degrees(const HnswIndex* index, size_t level, uint32_t* degrees) {
+    auto level_degrees = reinterpret_cast<const rabitqlib::hnsw::HierarchicalNSW*>(index)
+                             ->level_degrees(static_cast<int>(level));
+    std::copy(level_degrees.begin(), level_degrees.end(), degrees);
+    return level_degrees.size();
+}
+
+void rabitq_hnsw_memory(const HnswIndex* index, size_t* bytes) {
+    auto hnsw = reinterpret_cast<const rabitqlib::hnsw::HierarchicalNSW*>(index);
+    bytes[0] = hnsw->level0_links_bytes();
+    bytes[1] = hnsw->labels_bytes();
+    bytes[2] = hnsw->bin_data_bytes();
+    bytes[3] = hnsw->ex_data_bytes();
+    bytes[4] = hnsw->upper_links_bytes();
+    bytes[5] = hnsw->centroids_bytes();
+}
+
+// SymphonyQG
+QgIndex* rabitq_qg_new(
+    size_t num, size_t dim, size_t degree_bound, MetricType metric_type, uint64_t seed
+) {
+    if (degree_bound == 0 || degree_bound % 32 != 0) {
+        return nullptr;
+    }
+    auto index = new rabitqlib::symqg::QuantizedGraph<float>(
+        num,
+        dim,
+        degree_bound,
+        rabitqlib::RotatorType::FhtKacRotator,
+        seed,
+        static_cast<rabitqlib::MetricType>(metric_type)
+    );
+    return reinterpret_cast<QgIndex*>(index);
+}
+
+QgIndex* rabitq_qg_load(const char* file_path) {
+    if (!readable(file_path)) {
+        return nullptr;
+    }
+    auto index = new rabitqlib::symqg::QuantizedGraph<float>();
+    try {
+        index->load(file_path);
+    } catch (const std::exception&) {
+        delete index;
+        return nullptr;
+    }
+    return reinterpret_cast<QgIndex*>(index);
+}
+
+void rabitq_qg_free(QgIndex* index) {
+    delete reinterpret_cast<rabitqlib::symqg::QuantizedGraph<float>*>(index);
+}
+
+int rabitq_qg_build(
+    QgIndex* index,
+    const float* data,
+    size_t ef_build,
+    size_t num_threads,
+    size_t num_iter,
+    uint64_t seed,
+    const RabitqBuildMonitor* monitor
+) {
+    if (num_iter < 2) {
+        return -1;
+    }
+    auto* qg = reinterpret_cast<rabitqlib::symqg::QuantizedGraph<float>*>(index);
+    if (num_threads == 0) {
+        num_threads = std::numeric_limits<size_t>::max();
+    }
+    try {
+        rabitqlib::symqg::QGBuilder builder(*qg, ef_build, data, num_threads, seed);
+        builder.build(num_iter, build_monitor(monitor));
+    } catch (const std::exception&) {
+        return -1;
+    }
+    return 0;
+}
+
+int rabitq_qg_insert(QgIndex* index, const float* vector, size_t ef, uint32_t* id) {
+    try {
+        *id = reinterpret_cast<rabitqlib::symqg::QuantizedGraph<float>*>(index)->insert(
+            vector, ef
+        );
+    } catch (const std::exception&) {
+        return -1;
+    }
+    return 0;
+}
+
+int rabitq_qg_reserve(QgIndex* index, size_t capacity) {
+    try {
+        reinterpret_cast<rabitqlib::symqg::QuantizedGraph<float>*>(index)->reserve(capacity);
+    } catch (const std::exception&) {
+        return -1;
+    }
+    return 0;
+}
+
+size_t rabitq_qg_capacity(const QgIndex* index) {
+    return reinterpret_cast<const rabitqlib::symqg::QuantizedGraph<float>*>(index)->capacity();
+}
+
+int rabitq_qg_save(const QgIndex* index, const char* file_path) {
+    if (!writable(file_path)) {
+        return -1;
+    }
+    reinterpret_cast<const rabitqlib::symqg::QuantizedGraph<float>*>(index)->save(file_path);
+    return 0;
+}
+
+int rabitq_qg_set_rotator(QgIndex* index, const Rotator* rotator) {
+    try {
+        reinterpret_cast<rabitqlib::symqg::QuantizedGraph<float>*>(index)->set_rotator(
+            *reinterpret_cast<const rabitqlib::Rotator<float>*>(rotator)
+        );
+        return 0;
+    } catch (const std::exception&) {
+        return -1;
+    }
+}
+
+void rabitq_qg_search(
+    const QgIndex* index,
+    const float* query,
+    size_t k,
+    size_t ef,
+    size_t lut_bits,
+    uint32_t* results,
+    float* distances,
+    const RabitqSearchBudget* budget,
+    bool* partial,
+    RabitqSearchStats* stats
+) {
+    std::fill(results, results + k, std::numeric_limits<uint32_t>::max());
+    if (distances != nullptr) {
+        std::fill(distances, distances + k, std::numeric_limits<float>::infinity());
+    }
+    // search() with an explicit ef only reads the graph
+    auto* qg = const_cast<rabitqlib::symqg::QuantizedGraph<float>*>(
+        reinterpret_cast<const rabitqlib::symqg::QuantizedGraph<float>*>(index)
+    );
+    rabitqlib::SearchBudget limits;
+    if (budget != nullptr) {
+        limits = search_budget(*budget);
+    }
+    rabitqlib::SearchStats counts;
+    qg->search(
+        query,
+        static_cast<uint32_t>(k),
+        ef,
+        results,
+        lut_bits,
+        distances,
+        budget == nullptr ? nullptr : &limits,
+        stats == nullptr ? nullptr : &counts
+    );
+    if (budget != nullptr) {
+        *partial = limits.partial;
+    }
+    if (stats != nullptr) {
+        *stats = search_stats(counts);
+    }
+}
+
+size_t rabitq_qg_num(const QgIndex* index) {
+    return reinterpret_cast<const rabitqlib::symqg::QuantizedGraph<float>*>(index)
+        ->num_vertices();
+}
+
+size_t rabitq_qg_dim(const QgIndex* index) {
+    return reinterpret_cast<const rabitqlib::symqg::QuantizedGraph<float>*>(index)->dimension();
+}
+
+size_t rabitq_qg_degree_bound(const QgIndex* index) {
+    return reinterpret_cast<const rabitqlib::symqg::QuantizedGraph<float>*>(index)
+        ->degree_bound();
+}
+
+MetricType rabitq_qg_metric(const QgIndex* index) {
+    return static_cast<MetricType>(
+        reinterpret_cast<const rabitqlib::symqg::QuantizedGraph<float>*>(index)->metric_type()
+    );
+}
+
+uint32_t rabitq_qg_entry_point(const QgIndex* index) {
+    return reinterpret_cast<const rabitqlib::symqg::QuantizedGraph<float>*>(index)
+        ->entry_point();
+}
+
+void rabitq_qg_degrees(const QgIndex* index, uint32_t* degrees) {
+    auto qg_degrees =
+        reinterpret_cast<const rabitqlib::symqg::QuantizedGraph<float>*>(index)->degrees();
+    std::copy(qg_degrees.begin(), qg_degrees.end(), degrees);
+}
+
+void rabitq_qg_memory(const QgIndex* index, size_t* bytes) {
+    auto qg = reinterpret_cast<const rabitqlib::symqg::QuantizedGraph<float>*>(index);
+    bytes[0] = qg->vectors_bytes();
+    bytes[1] = qg->batch_data_bytes();
+    bytes[2] = qg->neighbors_bytes();
+}
+
+// Multi-vector (MaxSim)
+MultiVectorIndex* rabitq_maxsim_new(size_t dim, size_t total_bits, uint64_t seed) {
+    if (total_bits < 1 || total_bits > 9) {
+        return nullptr;
+    }
+    auto index = new rabitqlib::maxsim::MultiVectorIndex(
+        dim, total_bits, rabitqlib::RotatorType::FhtKacRotator, seed
+    );
+    return reinterpret_cast<MultiVectorIndex*>(index);
+}
+
+MultiVectorIndex* rabitq_maxsim_load(const char* file_path) {
+    if (!readable(file_path)) {
+        return nullptr;
+    }
+    auto index = new rabitqlib::maxsim::MultiVectorIndex();
+    try {
+        index->load(file_path);
+    } catch (const std::exception&) {
+        delete index;
+        return nullptr;
+    }
+    return reinterpret_cast<MultiVectorIndex*>(index);
+}
+
+void rabitq_maxsim_free(MultiVectorIndex* index) {
+    delete reinterpret_cast<rabitqlib::maxsim::MultiVectorIndex*>(index);
+}
+
+int rabitq_maxsim_set_rotator(MultiVectorIndex* index, const Rotator* rotator) {
+    try {
+        reinterpret_cast<rabitqlib::maxsim::MultiVectorIndex*>(index)->set_rotator(
+            *reinterpret_cast<const rabitqlib::Rotator<float>*>(rotator)
+        );
+        return 0;
+    } catch (const std::exception&) {
+        return -1;
+    }
+}
+
+int rabitq_maxsim_construct(
+    MultiVectorIndex* index,
+    const float* tokens,
+    const size_t* doc_offsets,
+    size_t num_docs,
+    bool faster
+) {
+    try {
+        reinterpret_cast<rabitqlib::maxsim::MultiVectorIndex*>(index)->construct(
+            tokens, doc_offsets, num_docs, faster
+        );
+    } catch (const std::exception&) {
+        return -1;
+    }
+    return 0;
+}
+
+int rabitq_maxsim_save(const MultiVectorIndex* index, const char* file_path) {
+    if (!writable(file_path)) {
+        return -1;
+    }
+    try {
+        reinterpret_cast<const rabitqlib::maxsim::MultiVectorIndex*>(index)->save(file_path);
+    } catch (const std::exception&) {
+        return -1;
+    }
+    return 0;
+}
+
+void rabitq_maxsim_search(
+    const MultiVectorIndex* index,
+    const float* query,
+    size_t num_query_tokens,
+    size_t k,
+    size_t rerank,
+    size_t lut_bits,
+    uint32_t* results,
+    float* scores
+) {
+    std::fill(results, results + k, std::numeric_limits<uint32_t>::max());
+    std::fill(scores, scores + k, -std::numeric_limits<float>::infinity());
+    reinterpret_cast<const rabitqlib::maxsim::MultiVectorIndex*>(index)->search(
+        query, num_query_tokens, k, rerank, results, scores, lut_bits
+    );
+}
+
+size_t rabitq_maxsim_num_docs(const MultiVectorIndex* index) {
+    return reinterpret_cast<const rabitqlib::maxsim::MultiVectorIndex*>(index)->num_docs();
+}
+
+size_t rabitq_maxsim_num_tokens(const MultiVectorIndex* index) {
+    return reinterpret_cast<const rabitqlib::maxsim::MultiVectorIndex*>(index)->num_tokens();
+}
+
+size_t rabitq_maxsim_dim(const MultiVectorIndex* index) {
+    return reinterpret_cast<const rabitqlib::maxsim::MultiVectorIndex*>(index)->dim();
+}
+
+size_t rabitq_maxsim_padded_dim(const MultiVectorIndex* index) {
+    return reinterpret_cast<const rabitqlib::maxsim::MultiVectorIndex*>(index)->padded_dim();
+}
+
+size_t rabitq_maxsim_ex_bits(const MultiVectorIndex* index) {
+    return reinterpret_cast<const rabitqlib::maxsim::MultiVectorIndex*>(index)->ex_bits();
+}
+
+void rabitq_maxsim_memory(const MultiVectorIndex* index, size_t* bytes) {
+    auto maxsim = reinterpret_cast<const rabitqlib::maxsim::MultiVectorIndex*>(index);
+    bytes[0] = maxsim->batch_data_bytes();
+    bytes[1] = maxsim->ex_data_bytes();
+    bytes[2] = maxsim->centroids_bytes();
+}
+}
@@ -0,0 +1,26 @@
+#!/usr/bin/env bash
+# Cross-compile rabitq-rs for aarch64 and run its tests under qemu-user, which runs the NEON
+# kernels of rabitqlib and of the FastScan module on an x86 machine. The kernel tests
+# (ex-code inner products, distances, rotator, FastScan against the scalar reference) are
+# in the library, `--lib` runs only those; the integration tests take much longer under
+# emulation.
+#
+# Needs (Debian / Ubuntu):
+#   apt install g++-aarch64-linux-gnu qemu-user libclang-dev
+#   rustup target add aarch64-unknown-linux-gnu
+#
+# Usage: rust/test_aarch64.sh [cargo test args], e.g. rust/test_aarch64.sh --lib
+set -euo pipefail
+
+target=aarch64-unknown-linux-gnu
+sysroot=/usr/aarch64-linux-gnu
+
+export CC_aarch64_unknown_linux_gnu=aarch64-linux-gnu-gcc
+export CXX_aarch64_unknown_linux_gnu=aarch64-linux-gnu-g++
+export CARGO_TARGET_AARCH64_UNKNOWN_LINUX_GNU_LINKER=aarch64-linux-gnu-gcc
+export CARGO_TARGET_AARCH64_UNKNOWN_LINUX_GNU_RUNNER="qemu-aarch64 -L $sysroot"
+# bindgen parses rabitq.h with the target's headers
+export BINDGEN_EXTRA_CLANG_ARGS_aarch64_unknown_linux_gnu="--target=aarch64-linux-gnu --sysroot=$sysroot"
+
+cd "$(dirname "$0")/rabitq-rs"
+cargo test --target "$target" "$@"
@@ -3,6 +3,7 @@
 
 #include "index/hnsw/hnsw.hpp"
 #include "utils/io.hpp"
+#include "utils/log.hpp"
 #include "utils/stopw.hpp"
 
 using PID = rabitqlib::PID;
@@ -11,6 +12,9 @@ using data_type = rabitqlib::RowMajorArray<float>;
 using gt_type = rabitqlib::RowMajorArray<uint32_t>;
 
 int main(int argc, char* argv[]) {
+    // the library is silent unless a log callback is set
+    rabitqlib::set_log_callback(rabitqlib::log_to_stderr);
+
     if (argc < 8) {
         std::cerr << "Usage: " << argv[0]
                   << " <arg1> <arg2> <arg3> <arg4> <arg5> <arg6> <arg7> <arg8>\n"
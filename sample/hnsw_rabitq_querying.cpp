@@ -3,6 +3,7 @@
 
 #include "index/hnsw/hnsw.hpp"
 #include "utils/io.hpp"
+#include "utils/log.hpp"
 #include "utils/stopw.hpp"
 
 std::vector<size_t> efs = {10,  20,  40,  50,  60,  80,  100, 150,  170,  190, 200,
@@ -17,6 +18,9 @@ using data_type = rabitqlib::RowMajorArray<float>;
 using gt_type = rabitqlib::RowMajorArray<uint32_t>;
 
 int main(int argc, char* argv[]) {
+    // the library is silent unless a log callback is set
+    rabitqlib::set_log_callback(rabitqlib::log_to_stderr);
+
     if (argc < 4) {
         std::cerr << "Usage: " << argv[0] << " <arg1> <arg2> <arg3> <arg4>\n"
                   << "arg1: path for index \n"
@@ -4,6 +4,7 @@
 #include "defines.hpp"
 #include "index/ivf/ivf.hpp"
 #include "utils/io.hpp"
+#include "utils/log.hpp"
 #include "utils/stopw.hpp"
 
 using PID = rabitqlib::PID;
@@ -12,6 +13,9 @@ using data_type = rabitqlib::RowMajorArray<float>;
 using gt_type = rabitqlib::RowMajorArray<uint32_t>;
 
 int main(int argc, char** argv) {
+    // the library is silent unless a log callback is set
+    rabitqlib::set_log_callback(rabitqlib::log_to_stderr);
+
     if (argc < 6) {
         std::cerr << "Usage: " << argv[0] << " <arg1> <arg2> <arg3> <arg4> <arg5>\n"
                   << "arg1: path for data file, format .fvecs\n"
@@ -4,6 +4,7 @@
 #include "defines.hpp"
 #include "index/ivf/ivf.hpp"
 #include "utils/io.hpp"
+#include "utils/log.hpp"
 #include "utils/stopw.hpp"
 #include "utils/tools.hpp"
 
@@ -23,6 +24,9 @@ static size_t topk = 100;
 static size_t test_round = 5;
 
 int main(int argc, char** argv) {
+    // the library is silent unless a log callback is set
+    rabitqlib::set_log_callback(rabitqlib::log_to_stderr);
+
     if (argc < 4) {
         std::cerr << "Usage: " << argv[0] << " <arg1> <arg2> <arg3> <arg4>\n"
                   << "arg1: path for index \n"
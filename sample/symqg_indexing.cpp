@@ -1,9 +1,11 @@
 #include <iostream>
+#include <string>
 
 #include "defines.hpp"
 #include "index/symqg/qg.hpp"
 #include "index/symqg/qg_builder.hpp"
 #include "utils/io.hpp"
+#include "utils/log.hpp"
 #include "utils/stopw.hpp"
 
 using PID = rabitqlib::PID;
@@ -12,12 +14,16 @@ using data_type = rabitqlib::RowMajorArray<float>;
 using gt_type = rabitqlib::RowMajorArray<uint32_t>;
 
 int main(int argc, char** argv) {
-    if (argc != 5) {
-        std::cerr << "Usage: " << argv[0] << " <arg1> <arg2> <arg3> <arg4>\n"
+    // the library is silent unless a log callback is set
+    rabitqlib::set_log_callback(rabitqlib::log_to_stderr);
+
+    if (argc < 5) {
+        std::cerr << "Usage: " << argv[0] << " <arg1> <arg2> <arg3> <arg4> <arg5>\n"
                   << "arg1: path for data file, format .fvecs\n"
                   << "arg2: degree bound for symqg, must be a multiple of 32\n"
                   << "arg3: ef for indexing \n"
-                  << "arg4: path for saving index\n";
+                  << "arg4: path for saving index\n"
+                  << "arg5: metric type (\"l2\" or \"ip\"), l2 by default\n";
         exit(1);
     }
 
@@ -26,13 +32,28 @@ int main(int argc, char** argv) {
     size_t ef = atoi(argv[3]);
     char* index_file = argv[4];
 
+    rabitqlib::MetricType metric_type = rabitqlib::METRIC_L2;
+    if (argc > 5) {
+        std::string metric_str(argv[5]);
+        if (metric_str == "ip" || metric_str == "IP") {
+            metric_type = rabitqlib::METRIC_IP;
+        }
+    }
+
     data_type data;
 
     rabitqlib::load_vecs<float, data_type>(data_file, data);
 
     rabitqlib::StopW stopw;
 
-    index_type qg(data.rows(), data.cols(), degree);
+    index_type qg(
+        data.rows(),
+        data.cols(),
+        degree,
+        rabitqlib::RotatorType::FhtKacRotator,
+        rabitqlib::random_seed(),
+        metric_type
+    );
 
     rabitqlib::symqg::QGBuilder builder(qg, ef, data.data());
 
@@ -3,6 +3,7 @@
 #include "defines.hpp"
 #include "index/symqg/qg.hpp"
 #include "utils/io.hpp"
+#include "utils/log.hpp"
 #include "utils/stopw.hpp"
 
 using PID = rabitqlib::PID;
@@ -17,6 +18,9 @@ size_t test_round = 3;
 size_t topk = 10;
 
 int main(int argc, char** argv) {
+    // the library is silent unless a log callback is set
+    rabitqlib::set_log_callback(rabitqlib::log_to_stderr);
+
     if (argc != 4) {
         std::cerr << "Usage: " << argv[0] << " <arg1> <arg2> <arg3>\n"
                   << "arg1: path for index \n"